@@ -0,0 +1,770 @@
+// AAC in transport streams comes in two wrappings:
+// - ADTS (stream_type 0x0f), see ISO/IEC 13818-7 / 14496-3 1.A.2
+// - LATM inside a LOAS AudioSyncStream (stream_type 0x11), see ISO/IEC 14496-3 1.7
+use super::bitstream::{bits_consumed, skip_bits, take_bytes, verify_error, BitInput, BitResult};
+use super::stream::{stream, PartialStream, Stream};
+use super::{AudioFrame, FrameParser};
+use std::fmt;
+use winnow::{
+    binary::{self, bits},
+    combinator, error, token, IResult, Parser,
+};
+
+pub const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// Keeps bits until a whole byte is available; used to serialise AudioSpecificConfig
+struct BitPacker {
+    bytes: Vec<u8>,
+    used_bits: usize,
+}
+
+impl BitPacker {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            used_bits: 0,
+        }
+    }
+
+    fn put(&mut self, value: u32, count: usize) {
+        for bit in (0..count).rev() {
+            if self.used_bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> bit) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.used_bits % 8);
+            }
+            self.used_bits += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioSpecificConfig {
+    /// 2 for AAC LC; for HE-AAC this is the core (not the SBR/PS) object type
+    pub audio_object_type: u8,
+    pub sampling_frequency_index: u8,
+    pub sampling_frequency: u32,
+    pub channel_configuration: u8,
+    /// Explicitly signalled SBR (HE-AAC) and PS (HE-AAC v2)
+    pub sbr_present: bool,
+    pub ps_present: bool,
+    pub extension_sampling_frequency_index: Option<u8>,
+    pub extension_sampling_frequency: Option<u32>,
+    /// With SBR and ER BSAC (object type 22)
+    pub extension_channel_configuration: Option<u8>,
+    // GASpecificConfig
+    pub frame_length_flag: bool,
+    pub core_coder_delay: Option<u16>,
+    pub extension_flag: bool,
+    /// Of AAC scalable and ER AAC scalable (object types 6 and 20)
+    pub layer_nr: Option<u8>,
+    // with extension_flag: numOfSubFrame and layer_length of ER BSAC, the three
+    // aac*DataResilienceFlags of the other ER object types, and extensionFlag3
+    pub bsac_layer: Option<(u8, u16)>,
+    pub resilience_flags: Option<[bool; 3]>,
+    pub extension_flag3: bool,
+}
+
+impl AudioSpecificConfig {
+    fn parse_audio_object_type(input: BitInput) -> BitResult<u8> {
+        let (input, audio_object_type) = bits::take::<_, u8, _, _>(5_usize).parse_next(input)?;
+        if audio_object_type != 31 {
+            return Ok((input, audio_object_type));
+        }
+        let (input, extension) = bits::take::<_, u8, _, _>(6_usize).parse_next(input)?;
+        Ok((input, 32 + extension))
+    }
+
+    fn parse_sampling_frequency(input: BitInput) -> BitResult<(u8, u32)> {
+        let (input, index) = bits::take::<_, u8, _, _>(4_usize).parse_next(input)?;
+        if index == 0xf {
+            let (input, frequency) = bits::take::<_, u32, _, _>(24_usize).parse_next(input)?;
+            return Ok((input, (index, frequency)));
+        }
+        match SAMPLING_FREQUENCIES.get(index as usize) {
+            Some(frequency) => Ok((input, (index, *frequency))),
+            None => verify_error(input),
+        }
+    }
+
+    fn is_general_audio(audio_object_type: u8) -> bool {
+        matches!(
+            audio_object_type,
+            1 | 2 | 3 | 4 | 6 | 7 | 17 | 19 | 20 | 21 | 22 | 23
+        )
+    }
+
+    pub fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, mut audio_object_type) = Self::parse_audio_object_type(input)?;
+        let (input, (sampling_frequency_index, sampling_frequency)) =
+            Self::parse_sampling_frequency(input)?;
+        let (mut input, channel_configuration) =
+            bits::take::<_, u8, _, _>(4_usize).parse_next(input)?;
+        let sbr_present = audio_object_type == 5 || audio_object_type == 29;
+        let ps_present = audio_object_type == 29;
+        let mut extension_sampling_frequency_index = None;
+        let mut extension_sampling_frequency = None;
+        let mut extension_channel_configuration = None;
+        if sbr_present {
+            let (rest, (index, frequency)) = Self::parse_sampling_frequency(input)?;
+            extension_sampling_frequency_index = Some(index);
+            extension_sampling_frequency = Some(frequency);
+            (input, audio_object_type) = Self::parse_audio_object_type(rest)?;
+            if audio_object_type == 22 {
+                let configuration;
+                (input, configuration) = bits::take::<_, u8, _, _>(4_usize).parse_next(input)?;
+                extension_channel_configuration = Some(configuration);
+            }
+        }
+        let mut config = Self {
+            audio_object_type,
+            sampling_frequency_index,
+            sampling_frequency,
+            channel_configuration,
+            sbr_present,
+            ps_present,
+            extension_sampling_frequency_index,
+            extension_sampling_frequency,
+            extension_channel_configuration,
+            frame_length_flag: false,
+            core_coder_delay: None,
+            extension_flag: false,
+            layer_nr: None,
+            bsac_layer: None,
+            resilience_flags: None,
+            extension_flag3: false,
+        };
+        if !Self::is_general_audio(audio_object_type) {
+            return Ok((input, config));
+        }
+        if channel_configuration == 0 {
+            // program_config_element() is not supported
+            return verify_error(input);
+        }
+        let (input, (frame_length_flag, depends_on_core_coder)) =
+            (bits::bool, bits::bool).parse_next(input)?;
+        let (input, core_coder_delay) =
+            combinator::cond(depends_on_core_coder, bits::take::<_, u16, _, _>(14_usize))
+                .parse_next(input)?;
+        let (mut input, extension_flag) = bits::bool.parse_next(input)?;
+        (input, config.layer_nr) = combinator::cond(
+            audio_object_type == 6 || audio_object_type == 20,
+            bits::take::<_, u8, _, _>(3_usize),
+        )
+        .parse_next(input)?;
+        if extension_flag {
+            (input, config.bsac_layer) = combinator::cond(
+                audio_object_type == 22,
+                (
+                    bits::take::<_, u8, _, _>(5_usize),
+                    bits::take::<_, u16, _, _>(11_usize),
+                ),
+            )
+            .parse_next(input)?;
+            (input, config.resilience_flags) = combinator::cond(
+                matches!(audio_object_type, 17 | 19 | 20 | 23),
+                (bits::bool, bits::bool, bits::bool).map(|(a, b, c)| [a, b, c]),
+            )
+            .parse_next(input)?;
+            (input, config.extension_flag3) = bits::bool.parse_next(input)?;
+        }
+        config.frame_length_flag = frame_length_flag;
+        config.core_coder_delay = core_coder_delay;
+        config.extension_flag = extension_flag;
+        Ok((input, config))
+    }
+
+    pub fn parse(input: Stream) -> IResult<Stream, Self> {
+        bits::bits::<_, _, error::Error<(_, usize)>, _, _>(Self::parse_bits).parse_next(input)
+    }
+
+    /// Serialised form, as used in the esds (MP4) or CodecPrivate (Matroska) of a remux
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut packer = BitPacker::new();
+        let put_audio_object_type = |packer: &mut BitPacker, audio_object_type: u8| {
+            if audio_object_type >= 32 {
+                packer.put(31, 5);
+                packer.put((audio_object_type - 32) as u32, 6);
+            } else {
+                packer.put(audio_object_type as u32, 5);
+            }
+        };
+        let put_sampling_frequency = |packer: &mut BitPacker, index: u8, frequency: u32| {
+            packer.put(index as u32, 4);
+            if index == 0xf {
+                packer.put(frequency, 24);
+            }
+        };
+        match (self.ps_present, self.sbr_present) {
+            (true, _) => put_audio_object_type(&mut packer, 29),
+            (false, true) => put_audio_object_type(&mut packer, 5),
+            _ => put_audio_object_type(&mut packer, self.audio_object_type),
+        }
+        put_sampling_frequency(
+            &mut packer,
+            self.sampling_frequency_index,
+            self.sampling_frequency,
+        );
+        packer.put(self.channel_configuration as u32, 4);
+        if self.sbr_present {
+            put_sampling_frequency(
+                &mut packer,
+                self.extension_sampling_frequency_index.unwrap_or(0xf),
+                self.extension_sampling_frequency
+                    .unwrap_or(self.sampling_frequency * 2),
+            );
+            put_audio_object_type(&mut packer, self.audio_object_type);
+            if self.audio_object_type == 22 {
+                let configuration = self.extension_channel_configuration.unwrap_or(0);
+                packer.put(configuration as u32, 4);
+            }
+        }
+        if Self::is_general_audio(self.audio_object_type) {
+            packer.put(self.frame_length_flag as u32, 1);
+            packer.put(self.core_coder_delay.is_some() as u32, 1);
+            if let Some(delay) = self.core_coder_delay {
+                packer.put(delay as u32, 14);
+            }
+            packer.put(self.extension_flag as u32, 1);
+            if self.audio_object_type == 6 || self.audio_object_type == 20 {
+                packer.put(self.layer_nr.unwrap_or(0) as u32, 3);
+            }
+            if self.extension_flag {
+                if self.audio_object_type == 22 {
+                    let (num_of_sub_frame, layer_length) = self.bsac_layer.unwrap_or((0, 0));
+                    packer.put(num_of_sub_frame as u32, 5);
+                    packer.put(layer_length as u32, 11);
+                }
+                if matches!(self.audio_object_type, 17 | 19 | 20 | 23) {
+                    for flag in self.resilience_flags.unwrap_or_default() {
+                        packer.put(flag as u32, 1);
+                    }
+                }
+                packer.put(self.extension_flag3 as u32, 1);
+            }
+        }
+        packer.bytes
+    }
+
+    pub fn samples_per_frame(&self) -> u32 {
+        if self.frame_length_flag {
+            960
+        } else {
+            1024
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ADTSHeader {
+    /// false for MPEG-4, true for MPEG-2
+    pub mpeg2: bool,
+    pub protection_absent: bool,
+    /// Audio object type - 1
+    pub profile: u8,
+    pub sampling_frequency_index: u8,
+    pub private_bit: bool,
+    pub channel_configuration: u8,
+    pub original_copy: bool,
+    pub home: bool,
+    pub copyright_identification_bit: bool,
+    pub copyright_identification_start: bool,
+    /// Length of the whole frame, including this header
+    pub frame_length: u16,
+    pub buffer_fullness: u16,
+    pub number_of_raw_data_blocks: u8,
+    pub raw_data_block_positions: Vec<u16>,
+    pub crc: Option<u16>,
+}
+
+impl ADTSHeader {
+    pub fn parse(input: PartialStream) -> IResult<PartialStream, Self> {
+        let (
+            input,
+            (
+                _,
+                mpeg2,
+                layer,
+                protection_absent,
+                profile,
+                sampling_frequency_index,
+                private_bit,
+                channel_configuration,
+                original_copy,
+                home,
+                copyright_identification_bit,
+                copyright_identification_start,
+                frame_length,
+                buffer_fullness,
+                raw_data_blocks_minus_one,
+            ),
+        ) = bits::bits::<_, _, error::Error<(_, usize)>, _, _>((
+            bits::tag(0xfff_u16, 12_usize),
+            bits::bool,
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::bool,
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::take::<_, u8, _, _>(4_usize),
+            bits::bool,
+            bits::take::<_, u8, _, _>(3_usize),
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::take::<_, u16, _, _>(13_usize),
+            bits::take::<_, u16, _, _>(11_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+        ))
+        .parse_next(input)?;
+        if layer != 0 || sampling_frequency_index as usize >= SAMPLING_FREQUENCIES.len() {
+            return verify_error(input);
+        }
+        let (input, raw_data_block_positions) = combinator::cond(
+            !protection_absent && raw_data_blocks_minus_one > 0,
+            combinator::repeat(raw_data_blocks_minus_one as usize, binary::be_u16),
+        )
+        .parse_next(input)?;
+        let (input, crc) =
+            combinator::cond(!protection_absent, binary::be_u16).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                mpeg2,
+                protection_absent,
+                profile,
+                sampling_frequency_index,
+                private_bit,
+                channel_configuration,
+                original_copy,
+                home,
+                copyright_identification_bit,
+                copyright_identification_start,
+                frame_length,
+                buffer_fullness,
+                number_of_raw_data_blocks: raw_data_blocks_minus_one + 1,
+                raw_data_block_positions: raw_data_block_positions.unwrap_or_default(),
+                crc,
+            },
+        ))
+    }
+
+    pub fn header_length(&self) -> usize {
+        match self.crc {
+            None => 7,
+            Some(_) => 9 + 2 * self.raw_data_block_positions.len(),
+        }
+    }
+
+    pub fn sampling_frequency(&self) -> u32 {
+        SAMPLING_FREQUENCIES[self.sampling_frequency_index as usize]
+    }
+
+    pub fn audio_specific_config(&self) -> AudioSpecificConfig {
+        AudioSpecificConfig {
+            audio_object_type: self.profile + 1,
+            sampling_frequency_index: self.sampling_frequency_index,
+            sampling_frequency: self.sampling_frequency(),
+            channel_configuration: self.channel_configuration,
+            sbr_present: false,
+            ps_present: false,
+            extension_sampling_frequency_index: None,
+            extension_sampling_frequency: None,
+            extension_channel_configuration: None,
+            frame_length_flag: false,
+            core_coder_delay: None,
+            extension_flag: false,
+            layer_nr: None,
+            bsac_layer: None,
+            resilience_flags: None,
+            extension_flag3: false,
+        }
+    }
+}
+
+pub struct ADTSFrame {
+    pub header: ADTSHeader,
+    pub raw_data: Vec<u8>,
+}
+
+impl ADTSFrame {
+    pub fn parse(input: PartialStream) -> IResult<PartialStream, Self> {
+        let (input, header) = ADTSHeader::parse(input)?;
+        let Some(data_length) = (header.frame_length as usize).checked_sub(header.header_length())
+        else {
+            return verify_error(input);
+        };
+        let (input, raw_data) = token::take(data_length)
+            .output_into::<Vec<u8>>()
+            .parse_next(input)?;
+        Ok((input, Self { header, raw_data }))
+    }
+}
+
+impl AudioFrame for ADTSFrame {
+    fn samples(&self) -> u32 {
+        1024 * self.header.number_of_raw_data_blocks as u32
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.header.sampling_frequency()
+    }
+}
+
+impl fmt::Debug for ADTSFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ADTSFrame(profile={}, {} Hz, channels={}, length={}, crc={:x?})",
+            self.header.profile,
+            self.header.sampling_frequency(),
+            self.header.channel_configuration,
+            self.header.frame_length,
+            self.header.crc,
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ADTSParser;
+
+impl FrameParser for ADTSParser {
+    type Frame = ADTSFrame;
+
+    fn parse_frame<'i>(
+        &mut self,
+        input: PartialStream<'i>,
+    ) -> IResult<PartialStream<'i>, Self::Frame> {
+        ADTSFrame::parse(input)
+    }
+
+    fn find_sync(data: &[u8]) -> Option<usize> {
+        data.windows(2)
+            .position(|w| w[0] == 0xff && w[1] & 0xf6 == 0xf0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LATMLayer {
+    pub program: u8,
+    pub layer: u8,
+    pub config: AudioSpecificConfig,
+    pub frame_length_type: u8,
+    pub latm_buffer_fullness: Option<u8>,
+    pub frame_length: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamMuxConfig {
+    pub audio_mux_version: bool,
+    pub audio_mux_version_a: bool,
+    pub tara_buffer_fullness: Option<u32>,
+    pub all_streams_same_time_framing: bool,
+    /// Number of sub frames in an AudioMuxElement - 1
+    pub num_sub_frames: u8,
+    /// All layers of all programs, in order of their stream id
+    pub layers: Vec<LATMLayer>,
+    pub other_data_len_bits: Option<u32>,
+    pub crc_checksum: Option<u8>,
+}
+
+fn latm_get_value(input: BitInput) -> BitResult<u32> {
+    let (mut input, bytes_for_value) = bits::take::<_, u8, _, _>(2_usize).parse_next(input)?;
+    let mut value = 0_u32;
+    for _ in 0..=bytes_for_value {
+        let byte;
+        (input, byte) = bits::take::<_, u32, _, _>(8_usize).parse_next(input)?;
+        value = value << 8 | byte;
+    }
+    Ok((input, value))
+}
+
+impl StreamMuxConfig {
+    pub fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, audio_mux_version) = bits::bool.parse_next(input)?;
+        let (input, audio_mux_version_a) =
+            combinator::cond(audio_mux_version, bits::bool).parse_next(input)?;
+        let audio_mux_version_a = audio_mux_version_a.unwrap_or(false);
+        if audio_mux_version_a {
+            // reserved for future use
+            return verify_error(input);
+        }
+        let (input, tara_buffer_fullness) =
+            combinator::cond(audio_mux_version, latm_get_value).parse_next(input)?;
+        let (mut input, (all_streams_same_time_framing, num_sub_frames, num_program)) = (
+            bits::bool,
+            bits::take::<_, u8, _, _>(6_usize),
+            bits::take::<_, u8, _, _>(4_usize),
+        )
+            .parse_next(input)?;
+        let mut layers: Vec<LATMLayer> = Vec::new();
+        for program in 0..=num_program {
+            let num_layer;
+            (input, num_layer) = bits::take::<_, u8, _, _>(3_usize).parse_next(input)?;
+            for layer in 0..=num_layer {
+                let use_same_config = match layers.last() {
+                    None => false,
+                    Some(_) => {
+                        let flag;
+                        (input, flag) = bits::bool.parse_next(input)?;
+                        flag
+                    }
+                };
+                let config = if use_same_config {
+                    layers.last().unwrap().config.clone()
+                } else if !audio_mux_version {
+                    let config;
+                    (input, config) = AudioSpecificConfig::parse_bits(input)?;
+                    config
+                } else {
+                    let asc_length;
+                    (input, asc_length) = latm_get_value(input)?;
+                    let start = input;
+                    let config;
+                    (input, config) = AudioSpecificConfig::parse_bits(input)?;
+                    let Some(fill_bits) =
+                        (asc_length as usize).checked_sub(bits_consumed(&start, &input))
+                    else {
+                        return verify_error(input);
+                    };
+                    (input, _) = skip_bits(input, fill_bits)?;
+                    config
+                };
+                let frame_length_type;
+                (input, frame_length_type) =
+                    bits::take::<_, u8, _, _>(3_usize).parse_next(input)?;
+                let mut latm_buffer_fullness = None;
+                let mut frame_length = None;
+                match frame_length_type {
+                    0 => {
+                        let fullness;
+                        (input, fullness) = bits::take::<_, u8, _, _>(8_usize).parse_next(input)?;
+                        latm_buffer_fullness = Some(fullness);
+                        // coreFrameOffset only exists for scalable CELP/HVXC layers, which
+                        // are not supported
+                    }
+                    1 => {
+                        let length;
+                        (input, length) = bits::take::<_, u16, _, _>(9_usize).parse_next(input)?;
+                        frame_length = Some(length);
+                    }
+                    3..=5 => {
+                        // CELPframeLengthTableIndex
+                        (input, _) = skip_bits(input, 6)?;
+                    }
+                    6 | 7 => {
+                        // HVXCframeLengthTableIndex
+                        (input, _) = skip_bits(input, 1)?;
+                    }
+                    _ => return verify_error(input),
+                }
+                layers.push(LATMLayer {
+                    program,
+                    layer,
+                    config,
+                    frame_length_type,
+                    latm_buffer_fullness,
+                    frame_length,
+                });
+            }
+        }
+        let other_data_present;
+        (input, other_data_present) = bits::bool.parse_next(input)?;
+        let mut other_data_len_bits = None;
+        if other_data_present {
+            if audio_mux_version {
+                let length;
+                (input, length) = latm_get_value(input)?;
+                other_data_len_bits = Some(length);
+            } else {
+                let mut length = 0_u32;
+                loop {
+                    let (escape, part);
+                    (input, (escape, part)) =
+                        (bits::bool, bits::take::<_, u32, _, _>(8_usize)).parse_next(input)?;
+                    length = (length << 8) + part;
+                    if !escape {
+                        break;
+                    }
+                }
+                other_data_len_bits = Some(length);
+            }
+        }
+        let (input, crc_check_present) = bits::bool.parse_next(input)?;
+        let (input, crc_checksum) =
+            combinator::cond(crc_check_present, bits::take::<_, u8, _, _>(8_usize))
+                .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                audio_mux_version,
+                audio_mux_version_a,
+                tara_buffer_fullness,
+                all_streams_same_time_framing,
+                num_sub_frames,
+                layers,
+                other_data_len_bits,
+                crc_checksum,
+            },
+        ))
+    }
+
+    /// The payload lengths (in bytes) for every layer of one sub frame
+    fn parse_payload_length_info<'i>(&self, mut input: BitInput<'i>) -> BitResult<'i, Vec<usize>> {
+        if !self.all_streams_same_time_framing {
+            // chunk based multiplexing is not supported
+            return verify_error(input);
+        }
+        let mut lengths = Vec::with_capacity(self.layers.len());
+        for layer in self.layers.iter() {
+            match layer.frame_length_type {
+                0 => {
+                    let mut length = 0;
+                    loop {
+                        let part;
+                        (input, part) = bits::take::<_, u8, _, _>(8_usize).parse_next(input)?;
+                        length += part as usize;
+                        if part != 255 {
+                            break;
+                        }
+                    }
+                    lengths.push(length);
+                }
+                1 => lengths.push(layer.frame_length.unwrap_or(0) as usize + 20),
+                _ => return verify_error(input),
+            }
+        }
+        Ok((input, lengths))
+    }
+}
+
+pub struct LATMFrame {
+    pub config: StreamMuxConfig,
+    /// Whether the config was sent in this AudioMuxElement (rather than repeated from before)
+    pub config_in_frame: bool,
+    /// Payloads, indexed as `[sub_frame][layer]`
+    pub sub_frames: Vec<Vec<Vec<u8>>>,
+}
+
+impl LATMFrame {
+    fn parse_audio_mux_element<'i>(
+        input: BitInput<'i>,
+        previous_config: Option<&StreamMuxConfig>,
+    ) -> BitResult<'i, Self> {
+        let (input, use_same_stream_mux) = bits::bool.parse_next(input)?;
+        let (mut input, config) = if use_same_stream_mux {
+            let Some(config) = previous_config else {
+                return verify_error(input);
+            };
+            (input, config.clone())
+        } else {
+            StreamMuxConfig::parse_bits(input)?
+        };
+        let mut sub_frames = Vec::with_capacity(config.num_sub_frames as usize + 1);
+        for _ in 0..=config.num_sub_frames {
+            let lengths;
+            (input, lengths) = config.parse_payload_length_info(input)?;
+            let mut payloads = Vec::with_capacity(lengths.len());
+            for length in lengths {
+                let payload;
+                (input, payload) = take_bytes(input, length)?;
+                payloads.push(payload);
+            }
+            sub_frames.push(payloads);
+        }
+        if let Some(other_data_len_bits) = config.other_data_len_bits {
+            (input, _) = skip_bits(input, other_data_len_bits as usize)?;
+        }
+        Ok((
+            input,
+            Self {
+                config,
+                config_in_frame: !use_same_stream_mux,
+                sub_frames,
+            },
+        ))
+    }
+
+    pub fn audio_specific_config(&self) -> &AudioSpecificConfig {
+        &self.config.layers[0].config
+    }
+}
+
+impl fmt::Debug for LATMFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let config = self.audio_specific_config();
+        write!(
+            f,
+            "LATMFrame(aot={}, {} Hz, channels={}, sub_frames={}, config_in_frame={})",
+            config.audio_object_type,
+            config.sampling_frequency,
+            config.channel_configuration,
+            self.sub_frames.len(),
+            self.config_in_frame,
+        )
+    }
+}
+
+impl AudioFrame for LATMFrame {
+    fn samples(&self) -> u32 {
+        self.audio_specific_config().samples_per_frame() * self.sub_frames.len() as u32
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.audio_specific_config().sampling_frequency
+    }
+}
+
+/// Parses a LOAS AudioSyncStream; remembers the last StreamMuxConfig for the frames that
+/// don't repeat it.
+#[derive(Debug, Default)]
+pub struct LATMParser {
+    config: Option<StreamMuxConfig>,
+}
+
+impl LATMParser {
+    const SYNC_WORD: u16 = 0x2b7;
+
+    pub fn new() -> Self {
+        Self { config: None }
+    }
+
+    /// The config of the first layer of the last seen StreamMuxConfig
+    pub fn audio_specific_config(&self) -> Option<&AudioSpecificConfig> {
+        Some(&self.config.as_ref()?.layers.first()?.config)
+    }
+}
+
+impl FrameParser for LATMParser {
+    type Frame = LATMFrame;
+
+    fn parse_frame<'i>(
+        &mut self,
+        input: PartialStream<'i>,
+    ) -> IResult<PartialStream<'i>, Self::Frame> {
+        let (input, (_, length)) = bits::bits::<_, _, error::Error<(_, usize)>, _, _>((
+            bits::tag(Self::SYNC_WORD, 11_usize),
+            bits::take::<_, u16, _, _>(13_usize),
+        ))
+        .parse_next(input)?;
+        let (rest, element) = token::take(length).parse_next(input)?;
+        let previous_config = self.config.as_ref();
+        let frame = match bits::bits::<_, _, error::Error<(_, usize)>, error::Error<_>, _>(|i| {
+            LATMFrame::parse_audio_mux_element(i, previous_config)
+        })
+        .parse_next(stream(element))
+        {
+            Ok((_, frame)) => frame,
+            Err(_) => return verify_error(input),
+        };
+        self.config = Some(frame.config.clone());
+        Ok((rest, frame))
+    }
+
+    fn find_sync(data: &[u8]) -> Option<usize> {
+        data.windows(2)
+            .position(|w| w[0] == 0x56 && w[1] & 0xe0 == 0xe0)
+    }
+}
@@ -0,0 +1,627 @@
+// Dolby Digital (AC-3) and Dolby Digital Plus (E-AC-3) sync frames, see ATSC A/52
+// - AC-3: HDMV stream_type 0x81, ATSC stream_type 0x81
+// - E-AC-3: HDMV stream_type 0x84 / 0xa1, ATSC stream_type 0x87
+use super::bitstream::{take_bytes, verify_error, BitInput, BitResult};
+use super::stream::{stream, PartialStream};
+use super::{AudioFrame, FrameParser};
+use std::collections::BTreeSet;
+use std::fmt;
+use winnow::{binary::bits, combinator, error, token, IResult, Parser};
+
+const SYNC_WORD: u16 = 0x0b77;
+const SAMPLE_RATES: [u32; 3] = [48000, 44100, 32000];
+const REDUCED_SAMPLE_RATES: [u32; 3] = [24000, 22050, 16000];
+const BITRATES_KBPS: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+const BLOCKS_PER_FRAME: [u32; 4] = [1, 2, 3, 6];
+const SAMPLES_PER_BLOCK: u32 = 256;
+
+/// CRC-16 with polynomial x^16 + x^15 + x^2 + 1, as used by both crc1 and crc2
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0_u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Audio coding mode; the number of front/rear channels (without LFE)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodingMode {
+    DualMono,
+    Mono,
+    Stereo,
+    ThreeFront,
+    TwoFrontOneRear,
+    ThreeFrontOneRear,
+    TwoFrontTwoRear,
+    ThreeFrontTwoRear,
+}
+
+impl AudioCodingMode {
+    fn from_acmod(acmod: u8) -> Self {
+        match acmod & 0x7 {
+            0 => Self::DualMono,
+            1 => Self::Mono,
+            2 => Self::Stereo,
+            3 => Self::ThreeFront,
+            4 => Self::TwoFrontOneRear,
+            5 => Self::ThreeFrontOneRear,
+            6 => Self::TwoFrontTwoRear,
+            _ => Self::ThreeFrontTwoRear,
+        }
+    }
+
+    pub fn channels(&self) -> u8 {
+        match self {
+            Self::DualMono => 2,
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::ThreeFront => 3,
+            Self::TwoFrontOneRear => 3,
+            Self::ThreeFrontOneRear => 4,
+            Self::TwoFrontTwoRear => 4,
+            Self::ThreeFrontTwoRear => 5,
+        }
+    }
+
+    /// Dolby notation, e.g. "3/2"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DualMono => "1+1",
+            Self::Mono => "1/0",
+            Self::Stereo => "2/0",
+            Self::ThreeFront => "3/0",
+            Self::TwoFrontOneRear => "2/1",
+            Self::ThreeFrontOneRear => "3/1",
+            Self::TwoFrontTwoRear => "2/2",
+            Self::ThreeFrontTwoRear => "3/2",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AC3Header {
+    pub crc1: u16,
+    pub fscod: u8,
+    pub frmsizecod: u8,
+    pub bsid: u8,
+    pub bsmod: u8,
+    pub acmod: AudioCodingMode,
+    pub cmixlev: Option<u8>,
+    pub surmixlev: Option<u8>,
+    pub dsurmod: Option<u8>,
+    pub lfeon: bool,
+    pub dialnorm: u8,
+    pub compr: Option<u8>,
+    pub langcod: Option<u8>,
+    /// (mixlevel, roomtyp)
+    pub audio_production_info: Option<(u8, u8)>,
+    /// Only present in dual mono (acmod 0)
+    pub dialnorm2: Option<u8>,
+    pub copyright: bool,
+    pub original: bool,
+    pub timecod1: Option<u16>,
+    pub timecod2: Option<u16>,
+    pub additional_bsi: Option<Vec<u8>>,
+}
+
+impl AC3Header {
+    pub fn frame_size(fscod: u8, frmsizecod: u8) -> Option<usize> {
+        let kbps = *BITRATES_KBPS.get(frmsizecod as usize / 2)?;
+        let words = match fscod {
+            0 => kbps * 2,
+            1 => kbps * 320 / 147 + (frmsizecod & 1) as u32,
+            2 => kbps * 3,
+            _ => return None,
+        };
+        Some(words as usize * 2)
+    }
+
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (_, crc1, fscod, frmsizecod, bsid, bsmod, acmod)) = (
+            bits::tag(SYNC_WORD, 16_usize),
+            bits::take::<_, u16, _, _>(16_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::take::<_, u8, _, _>(6_usize),
+            bits::take::<_, u8, _, _>(5_usize),
+            bits::take::<_, u8, _, _>(3_usize),
+            bits::take::<_, u8, _, _>(3_usize),
+        )
+            .parse_next(input)?;
+        let (input, cmixlev) = combinator::cond(
+            acmod & 0x1 == 1 && acmod != 1,
+            bits::take::<_, u8, _, _>(2_usize),
+        )
+        .parse_next(input)?;
+        let (input, surmixlev) =
+            combinator::cond(acmod & 0x4 != 0, bits::take::<_, u8, _, _>(2_usize))
+                .parse_next(input)?;
+        let (input, dsurmod) =
+            combinator::cond(acmod == 2, bits::take::<_, u8, _, _>(2_usize)).parse_next(input)?;
+        let (input, (lfeon, dialnorm)) =
+            (bits::bool, bits::take::<_, u8, _, _>(5_usize)).parse_next(input)?;
+        let (input, compr) = optional_field(input, 8)?;
+        let (input, langcod) = optional_field(input, 8)?;
+        let (input, audio_production_info) = optional_field(input, 7)?;
+        let mut input = input;
+        let mut dialnorm2 = None;
+        if acmod == 0 {
+            let value;
+            (input, value) = bits::take::<_, u8, _, _>(5_usize).parse_next(input)?;
+            dialnorm2 = Some(value);
+            // compr2, langcod2, (mixlevel2, roomtyp2)
+            (input, _) = optional_field(input, 8)?;
+            (input, _) = optional_field(input, 8)?;
+            (input, _) = optional_field(input, 7)?;
+        }
+        let (input, (copyright, original)) = (bits::bool, bits::bool).parse_next(input)?;
+        let (input, timecod1, timecod2) = if bsid == 6 {
+            // alternate bit stream syntax: xbsi1 and xbsi2 replace the time codes
+            let (input, _) = optional_field(input, 14)?;
+            let (input, _) = optional_field(input, 14)?;
+            (input, None, None)
+        } else {
+            let (input, timecod1) = optional_field(input, 14)?;
+            let (input, timecod2) = optional_field(input, 14)?;
+            (
+                input,
+                timecod1.map(|v| v as u16),
+                timecod2.map(|v| v as u16),
+            )
+        };
+        let (input, addbsie) = bits::bool.parse_next(input)?;
+        let (input, additional_bsi) = match addbsie {
+            false => (input, None),
+            true => {
+                let (input, addbsil) = bits::take::<_, u8, _, _>(6_usize).parse_next(input)?;
+                let (input, data) = take_bytes(input, addbsil as usize + 1)?;
+                (input, Some(data))
+            }
+        };
+        Ok((
+            input,
+            Self {
+                crc1,
+                fscod,
+                frmsizecod,
+                bsid,
+                bsmod,
+                acmod: AudioCodingMode::from_acmod(acmod),
+                cmixlev,
+                surmixlev,
+                dsurmod,
+                lfeon,
+                dialnorm,
+                compr: compr.map(|v| v as u8),
+                langcod: langcod.map(|v| v as u8),
+                audio_production_info: audio_production_info
+                    .map(|v| ((v >> 2) as u8, (v & 0x3) as u8)),
+                dialnorm2,
+                copyright,
+                original,
+                timecod1,
+                timecod2,
+                additional_bsi,
+            },
+        ))
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        // bsid 9 and 10 are the half and quarter sample rate variants
+        SAMPLE_RATES[self.fscod as usize] >> self.bsid.saturating_sub(8)
+    }
+
+    pub fn bitrate(&self) -> u32 {
+        (BITRATES_KBPS[self.frmsizecod as usize / 2] * 1000) >> self.bsid.saturating_sub(8)
+    }
+}
+
+/// A flag followed by a field of `count` bits when it is set
+fn optional_field(input: BitInput, count: usize) -> BitResult<Option<u32>> {
+    let (input, present) = bits::bool.parse_next(input)?;
+    combinator::cond(present, bits::take::<_, u32, _, _>(count)).parse_next(input)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EAC3StreamType {
+    Independent,
+    Dependent,
+    /// An AC-3 bit stream converted to E-AC-3
+    Converted,
+    Reserved,
+}
+
+#[derive(Debug, Clone)]
+pub struct EAC3Header {
+    pub strmtyp: EAC3StreamType,
+    pub substreamid: u8,
+    /// Frame size in 16 bit words - 1
+    pub frmsiz: u16,
+    pub fscod: u8,
+    pub fscod2: Option<u8>,
+    pub numblkscod: u8,
+    pub acmod: AudioCodingMode,
+    pub lfeon: bool,
+    pub bsid: u8,
+    pub dialnorm: u8,
+    pub compr: Option<u8>,
+    pub dialnorm2: Option<u8>,
+    /// Custom channel map of a dependent substream
+    pub chanmap: Option<u16>,
+}
+
+impl EAC3Header {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (_, strmtyp, substreamid, frmsiz, fscod)) = (
+            bits::tag(SYNC_WORD, 16_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::take::<_, u8, _, _>(3_usize),
+            bits::take::<_, u16, _, _>(11_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+        )
+            .parse_next(input)?;
+        let (input, (fscod2, numblkscod)) = if fscod == 3 {
+            let (input, fscod2) = bits::take::<_, u8, _, _>(2_usize).parse_next(input)?;
+            if fscod2 == 3 {
+                return verify_error(input);
+            }
+            (input, (Some(fscod2), 3))
+        } else {
+            let (input, numblkscod) = bits::take::<_, u8, _, _>(2_usize).parse_next(input)?;
+            (input, (None, numblkscod))
+        };
+        let (input, (acmod, lfeon, bsid, dialnorm)) = (
+            bits::take::<_, u8, _, _>(3_usize),
+            bits::bool,
+            bits::take::<_, u8, _, _>(5_usize),
+            bits::take::<_, u8, _, _>(5_usize),
+        )
+            .parse_next(input)?;
+        let (input, compr) = optional_field(input, 8)?;
+        let (input, dialnorm2) = match acmod {
+            0 => {
+                let (input, dialnorm2) = bits::take::<_, u8, _, _>(5_usize).parse_next(input)?;
+                let (input, _compr2) = optional_field(input, 8)?;
+                (input, Some(dialnorm2))
+            }
+            _ => (input, None),
+        };
+        let strmtyp = match strmtyp {
+            0 => EAC3StreamType::Independent,
+            1 => EAC3StreamType::Dependent,
+            2 => EAC3StreamType::Converted,
+            _ => EAC3StreamType::Reserved,
+        };
+        let (input, chanmap) = match strmtyp {
+            EAC3StreamType::Dependent => optional_field(input, 16)?,
+            _ => (input, None),
+        };
+        Ok((
+            input,
+            Self {
+                strmtyp,
+                substreamid,
+                frmsiz,
+                fscod,
+                fscod2,
+                numblkscod,
+                acmod: AudioCodingMode::from_acmod(acmod),
+                lfeon,
+                bsid,
+                dialnorm,
+                compr: compr.map(|v| v as u8),
+                dialnorm2,
+                chanmap: chanmap.map(|v| v as u16),
+            },
+        ))
+    }
+
+    pub fn frame_size(&self) -> usize {
+        (self.frmsiz as usize + 1) * 2
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        match self.fscod2 {
+            Some(fscod2) => REDUCED_SAMPLE_RATES[fscod2 as usize],
+            None => SAMPLE_RATES[self.fscod as usize],
+        }
+    }
+
+    pub fn blocks(&self) -> u32 {
+        BLOCKS_PER_FRAME[self.numblkscod as usize]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SyncFrameHeader {
+    AC3(AC3Header),
+    EAC3(EAC3Header),
+}
+
+pub struct SyncFrame {
+    pub header: SyncFrameHeader,
+    /// The whole frame, starting with the sync word
+    pub data: Vec<u8>,
+    /// Whether crc1 (AC-3 only) checks out
+    pub crc1_ok: Option<bool>,
+    /// Whether the CRC at the end of the frame checks out
+    pub crc2_ok: bool,
+}
+
+impl SyncFrame {
+    /// bsid 0..=10 is AC-3, 11..=16 is E-AC-3
+    const MAX_AC3_BSID: u8 = 10;
+
+    pub fn parse(input: PartialStream) -> IResult<PartialStream, Self> {
+        let (input, start) = combinator::peek(token::take(6_usize)).parse_next(input)?;
+        if u16::from_be_bytes([start[0], start[1]]) != SYNC_WORD {
+            return verify_error(input);
+        }
+        let bsid = start[5] >> 3;
+        let frame_size = if bsid <= Self::MAX_AC3_BSID {
+            AC3Header::frame_size(start[4] >> 6, start[4] & 0x3f)
+        } else if bsid <= 16 {
+            Some(((((start[2] & 0x7) as usize) << 8) | start[3] as usize) * 2 + 2)
+        } else {
+            None
+        };
+        let Some(frame_size) = frame_size else {
+            return verify_error(input);
+        };
+        let (input, data) = token::take(frame_size).parse_next(input)?;
+        let header_result = match bsid <= Self::MAX_AC3_BSID {
+            true => bits::bits::<_, _, error::Error<(_, usize)>, error::Error<_>, _>(
+                AC3Header::parse_bits.map(SyncFrameHeader::AC3),
+            )
+            .parse_next(stream(data)),
+            false => bits::bits::<_, _, error::Error<(_, usize)>, error::Error<_>, _>(
+                EAC3Header::parse_bits.map(SyncFrameHeader::EAC3),
+            )
+            .parse_next(stream(data)),
+        };
+        let Ok((_, header)) = header_result else {
+            return verify_error(input);
+        };
+        let (crc1_ok, crc2_ok) = match header {
+            SyncFrameHeader::AC3(_) => {
+                let size_5_8 = ((frame_size >> 2) + (frame_size >> 4)) << 1;
+                (
+                    Some(crc16(&data[2..size_5_8]) == 0),
+                    crc16(&data[size_5_8..]) == 0,
+                )
+            }
+            SyncFrameHeader::EAC3(_) => (None, crc16(&data[2..]) == 0),
+        };
+        Ok((
+            input,
+            Self {
+                header,
+                data: data.to_vec(),
+                crc1_ok,
+                crc2_ok,
+            },
+        ))
+    }
+
+    pub fn crc_ok(&self) -> bool {
+        self.crc2_ok && self.crc1_ok.unwrap_or(true)
+    }
+
+    pub fn acmod(&self) -> AudioCodingMode {
+        match &self.header {
+            SyncFrameHeader::AC3(h) => h.acmod,
+            SyncFrameHeader::EAC3(h) => h.acmod,
+        }
+    }
+
+    pub fn lfeon(&self) -> bool {
+        match &self.header {
+            SyncFrameHeader::AC3(h) => h.lfeon,
+            SyncFrameHeader::EAC3(h) => h.lfeon,
+        }
+    }
+
+    /// e.g. "3/2.1" for 5.1
+    pub fn channel_layout(&self) -> String {
+        format!(
+            "{}{}",
+            self.acmod().name(),
+            if self.lfeon() { ".1" } else { "" }
+        )
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.acmod().channels() + self.lfeon() as u8
+    }
+
+    /// Dialogue level in dB (-1 ..= -31)
+    pub fn dialnorm_db(&self) -> i8 {
+        let dialnorm = match &self.header {
+            SyncFrameHeader::AC3(h) => h.dialnorm,
+            SyncFrameHeader::EAC3(h) => h.dialnorm,
+        };
+        match dialnorm {
+            0 => -31,
+            value => -(value as i8),
+        }
+    }
+
+    pub fn is_dependent(&self) -> bool {
+        matches!(
+            &self.header,
+            SyncFrameHeader::EAC3(EAC3Header {
+                strmtyp: EAC3StreamType::Dependent,
+                ..
+            })
+        )
+    }
+
+    /// Samples in the frame, regardless of whether it is a dependent substream
+    pub fn frame_samples(&self) -> u32 {
+        match &self.header {
+            SyncFrameHeader::AC3(_) => 6 * SAMPLES_PER_BLOCK,
+            SyncFrameHeader::EAC3(h) => h.blocks() * SAMPLES_PER_BLOCK,
+        }
+    }
+
+    pub fn bitrate(&self) -> u32 {
+        match &self.header {
+            SyncFrameHeader::AC3(h) => h.bitrate(),
+            SyncFrameHeader::EAC3(h) => {
+                (h.frame_size() as u64 * 8 * h.sample_rate() as u64 / self.frame_samples() as u64)
+                    as u32
+            }
+        }
+    }
+}
+
+impl AudioFrame for SyncFrame {
+    /// Dependent substreams carry extra channels for the same time period as the
+    /// independent substream before them, so they don't advance the clock
+    fn samples(&self) -> u32 {
+        match self.is_dependent() {
+            true => 0,
+            false => self.frame_samples(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match &self.header {
+            SyncFrameHeader::AC3(h) => h.sample_rate(),
+            SyncFrameHeader::EAC3(h) => h.sample_rate(),
+        }
+    }
+}
+
+impl fmt::Debug for SyncFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match &self.header {
+            SyncFrameHeader::AC3(_) => "AC-3".to_string(),
+            SyncFrameHeader::EAC3(h) => format!("E-AC-3 {:?} {}", h.strmtyp, h.substreamid),
+        };
+        write!(
+            f,
+            "SyncFrame({}, {} Hz, {}, {} kbps, dialnorm {} dB, size={}, crc_ok={})",
+            kind,
+            self.sample_rate(),
+            self.channel_layout(),
+            self.bitrate() / 1000,
+            self.dialnorm_db(),
+            self.data.len(),
+            self.crc_ok(),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AC3Parser;
+
+impl FrameParser for AC3Parser {
+    type Frame = SyncFrame;
+
+    fn parse_frame<'i>(
+        &mut self,
+        input: PartialStream<'i>,
+    ) -> IResult<PartialStream<'i>, Self::Frame> {
+        SyncFrame::parse(input)
+    }
+
+    fn find_sync(data: &[u8]) -> Option<usize> {
+        data.windows(2).position(|w| w[0] == 0x0b && w[1] == 0x77)
+    }
+}
+
+/// Aggregated properties of an AC-3 / E-AC-3 elementary stream
+#[derive(Debug, Default, Clone)]
+pub struct AC3StreamSummary {
+    pub frames: u64,
+    pub dependent_frames: u64,
+    pub crc_errors: u64,
+    /// (stream type, substream id) of every substream seen
+    pub substreams: BTreeSet<(EAC3StreamType, u8)>,
+    pub sample_rates: BTreeSet<u32>,
+    pub channel_layouts: BTreeSet<String>,
+    pub dialnorms_db: BTreeSet<i8>,
+    pub min_bitrate: Option<u32>,
+    pub max_bitrate: Option<u32>,
+    pub bytes: u64,
+    /// Duration in samples of the independent substream(s)
+    pub samples: u64,
+}
+
+impl AC3StreamSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, frame: &SyncFrame) {
+        self.frames += 1;
+        self.bytes += frame.data.len() as u64;
+        if !frame.crc_ok() {
+            self.crc_errors += 1;
+        }
+        match &frame.header {
+            SyncFrameHeader::AC3(_) => {
+                self.substreams.insert((EAC3StreamType::Independent, 0));
+            }
+            SyncFrameHeader::EAC3(h) => {
+                self.substreams.insert((h.strmtyp, h.substreamid));
+            }
+        }
+        if frame.is_dependent() {
+            self.dependent_frames += 1;
+            return;
+        }
+        self.samples += frame.samples() as u64;
+        self.sample_rates.insert(frame.sample_rate());
+        self.channel_layouts.insert(frame.channel_layout());
+        self.dialnorms_db.insert(frame.dialnorm_db());
+        let bitrate = frame.bitrate();
+        self.min_bitrate = Some(self.min_bitrate.map_or(bitrate, |b| b.min(bitrate)));
+        self.max_bitrate = Some(self.max_bitrate.map_or(bitrate, |b| b.max(bitrate)));
+    }
+
+    pub fn duration_secs(&self) -> f64 {
+        match self.sample_rates.iter().next() {
+            Some(rate) => self.samples as f64 / *rate as f64,
+            None => 0.0,
+        }
+    }
+
+    pub fn average_bitrate(&self) -> u32 {
+        match self.duration_secs() {
+            d if d > 0.0 => (self.bytes as f64 * 8.0 / d) as u32,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for AC3StreamSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} frames ({} dependent), {:.3}s, substreams {:?}, {:?} Hz, layouts {:?}, \
+            dialnorm {:?} dB, bitrate {}..{} kbps (avg {} kbps), {} CRC errors",
+            self.frames,
+            self.dependent_frames,
+            self.duration_secs(),
+            self.substreams,
+            self.sample_rates,
+            self.channel_layouts,
+            self.dialnorms_db,
+            self.min_bitrate.unwrap_or(0) / 1000,
+            self.max_bitrate.unwrap_or(0) / 1000,
+            self.average_bitrate() / 1000,
+            self.crc_errors,
+        )
+    }
+}
@@ -0,0 +1,35 @@
+// Helpers for the bit oriented (rather than byte oriented) parts of audio headers
+use super::stream::Stream;
+use winnow::{
+    binary::bits,
+    combinator,
+    error::{self, ParseError},
+    IResult, Parser,
+};
+
+pub(crate) type BitInput<'i> = (Stream<'i>, usize);
+pub(crate) type BitResult<'i, O> = IResult<BitInput<'i>, O, error::Error<BitInput<'i>>>;
+
+pub(crate) fn verify_error<I: Clone, O>(input: I) -> IResult<I, O, error::Error<I>> {
+    Err(error::ErrMode::Backtrack(error::Error::from_error_kind(
+        input,
+        error::ErrorKind::Verify,
+    )))
+}
+
+pub(crate) fn bits_consumed(start: &BitInput, end: &BitInput) -> usize {
+    (start.0.len() - end.0.len()) * 8 + end.1 - start.1
+}
+
+pub(crate) fn skip_bits(mut input: BitInput, mut count: usize) -> BitResult<()> {
+    while count > 0 {
+        let step = count.min(32);
+        (input, _) = bits::take::<_, u32, _, _>(step).parse_next(input)?;
+        count -= step;
+    }
+    Ok((input, ()))
+}
+
+pub(crate) fn take_bytes(input: BitInput, count: usize) -> BitResult<Vec<u8>> {
+    combinator::repeat(count, bits::take::<_, u8, _, _>(8_usize)).parse_next(input)
+}
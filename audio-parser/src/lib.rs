@@ -0,0 +1,216 @@
+pub mod aac;
+pub mod ac3;
+mod bitstream;
+pub mod lpcm;
+pub mod mpa;
+pub mod stream;
+pub mod wav;
+
+use circular::Buffer;
+use mts_parser::stream_packet::PESPacket;
+use mts_parser::timestamp::{Timestamp, Unwrapper, CLOCK_27MHZ};
+use std::collections::VecDeque;
+
+use winnow::{error, stream::Offset, IResult};
+
+/// We will buffer PES payloads in chunks of this size
+const CHUNK_SIZE: usize = 10 * 1024;
+
+pub trait AudioFrame {
+    /// Number of PCM samples (per channel) this frame decodes to
+    fn samples(&self) -> u32;
+    fn sample_rate(&self) -> u32;
+}
+
+/// A parser for the frames of one audio elementary stream format.
+///
+/// The parser may keep state between frames (e.g. LATM configuration that is only sent
+/// every so many frames).
+pub trait FrameParser {
+    type Frame: AudioFrame;
+
+    fn parse_frame<'i>(
+        &mut self,
+        input: stream::PartialStream<'i>,
+    ) -> IResult<stream::PartialStream<'i>, Self::Frame>;
+
+    /// Offset of the first possible frame start in `data`, if any
+    fn find_sync(data: &[u8]) -> Option<usize>;
+}
+
+#[derive(Debug)]
+pub struct TimedFrame<F> {
+    /// Absolute (unwrapped) presentation time; taken from the PES header when a PES starts
+    /// with this frame, otherwise derived from the previous frames
+    pub pts: Option<Timestamp>,
+    /// Offset of the first byte of the frame in the elementary stream
+    pub offset: u64,
+    pub frame: F,
+}
+
+/// Running position in samples, so that rounding doesn't accumulate over many frames
+#[derive(Debug, Clone, Copy)]
+struct SampleClock {
+    anchor: Timestamp,
+    samples: u64,
+    sample_rate: u32,
+}
+
+impl SampleClock {
+    fn now(&self) -> Timestamp {
+        self.anchor
+            + Timestamp::from_27mhz(
+                (self.samples as i64 * CLOCK_27MHZ) / self.sample_rate.max(1) as i64,
+            )
+    }
+}
+
+/// Splits the payloads of consecutive PES packets (of one PID) into audio frames.
+pub struct AudioFrameIterator<I, P>
+where
+    I: Iterator<Item = PESPacket>,
+    P: FrameParser,
+{
+    packets: I,
+    parser: P,
+    buffer: Buffer,
+    has_reached_eof: bool,
+    consumed: u64,
+    pts_marks: VecDeque<(u64, Timestamp)>,
+    unwrapper: Unwrapper,
+    clock: Option<SampleClock>,
+    last_pts: Option<Timestamp>,
+    skipped_bytes: u64,
+}
+
+impl<I, P> AudioFrameIterator<I, P>
+where
+    I: Iterator<Item = PESPacket>,
+    P: FrameParser,
+{
+    pub fn new(packets: I, parser: P) -> Self {
+        Self {
+            packets,
+            parser,
+            buffer: Buffer::with_capacity(CHUNK_SIZE),
+            has_reached_eof: false,
+            consumed: 0,
+            pts_marks: VecDeque::new(),
+            unwrapper: Unwrapper::new(),
+            clock: None,
+            last_pts: None,
+            skipped_bytes: 0,
+        }
+    }
+
+    /// Number of bytes that were skipped while looking for a frame sync
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    pub fn parser(&self) -> &P {
+        &self.parser
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.buffer.consume(count);
+        self.consumed += count as u64;
+    }
+
+    fn read_packet(&mut self) {
+        let Some(packet) = self.packets.next() else {
+            self.has_reached_eof = true;
+            return;
+        };
+        let position = self.consumed + self.buffer.available_data() as u64;
+        if let Some(pts) = packet.header.as_ref().and_then(|h| h.pts) {
+            self.pts_marks
+                .push_back((position, self.unwrapper.unwrap_90khz(pts)));
+        }
+        if self.buffer.available_space() < packet.data.len() {
+            self.buffer.shift();
+        }
+        if self.buffer.available_space() < packet.data.len() {
+            self.buffer
+                .grow(self.buffer.capacity() + CHUNK_SIZE.max(packet.data.len()));
+        }
+        self.buffer.space()[..packet.data.len()].copy_from_slice(&packet.data);
+        self.buffer.fill(packet.data.len());
+    }
+
+    fn pts_for_frame(&mut self, offset: u64, frame: &P::Frame) -> Option<Timestamp> {
+        let mut pes_pts = None;
+        while let Some((position, pts)) = self.pts_marks.front() {
+            if *position > offset {
+                break;
+            }
+            pes_pts = Some(*pts);
+            self.pts_marks.pop_front();
+        }
+        if pes_pts.is_none() && frame.samples() == 0 {
+            // e.g. an E-AC-3 dependent substream; same time as the frame before it
+            return self.last_pts;
+        }
+        if let Some(pts) = pes_pts {
+            self.clock = Some(SampleClock {
+                anchor: pts,
+                samples: 0,
+                sample_rate: frame.sample_rate(),
+            });
+        }
+        let clock = self.clock.as_mut()?;
+        if clock.sample_rate != frame.sample_rate() {
+            *clock = SampleClock {
+                anchor: clock.now(),
+                samples: 0,
+                sample_rate: frame.sample_rate(),
+            };
+        }
+        let pts = clock.now();
+        clock.samples += frame.samples() as u64;
+        self.last_pts = Some(pts);
+        Some(pts)
+    }
+}
+
+impl<I, P> Iterator for AudioFrameIterator<I, P>
+where
+    I: Iterator<Item = PESPacket>,
+    P: FrameParser,
+{
+    type Item = TimedFrame<P::Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.has_reached_eof && self.buffer.empty() {
+                return None;
+            }
+            let input = stream::partialstream(self.buffer.data(), self.has_reached_eof);
+            match self.parser.parse_frame(input) {
+                Ok((remainder, frame)) => {
+                    let consumed = input.offset_to(&remainder);
+                    let offset = self.consumed;
+                    self.consume(consumed);
+                    let pts = self.pts_for_frame(offset, &frame);
+                    return Some(TimedFrame { pts, offset, frame });
+                }
+                Err(error::ErrMode::Incomplete(_)) if !self.has_reached_eof => {
+                    self.read_packet();
+                }
+                Err(error::ErrMode::Incomplete(_)) => {
+                    // truncated last frame
+                    let remaining = self.buffer.available_data();
+                    self.skipped_bytes += remaining as u64;
+                    self.consume(remaining);
+                }
+                Err(_) => {
+                    // lost sync; skip to the next candidate
+                    let skip = 1 + P::find_sync(&self.buffer.data()[1..])
+                        .unwrap_or(self.buffer.available_data().saturating_sub(2));
+                    self.skipped_bytes += skip as u64;
+                    self.consume(skip);
+                }
+            }
+        }
+    }
+}
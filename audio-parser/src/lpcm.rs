@@ -0,0 +1,329 @@
+// HDMV (Blu-ray / AVCHD) LPCM, stream_type 0x80
+//
+// Every PES payload starts with a 4 byte header, followed by big endian samples. Channels
+// are coded in pairs: odd channel counts have an extra (empty) channel after the last one.
+use super::bitstream::verify_error;
+use super::stream::{stream, Stream};
+use super::wav::{channel, WavFormat, WavWriter};
+use mts_parser::stream_packet::PESPacket;
+use mts_parser::timestamp::{Timestamp, Unwrapper, CLOCK_27MHZ};
+use std::io::{self, Seek, Write};
+use winnow::{binary::bits, error, IResult, Parser};
+
+/// PTS jumps smaller than this are considered jitter rather than missing audio
+const GAP_TOLERANCE_27MHZ: i64 = CLOCK_27MHZ / 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelAssignment {
+    Mono,
+    Stereo,
+    /// L R C
+    ThreeZero,
+    /// L R S
+    TwoOne,
+    /// L R C S
+    ThreeOne,
+    /// L R Ls Rs
+    TwoTwo,
+    /// L R C Ls Rs
+    ThreeTwo,
+    /// L R C LFE Ls Rs
+    ThreeTwoLFE,
+    /// L R C Ls Rls Rrs Rs
+    ThreeFour,
+    /// L R C Ls Rls Rrs Rs LFE
+    ThreeFourLFE,
+}
+
+impl ChannelAssignment {
+    fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            1 => Self::Mono,
+            3 => Self::Stereo,
+            4 => Self::ThreeZero,
+            5 => Self::TwoOne,
+            6 => Self::ThreeOne,
+            7 => Self::TwoTwo,
+            8 => Self::ThreeTwo,
+            9 => Self::ThreeTwoLFE,
+            10 => Self::ThreeFour,
+            11 => Self::ThreeFourLFE,
+            _ => return None,
+        })
+    }
+
+    pub fn channels(&self) -> u16 {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::ThreeZero | Self::TwoOne => 3,
+            Self::ThreeOne | Self::TwoTwo => 4,
+            Self::ThreeTwo => 5,
+            Self::ThreeTwoLFE => 6,
+            Self::ThreeFour => 7,
+            Self::ThreeFourLFE => 8,
+        }
+    }
+
+    /// Channels in the stream, including the padding channel
+    pub fn coded_channels(&self) -> u16 {
+        (self.channels() + 1) & !1
+    }
+
+    pub fn channel_mask(&self) -> u32 {
+        use channel::*;
+        let front = FRONT_LEFT | FRONT_RIGHT;
+        match self {
+            Self::Mono => FRONT_CENTER,
+            Self::Stereo => front,
+            Self::ThreeZero => front | FRONT_CENTER,
+            Self::TwoOne => front | BACK_CENTER,
+            Self::ThreeOne => front | FRONT_CENTER | BACK_CENTER,
+            Self::TwoTwo => front | SIDE_LEFT | SIDE_RIGHT,
+            Self::ThreeTwo => front | FRONT_CENTER | SIDE_LEFT | SIDE_RIGHT,
+            Self::ThreeTwoLFE => front | FRONT_CENTER | LOW_FREQUENCY | SIDE_LEFT | SIDE_RIGHT,
+            Self::ThreeFour => {
+                front | FRONT_CENTER | BACK_LEFT | BACK_RIGHT | SIDE_LEFT | SIDE_RIGHT
+            }
+            Self::ThreeFourLFE => {
+                front
+                    | FRONT_CENTER
+                    | LOW_FREQUENCY
+                    | BACK_LEFT
+                    | BACK_RIGHT
+                    | SIDE_LEFT
+                    | SIDE_RIGHT
+            }
+        }
+    }
+
+    /// For every coded channel, its position in the WAV file (ordered by channel mask bit)
+    pub fn wav_order(&self) -> &'static [usize] {
+        match self {
+            Self::ThreeFour => &[0, 1, 2, 5, 3, 4, 6],
+            Self::ThreeFourLFE => &[0, 1, 2, 6, 4, 5, 7, 3],
+            _ => &[0, 1, 2, 3, 4, 5][..self.channels() as usize],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LPCMHeader {
+    /// Number of sample bytes following the header
+    pub payload_size: u16,
+    pub channel_assignment: ChannelAssignment,
+    pub sample_rate: u32,
+    /// 16, 20 or 24; 20 and 24 bit samples both take 3 bytes
+    pub bits_per_sample: u8,
+    pub start_flag: bool,
+}
+
+impl LPCMHeader {
+    pub const LENGTH: usize = 4;
+
+    pub fn parse(input: Stream) -> IResult<Stream, Self> {
+        let (
+            input,
+            (payload_size, channel_assignment, sampling_frequency, bits_per_sample, start_flag, _),
+        ) = bits::bits::<_, _, error::Error<(_, usize)>, _, _>((
+            bits::take::<_, u16, _, _>(16_usize),
+            bits::take::<_, u8, _, _>(4_usize),
+            bits::take::<_, u8, _, _>(4_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::bool,
+            bits::take::<_, u8, _, _>(5_usize),
+        ))
+        .parse_next(input)?;
+        let channel_assignment = ChannelAssignment::from_code(channel_assignment);
+        let sample_rate = match sampling_frequency {
+            1 => Some(48000),
+            4 => Some(96000),
+            5 => Some(192000),
+            _ => None,
+        };
+        let bits_per_sample = match bits_per_sample {
+            1 => Some(16),
+            2 => Some(20),
+            3 => Some(24),
+            _ => None,
+        };
+        let (Some(channel_assignment), Some(sample_rate), Some(bits_per_sample)) =
+            (channel_assignment, sample_rate, bits_per_sample)
+        else {
+            return verify_error(input);
+        };
+        Ok((
+            input,
+            Self {
+                payload_size,
+                channel_assignment,
+                sample_rate,
+                bits_per_sample,
+                start_flag,
+            },
+        ))
+    }
+
+    pub fn bytes_per_sample(&self) -> usize {
+        match self.bits_per_sample {
+            16 => 2,
+            _ => 3,
+        }
+    }
+
+    /// Size of one sample for every coded channel
+    pub fn sample_frame_size(&self) -> usize {
+        self.channel_assignment.coded_channels() as usize * self.bytes_per_sample()
+    }
+
+    /// Whether the samples of `other` can go in the same WAV file
+    pub fn same_format(&self, other: &LPCMHeader) -> bool {
+        self.channel_assignment == other.channel_assignment
+            && self.sample_rate == other.sample_rate
+            && self.bits_per_sample == other.bits_per_sample
+    }
+
+    pub fn wav_format(&self) -> WavFormat {
+        WavFormat {
+            channels: self.channel_assignment.channels(),
+            sample_rate: self.sample_rate,
+            container_bits: self.bytes_per_sample() as u16 * 8,
+            valid_bits: self.bits_per_sample as u16,
+            channel_mask: self.channel_assignment.channel_mask(),
+        }
+    }
+}
+
+/// Writes the LPCM stream of one PID to a WAV (or RF64) file.
+///
+/// Sample frames that are split over two PES packets are joined, and when the PTS of a PES
+/// packet shows that audio is missing, silence is inserted so the file stays in sync.
+pub struct LPCMExtractor<W: Write + Seek> {
+    writer: Option<W>,
+    wav: Option<WavWriter<W>>,
+    header: Option<LPCMHeader>,
+    pending: Vec<u8>,
+    unwrapper: Unwrapper,
+    start: Option<Timestamp>,
+    inserted_silence: u64,
+}
+
+impl<W: Write + Seek> LPCMExtractor<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Some(writer),
+            wav: None,
+            header: None,
+            pending: Vec::new(),
+            unwrapper: Unwrapper::new(),
+            start: None,
+            inserted_silence: 0,
+        }
+    }
+
+    pub fn header(&self) -> Option<&LPCMHeader> {
+        self.header.as_ref()
+    }
+
+    /// Number of sample frames written, including inserted silence
+    pub fn frames_written(&self) -> u64 {
+        self.wav.as_ref().map_or(0, |wav| wav.frames_written())
+    }
+
+    /// Number of silent sample frames inserted for missing audio
+    pub fn inserted_silence(&self) -> u64 {
+        self.inserted_silence
+    }
+
+    pub fn add_pes(&mut self, pes: &PESPacket) -> io::Result<()> {
+        let Ok((samples, header)) = LPCMHeader::parse(stream(&pes.data)) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid HDMV LPCM header",
+            ));
+        };
+        let samples = &samples[..samples.len().min(header.payload_size as usize)];
+        if let Some(current) = self.header.as_ref() {
+            if !current.same_format(&header) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("LPCM format changed from {:?} to {:?}", current, header),
+                ));
+            }
+        } else {
+            let writer = self.writer.take().expect("Writer is only taken once");
+            self.wav = Some(WavWriter::new(writer, header.wav_format())?);
+            self.header = Some(header);
+        }
+        if let Some(pts) = pes.header.as_ref().and_then(|h| h.pts) {
+            let pts = self.unwrapper.unwrap_90khz(pts);
+            self.fill_gap(pts)?;
+        }
+        self.pending.extend_from_slice(samples);
+        let frame_size = header.sample_frame_size();
+        let complete = self.pending.len() - self.pending.len() % frame_size;
+        let converted = convert_samples(&header, &self.pending[..complete]);
+        self.wav
+            .as_mut()
+            .expect("Writer was created above")
+            .write_samples(&converted)?;
+        self.pending.drain(..complete);
+        Ok(())
+    }
+
+    /// Inserts silence when `pts` is later than the end of the samples written so far
+    fn fill_gap(&mut self, pts: Timestamp) -> io::Result<()> {
+        let header = self.header.expect("Called after the first header");
+        let start = *self.start.get_or_insert(pts);
+        let frames =
+            self.frames_written() + (self.pending.len() / header.sample_frame_size()) as u64;
+        let expected =
+            start + Timestamp::from_27mhz(frames as i64 * CLOCK_27MHZ / header.sample_rate as i64);
+        let gap = (pts - expected).as_27mhz();
+        if gap <= GAP_TOLERANCE_27MHZ {
+            return Ok(());
+        }
+        let missing = ((gap * header.sample_rate as i64 + CLOCK_27MHZ / 2) / CLOCK_27MHZ) as u64;
+        let wav = self
+            .wav
+            .as_mut()
+            .expect("Called after the writer is created");
+        let block = vec![0; wav.format().block_align() as usize * 1024];
+        let mut remaining = missing as usize * wav.format().block_align() as usize;
+        // a partial sample frame left over from the previous packet is dropped
+        self.pending.clear();
+        while remaining > 0 {
+            let count = remaining.min(block.len());
+            wav.write_samples(&block[..count])?;
+            remaining -= count;
+        }
+        self.inserted_silence += missing;
+        Ok(())
+    }
+
+    /// Completes the WAV header; `None` if no LPCM packet was seen
+    pub fn finish(self) -> io::Result<Option<W>> {
+        self.wav.map(|wav| wav.finish()).transpose()
+    }
+}
+
+/// Big endian coded channel order to little endian WAV channel order, dropping padding
+fn convert_samples(header: &LPCMHeader, data: &[u8]) -> Vec<u8> {
+    let bytes = header.bytes_per_sample();
+    let order = header.channel_assignment.wav_order();
+    let out_frame_size = order.len() * bytes;
+    let mut out = vec![0; data.len() / header.sample_frame_size() * out_frame_size];
+    for (frame, out_frame) in data
+        .chunks_exact(header.sample_frame_size())
+        .zip(out.chunks_exact_mut(out_frame_size))
+    {
+        for (channel, position) in order.iter().enumerate() {
+            let sample = &frame[channel * bytes..(channel + 1) * bytes];
+            let target = &mut out_frame[position * bytes..(position + 1) * bytes];
+            for (t, s) in target.iter_mut().zip(sample.iter().rev()) {
+                *t = *s;
+            }
+        }
+    }
+    out
+}
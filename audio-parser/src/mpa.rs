@@ -0,0 +1,531 @@
+// MPEG-1/2 audio layers I, II and III (ISO/IEC 11172-3, 13818-3), and the unofficial
+// MPEG 2.5 extension; stream_type 0x03 (MPEG-1) and 0x04 (MPEG-2)
+use super::bitstream::{verify_error, BitInput, BitResult};
+use super::stream::{stream, PartialStream};
+use super::{AudioFrame, FrameParser};
+use std::collections::BTreeSet;
+use std::fmt;
+use winnow::{
+    binary::{self, bits},
+    combinator, error, token, IResult, Parser,
+};
+
+const SYNC_WORD: u16 = 0x7ff;
+const HEADER_SIZE: usize = 4;
+
+/// Indexed by [MPEG-1 / MPEG-2 (and 2.5)][layer I, II, III][bitrate_index]
+const BITRATES_KBPS: [[[u32; 15]; 3]; 2] = [
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ],
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ],
+];
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// CRC-16 with polynomial x^16 + x^15 + x^2 + 1 and initial value 0xffff
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffff_u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    MPEG1,
+    MPEG2,
+    MPEG25,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    I,
+    II,
+    III,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChannelMode {
+    Stereo,
+    JointStereo,
+    DualChannel,
+    Mono,
+}
+
+#[derive(Debug, Clone)]
+pub struct MPAHeader {
+    pub version: Version,
+    pub layer: Layer,
+    /// protection_bit is 0 when a CRC follows the header
+    pub crc_present: bool,
+    pub bitrate_index: u8,
+    pub sampling_frequency_index: u8,
+    pub padding: bool,
+    pub private: bool,
+    pub channel_mode: ChannelMode,
+    pub mode_extension: u8,
+    pub copyright: bool,
+    pub original: bool,
+    pub emphasis: u8,
+}
+
+impl MPAHeader {
+    pub fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (_, version, layer, protection_bit)) = (
+            bits::tag(SYNC_WORD, 11_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::bool,
+        )
+            .parse_next(input)?;
+        let (input, (bitrate_index, sampling_frequency_index, padding, private)) = (
+            bits::take::<_, u8, _, _>(4_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::bool,
+            bits::bool,
+        )
+            .parse_next(input)?;
+        let (input, (mode, mode_extension, copyright, original, emphasis)) = (
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::bool,
+            bits::bool,
+            bits::take::<_, u8, _, _>(2_usize),
+        )
+            .parse_next(input)?;
+        let version = match version {
+            0 => Version::MPEG25,
+            2 => Version::MPEG2,
+            3 => Version::MPEG1,
+            _ => return verify_error(input),
+        };
+        let layer = match layer {
+            1 => Layer::III,
+            2 => Layer::II,
+            3 => Layer::I,
+            _ => return verify_error(input),
+        };
+        // free format (0) isn't supported, since the frame size can't be known up front
+        if bitrate_index == 0 || bitrate_index == 0xf || sampling_frequency_index == 3 {
+            return verify_error(input);
+        }
+        let channel_mode = match mode {
+            0 => ChannelMode::Stereo,
+            1 => ChannelMode::JointStereo,
+            2 => ChannelMode::DualChannel,
+            _ => ChannelMode::Mono,
+        };
+        Ok((
+            input,
+            Self {
+                version,
+                layer,
+                crc_present: !protection_bit,
+                bitrate_index,
+                sampling_frequency_index,
+                padding,
+                private,
+                channel_mode,
+                mode_extension,
+                copyright,
+                original,
+                emphasis,
+            },
+        ))
+    }
+
+    pub fn bitrate(&self) -> u32 {
+        let version = (self.version != Version::MPEG1) as usize;
+        BITRATES_KBPS[version][self.layer as usize][self.bitrate_index as usize] * 1000
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        let rate = SAMPLE_RATES[self.sampling_frequency_index as usize];
+        match self.version {
+            Version::MPEG1 => rate,
+            Version::MPEG2 => rate / 2,
+            Version::MPEG25 => rate / 4,
+        }
+    }
+
+    pub fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.version) {
+            (Layer::I, _) => 384,
+            (Layer::II, _) | (Layer::III, Version::MPEG1) => 1152,
+            (Layer::III, _) => 576,
+        }
+    }
+
+    /// Size of the whole frame in bytes, including the header
+    pub fn frame_size(&self) -> usize {
+        let size = self.samples_per_frame() / 8 * self.bitrate() / self.sample_rate();
+        match self.layer {
+            // layer I frames are made of 4 byte slots
+            Layer::I => (size as usize / 4 + self.padding as usize) * 4,
+            _ => size as usize + self.padding as usize,
+        }
+    }
+
+    pub fn channels(&self) -> u8 {
+        match self.channel_mode {
+            ChannelMode::Mono => 1,
+            _ => 2,
+        }
+    }
+
+    /// Size of the layer III side information, which follows the header (and CRC)
+    fn side_info_size(&self) -> usize {
+        match (self.version, self.channel_mode) {
+            (Version::MPEG1, ChannelMode::Mono) => 17,
+            (Version::MPEG1, _) => 32,
+            (_, ChannelMode::Mono) => 9,
+            _ => 17,
+        }
+    }
+
+    /// Number of bytes after the CRC that the CRC protects. Layer II is not supported, as
+    /// its bit allocation size depends on the allocation tables.
+    fn crc_protected_size(&self) -> Option<usize> {
+        match self.layer {
+            Layer::I => {
+                let bound = match self.channel_mode {
+                    ChannelMode::Mono => return Some(16),
+                    ChannelMode::JointStereo => 4 * (self.mode_extension as usize + 1),
+                    _ => 32,
+                };
+                // 4 bits of allocation per subband and channel, shared above the bound
+                Some((32 + bound) / 2)
+            }
+            Layer::II => None,
+            Layer::III => Some(self.side_info_size()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VBRHeaderKind {
+    /// Xing header of a VBR stream
+    Xing,
+    /// Xing header of a CBR stream, as written by LAME
+    Info,
+    /// Fraunhofer header
+    VBRI,
+}
+
+/// Xing / Info or VBRI header, sent in place of the audio data of the first frame
+#[derive(Debug, Clone)]
+pub struct VBRHeader {
+    pub kind: VBRHeaderKind,
+    /// Number of audio frames, not including the frame with this header
+    pub frames: Option<u32>,
+    /// Size of the stream in bytes
+    pub bytes: Option<u32>,
+    pub quality: Option<u32>,
+    /// Samples added by the encoder at the start and at the end, from the LAME tag or
+    /// the VBRI header (delay only)
+    pub encoder_delay: Option<u16>,
+    pub encoder_padding: Option<u16>,
+}
+
+impl VBRHeader {
+    const XING_FRAMES: u32 = 0x1;
+    const XING_BYTES: u32 = 0x2;
+    const XING_TOC: u32 = 0x4;
+    const XING_QUALITY: u32 = 0x8;
+    /// The VBRI header is always at this offset from the start of the frame
+    const VBRI_OFFSET: usize = HEADER_SIZE + 32;
+
+    fn parse_xing(data: &[u8]) -> IResult<&[u8], Self> {
+        let (data, tag) =
+            combinator::alt((b"Xing".as_slice(), b"Info".as_slice())).parse_next(data)?;
+        let (data, flags) = binary::be_u32(data)?;
+        let (data, frames) =
+            combinator::cond(flags & Self::XING_FRAMES != 0, binary::be_u32).parse_next(data)?;
+        let (data, bytes) =
+            combinator::cond(flags & Self::XING_BYTES != 0, binary::be_u32).parse_next(data)?;
+        let (data, _) = combinator::cond(flags & Self::XING_TOC != 0, token::take(100_usize))
+            .parse_next(data)?;
+        let (data, quality) =
+            combinator::cond(flags & Self::XING_QUALITY != 0, binary::be_u32).parse_next(data)?;
+        // the LAME tag keeps the encoder delay and padding in 12 bits each at offset 21
+        let (encoder_delay, encoder_padding) = match data.get(21..24) {
+            Some(d)
+                if [b"LAME", b"Lavc", b"Lavf"]
+                    .iter()
+                    .any(|tag| data.starts_with(*tag)) =>
+            {
+                (
+                    Some(((d[0] as u16) << 4) | (d[1] as u16 >> 4)),
+                    Some((((d[1] & 0xf) as u16) << 8) | d[2] as u16),
+                )
+            }
+            _ => (None, None),
+        };
+        let kind = match tag {
+            b"Xing" => VBRHeaderKind::Xing,
+            _ => VBRHeaderKind::Info,
+        };
+        Ok((
+            data,
+            Self {
+                kind,
+                frames,
+                bytes,
+                quality,
+                encoder_delay,
+                encoder_padding,
+            },
+        ))
+    }
+
+    fn parse_vbri(data: &[u8]) -> IResult<&[u8], Self> {
+        let (data, (_, _version, delay, quality, bytes, frames)) = (
+            b"VBRI".as_slice(),
+            binary::be_u16,
+            binary::be_u16,
+            binary::be_u16,
+            binary::be_u32,
+            binary::be_u32,
+        )
+            .parse_next(data)?;
+        Ok((
+            data,
+            Self {
+                kind: VBRHeaderKind::VBRI,
+                frames: Some(frames),
+                bytes: Some(bytes),
+                quality: Some(quality as u32),
+                encoder_delay: Some(delay),
+                encoder_padding: None,
+            },
+        ))
+    }
+
+    /// Looks for a VBR header in a layer III frame
+    fn find(header: &MPAHeader, frame: &[u8]) -> Option<Self> {
+        if header.layer != Layer::III {
+            return None;
+        }
+        let xing_offset = HEADER_SIZE + 2 * header.crc_present as usize + header.side_info_size();
+        if let Some(Ok((_, vbr))) = frame.get(xing_offset..).map(Self::parse_xing) {
+            return Some(vbr);
+        }
+        frame
+            .get(Self::VBRI_OFFSET..)
+            .and_then(|data| Self::parse_vbri(data).ok())
+            .map(|(_, vbr)| vbr)
+    }
+}
+
+pub struct MPAFrame {
+    pub header: MPAHeader,
+    pub crc: Option<u16>,
+    /// Whether the CRC checks out; None if there is none, or for layer II
+    pub crc_ok: Option<bool>,
+    pub vbr_header: Option<VBRHeader>,
+    /// The whole frame, starting with the header
+    pub data: Vec<u8>,
+}
+
+impl MPAFrame {
+    pub fn parse(input: PartialStream) -> IResult<PartialStream, Self> {
+        let (_, start) = combinator::peek(token::take(HEADER_SIZE)).parse_next(input)?;
+        let Ok((_, header)) =
+            bits::bits::<_, _, error::Error<(_, usize)>, error::Error<_>, _>(MPAHeader::parse_bits)
+                .parse_next(stream(start))
+        else {
+            return verify_error(input);
+        };
+        let (input, data) = token::take(header.frame_size()).parse_next(input)?;
+        let crc = match header.crc_present {
+            true => data.get(4..6).map(|c| u16::from_be_bytes([c[0], c[1]])),
+            false => None,
+        };
+        let crc_ok = match (crc, header.crc_protected_size()) {
+            (Some(crc), Some(size)) => data.get(6..6 + size).map(|protected| {
+                let mut checked = data[2..4].to_vec();
+                checked.extend_from_slice(protected);
+                crc16(&checked) == crc
+            }),
+            _ => None,
+        };
+        let vbr_header = VBRHeader::find(&header, data);
+        Ok((
+            input,
+            Self {
+                header,
+                crc,
+                crc_ok,
+                vbr_header,
+                data: data.to_vec(),
+            },
+        ))
+    }
+}
+
+impl AudioFrame for MPAFrame {
+    /// A frame with a VBR header carries no audio
+    fn samples(&self) -> u32 {
+        match self.vbr_header {
+            Some(_) => 0,
+            None => self.header.samples_per_frame(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.header.sample_rate()
+    }
+}
+
+impl fmt::Debug for MPAFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MPAFrame({:?} layer {:?}, {} Hz, {:?}, {} kbps, padding={}, size={}",
+            self.header.version,
+            self.header.layer,
+            self.header.sample_rate(),
+            self.header.channel_mode,
+            self.header.bitrate() / 1000,
+            self.header.padding,
+            self.data.len(),
+        )?;
+        if let Some(crc) = self.crc {
+            write!(f, ", crc=0x{:04x} ok={:?}", crc, self.crc_ok)?;
+        }
+        if let Some(vbr) = &self.vbr_header {
+            write!(f, ", {:?}", vbr)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MPAParser;
+
+impl FrameParser for MPAParser {
+    type Frame = MPAFrame;
+
+    fn parse_frame<'i>(
+        &mut self,
+        input: PartialStream<'i>,
+    ) -> IResult<PartialStream<'i>, Self::Frame> {
+        MPAFrame::parse(input)
+    }
+
+    fn find_sync(data: &[u8]) -> Option<usize> {
+        data.windows(2)
+            .position(|w| w[0] == 0xff && w[1] & 0xe0 == 0xe0)
+    }
+}
+
+/// Aggregated properties of an MPEG audio elementary stream
+#[derive(Debug, Default, Clone)]
+pub struct MPAStreamSummary {
+    pub frames: u64,
+    pub crc_errors: u64,
+    pub formats: BTreeSet<(Version, Layer)>,
+    pub sample_rates: BTreeSet<u32>,
+    pub channel_modes: BTreeSet<ChannelMode>,
+    pub min_bitrate: Option<u32>,
+    pub max_bitrate: Option<u32>,
+    pub bytes: u64,
+    pub samples: u64,
+    /// The first VBR header seen
+    pub vbr_header: Option<VBRHeader>,
+}
+
+impl MPAStreamSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, frame: &MPAFrame) {
+        self.frames += 1;
+        if frame.crc_ok == Some(false) {
+            self.crc_errors += 1;
+        }
+        if let Some(vbr_header) = &frame.vbr_header {
+            if self.vbr_header.is_none() {
+                self.vbr_header = Some(vbr_header.clone());
+            }
+            return;
+        }
+        let header = &frame.header;
+        self.bytes += frame.data.len() as u64;
+        self.samples += frame.samples() as u64;
+        self.formats.insert((header.version, header.layer));
+        self.sample_rates.insert(header.sample_rate());
+        self.channel_modes.insert(header.channel_mode);
+        let bitrate = header.bitrate();
+        self.min_bitrate = Some(self.min_bitrate.map_or(bitrate, |b| b.min(bitrate)));
+        self.max_bitrate = Some(self.max_bitrate.map_or(bitrate, |b| b.max(bitrate)));
+    }
+
+    /// Duration of the decoded audio, without the encoder delay and padding if a LAME
+    /// tag says how long they are
+    pub fn duration_secs(&self) -> f64 {
+        let Some(rate) = self.sample_rates.iter().next() else {
+            return 0.0;
+        };
+        let (delay, padding) = self.vbr_header.as_ref().map_or((0, 0), |vbr| {
+            (
+                vbr.encoder_delay.unwrap_or(0) as u64,
+                vbr.encoder_padding.unwrap_or(0) as u64,
+            )
+        });
+        self.samples.saturating_sub(delay + padding) as f64 / *rate as f64
+    }
+
+    pub fn average_bitrate(&self) -> u32 {
+        match self.duration_secs() {
+            d if d > 0.0 => (self.bytes as f64 * 8.0 / d) as u32,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for MPAStreamSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, {:.3}s, {:?}, {:?} Hz, {:?}, bitrate {}..{} kbps (avg {} kbps), \
+            {} CRC errors",
+            self.frames,
+            self.duration_secs(),
+            self.formats,
+            self.sample_rates,
+            self.channel_modes,
+            self.min_bitrate.unwrap_or(0) / 1000,
+            self.max_bitrate.unwrap_or(0) / 1000,
+            self.average_bitrate() / 1000,
+            self.crc_errors,
+        )?;
+        if let Some(vbr) = &self.vbr_header {
+            write!(f, ", {:?}", vbr)?;
+        }
+        Ok(())
+    }
+}
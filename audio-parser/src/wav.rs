@@ -0,0 +1,133 @@
+// WAVE_FORMAT_EXTENSIBLE writer that switches to RF64 (EBU Tech 3306) when the data
+// doesn't fit in a 32 bit RIFF size.
+//
+// The header is written up front with a JUNK chunk that is large enough to be replaced by
+// a ds64 chunk, so the sizes can be patched in place once all samples are known.
+use std::io::{self, Seek, SeekFrom, Write};
+
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+const KSDATAFORMAT_SUBTYPE_PCM: [u8; 16] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+const DS64_SIZE: u32 = 28;
+const FMT_SIZE: u32 = 40;
+/// RIFF header + JUNK/ds64 + fmt + data chunk header
+const HEADER_SIZE: u64 = 12 + 8 + DS64_SIZE as u64 + 8 + FMT_SIZE as u64 + 8;
+
+pub mod channel {
+    pub const FRONT_LEFT: u32 = 0x1;
+    pub const FRONT_RIGHT: u32 = 0x2;
+    pub const FRONT_CENTER: u32 = 0x4;
+    pub const LOW_FREQUENCY: u32 = 0x8;
+    pub const BACK_LEFT: u32 = 0x10;
+    pub const BACK_RIGHT: u32 = 0x20;
+    pub const BACK_CENTER: u32 = 0x100;
+    pub const SIDE_LEFT: u32 = 0x200;
+    pub const SIDE_RIGHT: u32 = 0x400;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    /// Size of one sample in the file; 16 or 24
+    pub container_bits: u16,
+    /// Significant bits in each sample, e.g. 20 in a 24 bit container
+    pub valid_bits: u16,
+    pub channel_mask: u32,
+}
+
+impl WavFormat {
+    pub fn block_align(&self) -> u16 {
+        self.channels * self.container_bits / 8
+    }
+}
+
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    format: WavFormat,
+    data_size: u64,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, format: WavFormat) -> io::Result<Self> {
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&u32::MAX.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"JUNK")?;
+        writer.write_all(&DS64_SIZE.to_le_bytes())?;
+        writer.write_all(&[0; DS64_SIZE as usize])?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&FMT_SIZE.to_le_bytes())?;
+        writer.write_all(&WAVE_FORMAT_EXTENSIBLE.to_le_bytes())?;
+        writer.write_all(&format.channels.to_le_bytes())?;
+        writer.write_all(&format.sample_rate.to_le_bytes())?;
+        let byte_rate = format.sample_rate * format.block_align() as u32;
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&format.block_align().to_le_bytes())?;
+        writer.write_all(&format.container_bits.to_le_bytes())?;
+        writer.write_all(&22_u16.to_le_bytes())?;
+        writer.write_all(&format.valid_bits.to_le_bytes())?;
+        writer.write_all(&format.channel_mask.to_le_bytes())?;
+        writer.write_all(&KSDATAFORMAT_SUBTYPE_PCM)?;
+
+        writer.write_all(b"data")?;
+        writer.write_all(&u32::MAX.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            format,
+            data_size: 0,
+        })
+    }
+
+    pub fn format(&self) -> &WavFormat {
+        &self.format
+    }
+
+    /// Number of sample frames (one sample for every channel) written so far
+    pub fn frames_written(&self) -> u64 {
+        self.data_size / self.format.block_align() as u64
+    }
+
+    /// Appends interleaved little endian samples
+    pub fn write_samples(&mut self, data: &[u8]) -> io::Result<()> {
+        self.writer.write_all(data)?;
+        self.data_size += data.len() as u64;
+        Ok(())
+    }
+
+    /// Patches the sizes in the header and returns the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.data_size % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        let riff_size = HEADER_SIZE - 8 + self.data_size + self.data_size % 2;
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(0))?;
+        match (u32::try_from(riff_size), u32::try_from(self.data_size)) {
+            (Ok(riff_size), Ok(data_size)) => {
+                self.writer.seek(SeekFrom::Start(4))?;
+                self.writer.write_all(&riff_size.to_le_bytes())?;
+                self.writer.seek(SeekFrom::Start(HEADER_SIZE - 4))?;
+                self.writer.write_all(&data_size.to_le_bytes())?;
+            }
+            _ => {
+                // RF64: the 32 bit sizes stay at 0xffffffff, the real ones go in ds64
+                self.writer.write_all(b"RF64")?;
+                self.writer.seek(SeekFrom::Start(12))?;
+                self.writer.write_all(b"ds64")?;
+                self.writer.write_all(&DS64_SIZE.to_le_bytes())?;
+                self.writer.write_all(&riff_size.to_le_bytes())?;
+                self.writer.write_all(&self.data_size.to_le_bytes())?;
+                self.writer
+                    .write_all(&self.frames_written().to_le_bytes())?;
+                // no table entries
+                self.writer.write_all(&0_u32.to_le_bytes())?;
+            }
+        }
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
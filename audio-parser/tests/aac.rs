@@ -0,0 +1,99 @@
+use audio_parser::aac::AudioSpecificConfig;
+use audio_parser::stream::stream;
+
+/// Packs (value, bit count) pairs MSB first, padding the last byte with zero bits
+fn pack(fields: &[(u32, usize)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut used_bits = 0;
+    for &(value, count) in fields {
+        for bit in (0..count).rev() {
+            if used_bits % 8 == 0 {
+                bytes.push(0);
+            }
+            if (value >> bit) & 1 == 1 {
+                *bytes.last_mut().unwrap() |= 0x80 >> (used_bits % 8);
+            }
+            used_bits += 1;
+        }
+    }
+    bytes
+}
+
+fn round_trip(data: &[u8]) -> AudioSpecificConfig {
+    let (_, config) = AudioSpecificConfig::parse(stream(data)).unwrap();
+    assert_eq!(config.to_bytes(), data);
+    config
+}
+
+#[test]
+fn aac_lc() {
+    let config = round_trip(&[0x11, 0x90]);
+    assert_eq!(config.audio_object_type, 2);
+    assert_eq!(config.sampling_frequency, 48000);
+    assert_eq!(config.channel_configuration, 2);
+    assert!(!config.extension_flag);
+}
+
+#[test]
+fn scalable_layer_nr_without_extension() {
+    // AAC scalable, 44.1 kHz mono, core coder delay 100, layerNr 3
+    let data = pack(&[
+        (6, 5),
+        (4, 4),
+        (1, 4),
+        (0, 1),
+        (1, 1),
+        (100, 14),
+        (0, 1),
+        (3, 3),
+    ]);
+    let config = round_trip(&data);
+    assert_eq!(config.core_coder_delay, Some(100));
+    assert_eq!(config.layer_nr, Some(3));
+}
+
+#[test]
+fn error_resilient_scalable_with_extension() {
+    // extensionFlag comes before layerNr, then the resilience flags and extensionFlag3
+    let data = pack(&[
+        (20, 5),
+        (4, 4),
+        (1, 4),
+        (0, 1),
+        (0, 1),
+        (1, 1),
+        (5, 3),
+        (0b101, 3),
+        (1, 1),
+    ]);
+    let config = round_trip(&data);
+    assert!(config.extension_flag);
+    assert_eq!(config.layer_nr, Some(5));
+    assert_eq!(config.resilience_flags, Some([true, false, true]));
+    assert!(config.extension_flag3);
+}
+
+#[test]
+fn bsac_with_sbr() {
+    // SBR, 24 kHz stereo core at 48 kHz, ER BSAC with extensionChannelConfiguration
+    let data = pack(&[
+        (5, 5),
+        (6, 4),
+        (2, 4),
+        (3, 4),
+        (22, 5),
+        (2, 4),
+        (1, 1),
+        (0, 1),
+        (1, 1),
+        (7, 5),
+        (300, 11),
+        (0, 1),
+    ]);
+    let config = round_trip(&data);
+    assert!(config.sbr_present);
+    assert_eq!(config.audio_object_type, 22);
+    assert_eq!(config.extension_channel_configuration, Some(2));
+    assert_eq!(config.bsac_layer, Some((7, 300)));
+    assert_eq!(config.samples_per_frame(), 960);
+}
@@ -0,0 +1,209 @@
+use audio_parser::ac3::{
+    AC3Header, AC3Parser, AC3StreamSummary, AudioCodingMode, EAC3StreamType, SyncFrame,
+    SyncFrameHeader,
+};
+use audio_parser::stream::partialstream;
+use audio_parser::{AudioFrame, AudioFrameIterator, FrameParser};
+use mts_parser::stream_packet::{PESHeader, PESPacket};
+use mts_parser::timestamp::Timestamp;
+use winnow::error::ErrMode;
+
+/// A 128 byte frame: `start` (with crc1 where there is one), zero bytes and crc2
+fn frame(start: &[u8], crc2: [u8; 2]) -> Vec<u8> {
+    let mut data = vec![0; 128];
+    data[..start.len()].copy_from_slice(start);
+    data[126..].copy_from_slice(&crc2);
+    data
+}
+
+/// AC-3, 48 kHz, 32 kbps, 3/2 with LFE, dialnorm 27, copyright and original
+fn ac3_frame() -> Vec<u8> {
+    frame(
+        &[0x0b, 0x77, 0xa5, 0x52, 0x00, 0x40, 0xe1, 0xd8, 0xc0],
+        [0, 0],
+    )
+}
+
+/// E-AC-3 independent substream 0, 48 kHz, 6 blocks, 2/0, dialnorm 31
+fn eac3_independent_frame() -> Vec<u8> {
+    frame(&[0x0b, 0x77, 0x00, 0x3f, 0x34, 0x87, 0xc0], [0xe6, 0xfc])
+}
+
+/// E-AC-3 dependent substream 0 with LFE and a custom channel map
+fn eac3_dependent_frame() -> Vec<u8> {
+    frame(
+        &[0x0b, 0x77, 0x40, 0x3f, 0x35, 0x87, 0xd0, 0x20],
+        [0xad, 0x50],
+    )
+}
+
+fn parse(data: &[u8]) -> SyncFrame {
+    let (rest, frame) = SyncFrame::parse(partialstream(data, true)).unwrap();
+    assert!(rest.is_empty());
+    frame
+}
+
+#[test]
+fn ac3_frame_sizes() {
+    // 48 kHz: 2 words per kbps
+    assert_eq!(AC3Header::frame_size(0, 0), Some(128));
+    assert_eq!(AC3Header::frame_size(0, 37), Some(2560));
+    // 44.1 kHz: odd frmsizecod pads the frame by a word
+    assert_eq!(AC3Header::frame_size(1, 0), Some(138));
+    assert_eq!(AC3Header::frame_size(1, 1), Some(140));
+    assert_eq!(AC3Header::frame_size(1, 37), Some(2788));
+    // 32 kHz: 3 words per kbps
+    assert_eq!(AC3Header::frame_size(2, 37), Some(3840));
+    assert_eq!(AC3Header::frame_size(3, 0), None);
+    assert_eq!(AC3Header::frame_size(0, 38), None);
+}
+
+#[test]
+fn ac3_header() {
+    let frame = parse(&ac3_frame());
+    let SyncFrameHeader::AC3(header) = &frame.header else {
+        panic!("not AC-3");
+    };
+    assert_eq!(header.bsid, 8);
+    assert_eq!(header.acmod, AudioCodingMode::ThreeFrontTwoRear);
+    assert_eq!((header.cmixlev, header.surmixlev), (Some(0), Some(0)));
+    assert_eq!(header.dsurmod, None);
+    assert!(header.copyright && header.original);
+    assert_eq!(header.additional_bsi, None);
+    assert_eq!(frame.crc1_ok, Some(true));
+    assert!(frame.crc_ok());
+    assert_eq!(frame.sample_rate(), 48000);
+    assert_eq!(frame.bitrate(), 32000);
+    assert_eq!(frame.channel_layout(), "3/2.1");
+    assert_eq!(frame.channels(), 6);
+    assert_eq!(frame.dialnorm_db(), -27);
+    assert_eq!(frame.samples(), 1536);
+}
+
+#[test]
+fn ac3_crc_errors() {
+    let mut data = ac3_frame();
+    data[40] ^= 1;
+    let frame = parse(&data);
+    assert_eq!(frame.crc1_ok, Some(false));
+    assert!(frame.crc2_ok);
+    assert!(!frame.crc_ok());
+    let mut data = ac3_frame();
+    data[100] ^= 1;
+    let frame = parse(&data);
+    assert_eq!(frame.crc1_ok, Some(true));
+    assert!(!frame.crc_ok());
+}
+
+#[test]
+fn eac3_substreams() {
+    let independent = parse(&eac3_independent_frame());
+    let SyncFrameHeader::EAC3(header) = &independent.header else {
+        panic!("not E-AC-3");
+    };
+    assert_eq!(header.strmtyp, EAC3StreamType::Independent);
+    assert_eq!((header.frame_size(), header.blocks()), (128, 6));
+    assert_eq!(independent.crc1_ok, None);
+    assert!(independent.crc_ok());
+    assert_eq!(independent.channel_layout(), "2/0");
+    assert_eq!(independent.dialnorm_db(), -31);
+    assert_eq!(independent.bitrate(), 32000);
+    assert_eq!(independent.samples(), 1536);
+
+    let dependent = parse(&eac3_dependent_frame());
+    let SyncFrameHeader::EAC3(header) = &dependent.header else {
+        panic!("not E-AC-3");
+    };
+    assert_eq!(header.strmtyp, EAC3StreamType::Dependent);
+    assert_eq!(header.chanmap, Some(0x0200));
+    assert!(dependent.is_dependent() && dependent.crc_ok());
+    // the same time as the independent substream, so it doesn't add samples
+    assert_eq!((dependent.frame_samples(), dependent.samples()), (1536, 0));
+}
+
+#[test]
+fn eac3_reduced_sample_rate() {
+    let frame = parse(&frame(
+        &[0x0b, 0x77, 0x00, 0x3f, 0xd4, 0x87, 0xc0],
+        [0xab, 0xa8],
+    ));
+    let SyncFrameHeader::EAC3(header) = &frame.header else {
+        panic!("not E-AC-3");
+    };
+    assert_eq!((header.fscod2, header.blocks()), (Some(1), 6));
+    assert_eq!(frame.sample_rate(), 22050);
+    assert!(frame.crc_ok());
+}
+
+#[test]
+fn incomplete_and_lost_sync() {
+    let data = ac3_frame();
+    assert!(matches!(
+        SyncFrame::parse(partialstream(&data[..100], false)),
+        Err(ErrMode::Incomplete(_))
+    ));
+    assert!(SyncFrame::parse(partialstream(&data[1..], true)).is_err());
+    let mut garbage = vec![0x0b, 0x00, 0x12];
+    garbage.extend(&data);
+    assert_eq!(AC3Parser::find_sync(&garbage), Some(3));
+}
+
+fn pes(pts: Option<u64>, data: Vec<u8>) -> PESPacket {
+    PESPacket {
+        stream_id: 0xbd,
+        header: Some(PESHeader {
+            scrambling_control: 0,
+            priority: false,
+            data_alignment_indicator: true,
+            copyright: false,
+            is_original: true,
+            pts,
+            dts: None,
+            escr: None,
+            es_rate: None,
+            dsm_trick_mode: None,
+            additional_copy_info: None,
+            previous_pes_packet_crc: None,
+            pes_extension: None,
+        }),
+        data,
+    }
+}
+
+#[test]
+fn frames_in_pes_packets() {
+    // two frames and garbage in one PES, then an independent and a dependent substream
+    let mut first = ac3_frame();
+    first.extend([0x12, 0x34]);
+    first.extend(ac3_frame());
+    let mut second = eac3_independent_frame();
+    second.extend(eac3_dependent_frame());
+    let packets = vec![pes(Some(90_000), first), pes(None, second)];
+    let mut frames = AudioFrameIterator::new(packets.into_iter(), AC3Parser);
+    let mut summary = AC3StreamSummary::new();
+    let mut times = Vec::new();
+    for frame in frames.by_ref() {
+        times.push((frame.offset, frame.pts.map(|pts| pts.as_90khz())));
+        summary.add(&frame.frame);
+    }
+    // 1536 samples at 48 kHz are 2880 ticks at 90 kHz
+    assert_eq!(
+        times,
+        [
+            (0, Some(90_000)),
+            (130, Some(92_880)),
+            (258, Some(95_760)),
+            (386, Some(95_760))
+        ]
+    );
+    assert_eq!(frames.skipped_bytes(), 2);
+    assert_eq!((summary.frames, summary.dependent_frames), (4, 1));
+    assert_eq!(summary.samples, 3 * 1536);
+    assert_eq!(summary.crc_errors, 0);
+    assert_eq!(summary.channel_layouts.len(), 2);
+    assert_eq!(summary.sample_rates.iter().collect::<Vec<_>>(), [&48000]);
+    assert_eq!(
+        Timestamp::from_secs_f64(summary.duration_secs()).as_90khz(),
+        8640
+    );
+}
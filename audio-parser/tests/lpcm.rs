@@ -0,0 +1,242 @@
+use audio_parser::lpcm::{ChannelAssignment, LPCMExtractor, LPCMHeader};
+use audio_parser::stream::stream;
+use audio_parser::wav::{WavFormat, WavWriter};
+use mts_parser::stream_packet::{PESHeader, PESPacket};
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+
+/// Size of the WAV header up to the samples
+const HEADER_SIZE: usize = 104;
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(data[at..at + 2].try_into().unwrap())
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn pes(pts: Option<u64>, header: [u8; 4], samples: &[u8]) -> PESPacket {
+    let mut data = header.to_vec();
+    data.extend(samples);
+    PESPacket {
+        stream_id: 0xbd,
+        header: Some(PESHeader {
+            scrambling_control: 0,
+            priority: false,
+            data_alignment_indicator: false,
+            copyright: false,
+            is_original: true,
+            pts,
+            dts: None,
+            escr: None,
+            es_rate: None,
+            dsm_trick_mode: None,
+            additional_copy_info: None,
+            previous_pes_packet_crc: None,
+            pes_extension: None,
+        }),
+        data,
+    }
+}
+
+/// Stereo, 48 kHz, 16 bit, with `size` bytes of samples
+fn stereo_header(size: u16) -> [u8; 4] {
+    let [high, low] = size.to_be_bytes();
+    [high, low, 0x31, 0x40]
+}
+
+#[test]
+fn lpcm_headers() {
+    let (_, header) = LPCMHeader::parse(stream(&[0x03, 0xc0, 0x31, 0x40])).unwrap();
+    assert_eq!(
+        header,
+        LPCMHeader {
+            payload_size: 960,
+            channel_assignment: ChannelAssignment::Stereo,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            start_flag: false,
+        }
+    );
+    assert_eq!(header.sample_frame_size(), 4);
+    // 5.1 at 96 kHz, 24 bit, with the start flag
+    let (_, header) = LPCMHeader::parse(stream(&[0x11, 0x40, 0x94, 0xe0])).unwrap();
+    assert_eq!(header.channel_assignment, ChannelAssignment::ThreeTwoLFE);
+    assert_eq!((header.sample_rate, header.bits_per_sample), (96000, 24));
+    assert!(header.start_flag);
+    assert_eq!(header.sample_frame_size(), 18);
+    // 3/0 at 192 kHz, 20 bit: three channels coded as four, in 3 bytes each
+    let (_, header) = LPCMHeader::parse(stream(&[0x00, 0x00, 0x45, 0x80])).unwrap();
+    assert_eq!(header.channel_assignment.coded_channels(), 4);
+    assert_eq!(header.sample_frame_size(), 12);
+    assert_eq!(
+        header.wav_format(),
+        WavFormat {
+            channels: 3,
+            sample_rate: 192000,
+            container_bits: 24,
+            valid_bits: 20,
+            channel_mask: 0x7,
+        }
+    );
+    // reserved channel assignment, sampling frequency and bits per sample
+    for header in [[0, 0, 0x21, 0x40], [0, 0, 0x32, 0x40], [0, 0, 0x31, 0x00]] {
+        assert!(LPCMHeader::parse(stream(&header)).is_err());
+    }
+}
+
+#[test]
+fn wav_file() {
+    let mut extractor = LPCMExtractor::new(Cursor::new(Vec::new()));
+    // left 0x0102, right 0x0304 twice; the second sample frame is split over two packets
+    extractor
+        .add_pes(&pes(Some(0), stereo_header(6), &[1, 2, 3, 4, 1, 2]))
+        .unwrap();
+    assert_eq!(extractor.frames_written(), 1);
+    extractor
+        .add_pes(&pes(None, stereo_header(2), &[3, 4]))
+        .unwrap();
+    assert_eq!(extractor.frames_written(), 2);
+    let data = extractor.finish().unwrap().unwrap().into_inner();
+    assert_eq!(data.len(), HEADER_SIZE + 8);
+    assert_eq!(&data[..4], b"RIFF");
+    assert_eq!(le_u32(&data, 4), (HEADER_SIZE - 8 + 8) as u32);
+    assert_eq!(&data[8..16], b"WAVEJUNK");
+    assert_eq!(&data[48..52], b"fmt ");
+    assert_eq!(le_u16(&data, 56), 0xfffe);
+    // channels, sample rate, byte rate, block align, container and valid bits, mask
+    assert_eq!(le_u16(&data, 58), 2);
+    assert_eq!(le_u32(&data, 60), 48000);
+    assert_eq!(le_u32(&data, 64), 192000);
+    assert_eq!((le_u16(&data, 68), le_u16(&data, 70)), (4, 16));
+    assert_eq!(le_u16(&data, 74), 16);
+    assert_eq!(le_u32(&data, 76), 0x3);
+    assert_eq!(&data[96..100], b"data");
+    assert_eq!(le_u32(&data, 100), 8);
+    assert_eq!(&data[HEADER_SIZE..], [2, 1, 4, 3, 2, 1, 4, 3]);
+}
+
+#[test]
+fn channel_order() {
+    // 7.1, 16 bit: L R C Ls Rls Rrs Rs LFE in the stream, by channel mask bit in the file
+    let mut extractor = LPCMExtractor::new(Cursor::new(Vec::new()));
+    let samples: Vec<u8> = (1..=8).flat_map(|channel| [0, channel]).collect();
+    extractor
+        .add_pes(&pes(Some(0), [0, 16, 0xb1, 0x40], &samples))
+        .unwrap();
+    let data = extractor.finish().unwrap().unwrap().into_inner();
+    let channels: Vec<u8> = data[HEADER_SIZE..].chunks(2).map(|s| s[0]).collect();
+    assert_eq!(channels, [1, 2, 3, 8, 5, 6, 4, 7]);
+    // mono is coded with an empty second channel, which is dropped
+    let mut extractor = LPCMExtractor::new(Cursor::new(Vec::new()));
+    extractor
+        .add_pes(&pes(Some(0), [0, 8, 0x11, 0x40], &[0, 1, 0, 0, 0, 2, 0, 0]))
+        .unwrap();
+    let data = extractor.finish().unwrap().unwrap().into_inner();
+    assert_eq!(&data[HEADER_SIZE..], [1, 0, 2, 0]);
+}
+
+#[test]
+fn silence_for_missing_audio() {
+    // 10 ms of audio at 0, then the next packet 10 ms late
+    let mut extractor = LPCMExtractor::new(Cursor::new(Vec::new()));
+    let samples = vec![0x11; 480 * 4];
+    extractor
+        .add_pes(&pes(Some(0), stereo_header(1920), &samples))
+        .unwrap();
+    // a millisecond of jitter is not a gap
+    extractor
+        .add_pes(&pes(Some(990), stereo_header(1920), &samples))
+        .unwrap();
+    assert_eq!(extractor.inserted_silence(), 0);
+    extractor
+        .add_pes(&pes(Some(2700), stereo_header(1920), &samples))
+        .unwrap();
+    assert_eq!(extractor.inserted_silence(), 480);
+    assert_eq!(extractor.frames_written(), 4 * 480);
+    let data = extractor.finish().unwrap().unwrap().into_inner();
+    let silence = HEADER_SIZE + 2 * 1920;
+    assert!(data[silence..silence + 1920].iter().all(|b| *b == 0));
+    assert!(data[silence + 1920..].iter().all(|b| *b == 0x11));
+}
+
+#[test]
+fn format_change_is_an_error() {
+    let mut extractor = LPCMExtractor::new(Cursor::new(Vec::new()));
+    extractor
+        .add_pes(&pes(Some(0), stereo_header(4), &[0; 4]))
+        .unwrap();
+    let error = extractor
+        .add_pes(&pes(None, [0, 4, 0x34, 0x40], &[0; 4]))
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(extractor.add_pes(&pes(None, [0, 0, 0, 0], &[])).is_err());
+}
+
+/// Keeps the header and counts the rest, so the samples of an RF64 file need no memory
+struct HeaderSink {
+    header: Vec<u8>,
+    position: u64,
+    end: u64,
+}
+
+impl Write for HeaderSink {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let start = (self.position as usize).min(HEADER_SIZE);
+        let end = (self.position as usize + data.len()).min(HEADER_SIZE);
+        self.header[start..end].copy_from_slice(&data[..end - start]);
+        self.position += data.len() as u64;
+        self.end = self.end.max(self.position);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for HeaderSink {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(position) => position,
+            SeekFrom::End(offset) => (self.end as i64 + offset) as u64,
+            SeekFrom::Current(offset) => (self.position as i64 + offset) as u64,
+        };
+        Ok(self.position)
+    }
+}
+
+#[test]
+fn rf64_past_4_gib() {
+    let sink = HeaderSink {
+        header: vec![0; HEADER_SIZE],
+        position: 0,
+        end: 0,
+    };
+    let format = WavFormat {
+        channels: 2,
+        sample_rate: 48000,
+        container_bits: 16,
+        valid_bits: 16,
+        channel_mask: 0x3,
+    };
+    let mut writer = WavWriter::new(sink, format).unwrap();
+    let block = vec![0; 1 << 24];
+    for _ in 0..256 {
+        writer.write_samples(&block).unwrap();
+    }
+    writer.write_samples(&[0; 4]).unwrap();
+    let data_size = (1_u64 << 32) + 4;
+    assert_eq!(writer.frames_written(), data_size / 4);
+    let sink = writer.finish().unwrap();
+    let header = &sink.header;
+    assert_eq!(&header[..4], b"RF64");
+    assert_eq!(le_u32(header, 4), u32::MAX);
+    assert_eq!(&header[12..16], b"ds64");
+    let u64_at = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+    assert_eq!(u64_at(20), HEADER_SIZE as u64 - 8 + data_size);
+    assert_eq!(u64_at(28), data_size);
+    assert_eq!(u64_at(36), data_size / 4);
+    assert_eq!(le_u32(header, 100), u32::MAX);
+    assert_eq!(sink.end, HEADER_SIZE as u64 + data_size);
+}
@@ -0,0 +1,198 @@
+use audio_parser::mpa::{
+    ChannelMode, Layer, MPAFrame, MPAParser, MPAStreamSummary, VBRHeaderKind, Version,
+};
+use audio_parser::stream::partialstream;
+use audio_parser::{AudioFrame, FrameParser};
+use winnow::error::ErrMode;
+
+/// A frame of `size` bytes starting with `header`, the rest zero
+fn frame(header: [u8; 4], size: usize) -> Vec<u8> {
+    let mut data = vec![0; size];
+    data[..4].copy_from_slice(&header);
+    data
+}
+
+fn parse(data: &[u8]) -> MPAFrame {
+    let (rest, frame) = MPAFrame::parse(partialstream(data, true)).unwrap();
+    assert!(rest.is_empty());
+    frame
+}
+
+/// MPEG-1 layer III, 128 kbps, 44.1 kHz, joint stereo, no CRC
+const LAYER_III_HEADER: [u8; 4] = [0xff, 0xfb, 0x90, 0x64];
+
+#[test]
+fn mpeg1_layer_iii() {
+    let frame = parse(&frame(LAYER_III_HEADER, 417));
+    let header = &frame.header;
+    assert_eq!((header.version, header.layer), (Version::MPEG1, Layer::III));
+    assert!(!header.crc_present && !header.padding);
+    assert_eq!(header.channel_mode, ChannelMode::JointStereo);
+    assert_eq!(header.mode_extension, 2);
+    assert!(header.original && !header.copyright);
+    assert_eq!((header.bitrate(), header.sample_rate()), (128_000, 44100));
+    assert_eq!(header.channels(), 2);
+    // 144 * 128000 / 44100 bytes, rounded down
+    assert_eq!(header.frame_size(), 417);
+    assert_eq!((frame.samples(), frame.sample_rate()), (1152, 44100));
+    assert_eq!((frame.crc, frame.crc_ok), (None, None));
+    assert!(frame.vbr_header.is_none());
+    // the padding bit adds a byte
+    let frame = parse(&frame_with_padding());
+    assert_eq!(frame.data.len(), 418);
+}
+
+fn frame_with_padding() -> Vec<u8> {
+    let [a, b, c, d] = LAYER_III_HEADER;
+    frame([a, b, c | 0x02, d], 418)
+}
+
+#[test]
+fn frame_sizes() {
+    let header = |data: [u8; 4]| {
+        MPAFrame::parse(partialstream(&frame(data, 2000), true))
+            .unwrap()
+            .1
+            .header
+    };
+    // MPEG-2 layer III, 64 kbps, 24 kHz, mono: 576 samples per frame
+    let mpeg2 = header([0xff, 0xf3, 0x84, 0xc0]);
+    assert_eq!(mpeg2.version, Version::MPEG2);
+    assert_eq!(
+        (mpeg2.sample_rate(), mpeg2.samples_per_frame()),
+        (24000, 576)
+    );
+    assert_eq!((mpeg2.channels(), mpeg2.frame_size()), (1, 192));
+    // MPEG 2.5 layer III, 8 kbps, 8 kHz
+    let mpeg25 = header([0xff, 0xe3, 0x18, 0xc0]);
+    assert_eq!(mpeg25.version, Version::MPEG25);
+    assert_eq!((mpeg25.bitrate(), mpeg25.sample_rate()), (8000, 8000));
+    assert_eq!(mpeg25.frame_size(), 72);
+    // layer I, 384 kbps, 48 kHz with padding: 96 four byte slots and a padding slot
+    let layer_i = header([0xff, 0xff, 0xc6, 0x00]);
+    assert_eq!(layer_i.layer, Layer::I);
+    assert_eq!(layer_i.samples_per_frame(), 384);
+    assert_eq!(layer_i.frame_size(), 388);
+    // layer II, 192 kbps, 48 kHz
+    assert_eq!(header([0xff, 0xfd, 0xa4, 0x00]).frame_size(), 576);
+}
+
+#[test]
+fn invalid_headers() {
+    for header in [
+        // free format and bad bitrate index
+        [0xff, 0xfb, 0x00, 0x64],
+        [0xff, 0xfb, 0xf0, 0x64],
+        // reserved sampling frequency, version and layer
+        [0xff, 0xfb, 0x9c, 0x64],
+        [0xff, 0xeb, 0x90, 0x64],
+        [0xff, 0xf9, 0x90, 0x64],
+        // no sync word
+        [0xff, 0x1b, 0x90, 0x64],
+    ] {
+        assert!(MPAFrame::parse(partialstream(&frame(header, 500), true)).is_err());
+    }
+    assert!(matches!(
+        MPAFrame::parse(partialstream(&frame(LAYER_III_HEADER, 417)[..400], false)),
+        Err(ErrMode::Incomplete(_))
+    ));
+}
+
+#[test]
+fn crc() {
+    // MPEG-1 layer III mono with a CRC over the last two header bytes and the 17 bytes of
+    // side information
+    let mut data = frame([0xff, 0xfa, 0x90, 0xc4], 417);
+    data[4..6].copy_from_slice(&[0x00, 0xec]);
+    let frame = parse(&data);
+    assert!(frame.header.crc_present);
+    assert_eq!((frame.crc, frame.crc_ok), (Some(0xec), Some(true)));
+    data[22] = 1;
+    assert_eq!(parse(&data).crc_ok, Some(false));
+    // bytes after the side information are not protected
+    data[22] = 0;
+    data[23] = 1;
+    assert_eq!(parse(&data).crc_ok, Some(true));
+}
+
+/// A layer III frame with a Xing (or Info) header after the 32 bytes of side information:
+/// 1000 frames, 417000 bytes, a table of contents, quality 57 and a LAME tag with 576
+/// samples of delay and 1000 of padding
+fn xing_frame(tag: &[u8; 4]) -> Vec<u8> {
+    let mut data = frame(LAYER_III_HEADER, 417);
+    let mut xing = tag.to_vec();
+    xing.extend(0xf_u32.to_be_bytes());
+    xing.extend(1000_u32.to_be_bytes());
+    xing.extend(417_000_u32.to_be_bytes());
+    xing.extend([0; 100]);
+    xing.extend(57_u32.to_be_bytes());
+    xing.extend(b"LAME3.100");
+    xing.extend([0; 12]);
+    xing.extend([0x24, 0x03, 0xe8]);
+    data[36..36 + xing.len()].copy_from_slice(&xing);
+    data
+}
+
+#[test]
+fn xing_header() {
+    let frame = parse(&xing_frame(b"Xing"));
+    let vbr = frame.vbr_header.as_ref().unwrap();
+    assert_eq!(vbr.kind, VBRHeaderKind::Xing);
+    assert_eq!((vbr.frames, vbr.bytes), (Some(1000), Some(417_000)));
+    assert_eq!(vbr.quality, Some(57));
+    assert_eq!(
+        (vbr.encoder_delay, vbr.encoder_padding),
+        (Some(576), Some(1000))
+    );
+    // the frame carries the header instead of audio
+    assert_eq!(frame.samples(), 0);
+    let frame = parse(&xing_frame(b"Info"));
+    assert_eq!(frame.vbr_header.unwrap().kind, VBRHeaderKind::Info);
+}
+
+#[test]
+fn vbri_header() {
+    let mut data = frame(LAYER_III_HEADER, 417);
+    let mut vbri = b"VBRI".to_vec();
+    vbri.extend(1_u16.to_be_bytes());
+    vbri.extend(576_u16.to_be_bytes());
+    vbri.extend(75_u16.to_be_bytes());
+    vbri.extend(417_000_u32.to_be_bytes());
+    vbri.extend(1000_u32.to_be_bytes());
+    data[36..36 + vbri.len()].copy_from_slice(&vbri);
+    let vbr = parse(&data).vbr_header.unwrap();
+    assert_eq!(vbr.kind, VBRHeaderKind::VBRI);
+    assert_eq!((vbr.frames, vbr.bytes), (Some(1000), Some(417_000)));
+    assert_eq!((vbr.quality, vbr.encoder_delay), (Some(75), Some(576)));
+    assert_eq!(vbr.encoder_padding, None);
+}
+
+#[test]
+fn stream_summary() {
+    let mut summary = MPAStreamSummary::new();
+    summary.add(&parse(&xing_frame(b"Xing")));
+    summary.add(&parse(&frame(LAYER_III_HEADER, 417)));
+    summary.add(&parse(&frame_with_padding()));
+    assert_eq!((summary.frames, summary.crc_errors), (3, 0));
+    // the Xing frame counts as a frame, but not towards the audio
+    assert_eq!((summary.bytes, summary.samples), (835, 2304));
+    assert_eq!(
+        summary.formats.iter().collect::<Vec<_>>(),
+        [&(Version::MPEG1, Layer::III)]
+    );
+    assert_eq!(
+        (summary.min_bitrate, summary.max_bitrate),
+        (Some(128_000), Some(128_000))
+    );
+    // without the encoder delay and padding from the LAME tag
+    assert_eq!(summary.duration_secs(), 728.0 / 44100.0);
+    assert_eq!(summary.vbr_header.unwrap().frames, Some(1000));
+}
+
+#[test]
+fn find_sync() {
+    let mut data = vec![0xff, 0x00, 0x12];
+    data.extend(frame(LAYER_III_HEADER, 417));
+    assert_eq!(MPAParser::find_sync(&data), Some(3));
+    assert_eq!(MPAParser::find_sync(&[0xff, 0x1f, 0xff]), None);
+}
@@ -0,0 +1,538 @@
+// Grouping NAL units into access units (ITU-T H.264 7.4.1.2.3): the primary coded
+// picture and the non-VCL NAL units that come with it, and with MVC (H.7.4.1.2.3) the
+// view components of the other views
+use crate::annexb::{ByteStreamIterator, ByteStreamProblem};
+use crate::nalunits::{
+    mvc::NALUnitHeaderExtension, pps::ParameterSets, slice::SliceHeader, slice::SliceType, NALUnit,
+};
+use crate::poc::{PicOrderCnt, PicOrderCntDecoder};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Read;
+
+/// Slice types allowed by each primary_pic_type (Table 7-5)
+const PRIMARY_PIC_TYPES: [&[SliceType]; 8] = [
+    &[SliceType::I],
+    &[SliceType::I, SliceType::P],
+    &[SliceType::I, SliceType::P, SliceType::B],
+    &[SliceType::SI],
+    &[SliceType::SI, SliceType::SP],
+    &[SliceType::I, SliceType::SI],
+    &[SliceType::I, SliceType::SI, SliceType::P, SliceType::SP],
+    &[
+        SliceType::I,
+        SliceType::SI,
+        SliceType::P,
+        SliceType::SP,
+        SliceType::B,
+    ],
+];
+
+/// The smallest primary_pic_type that allows all of `slice_types`
+pub fn primary_pic_type_of(slice_types: &[SliceType]) -> Option<u8> {
+    if slice_types.is_empty() {
+        return None;
+    }
+    let allowed = |i: &usize| {
+        slice_types
+            .iter()
+            .all(|t| PRIMARY_PIC_TYPES[*i].contains(t))
+    };
+    (0..PRIMARY_PIC_TYPES.len()).find(allowed).map(|i| i as u8)
+}
+
+pub struct AccessUnit {
+    /// Offset in the input of the start code of the first NAL unit: of its zero_byte if
+    /// it has one, otherwise of the start code prefix
+    pub offset: u64,
+    /// From the start code of the first NAL unit up to that of the next access unit, so
+    /// including start codes, trailing zero bytes and stray bytes
+    pub size: usize,
+    pub nal_units: Vec<NALUnit>,
+    /// From the access unit delimiter if there is one, otherwise from the slice types
+    /// of the primary coded picture; None without readable slice headers
+    pub primary_pic_type: Option<u8>,
+    pub idr: bool,
+    /// Of the primary coded picture; None without a readable slice header, or if it is
+    /// out of range
+    pub pic_order_cnt: Option<PicOrderCnt>,
+}
+
+impl AccessUnit {
+    fn new(offset: u64) -> Self {
+        Self {
+            offset,
+            size: 0,
+            nal_units: Vec::new(),
+            primary_pic_type: None,
+            idr: false,
+            pic_order_cnt: None,
+        }
+    }
+
+    /// Whether the picture has memory_management_control_operation 5, which like an IDR
+    /// picture starts POC from 0
+    pub fn has_mmco5(&self) -> bool {
+        self.first_slice_header()
+            .and_then(|header| header.dec_ref_pic_marking.as_ref())
+            .is_some_and(|marking| marking.has_mmco5())
+    }
+
+    /// 1 for a field, 2 for a frame
+    pub fn fields(&self) -> u64 {
+        match self.first_slice_header() {
+            Some(header) if header.field_pic => 1,
+            _ => 2,
+        }
+    }
+
+    /// The coded slices of the primary coded picture: of the base view, or of the first
+    /// view if there are only coded slice extensions
+    pub fn primary_slices(&self) -> impl Iterator<Item = &NALUnit> {
+        let base = self
+            .nal_units
+            .iter()
+            .any(|nal_unit| matches!(nal_unit.nal_unit_type(), 1 | 5));
+        let first_view = self
+            .nal_units
+            .iter()
+            .find(|nal_unit| nal_unit.nal_unit_type() == 20)
+            .and_then(|nal_unit| nal_unit.header_extension()?.view_id());
+        self.nal_units
+            .iter()
+            .filter(move |nal_unit| match nal_unit.nal_unit_type() {
+                1 | 5 => true,
+                20 => !base && view_key(nal_unit) == first_view,
+                _ => false,
+            })
+    }
+
+    /// The slice headers of the primary coded picture, without redundant slices
+    pub fn slice_headers(&self) -> impl Iterator<Item = &SliceHeader> {
+        self.primary_slices()
+            .filter_map(NALUnit::slice_header)
+            .filter(|header| header.redundant_pic_cnt == 0)
+    }
+
+    pub fn first_slice_header(&self) -> Option<&SliceHeader> {
+        self.slice_headers().next()
+    }
+
+    /// The slice types of the primary coded picture, each once
+    pub fn slice_types(&self) -> Vec<SliceType> {
+        let mut slice_types = Vec::new();
+        for header in self.slice_headers() {
+            if !slice_types.contains(&header.slice_type) {
+                slice_types.push(header.slice_type);
+            }
+        }
+        slice_types
+    }
+
+    /// The NAL units of each view, by view_id and with the base view first. Non-VCL NAL
+    /// units belong to the view of the slice after them; the base view has the view_id of
+    /// its prefix NAL units, or 0 without them.
+    pub fn views(&self) -> Vec<(u16, Vec<&NALUnit>)> {
+        let base_view_id = self
+            .nal_units
+            .iter()
+            .find(|nal_unit| nal_unit.nal_unit_type() == 14)
+            .and_then(|nal_unit| nal_unit.header_extension()?.view_id())
+            .unwrap_or(0);
+        let mut views: Vec<(u16, Vec<&NALUnit>)> = Vec::new();
+        let mut pending = Vec::new();
+        for nal_unit in self.nal_units.iter() {
+            pending.push(nal_unit);
+            if !matches!(nal_unit.nal_unit_type(), 1 | 5 | 20) {
+                continue;
+            }
+            // SVC layers are not views
+            let view_id = view_key(nal_unit).unwrap_or(base_view_id);
+            match views.iter_mut().find(|(id, _)| *id == view_id) {
+                Some((_, nal_units)) => nal_units.append(&mut pending),
+                None => views.push((view_id, std::mem::take(&mut pending))),
+            }
+        }
+        match views.last_mut() {
+            Some((_, nal_units)) => nal_units.append(&mut pending),
+            None if !pending.is_empty() => views.push((base_view_id, pending)),
+            None => (),
+        }
+        views
+    }
+
+    fn finish(&mut self) {
+        let delimiter = self.nal_units.iter().find_map(|nal_unit| match nal_unit {
+            NALUnit::AUD(aud) => Some(aud.primary_pic_type),
+            _ => None,
+        });
+        self.primary_pic_type = delimiter.or_else(|| primary_pic_type_of(&self.slice_types()));
+    }
+}
+
+impl fmt::Debug for AccessUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let types: Vec<u8> = self.nal_units.iter().map(NALUnit::nal_unit_type).collect();
+        write!(
+            f,
+            "AccessUnit(offset={}, size={}, idr={}, primary_pic_type={:?}, poc={:?}, \
+             slices {:?}, nal units {:?})",
+            self.offset,
+            self.size,
+            self.idr,
+            self.primary_pic_type,
+            self.pic_order_cnt.map(|poc| poc.pic_order_cnt()),
+            self.slice_types(),
+            types
+        )
+    }
+}
+
+/// The view_id of a coded slice extension of MVC; None for the base view and SVC
+fn view_key(nal_unit: &NALUnit) -> Option<u16> {
+    match nal_unit {
+        NALUnit::SliceExtension(nu) => nu.extension.view_id(),
+        _ => None,
+    }
+}
+
+/// What 7.4.1.2.4 compares between the slices of consecutive pictures
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PictureId {
+    frame_num: u32,
+    pic_parameter_set_id: u32,
+    field_pic: bool,
+    bottom_field: bool,
+    /// nal_ref_idc is 0
+    non_reference: bool,
+    pic_order_cnt_lsb: Option<u32>,
+    delta_pic_order_cnt_bottom: i32,
+    delta_pic_order_cnt: [i32; 2],
+    idr_pic_id: Option<u32>,
+}
+
+impl PictureId {
+    fn new(header: &SliceHeader, ref_idc: u8) -> Self {
+        Self {
+            frame_num: header.frame_num,
+            pic_parameter_set_id: header.pic_parameter_set_id,
+            field_pic: header.field_pic,
+            bottom_field: header.bottom_field,
+            non_reference: ref_idc == 0,
+            pic_order_cnt_lsb: header.pic_order_cnt_lsb,
+            delta_pic_order_cnt_bottom: header.delta_pic_order_cnt_bottom,
+            delta_pic_order_cnt: header.delta_pic_order_cnt,
+            idr_pic_id: header.idr_pic_id,
+        }
+    }
+}
+
+/// A view component of the current access unit
+struct ViewState {
+    /// view_id of a non-base view
+    view: Option<u16>,
+    /// Of its last slice, if it could be read
+    picture: Option<PictureId>,
+    /// Set once it has a slice starting at macroblock 0
+    has_first_mb: bool,
+}
+
+/// Collects the NAL units of an Annex B byte stream into access units
+pub struct AccessUnitIterator {
+    units: ByteStreamIterator<Box<dyn Read>>,
+    parameter_sets: ParameterSets,
+    pic_order_cnt_decoder: PicOrderCntDecoder,
+    /// Whether the POC of the current access unit has been derived
+    pic_order_cnt_decoded: bool,
+    current: Option<AccessUnit>,
+    /// The views of which the current access unit has slices
+    views: Vec<ViewState>,
+    /// Non-VCL NAL units after the slices of the current access unit, with their offset
+    /// and size, until the next slice tells whether they start a new one
+    pending: Vec<(u64, usize, NALUnit)>,
+}
+
+impl AccessUnitIterator {
+    pub fn new(input_reader: Box<dyn Read>) -> Self {
+        Self {
+            units: ByteStreamIterator::new(input_reader),
+            parameter_sets: ParameterSets::new(),
+            pic_order_cnt_decoder: PicOrderCntDecoder::new(),
+            pic_order_cnt_decoded: false,
+            current: None,
+            views: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// The SPS and PPS seen so far
+    pub fn parameter_sets(&self) -> &ParameterSets {
+        &self.parameter_sets
+    }
+
+    /// What is wrong with the byte stream so far
+    pub fn problems(&self) -> &[ByteStreamProblem] {
+        self.units.problems()
+    }
+
+    /// Whether the slice `nal_unit` starts a new access unit: its view already has a
+    /// different picture in the current one
+    fn starts_access_unit(&mut self, nal_unit: &NALUnit) -> bool {
+        // the layers of SVC all belong to the access unit of the base layer
+        if let Some(NALUnitHeaderExtension::SVC(_)) = nal_unit.header_extension() {
+            return false;
+        }
+        let view = view_key(nal_unit);
+        let Some(header) = nal_unit.slice_header() else {
+            // can't tell without a header
+            return false;
+        };
+        if header.redundant_pic_cnt > 0 {
+            return false;
+        }
+        let Some(state) = self.views.iter_mut().find(|state| state.view == view) else {
+            return false;
+        };
+        let picture = PictureId::new(header, nal_unit.ref_idc());
+        let previous = state.picture.replace(picture.clone());
+        // a picture has one slice starting at macroblock 0, in any order (ASO)
+        (header.first_mb_in_slice == 0 && state.has_first_mb)
+            || previous.is_some_and(|p| p != picture)
+    }
+
+    /// Adds a NAL unit to the current access unit, and a slice to its view
+    fn add(&mut self, offset: u64, size: usize, nal_unit: NALUnit) {
+        let access_unit = self.current.get_or_insert_with(|| AccessUnit::new(offset));
+        access_unit.size += size;
+        if matches!(nal_unit.nal_unit_type(), 1 | 5 | 20) {
+            let view = view_key(&nal_unit);
+            let index = match self.views.iter().position(|state| state.view == view) {
+                Some(index) => index,
+                None => {
+                    self.views.push(ViewState {
+                        view,
+                        picture: None,
+                        has_first_mb: false,
+                    });
+                    self.views.len() - 1
+                }
+            };
+            access_unit.idr |= nal_unit.nal_unit_type() == 5
+                || nal_unit.header_extension().is_some_and(|e| e.idr());
+            let header = nal_unit.slice_header().filter(|h| h.redundant_pic_cnt == 0);
+            if let Some(header) = header {
+                let state = &mut self.views[index];
+                state.picture = Some(PictureId::new(header, nal_unit.ref_idc()));
+                state.has_first_mb |= header.first_mb_in_slice == 0;
+                // POC is derived once per picture, in decoding order, from the base view
+                // if there is one
+                let base = self.views.iter().any(|state| state.view.is_none());
+                let sps = match nal_unit.nal_unit_type() {
+                    20 if base => None,
+                    20 => self
+                        .parameter_sets
+                        .get_subset(header.pic_parameter_set_id)
+                        .map(|(_, subset_sps)| &subset_sps.sps),
+                    _ => self
+                        .parameter_sets
+                        .get(header.pic_parameter_set_id)
+                        .map(|(_, sps)| sps),
+                };
+                if let (false, Some(sps)) = (self.pic_order_cnt_decoded, sps) {
+                    let decoder = &mut self.pic_order_cnt_decoder;
+                    access_unit.pic_order_cnt = decoder.decode(header, nal_unit.ref_idc(), sps);
+                    self.pic_order_cnt_decoded = true;
+                }
+            }
+        }
+        access_unit.nal_units.push(nal_unit);
+    }
+
+    /// Adds the pending NAL units to the current access unit
+    fn add_pending(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        for (offset, size, nal_unit) in pending {
+            self.add(offset, size, nal_unit);
+        }
+    }
+
+    /// Ends the current access unit; the pending NAL units go to the next one
+    fn take_current(&mut self) -> Option<AccessUnit> {
+        self.views.clear();
+        self.pic_order_cnt_decoded = false;
+        let mut finished = self.current.take();
+        if let Some(access_unit) = finished.as_mut() {
+            access_unit.finish();
+        }
+        self.add_pending();
+        finished
+    }
+}
+
+impl Iterator for AccessUnitIterator {
+    type Item = AccessUnit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(unit) = self.units.next() else {
+                // non-VCL NAL units after the last picture make an access unit of their own
+                return self.take_current();
+            };
+            let (offset, size) = (unit.start, unit.size);
+            let nal_unit = self.units.parse(&unit, &mut self.parameter_sets);
+            let mut finished = None;
+            match nal_unit.nal_unit_type() {
+                1 | 5 | 20 if self.starts_access_unit(&nal_unit) => {
+                    finished = self.take_current();
+                }
+                1 | 5 | 20 => self.add_pending(),
+                // AUD
+                9 if !self.views.is_empty() => finished = self.take_current(),
+                // SEI, SPS, PPS, prefix NAL units, subset SPS and the reserved 16 to 18 start
+                // an access unit if the slice after them is of a new picture. In MPEG-2
+                // systems NAL units of type 24 delimit the non-base views (ISO/IEC 13818-1
+                // 2.14.3).
+                6..=8 | 14..=18 | 24 if !self.views.is_empty() => {
+                    self.pending.push((offset, size, nal_unit));
+                    continue;
+                }
+                _ if !self.pending.is_empty() => {
+                    self.pending.push((offset, size, nal_unit));
+                    continue;
+                }
+                _ => (),
+            }
+            self.add(offset, size, nal_unit);
+            if finished.is_some() {
+                return finished;
+            }
+        }
+    }
+}
+
+/// An access unit with its place in display order
+pub struct TimedAccessUnit {
+    pub access_unit: AccessUnit,
+    pub decode_index: u64,
+    pub display_index: u64,
+    /// 90 kHz, synthesised from the frame rate of the SPS and starting at 0; None
+    /// without a frame rate
+    pub pts: Option<u64>,
+    pub dts: Option<u64>,
+}
+
+/// Puts access units in display order by their POC, as the bumping process of a
+/// decoder that holds max_num_reorder_frames frames would (C.4.5.3)
+pub struct DisplayOrderIterator {
+    access_units: AccessUnitIterator,
+    /// For streams whose SPS has no timing info
+    default_frame_rate: Option<(u64, u64)>,
+    /// In decoding order, with their decode index and the fields decoded before them
+    pending: Vec<(AccessUnit, u64, u64)>,
+    ready: VecDeque<TimedAccessUnit>,
+    decoded: u64,
+    decoded_fields: u64,
+    displayed: u64,
+    displayed_fields: u64,
+}
+
+impl DisplayOrderIterator {
+    pub fn new(access_units: AccessUnitIterator, default_frame_rate: Option<(u64, u64)>) -> Self {
+        Self {
+            access_units,
+            default_frame_rate,
+            pending: Vec::new(),
+            ready: VecDeque::new(),
+            decoded: 0,
+            decoded_fields: 0,
+            displayed: 0,
+            displayed_fields: 0,
+        }
+    }
+
+    /// max_num_reorder_frames of the active SPS, in fields
+    fn reorder_fields(&self) -> u64 {
+        let sps = self.access_units.parameter_sets().active_sps();
+        sps.map_or(0, |sps| 2 * sps.max_num_reorder_frames() as u64)
+    }
+
+    /// Moves the pending access unit with the lowest POC to `ready`
+    fn bump(&mut self) {
+        let key = |(access_unit, decode_index, _): &(AccessUnit, u64, u64)| {
+            let poc = access_unit.pic_order_cnt.map(|poc| poc.pic_order_cnt());
+            (poc.unwrap_or(i32::MIN), *decode_index)
+        };
+        let Some(index) = (0..self.pending.len()).min_by_key(|&i| key(&self.pending[i])) else {
+            return;
+        };
+        let (access_unit, decode_index, decoded_fields) = self.pending.remove(index);
+        let frame_rate = self
+            .access_units
+            .parameter_sets()
+            .active_sps()
+            .and_then(|sps| sps.frame_rate())
+            .or(self.default_frame_rate);
+        // a field lasts half a frame; pictures are shown at least `reorder_fields` after
+        // they are decoded
+        let time = |fields: u64| {
+            frame_rate
+                .filter(|(numerator, _)| *numerator != 0)
+                .map(|(numerator, denominator)| {
+                    // a u32 num_units_in_tick makes the denominator up to 33 bits
+                    (fields as u128 * 90000 * denominator as u128 / (2 * numerator as u128)) as u64
+                })
+        };
+        let fields = access_unit.fields();
+        self.ready.push_back(TimedAccessUnit {
+            access_unit,
+            decode_index,
+            display_index: self.displayed,
+            pts: time(self.displayed_fields + self.reorder_fields()),
+            dts: time(decoded_fields),
+        });
+        self.displayed += 1;
+        self.displayed_fields += fields;
+    }
+}
+
+impl Iterator for DisplayOrderIterator {
+    type Item = TimedAccessUnit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(timed) = self.ready.pop_front() {
+                return Some(timed);
+            }
+            let Some(access_unit) = self.access_units.next() else {
+                if self.pending.is_empty() {
+                    return None;
+                }
+                while !self.pending.is_empty() {
+                    self.bump();
+                }
+                continue;
+            };
+            // POC starts again, so everything before is shown first (C.4.4)
+            if access_unit.idr || access_unit.has_mmco5() || access_unit.pic_order_cnt.is_none() {
+                while !self.pending.is_empty() {
+                    self.bump();
+                }
+            }
+            let fields = access_unit.fields();
+            self.pending
+                .push((access_unit, self.decoded, self.decoded_fields));
+            self.decoded += 1;
+            self.decoded_fields += fields;
+            let reorder_fields = self.reorder_fields();
+            while self
+                .pending
+                .iter()
+                .map(|(au, _, _)| au.fields())
+                .sum::<u64>()
+                > reorder_fields
+            {
+                self.bump();
+            }
+        }
+    }
+}
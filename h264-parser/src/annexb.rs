@@ -0,0 +1,179 @@
+// Splitting an Annex B byte stream into NAL units as ITU-T H.264 B.2 does: which zero
+// bytes belong to start codes, and which bytes belong to no NAL unit at all
+use crate::nalunits::{parse_nal_unit_data, pps::ParameterSets, NALUnit};
+use crate::startcode::{StartCodeIterator, StartCodeUnit};
+use std::fmt;
+use std::io::Read;
+
+/// The length of the NAL unit at the start of the data after a start code prefix: up to
+/// three zero bytes, without the trailing zero bytes before them or the end
+pub fn nal_unit_end(data: &[u8]) -> usize {
+    let end = data
+        .windows(3)
+        .position(|bytes| bytes == [0, 0, 0])
+        .unwrap_or(data.len());
+    data[..end]
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |p| p + 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteStreamProblem {
+    /// Bytes before the first start code that are not leading zero bytes
+    LeadingGarbage { size: u64 },
+    /// A start code followed by zero bytes and the next start code or the end
+    EmptyNALUnit { offset: u64 },
+    /// Non-zero bytes after three zero bytes that end a NAL unit
+    StrayBytes { offset: u64, size: usize },
+    /// The last NAL unit could not be read, likely because the stream is cut off
+    Truncated { offset: u64, size: usize },
+}
+
+impl fmt::Display for ByteStreamProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LeadingGarbage { size } => {
+                write!(f, "{} bytes before the first start code", size)
+            }
+            Self::EmptyNALUnit { offset } => write!(f, "empty NAL unit at {}", offset),
+            Self::StrayBytes { offset, size } => write!(f, "{} stray bytes at {}", size, offset),
+            Self::Truncated { offset, size } => {
+                write!(f, "last NAL unit at {} ({} bytes) is cut off", offset, size)
+            }
+        }
+    }
+}
+
+/// A NAL unit and where it is in the byte stream
+pub struct ByteStreamUnit {
+    /// Offset of its start code: of the zero_byte of a 4 byte start code, otherwise of
+    /// the start code prefix
+    pub start: u64,
+    /// Offset of the NAL unit header byte
+    pub offset: u64,
+    /// From start to the start of the next NAL unit, so with the start code, trailing
+    /// zero bytes and stray bytes
+    pub size: usize,
+    /// The NAL unit with emulation prevention bytes
+    pub data: Vec<u8>,
+    /// Nothing follows it
+    pub last: bool,
+}
+
+/// Iterates over the NAL units of an Annex B byte stream in a `Read`, and collects what
+/// is wrong with the stream around them
+pub struct ByteStreamIterator<R: Read> {
+    units: StartCodeIterator<R>,
+    /// The next unit with a NAL unit and the offset of its start code
+    next: Option<(u64, StartCodeUnit)>,
+    /// Whether the last unit read ended in a zero byte, the zero_byte of the next start
+    /// code; None before the first one
+    zero_byte: Option<bool>,
+    /// End of the last unit read
+    end: u64,
+    /// Of the bytes the start code iterator skipped
+    reported_skipped_bytes: u64,
+    problems: Vec<ByteStreamProblem>,
+}
+
+impl<R: Read> ByteStreamIterator<R> {
+    pub fn new(input_reader: R) -> Self {
+        Self {
+            units: StartCodeIterator::new(input_reader),
+            next: None,
+            zero_byte: None,
+            end: 0,
+            reported_skipped_bytes: 0,
+            problems: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.units.get_ref()
+    }
+
+    /// The problems found so far, in the order of the stream
+    pub fn problems(&self) -> &[ByteStreamProblem] {
+        &self.problems
+    }
+
+    /// Parses the NAL unit of `unit`; the last one is reported as cut off if it can't be
+    /// read
+    pub fn parse(&mut self, unit: &ByteStreamUnit, parameter_sets: &mut ParameterSets) -> NALUnit {
+        let nal_unit = parse_nal_unit_data(&unit.data, parameter_sets);
+        if unit.last && nal_unit.is_unreadable() {
+            self.problems.push(ByteStreamProblem::Truncated {
+                offset: unit.offset,
+                size: unit.data.len(),
+            });
+        }
+        nal_unit
+    }
+
+    /// The next unit that has a NAL unit, and the offset of its start code
+    fn read_unit(&mut self) -> Option<(u64, StartCodeUnit)> {
+        loop {
+            let unit = self.units.next();
+            let skipped_bytes = self.units.skipped_bytes();
+            if skipped_bytes > self.reported_skipped_bytes {
+                let size = skipped_bytes - self.reported_skipped_bytes;
+                self.problems
+                    .push(ByteStreamProblem::LeadingGarbage { size });
+                self.reported_skipped_bytes = skipped_bytes;
+            }
+            let unit = unit?;
+            let prefix_offset = unit.offset.saturating_sub(3);
+            let ends_in_zero = unit.data.last() == Some(&0);
+            let zero_byte = match self.zero_byte.replace(ends_in_zero) {
+                Some(zero_byte) => zero_byte,
+                // zero bytes before the first start code that are not garbage
+                None => prefix_offset > self.units.skipped_bytes(),
+            };
+            let start = prefix_offset - zero_byte as u64;
+            self.end = unit.offset + unit.data.len() as u64;
+            if nal_unit_end(&unit.data) == 0 {
+                self.problems
+                    .push(ByteStreamProblem::EmptyNALUnit { offset: start });
+                continue;
+            }
+            return Some((start, unit));
+        }
+    }
+}
+
+impl<R: Read> Iterator for ByteStreamIterator<R> {
+    type Item = ByteStreamUnit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, unit) = match self.next.take() {
+            Some(next) => next,
+            None => self.read_unit()?,
+        };
+        let StartCodeUnit { offset, mut data } = unit;
+        let nal_end = nal_unit_end(&data);
+        // from the first to the last non-zero byte after the NAL unit
+        let stray = data[nal_end..].iter().position(|byte| *byte != 0);
+        if let Some(stray_start) = stray.map(|p| nal_end + p) {
+            let stray_end = data.iter().rposition(|byte| *byte != 0).unwrap() + 1;
+            self.problems.push(ByteStreamProblem::StrayBytes {
+                offset: offset + stray_start as u64,
+                size: stray_end - stray_start,
+            });
+        }
+        data.truncate(nal_end);
+        self.next = self.read_unit();
+        // empty units before the next one are part of this one
+        let next_start = match self.next.as_ref() {
+            Some((next_start, _)) => *next_start,
+            None => self.end,
+        };
+        Some(ByteStreamUnit {
+            start,
+            offset,
+            size: (next_start - start) as usize,
+            data,
+            last: self.next.is_none(),
+        })
+    }
+}
@@ -0,0 +1,566 @@
+// AVCC: NAL units with a big endian length before each one instead of start codes, as
+// in MP4/MOV and Matroska samples (ISO/IEC 14496-15) and WebRTC. The SPS and PPS are
+// usually out of band, in the decoder configuration.
+use crate::access_unit::AccessUnit;
+use crate::bitstream::verify_error;
+use crate::nalunits::{
+    parse_nal_unit_data, pps::ParameterSets, IDRPictureNU, KnownNALUnit, NALUnit, AUDNU, PPSNU,
+    SPSNU,
+};
+use crate::stream::Stream;
+use crate::writer::{Framing, NALUnitWriter};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use winnow::{binary, combinator, IResult, Parser};
+
+/// profile_idc values whose decoder configuration has the chroma format and bit depths
+const HIGH_PROFILES: [u8; 4] = [100, 110, 122, 144];
+
+/// Splits an AVCC sample into its NAL units; None if a length goes past the end or
+/// `length_size` isn't 1, 2 or 4 bytes
+pub fn split_length_prefixed(sample: &[u8], length_size: usize) -> Option<Vec<&[u8]>> {
+    if !matches!(length_size, 1 | 2 | 4) {
+        return None;
+    }
+    let mut units = Vec::new();
+    let mut rest = sample;
+    while !rest.is_empty() {
+        if rest.len() < length_size {
+            return None;
+        }
+        let (prefix, tail) = rest.split_at(length_size);
+        let length = prefix
+            .iter()
+            .fold(0_usize, |length, byte| length << 8 | *byte as usize);
+        if tail.len() < length {
+            return None;
+        }
+        let (unit, tail) = tail.split_at(length);
+        units.push(unit);
+        rest = tail;
+    }
+    Some(units)
+}
+
+/// Iterates over length prefixed NAL units in a `Read`, up to the end of the input or a
+/// NAL unit that is cut off by it. Out of band parameter sets go into
+/// `parameter_sets_mut` before the first slice.
+pub struct LengthPrefixedIterator<R: Read> {
+    input_reader: R,
+    length_size: usize,
+    parameter_sets: ParameterSets,
+}
+
+impl<R: Read> LengthPrefixedIterator<R> {
+    /// `length_size` is 1, 2 or 4 bytes
+    pub fn new(input_reader: R, length_size: usize) -> Self {
+        assert!(
+            matches!(length_size, 1 | 2 | 4),
+            "NAL unit length of {} bytes",
+            length_size
+        );
+        Self {
+            input_reader,
+            length_size,
+            parameter_sets: ParameterSets::new(),
+        }
+    }
+
+    pub fn parameter_sets(&self) -> &ParameterSets {
+        &self.parameter_sets
+    }
+
+    pub fn parameter_sets_mut(&mut self) -> &mut ParameterSets {
+        &mut self.parameter_sets
+    }
+
+    /// Up to `length` bytes; fewer at the end of the input
+    fn read(&mut self, length: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        if let Err(e) = (&mut self.input_reader)
+            .take(length as u64)
+            .read_to_end(&mut data)
+        {
+            panic!("error: {}", e);
+        }
+        data
+    }
+}
+
+impl<R: Read> Iterator for LengthPrefixedIterator<R> {
+    type Item = NALUnit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let prefix = self.read(self.length_size);
+            if prefix.len() < self.length_size {
+                return None;
+            }
+            let length = prefix
+                .iter()
+                .fold(0_usize, |length, byte| length << 8 | *byte as usize);
+            let data = self.read(length);
+            if data.len() < length {
+                return None;
+            }
+            // a NAL unit has at least its header byte
+            if data.iter().any(|byte| *byte != 0) {
+                return Some(parse_nal_unit_data(&data, &mut self.parameter_sets));
+            }
+        }
+    }
+}
+
+/// An access unit as an AVCC sample
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AVCCSample {
+    /// Offset of the access unit in the Annex B stream
+    pub offset: u64,
+    /// An IDR access unit, so a sync sample
+    pub idr: bool,
+    pub data: Vec<u8>,
+}
+
+/// Converts the access units of an Annex B stream to AVCC samples. Access unit
+/// delimiters are dropped; so are SPS and PPS, unless they are to stay in band (as in
+/// avc3 tracks), they are collected for the decoder configuration instead. A NAL unit
+/// that is too long for the length size is an error.
+pub struct AVCCSampleIterator<I: Iterator<Item = AccessUnit>> {
+    access_units: I,
+    length_size: usize,
+    in_band_parameter_sets: bool,
+    /// The last SPS and PPS of every id, as NAL units without length
+    sequence_parameter_sets: BTreeMap<u32, Vec<u8>>,
+    picture_parameter_sets: BTreeMap<u32, Vec<u8>>,
+}
+
+impl<I: Iterator<Item = AccessUnit>> AVCCSampleIterator<I> {
+    pub fn new(access_units: I, length_size: usize, in_band_parameter_sets: bool) -> Self {
+        Self {
+            access_units,
+            length_size,
+            in_band_parameter_sets,
+            sequence_parameter_sets: BTreeMap::new(),
+            picture_parameter_sets: BTreeMap::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &I {
+        &self.access_units
+    }
+
+    /// The SPS seen so far, by id; the last one of every id
+    pub fn sequence_parameter_sets(&self) -> Vec<&[u8]> {
+        self.sequence_parameter_sets
+            .values()
+            .map(Vec::as_slice)
+            .collect()
+    }
+
+    /// The PPS seen so far, by id; the last one of every id
+    pub fn picture_parameter_sets(&self) -> Vec<&[u8]> {
+        self.picture_parameter_sets
+            .values()
+            .map(Vec::as_slice)
+            .collect()
+    }
+}
+
+impl<I: Iterator<Item = AccessUnit>> Iterator for AVCCSampleIterator<I> {
+    type Item = io::Result<AVCCSample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let access_unit = self.access_units.next()?;
+        let mut writer = NALUnitWriter::new(Vec::new(), Framing::LengthPrefix(self.length_size));
+        for nal_unit in access_unit.nal_units.iter() {
+            let in_band = match nal_unit {
+                NALUnit::SPS(nu) => {
+                    let id = nu.sps.seq_parameter_set_id;
+                    self.sequence_parameter_sets.insert(id, nal_unit.to_bytes());
+                    self.in_band_parameter_sets
+                }
+                NALUnit::PPS(nu) => {
+                    let id = nu.pps.pic_parameter_set_id;
+                    self.picture_parameter_sets.insert(id, nal_unit.to_bytes());
+                    self.in_band_parameter_sets
+                }
+                NALUnit::AUD(_) => false,
+                _ => true,
+            };
+            if in_band {
+                if let Err(e) = writer.write(nal_unit) {
+                    return Some(Err(e));
+                }
+            }
+        }
+        Some(Ok(AVCCSample {
+            offset: access_unit.offset,
+            idr: access_unit.idr,
+            data: writer.into_inner(),
+        }))
+    }
+}
+
+/// Writes AVCC samples as an Annex B byte stream, with the out of band SPS and PPS
+/// before every IDR access unit that doesn't have its own
+pub struct AnnexBWriter<W: Write> {
+    writer: NALUnitWriter<W>,
+    length_size: usize,
+    parameter_sets: Vec<Vec<u8>>,
+}
+
+impl<W: Write> AnnexBWriter<W> {
+    /// `parameter_sets` are the SPS and PPS NAL units without length, as in the
+    /// decoder configuration. `length_size` is 1, 2 or 4 bytes.
+    pub fn new(writer: W, length_size: usize, parameter_sets: Vec<Vec<u8>>) -> Self {
+        assert!(
+            matches!(length_size, 1 | 2 | 4),
+            "NAL unit length of {} bytes",
+            length_size
+        );
+        Self {
+            writer: NALUnitWriter::new(writer, Framing::AnnexB),
+            length_size,
+            parameter_sets,
+        }
+    }
+
+    pub fn write_sample(&mut self, sample: &[u8]) -> io::Result<()> {
+        let units = split_length_prefixed(sample, self.length_size).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "NAL unit length past the end of the sample",
+            )
+        })?;
+        let units: Vec<&[u8]> = units.into_iter().filter(|unit| !unit.is_empty()).collect();
+        let has_type = |nal_unit_type| units.iter().any(|unit| unit[0] & 0x1f == nal_unit_type);
+        let insert = has_type(IDRPictureNU::NU_TYPE)
+            && !has_type(SPSNU::NU_TYPE)
+            && !has_type(PPSNU::NU_TYPE);
+        // the parameter sets go after the access unit delimiter, which has to be first
+        let position = match units.first() {
+            Some(unit) if unit[0] & 0x1f == AUDNU::NU_TYPE => 1,
+            _ => 0,
+        };
+        let mut first = true;
+        for (i, unit) in units.iter().enumerate() {
+            if insert && i == position {
+                for parameter_set in self.parameter_sets.iter() {
+                    self.writer.write_bytes(parameter_set, first)?;
+                    first = false;
+                }
+            }
+            self.writer.write_bytes(unit, first)?;
+            first = false;
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+/// The part of the decoder configuration for the High profiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighProfileExtension {
+    pub chroma_format: u8,
+    pub bit_depth_luma: u8,
+    pub bit_depth_chroma: u8,
+    /// Sequence parameter set extension NAL units
+    pub sequence_parameter_set_exts: Vec<Vec<u8>>,
+}
+
+/// AVCDecoderConfigurationRecord (ISO/IEC 14496-15 5.3.3.1), the payload of an avcC box
+/// or the CodecPrivate of a Matroska track. Parameter sets are NAL units without length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AVCDecoderConfigurationRecord {
+    pub profile_indication: u8,
+    pub profile_compatibility: u8,
+    pub level_indication: u8,
+    /// Bytes of the NAL unit lengths in the samples: 1, 2 or 4
+    pub length_size: usize,
+    pub sequence_parameter_sets: Vec<Vec<u8>>,
+    pub picture_parameter_sets: Vec<Vec<u8>>,
+    /// Not always there, even for the High profiles
+    pub high_profile: Option<HighProfileExtension>,
+}
+
+impl AVCDecoderConfigurationRecord {
+    const VERSION: u8 = 1;
+    /// numOfSequenceParameterSets has 5 bits, numOfPictureParameterSets 8
+    const MAX_SPS: usize = 0b1_1111;
+    const MAX_PPS: usize = 0xff;
+
+    /// From the first SPS and PPS of every id among `nal_units`; profile and level are
+    /// those of the first SPS. None without an SPS, with a `length_size` other than 1, 2
+    /// or 4, or with more parameter sets than the record can count (31 SPS, 255 PPS).
+    pub fn from_nal_units<'a>(
+        nal_units: impl IntoIterator<Item = &'a NALUnit>,
+        length_size: usize,
+    ) -> Option<Self> {
+        if !matches!(length_size, 1 | 2 | 4) {
+            return None;
+        }
+        // ids and NAL units
+        let mut sps: Vec<(u32, Vec<u8>)> = Vec::new();
+        let mut pps: Vec<(u32, Vec<u8>)> = Vec::new();
+        let mut first = None;
+        for nal_unit in nal_units {
+            let (list, id) = match nal_unit {
+                NALUnit::SPS(nu) => {
+                    first = first.or(Some(&nu.sps));
+                    (&mut sps, nu.sps.seq_parameter_set_id)
+                }
+                NALUnit::PPS(nu) => (&mut pps, nu.pps.pic_parameter_set_id),
+                _ => continue,
+            };
+            if !list.iter().any(|(known, _)| *known == id) {
+                list.push((id, nal_unit.to_bytes()));
+            }
+        }
+        let first = first?;
+        if sps.len() > Self::MAX_SPS || pps.len() > Self::MAX_PPS {
+            return None;
+        }
+        let high_profile =
+            HIGH_PROFILES
+                .contains(&first.profile_idc)
+                .then(|| HighProfileExtension {
+                    chroma_format: first.chroma_format_idc as u8,
+                    bit_depth_luma: first.bit_depth_luma as u8,
+                    bit_depth_chroma: first.bit_depth_chroma as u8,
+                    sequence_parameter_set_exts: Vec::new(),
+                });
+        Some(Self {
+            profile_indication: first.profile_idc,
+            profile_compatibility: first.constraint_flags,
+            level_indication: first.level_idc,
+            length_size,
+            sequence_parameter_sets: sps.into_iter().map(|(_, data)| data).collect(),
+            picture_parameter_sets: pps.into_iter().map(|(_, data)| data).collect(),
+            high_profile,
+        })
+    }
+
+    fn parse_parameter_sets(input: Stream, count: usize) -> IResult<Stream, Vec<Vec<u8>>> {
+        combinator::repeat(
+            count,
+            binary::length_data(binary::be_u16).map(|data: &[u8]| data.to_vec()),
+        )
+        .parse_next(input)
+    }
+
+    pub fn parse(input: Stream) -> IResult<Stream, Self> {
+        let (input, (version, profile_indication, profile_compatibility, level_indication)) =
+            (binary::be_u8, binary::be_u8, binary::be_u8, binary::be_u8).parse_next(input)?;
+        let (input, (length_size_minus_one, num_sps)) =
+            (binary::be_u8, binary::be_u8).parse_next(input)?;
+        if version != Self::VERSION || length_size_minus_one & 0b11 == 2 {
+            return verify_error(input);
+        }
+        let (input, sequence_parameter_sets) =
+            Self::parse_parameter_sets(input, (num_sps & 0b1_1111) as usize)?;
+        let (input, num_pps) = binary::be_u8.parse_next(input)?;
+        let (input, picture_parameter_sets) = Self::parse_parameter_sets(input, num_pps as usize)?;
+        let (input, high_profile) = combinator::cond(
+            HIGH_PROFILES.contains(&profile_indication) && !input.is_empty(),
+            |input| {
+                let (input, (chroma_format, bit_depth_luma_minus8, bit_depth_chroma_minus8)) =
+                    (binary::be_u8, binary::be_u8, binary::be_u8).parse_next(input)?;
+                let (input, num_sps_ext) = binary::be_u8.parse_next(input)?;
+                let (input, sequence_parameter_set_exts) =
+                    Self::parse_parameter_sets(input, num_sps_ext as usize)?;
+                Ok((
+                    input,
+                    HighProfileExtension {
+                        chroma_format: chroma_format & 0b11,
+                        bit_depth_luma: (bit_depth_luma_minus8 & 0b111) + 8,
+                        bit_depth_chroma: (bit_depth_chroma_minus8 & 0b111) + 8,
+                        sequence_parameter_set_exts,
+                    },
+                ))
+            },
+        )
+        .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                profile_indication,
+                profile_compatibility,
+                level_indication,
+                length_size: (length_size_minus_one & 0b11) as usize + 1,
+                sequence_parameter_sets,
+                picture_parameter_sets,
+                high_profile,
+            },
+        ))
+    }
+
+    /// None if the record cannot be written as it is: a `length_size` other than 1, 2 or
+    /// 4, more than 31 SPS or 255 PPS or SPS extensions, or a parameter set too long for
+    /// its 16 bit length
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        // the count, with the reserved bits above it, and the length prefixed sets
+        fn put_parameter_sets(
+            data: &mut Vec<u8>,
+            reserved: u8,
+            parameter_sets: &[Vec<u8>],
+            max: usize,
+        ) -> Option<()> {
+            if parameter_sets.len() > max {
+                return None;
+            }
+            data.push(reserved | parameter_sets.len() as u8);
+            for parameter_set in parameter_sets {
+                data.extend(u16::try_from(parameter_set.len()).ok()?.to_be_bytes());
+                data.extend(parameter_set);
+            }
+            Some(())
+        }
+        if !matches!(self.length_size, 1 | 2 | 4) {
+            return None;
+        }
+        let mut data = vec![
+            Self::VERSION,
+            self.profile_indication,
+            self.profile_compatibility,
+            self.level_indication,
+            0b1111_1100 | (self.length_size as u8 - 1),
+        ];
+        put_parameter_sets(
+            &mut data,
+            0b1110_0000,
+            &self.sequence_parameter_sets,
+            Self::MAX_SPS,
+        )?;
+        put_parameter_sets(&mut data, 0, &self.picture_parameter_sets, Self::MAX_PPS)?;
+        if let Some(high_profile) = &self.high_profile {
+            data.push(0b1111_1100 | (high_profile.chroma_format & 0b11));
+            data.push(0b1111_1000 | (high_profile.bit_depth_luma.wrapping_sub(8) & 0b111));
+            data.push(0b1111_1000 | (high_profile.bit_depth_chroma.wrapping_sub(8) & 0b111));
+            put_parameter_sets(
+                &mut data,
+                0,
+                &high_profile.sequence_parameter_set_exts,
+                Self::MAX_PPS,
+            )?;
+        }
+        Some(data)
+    }
+
+    /// The SPS and PPS of the configuration, parsed
+    pub fn parameter_sets(&self) -> ParameterSets {
+        let mut parameter_sets = ParameterSets::new();
+        let all = self.sequence_parameter_sets.iter();
+        for parameter_set in all.chain(self.picture_parameter_sets.iter()) {
+            if !parameter_set.is_empty() {
+                parse_nal_unit_data(parameter_set, &mut parameter_sets);
+            }
+        }
+        parameter_sets
+    }
+
+    /// Where the configuration disagrees with itself, or with the parameter sets of the
+    /// stream (`in_band`): in-band SPS and PPS have to be in the configuration as they are
+    pub fn check(&self, in_band: &ParameterSets) -> Vec<ConfigurationMismatch> {
+        let mut mismatches = Vec::new();
+        let mut parameter_sets = ParameterSets::new();
+        let all = self.sequence_parameter_sets.iter();
+        for parameter_set in all.chain(self.picture_parameter_sets.iter()) {
+            let nal_unit_type = match parameter_set.is_empty() {
+                false => parse_nal_unit_data(parameter_set, &mut parameter_sets).nal_unit_type(),
+                true => 0,
+            };
+            if !matches!(nal_unit_type, SPSNU::NU_TYPE | PPSNU::NU_TYPE) {
+                mismatches.push(ConfigurationMismatch::Unreadable(nal_unit_type));
+            }
+        }
+        let first =
+            self.sequence_parameter_sets.first().and_then(|data| {
+                match parse_nal_unit_data(data, &mut ParameterSets::new()) {
+                    NALUnit::SPS(nu) => Some(nu.sps),
+                    _ => None,
+                }
+            });
+        if let Some(sps) = first {
+            let indication = (sps.profile_idc, sps.constraint_flags, sps.level_idc);
+            if indication
+                != (
+                    self.profile_indication,
+                    self.profile_compatibility,
+                    self.level_indication,
+                )
+            {
+                mismatches.push(ConfigurationMismatch::Indication);
+            }
+            let chroma_and_bit_depths = (
+                sps.chroma_format_idc,
+                sps.bit_depth_luma,
+                sps.bit_depth_chroma,
+            );
+            if self.high_profile.as_ref().is_some_and(|high_profile| {
+                chroma_and_bit_depths
+                    != (
+                        high_profile.chroma_format as u32,
+                        high_profile.bit_depth_luma as u32,
+                        high_profile.bit_depth_chroma as u32,
+                    )
+            }) {
+                mismatches.push(ConfigurationMismatch::HighProfileExtension);
+            }
+        }
+        let mut sps_ids: Vec<_> = in_band.sps.keys().copied().collect();
+        sps_ids.sort();
+        for id in sps_ids {
+            if parameter_sets.sps.get(&id) != in_band.sps.get(&id) {
+                mismatches.push(ConfigurationMismatch::SPS(id));
+            }
+        }
+        let mut pps_ids: Vec<_> = in_band.pps.keys().copied().collect();
+        pps_ids.sort();
+        for id in pps_ids {
+            if parameter_sets.pps.get(&id) != in_band.pps.get(&id) {
+                mismatches.push(ConfigurationMismatch::PPS(id));
+            }
+        }
+        mismatches
+    }
+}
+
+/// A problem `AVCDecoderConfigurationRecord::check` found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigurationMismatch {
+    /// A parameter set of the configuration that isn't a readable SPS or PPS; its
+    /// nal_unit_type
+    Unreadable(u8),
+    /// Profile, compatibility or level differ from those of the first SPS
+    Indication,
+    /// Chroma format or bit depths differ from those of the first SPS
+    HighProfileExtension,
+    /// The in-band SPS with this id is not in the configuration, or differs from it
+    SPS(u32),
+    /// The in-band PPS with this id is not in the configuration, or differs from it
+    PPS(u32),
+}
+
+impl fmt::Display for ConfigurationMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unreadable(nal_unit_type) => {
+                write!(
+                    f,
+                    "unreadable parameter set (NAL unit type {})",
+                    nal_unit_type
+                )
+            }
+            Self::Indication => write!(f, "profile or level differ from the SPS"),
+            Self::HighProfileExtension => {
+                write!(f, "chroma format or bit depths differ from the SPS")
+            }
+            Self::SPS(id) => write!(f, "SPS {} differs from the in-band one", id),
+            Self::PPS(id) => write!(f, "PPS {} differs from the in-band one", id),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+// Bit level reading and writing of RBSPs (NAL unit payloads without emulation prevention
+// bytes): u(n), ue(v), se(v), more_rbsp_data() and rbsp_trailing_bits() of ITU-T H.264
+// 7.2.
+//
+// Reading past the end of the RBSP is an error (ErrorKind::Eof or ErrorKind::Token),
+// never a panic.
+use super::stream::{stream, Stream};
+use winnow::{
+    binary::bits,
+    error::{self, ParseError},
+    IResult, Parser,
+};
+
+/// The remaining bytes, and the number of bits of the first one that are already read
+pub type BitInput<'i> = (Stream<'i>, usize);
+pub type BitResult<'i, O> = IResult<BitInput<'i>, O, error::Error<BitInput<'i>>>;
+
+pub fn verify_error<I: Clone, O>(input: I) -> IResult<I, O, error::Error<I>> {
+    Err(error::ErrMode::Backtrack(error::Error::from_error_kind(
+        input,
+        error::ErrorKind::Verify,
+    )))
+}
+
+pub fn bits_consumed(start: &BitInput, end: &BitInput) -> usize {
+    (start.0.len() - end.0.len()) * 8 + end.1 - start.1
+}
+
+pub fn bits_remaining(input: &BitInput) -> usize {
+    input.0.len() * 8 - input.1
+}
+
+pub fn byte_aligned(input: &BitInput) -> bool {
+    input.1 == 0
+}
+
+pub fn skip_bits(mut input: BitInput, mut count: usize) -> BitResult<()> {
+    while count > 0 {
+        let step = count.min(32);
+        (input, _) = bits::take::<_, u32, _, _>(step).parse_next(input)?;
+        count -= step;
+    }
+    Ok((input, ()))
+}
+
+/// u(1)
+pub fn flag(input: BitInput) -> BitResult<bool> {
+    bits::bool.parse_next(input)
+}
+
+/// u(n) for n up to 32
+pub fn u(count: usize) -> impl FnMut(BitInput) -> BitResult<u32> {
+    move |input| bits::take::<_, u32, _, _>(count).parse_next(input)
+}
+
+/// codeNum of an Exp-Golomb code with at most `max_leading_zeros` leading zero bits
+fn exp_golomb_code(mut input: BitInput, max_leading_zeros: usize) -> BitResult<u64> {
+    let mut leading_zeros = 0_usize;
+    loop {
+        let bit;
+        (input, bit) = bits::bool.parse_next(input)?;
+        if bit {
+            break;
+        }
+        leading_zeros += 1;
+        if leading_zeros > max_leading_zeros {
+            return verify_error(input);
+        }
+    }
+    let (input, suffix) = bits::take::<_, u64, _, _>(leading_zeros).parse_next(input)?;
+    Ok((input, (1_u64 << leading_zeros) - 1 + suffix))
+}
+
+/// ue(v), unsigned Exp-Golomb; codes longer than 32 bits don't fit in a u32 and fail
+pub fn ue(input: BitInput) -> BitResult<u32> {
+    let (input, code) = exp_golomb_code(input, 31)?;
+    Ok((input, code as u32))
+}
+
+/// se(v), signed Exp-Golomb; i32::MIN is the only value with a 33 bit code
+pub fn se(input: BitInput) -> BitResult<i32> {
+    let (input, code) = exp_golomb_code(input, 32)?;
+    let magnitude = code.div_ceil(2) as i64;
+    match i32::try_from(if code % 2 == 0 { -magnitude } else { magnitude }) {
+        Ok(value) => Ok((input, value)),
+        Err(_) => verify_error(input),
+    }
+}
+
+/// me(v) and te(v) with a range above 1 are coded like ue(v); te(v) with range 1 is the
+/// inverted bit
+pub fn te(range: u32) -> impl FnMut(BitInput) -> BitResult<u32> {
+    move |input| match range {
+        1 => flag.map(|bit| !bit as u32).parse_next(input),
+        _ => ue(input),
+    }
+}
+
+/// more_rbsp_data(): whether there is anything before the rbsp_stop_one_bit
+pub fn more_rbsp_data(input: &BitInput) -> bool {
+    let (bytes, bit_offset) = input;
+    let Some(last) = bytes.iter().rposition(|byte| *byte != 0) else {
+        return false;
+    };
+    // position (in bits from the start of `bytes`) of the stop bit, the last one bit
+    let stop_bit = last * 8 + 7 - bytes[last].trailing_zeros() as usize;
+    *bit_offset < stop_bit
+}
+
+/// rbsp_trailing_bits(): the stop bit and the zero bits up to the next byte boundary
+pub fn rbsp_trailing_bits(input: BitInput) -> BitResult<()> {
+    let (input, _) = bits::tag(1_u8, 1_usize).parse_next(input)?;
+    let alignment = (8 - input.1) % 8;
+    let (input, _) = bits::tag(0_u8, alignment).parse_next(input)?;
+    Ok((input, ()))
+}
+
+/// Parses a whole RBSP with a bit level parser
+pub fn parse_rbsp<'i, O>(
+    rbsp: &'i [u8],
+    parser: impl Parser<BitInput<'i>, O, error::Error<BitInput<'i>>>,
+) -> Result<O, error::ErrMode<error::Error<Stream<'i>>>> {
+    bits::bits::<_, _, error::Error<(_, usize)>, error::Error<_>, _>(parser)
+        .parse_next(stream(rbsp))
+        .map(|(_, output)| output)
+}
+
+/// Builds an RBSP bit by bit, most significant bit first
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits of the last byte that are already written; 0 when byte aligned
+    bit_offset: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn byte_aligned(&self) -> bool {
+        self.bit_offset == 0
+    }
+
+    fn put(&mut self, count: usize, value: u64) {
+        for i in (0..count).rev() {
+            if self.bit_offset == 0 {
+                self.bytes.push(0);
+            }
+            let bit = (value >> i) as u8 & 1;
+            *self.bytes.last_mut().unwrap() |= bit << (7 - self.bit_offset);
+            self.bit_offset = (self.bit_offset + 1) % 8;
+        }
+    }
+
+    /// u(1)
+    pub fn flag(&mut self, value: bool) {
+        self.put(1, value as u64);
+    }
+
+    /// u(n) for n up to 32; only the lowest `count` bits of `value` are written
+    pub fn u(&mut self, count: usize, value: u32) {
+        self.put(count, value as u64);
+    }
+
+    /// The Exp-Golomb code of codeNum `code`
+    fn exp_golomb(&mut self, code: u64) {
+        let code = code + 1;
+        let length = (u64::BITS - code.leading_zeros()) as usize;
+        self.put(length - 1, 0);
+        self.put(length, code);
+    }
+
+    /// ue(v)
+    pub fn ue(&mut self, value: u32) {
+        self.exp_golomb(value as u64);
+    }
+
+    /// se(v); the code of i32::MIN doesn't fit in a u32
+    pub fn se(&mut self, value: i32) {
+        let code = match value {
+            v if v > 0 => 2 * v as u64 - 1,
+            v => 2 * v.unsigned_abs() as u64,
+        };
+        self.exp_golomb(code);
+    }
+
+    /// rbsp_trailing_bits()
+    pub fn rbsp_trailing_bits(&mut self) {
+        self.flag(true);
+        self.put((8 - self.bit_offset) % 8, 0);
+    }
+
+    /// The bytes written so far; a last byte that isn't complete is padded with zero bits
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
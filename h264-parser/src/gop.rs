@@ -0,0 +1,385 @@
+// GOP structure of an H.264 stream: where GOPs start, whether they are open, their
+// picture types in decoding and display order and how deep their B-pyramids are
+use crate::access_unit::AccessUnit;
+use crate::nalunits::{
+    sei::SEIMessage,
+    slice::{SliceHeader, SliceType},
+    NALUnit,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GOPStart {
+    IDR,
+    /// An access unit with a recovery point SEI message
+    RecoveryPoint {
+        recovery_frame_cnt: u32,
+        broken_link: bool,
+    },
+    /// A non-IDR I picture without recovery point
+    Intra,
+    /// The stream starts inside the GOP
+    Incomplete,
+}
+
+impl GOPStart {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::IDR => "IDR",
+            Self::RecoveryPoint { .. } => "recovery point",
+            Self::Intra => "I",
+            Self::Incomplete => "incomplete",
+        }
+    }
+}
+
+/// One access unit of a GOP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GOPPicture {
+    /// B if the picture has B slices, otherwise P if it has P or SP slices; None
+    /// without readable slice headers
+    pub slice_type: Option<SliceType>,
+    /// nal_ref_idc is not 0
+    pub reference: bool,
+    pub pic_order_cnt: Option<i32>,
+    /// 1 for a field, 2 for a frame
+    pub fields: u64,
+    pub size: usize,
+}
+
+impl GOPPicture {
+    fn new(access_unit: &AccessUnit) -> Self {
+        let slice_types = access_unit.slice_types();
+        let slice_type = [SliceType::B, SliceType::P, SliceType::SP, SliceType::I]
+            .into_iter()
+            .find(|t| slice_types.contains(t))
+            .or(slice_types.first().copied());
+        let reference = access_unit
+            .primary_slices()
+            .any(|nal_unit| nal_unit.slice_header().is_some() && nal_unit.ref_idc() != 0);
+        Self {
+            slice_type,
+            reference,
+            pic_order_cnt: access_unit.pic_order_cnt.map(|poc| poc.pic_order_cnt()),
+            fields: access_unit.fields(),
+            size: access_unit.size,
+        }
+    }
+
+    /// I, P, B, or ? without slice headers
+    pub fn letter(&self) -> char {
+        match self.slice_type {
+            Some(SliceType::I | SliceType::SI) => 'I',
+            Some(SliceType::P | SliceType::SP) => 'P',
+            Some(SliceType::B) => 'B',
+            None => '?',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GOP {
+    /// Offset of the first access unit in the stream
+    pub offset: u64,
+    pub start: GOPStart,
+    /// No picture refers to one before the GOP: it starts with an IDR picture, or no
+    /// inter picture after the first one is shown before it
+    pub closed: bool,
+    /// In decoding order; fields of a pair are separate pictures
+    pub pictures: Vec<GOPPicture>,
+}
+
+impl GOP {
+    fn new(offset: u64, start: GOPStart) -> Self {
+        Self {
+            offset,
+            start,
+            closed: start != GOPStart::Incomplete,
+            pictures: Vec::new(),
+        }
+    }
+
+    /// Length in frames, counting a field pair as one
+    pub fn frames(&self) -> u64 {
+        self.pictures
+            .iter()
+            .map(|p| p.fields)
+            .sum::<u64>()
+            .div_ceil(2)
+    }
+
+    /// Bytes, including start codes
+    pub fn size(&self) -> usize {
+        self.pictures.iter().map(|p| p.size).sum()
+    }
+
+    pub fn count(&self, letter: char) -> usize {
+        self.pictures
+            .iter()
+            .filter(|p| p.letter() == letter)
+            .count()
+    }
+
+    pub fn decode_pattern(&self) -> String {
+        self.pictures.iter().map(GOPPicture::letter).collect()
+    }
+
+    /// By POC; pictures without one keep their place in decoding order
+    pub fn display_pattern(&self) -> String {
+        let mut sorted: Vec<&GOPPicture> = self
+            .pictures
+            .iter()
+            .filter(|p| p.pic_order_cnt.is_some())
+            .collect();
+        sorted.sort_by_key(|p| p.pic_order_cnt);
+        let mut sorted = sorted.into_iter();
+        // the pictures with a POC are put in display order in the places they take
+        self.pictures
+            .iter()
+            .map(|p| match p.pic_order_cnt {
+                Some(_) => sorted.next().unwrap().letter(),
+                None => p.letter(),
+            })
+            .collect()
+    }
+
+    /// B pictures that other pictures refer to
+    pub fn reference_b_pictures(&self) -> usize {
+        let reference_b = |p: &&GOPPicture| p.reference && p.slice_type == Some(SliceType::B);
+        self.pictures.iter().filter(reference_b).count()
+    }
+
+    /// The number of levels of B pictures: 0 without B pictures, 1 if they only refer
+    /// to I and P pictures, 2 if some refer to reference B pictures and so on. A B
+    /// picture is a level above the reference pictures decoded before it that are
+    /// next to it in display order.
+    pub fn b_pyramid_depth(&self) -> u32 {
+        // POC and level of the reference pictures decoded so far
+        let mut references: Vec<(i32, u32)> = Vec::new();
+        let mut depth = 0;
+        for picture in self.pictures.iter() {
+            let Some(poc) = picture.pic_order_cnt else {
+                continue;
+            };
+            let level = match picture.slice_type {
+                Some(SliceType::B) => {
+                    let before = references.iter().filter(|r| r.0 < poc).max_by_key(|r| r.0);
+                    let after = references.iter().filter(|r| r.0 > poc).min_by_key(|r| r.0);
+                    let level = before.map_or(0, |r| r.1).max(after.map_or(0, |r| r.1)) + 1;
+                    depth = depth.max(level);
+                    level
+                }
+                _ => 0,
+            };
+            if picture.reference {
+                references.push((poc, level));
+            }
+        }
+        depth
+    }
+}
+
+impl fmt::Display for GOP {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, {} frames, {} bytes, decode {}, display {}",
+            self.start.name(),
+            if self.closed { "closed" } else { "open" },
+            self.frames(),
+            self.size(),
+            self.decode_pattern(),
+            self.display_pattern(),
+        )?;
+        if let GOPStart::RecoveryPoint {
+            recovery_frame_cnt,
+            broken_link,
+        } = self.start
+        {
+            write!(f, ", recovery after {} frames", recovery_frame_cnt)?;
+            if broken_link {
+                write!(f, ", broken link")?;
+            }
+        }
+        if self.count('B') > 0 {
+            write!(
+                f,
+                ", B-pyramid depth {} ({} reference B)",
+                self.b_pyramid_depth(),
+                self.reference_b_pictures()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects access units (in decoding order) into GOPs
+pub struct GOPIterator<I: Iterator<Item = AccessUnit>> {
+    access_units: I,
+    current: Option<GOP>,
+    /// POC of the first picture of the current GOP
+    start_pic_order_cnt: Option<i32>,
+    /// The first slice header of the previous access unit
+    previous: Option<SliceHeader>,
+}
+
+impl<I: Iterator<Item = AccessUnit>> GOPIterator<I> {
+    pub fn new(access_units: I) -> Self {
+        Self {
+            access_units,
+            current: None,
+            start_pic_order_cnt: None,
+            previous: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &I {
+        &self.access_units
+    }
+
+    /// How `access_unit` starts a GOP, if it does
+    fn start(&self, access_unit: &AccessUnit) -> Option<GOPStart> {
+        // the second field of a pair belongs to the GOP of the first
+        let header = access_unit.first_slice_header();
+        if let (Some(header), Some(previous)) = (header, self.previous.as_ref()) {
+            if header.is_second_field_of(previous) {
+                return None;
+            }
+        }
+        if access_unit.idr {
+            return Some(GOPStart::IDR);
+        }
+        let recovery_point = access_unit
+            .nal_units
+            .iter()
+            .filter_map(|nal_unit| match nal_unit {
+                NALUnit::SEI(sei) => Some(sei.messages.iter()),
+                _ => None,
+            })
+            .flatten()
+            .find_map(|message| match message {
+                SEIMessage::RecoveryPoint(recovery_point) => Some(recovery_point),
+                _ => None,
+            });
+        if let Some(recovery_point) = recovery_point {
+            return Some(GOPStart::RecoveryPoint {
+                recovery_frame_cnt: recovery_point.recovery_frame_cnt,
+                broken_link: recovery_point.broken_link,
+            });
+        }
+        let slice_types = access_unit.slice_types();
+        let intra = !slice_types.is_empty() && slice_types.iter().all(SliceType::is_intra);
+        intra.then_some(GOPStart::Intra)
+    }
+}
+
+impl<I: Iterator<Item = AccessUnit>> Iterator for GOPIterator<I> {
+    type Item = GOP;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(access_unit) = self.access_units.next() else {
+                return self.current.take();
+            };
+            let start = self.start(&access_unit);
+            self.previous = access_unit.first_slice_header().cloned();
+            let picture = GOPPicture::new(&access_unit);
+            let mut finished = None;
+            if let Some(start) = start {
+                finished = self.current.replace(GOP::new(access_unit.offset, start));
+                self.start_pic_order_cnt = picture.pic_order_cnt;
+            }
+            let gop = self
+                .current
+                .get_or_insert_with(|| GOP::new(access_unit.offset, GOPStart::Incomplete));
+            // leading pictures: after the first one in decoding order, before it in
+            // display order, and predicted from the previous GOP
+            let leading = match (picture.pic_order_cnt, self.start_pic_order_cnt) {
+                (Some(poc), Some(start)) => !gop.pictures.is_empty() && poc < start,
+                _ => false,
+            };
+            if leading && picture.letter() != 'I' && gop.start != GOPStart::IDR {
+                gop.closed = false;
+            }
+            gop.pictures.push(picture);
+            if finished.is_some() {
+                return finished;
+            }
+        }
+    }
+}
+
+/// Aggregates of all GOPs of a stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GOPSummary {
+    pub gops: usize,
+    pub closed: usize,
+    pub idr_starts: usize,
+    pub recovery_point_starts: usize,
+    pub intra_starts: usize,
+    pub min_frames: Option<u64>,
+    pub max_frames: u64,
+    pub frames: u64,
+    pub i_pictures: usize,
+    pub p_pictures: usize,
+    pub b_pictures: usize,
+    pub reference_b_pictures: usize,
+    pub max_b_pyramid_depth: u32,
+    pub size: u64,
+}
+
+impl GOPSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, gop: &GOP) {
+        self.gops += 1;
+        self.closed += gop.closed as usize;
+        match gop.start {
+            GOPStart::IDR => self.idr_starts += 1,
+            GOPStart::RecoveryPoint { .. } => self.recovery_point_starts += 1,
+            GOPStart::Intra => self.intra_starts += 1,
+            GOPStart::Incomplete => (),
+        }
+        let frames = gop.frames();
+        self.min_frames = Some(self.min_frames.map_or(frames, |min| min.min(frames)));
+        self.max_frames = self.max_frames.max(frames);
+        self.frames += frames;
+        self.i_pictures += gop.count('I');
+        self.p_pictures += gop.count('P');
+        self.b_pictures += gop.count('B');
+        self.reference_b_pictures += gop.reference_b_pictures();
+        self.max_b_pyramid_depth = self.max_b_pyramid_depth.max(gop.b_pyramid_depth());
+        self.size += gop.size() as u64;
+    }
+}
+
+impl fmt::Display for GOPSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.gops == 0 {
+            return write!(f, "no GOPs");
+        }
+        write!(
+            f,
+            "{} GOPs ({} closed; {} IDR, {} recovery point, {} I starts), {}-{} frames \
+             (average {:.1}), {} I / {} P / {} B pictures ({} reference B), B-pyramid \
+             depth up to {}, {} bytes (average {} per GOP)",
+            self.gops,
+            self.closed,
+            self.idr_starts,
+            self.recovery_point_starts,
+            self.intra_starts,
+            self.min_frames.unwrap_or(0),
+            self.max_frames,
+            self.frames as f64 / self.gops as f64,
+            self.i_pictures,
+            self.p_pictures,
+            self.b_pictures,
+            self.reference_b_pictures,
+            self.max_b_pyramid_depth,
+            self.size,
+            self.size / self.gops as u64,
+        )
+    }
+}
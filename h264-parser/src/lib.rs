@@ -1,70 +1,58 @@
+pub mod access_unit;
+pub mod annexb;
+pub mod avcc;
+pub mod bitstream;
+pub mod gop;
+pub mod mdpm;
 pub mod nalunits;
+pub mod poc;
+pub mod startcode;
 pub mod stream;
+pub mod writer;
 
+use annexb::{ByteStreamIterator, ByteStreamProblem};
+use nalunits::pps::ParameterSets;
 use std::io::Read;
-use circular::Buffer;
-
-use winnow::{
-    error,
-    stream::Offset,
-};
-
-/// We will read the file in chunks of this size
-const CHUNK_SIZE: usize = 10 * 1024;
 
 
 pub struct NALUnitIterator {
-    input_reader: Box<dyn Read>,
-    buffer: Buffer,
-    has_reached_eof: bool,
-    
+    units: ByteStreamIterator<Box<dyn Read>>,
+    parameter_sets: ParameterSets,
+    offset: Option<u64>,
 }
 
 
 impl NALUnitIterator {
     pub fn new(input_reader: Box<dyn Read>) -> NALUnitIterator {
-        return Self {
-            input_reader,
-            buffer: Buffer::with_capacity(CHUNK_SIZE),
-            has_reached_eof: false,
+        Self {
+            units: ByteStreamIterator::new(input_reader),
+            parameter_sets: ParameterSets::new(),
+            offset: None,
         }
     }
+
+    /// Offset in the byte stream of the header byte of the last NAL unit returned
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// The SPS and PPS seen so far
+    pub fn parameter_sets(&self) -> &ParameterSets {
+        &self.parameter_sets
+    }
+
+    /// What is wrong with the byte stream so far
+    pub fn problems(&self) -> &[ByteStreamProblem] {
+        self.units.problems()
+    }
 }
 
 impl Iterator for NALUnitIterator {
     type Item = nalunits::NALUnit;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if self.has_reached_eof && self.buffer.available_data() == 0 {
-                return None;
-            }
-            let input = stream::partialstream(self.buffer.data(), self.has_reached_eof);
-            match nalunits::parse_nal_unit(input) {
-                Ok((remainer, return_value)) => {
-                    let consumed = input.offset_to(&remainer);
-                    self.buffer.consume(consumed);
-                    return Some(return_value);
-                }
-                Err(error::ErrMode::Incomplete(_)) => {
-                    if self.buffer.position() + self.buffer.available_space() >= CHUNK_SIZE {
-                        self.buffer.shift();
-                    } else {
-                        self.buffer.grow(self.buffer.capacity() + CHUNK_SIZE);
-                    }
-                    match self.input_reader.read(self.buffer.space()) {
-                        Ok(read) => {
-                            self.buffer.fill(read);
-                            if read == 0 {
-                                self.has_reached_eof = true;
-                            }
-                            continue;
-                        },
-                        Err(e) => panic!("error: {}", e),
-                    }
-                }
-                Err(e) => panic!("Parse error: {}", e),
-            };
-        }
+        let unit = self.units.next()?;
+        self.offset = Some(unit.offset);
+        Some(self.units.parse(&unit, &mut self.parameter_sets))
     }
 }
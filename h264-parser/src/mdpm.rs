@@ -0,0 +1,527 @@
+// AVCHD camera metadata: the "MDPM" user_data_unregistered SEI message that Sony,
+// Panasonic and Canon camcorders put in the first access unit of every GOP. Sony has
+// not published the format; the tags below are the ones ExifTool has worked out.
+use crate::nalunits::{sei::SEIMessage, NALUnit};
+use std::fmt;
+use std::io::{self, Write};
+
+/// uuid_iso_iec_11578 of the MDPM user data
+pub const MDPM_UUID: [u8; 16] = [
+    0x17, 0xee, 0x8c, 0x60, 0xf8, 0x4d, 0x11, 0xd9, 0x8c, 0xd6, 0x08, 0x00, 0x20, 0x0c, 0x9a, 0x66,
+];
+
+const DATE_TIME: u8 = 0x18;
+const CAMERA: u8 = 0x70;
+const EXPOSURE_TIME: u8 = 0xa0;
+const F_NUMBER: u8 = 0xa2;
+const EXPOSURE_COMPENSATION: u8 = 0xa4;
+const GPS_LATITUDE_REF: u8 = 0xb1;
+const GPS_LATITUDE: u8 = 0xb2;
+const GPS_LONGITUDE_REF: u8 = 0xb8;
+const GPS_LONGITUDE: u8 = 0xb9;
+const GPS_ALTITUDE_REF: u8 = 0xbf;
+const GPS_ALTITUDE: u8 = 0xc0;
+const MAKE: u8 = 0xe0;
+const MODEL: u8 = 0xe4;
+const MODEL_END: u8 = 0xef;
+
+/// The tag/value records of one MDPM message. Values longer than 4 bytes are split
+/// over records with consecutive tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MDPM {
+    pub records: Vec<(u8, [u8; 4])>,
+}
+
+impl MDPM {
+    /// The data after the uuid: "MDPM", the number of records and 5 bytes per record
+    pub fn from_payload(data: &[u8]) -> Option<Self> {
+        let count = *data.strip_prefix(b"MDPM")?.first()? as usize;
+        let records = data[5..]
+            .chunks_exact(5)
+            .take(count)
+            .map(|record| (record[0], record[1..].try_into().unwrap()))
+            .collect();
+        Some(Self { records })
+    }
+
+    pub fn from_sei(message: &SEIMessage) -> Option<Self> {
+        match message {
+            SEIMessage::UserDataUnregistered(uuid, data) if *uuid == MDPM_UUID => {
+                Self::from_payload(data)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, tag: u8) -> Option<[u8; 4]> {
+        self.records
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| *value)
+    }
+
+    /// The values of `count` records from `tag` on, all of which have to be present
+    fn value(&self, tag: u8, count: u8) -> Option<Vec<u8>> {
+        let values: Option<Vec<_>> = (tag..tag + count).map(|t| self.get(t)).collect();
+        Some(values?.concat())
+    }
+
+    /// An unsigned rational over two records
+    fn rational(&self, tag: u8) -> Option<(u32, u32)> {
+        let value = self.value(tag, 2)?;
+        let numerator = u32::from_be_bytes(value[..4].try_into().unwrap());
+        let denominator = u32::from_be_bytes(value[4..].try_into().unwrap());
+        Some((numerator, denominator))
+    }
+
+    /// Degrees, minutes and seconds over six records, negative on the `negative` side
+    fn coordinate(&self, ref_tag: u8, tag: u8, negative: u8) -> Option<f64> {
+        let mut degrees = 0.0;
+        for (i, unit) in [1.0, 60.0, 3600.0].into_iter().enumerate() {
+            let (numerator, denominator) = self.rational(tag + 2 * i as u8)?;
+            if denominator == 0 {
+                return None;
+            }
+            degrees += numerator as f64 / denominator as f64 / unit;
+        }
+        match self.get(ref_tag)?[0] {
+            r if r == negative => Some(-degrees),
+            _ => Some(degrees),
+        }
+    }
+
+    fn position(&self) -> Option<GPSPosition> {
+        let latitude = self.coordinate(GPS_LATITUDE_REF, GPS_LATITUDE, b'S')?;
+        let longitude = self.coordinate(GPS_LONGITUDE_REF, GPS_LONGITUDE, b'W')?;
+        let altitude = self
+            .rational(GPS_ALTITUDE)
+            .filter(|(_, denominator)| *denominator != 0)
+            .map(|(numerator, denominator)| {
+                let altitude = numerator as f64 / denominator as f64;
+                // 1: below sea level
+                match self.get(GPS_ALTITUDE_REF).map(|r| r[0]) {
+                    Some(1) => -altitude,
+                    _ => altitude,
+                }
+            });
+        Some(GPSPosition {
+            latitude,
+            longitude,
+            altitude,
+        })
+    }
+
+    fn model(&self) -> Option<String> {
+        let mut bytes = Vec::new();
+        for tag in MODEL..=MODEL_END {
+            let Some(value) = self.get(tag) else {
+                break;
+            };
+            bytes.extend_from_slice(&value);
+        }
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        let model = String::from_utf8_lossy(&bytes[..end]).trim().to_string();
+        (!model.is_empty()).then_some(model)
+    }
+
+    pub fn metadata(&self) -> CameraMetadata {
+        let camera = self.get(CAMERA);
+        CameraMetadata {
+            recorded: self
+                .value(DATE_TIME, 2)
+                .and_then(|v| RecordingTime::from_bytes(&v)),
+            exposure_time: self.rational(EXPOSURE_TIME),
+            f_number: self
+                .rational(F_NUMBER)
+                .filter(|(_, denominator)| *denominator != 0)
+                .map(|(numerator, denominator)| numerator as f64 / denominator as f64),
+            exposure_compensation: self
+                .rational(EXPOSURE_COMPENSATION)
+                .filter(|(_, denominator)| *denominator as i32 != 0)
+                .map(|(numerator, denominator)| {
+                    numerator as i32 as f64 / denominator as i32 as f64
+                }),
+            gain: camera.map(|c| c[1] & 0x0f).and_then(|gain| match gain {
+                0 => Some(Gain::Auto),
+                0x0f => None,
+                _ => Some(Gain::Decibels((gain as i32 - 1) * 3)),
+            }),
+            exposure_program: camera.map(|c| c[1] >> 4).filter(|&p| p != 0x0f),
+            white_balance: camera.map(|c| c[2] >> 5).filter(|&w| w != 0x07),
+            focus: camera.map(|c| c[3]).filter(|&f| f != 0xff).map(|f| Focus {
+                manual: f & 0x80 != 0,
+                distance: (f & 0x7e) as f32 / if f & 0x01 != 0 { 40.0 } else { 400.0 },
+            }),
+            position: self.position(),
+            make: self.get(MAKE).map(|m| u16::from_be_bytes([m[0], m[1]])),
+            model: self.model(),
+        }
+    }
+}
+
+/// Local date and time of the recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Minutes east of UTC, if the camera knows its time zone
+    pub utc_offset: Option<i16>,
+    pub daylight_saving: bool,
+}
+
+impl RecordingTime {
+    /// The time zone byte and 7 BCD bytes: century, year, month, day, hour, minute,
+    /// second
+    fn from_bytes(value: &[u8]) -> Option<Self> {
+        let bcd = |b: u8| (b >> 4 < 10 && b & 0x0f < 10).then_some((b >> 4) * 10 + (b & 0x0f));
+        let mut digits = [0; 7];
+        for (digit, &b) in digits.iter_mut().zip(value[1..].iter()) {
+            *digit = bcd(b)?;
+        }
+        let [century, year, month, day, hour, minute, second] = digits;
+        // sign, hours and half an hour
+        let time_zone = value[0];
+        let utc_offset = (time_zone != 0xff).then(|| {
+            let minutes = ((time_zone >> 1) & 0x0f) as i16 * 60 + (time_zone & 0x01) as i16 * 30;
+            if time_zone & 0x20 != 0 {
+                -minutes
+            } else {
+                minutes
+            }
+        });
+        Some(Self {
+            year: century as u16 * 100 + year as u16,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            utc_offset,
+            daylight_saving: time_zone != 0xff && time_zone & 0x40 != 0,
+        })
+    }
+}
+
+impl fmt::Display for RecordingTime {
+    /// ISO 8601, with the UTC offset if known
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        if let Some(offset) = self.utc_offset {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.unsigned_abs();
+            write!(f, "{}{:02}:{:02}", sign, offset / 60, offset % 60)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gain {
+    Auto,
+    Decibels(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Focus {
+    pub manual: bool,
+    /// Metres
+    pub distance: f32,
+}
+
+/// WGS 84 degrees, negative south and west, and metres above sea level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GPSPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+/// The decoded fields of an MDPM message
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraMetadata {
+    pub recorded: Option<RecordingTime>,
+    /// Seconds, as a rational
+    pub exposure_time: Option<(u32, u32)>,
+    pub f_number: Option<f64>,
+    /// EV
+    pub exposure_compensation: Option<f64>,
+    pub gain: Option<Gain>,
+    pub exposure_program: Option<u8>,
+    pub white_balance: Option<u8>,
+    pub focus: Option<Focus>,
+    pub position: Option<GPSPosition>,
+    pub make: Option<u16>,
+    pub model: Option<String>,
+}
+
+impl CameraMetadata {
+    pub fn exposure_program_name(&self) -> Option<&'static str> {
+        Some(match self.exposure_program? {
+            0 => "program AE",
+            1 => "gain",
+            2 => "shutter speed priority AE",
+            3 => "aperture priority AE",
+            4 => "manual",
+            _ => "unknown",
+        })
+    }
+
+    pub fn white_balance_name(&self) -> Option<&'static str> {
+        Some(match self.white_balance? {
+            0 => "auto",
+            1 => "hold",
+            2 => "one push",
+            3 => "daylight",
+            _ => "unknown",
+        })
+    }
+
+    pub fn make_name(&self) -> Option<&'static str> {
+        Some(match self.make? {
+            0x0103 => "Panasonic",
+            0x0108 => "Sony",
+            0x1011 => "Canon",
+            0x1104 => "JVC",
+            _ => "unknown",
+        })
+    }
+}
+
+/// The metadata of one GOP
+#[derive(Debug, Clone, PartialEq)]
+pub struct GOPMetadata {
+    /// Index of the MDPM message in the stream
+    pub gop: usize,
+    /// Index of the first picture of the GOP; fields of a pair count separately
+    pub picture: u64,
+    pub metadata: CameraMetadata,
+}
+
+/// The MDPM messages of a stream of NAL units, e.g. a `NALUnitIterator`
+pub struct CameraMetadataIterator<I: Iterator<Item = NALUnit>> {
+    nal_units: I,
+    gops: usize,
+    pictures: u64,
+}
+
+impl<I: Iterator<Item = NALUnit>> CameraMetadataIterator<I> {
+    pub fn new(nal_units: I) -> Self {
+        Self {
+            nal_units,
+            gops: 0,
+            pictures: 0,
+        }
+    }
+}
+
+impl<I: Iterator<Item = NALUnit>> Iterator for CameraMetadataIterator<I> {
+    type Item = GOPMetadata;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let header = match self.nal_units.next()? {
+                NALUnit::SEI(nu) => {
+                    let Some(mdpm) = nu.messages.iter().find_map(MDPM::from_sei) else {
+                        continue;
+                    };
+                    let gop = GOPMetadata {
+                        gop: self.gops,
+                        picture: self.pictures,
+                        metadata: mdpm.metadata(),
+                    };
+                    self.gops += 1;
+                    return Some(gop);
+                }
+                NALUnit::IDRPicture(nu) => nu.header,
+                NALUnit::NonIDRPicture(nu) => nu.header,
+                _ => continue,
+            };
+            if header.is_some_and(|h| h.first_mb_in_slice == 0) {
+                self.pictures += 1;
+            }
+        }
+    }
+}
+
+/// Summary of the metadata of a clip
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClipMetadata {
+    pub gops: usize,
+    pub start: Option<RecordingTime>,
+    pub end: Option<RecordingTime>,
+    pub make: Option<&'static str>,
+    pub model: Option<String>,
+    /// The first GPS fix, and the number of GOPs with one
+    pub position: Option<GPSPosition>,
+    pub positions: usize,
+}
+
+impl ClipMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, gop: &GOPMetadata) {
+        let metadata = &gop.metadata;
+        self.gops += 1;
+        if metadata.recorded.is_some() {
+            self.start = self.start.or(metadata.recorded);
+            self.end = metadata.recorded;
+        }
+        self.make = self.make.or(metadata.make_name());
+        if self.model.is_none() {
+            self.model = metadata.model.clone();
+        }
+        if metadata.position.is_some() {
+            self.position = self.position.or(metadata.position);
+            self.positions += 1;
+        }
+    }
+}
+
+impl fmt::Display for ClipMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} GOPs", self.gops)?;
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            write!(f, ", recorded {} to {}", start, end)?;
+        }
+        if self.make.is_some() || self.model.is_some() {
+            write!(
+                f,
+                ", camera {} {}",
+                self.make.unwrap_or("-"),
+                self.model.as_deref().unwrap_or("-")
+            )?;
+        }
+        if let Some(position) = self.position {
+            write!(
+                f,
+                ", {} GPS fixes from {:.6},{:.6}",
+                self.positions, position.latitude, position.longitude
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes one line per GOP, after a header line
+pub struct CSVWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CSVWriter<W> {
+    const HEADER: &str = "gop,picture,recorded,exposure_time,f_number,exposure_compensation,\
+        gain_db,exposure_program,white_balance,focus,focus_distance,latitude,longitude,\
+        altitude,make,model";
+
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", Self::HEADER)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write_gop(&mut self, gop: &GOPMetadata) -> io::Result<()> {
+        let metadata = &gop.metadata;
+        let field = |value: Option<String>| value.unwrap_or_default();
+        let position = metadata.position;
+        let fields = [
+            gop.gop.to_string(),
+            gop.picture.to_string(),
+            field(metadata.recorded.map(|r| r.to_string())),
+            field(metadata.exposure_time.map(|(n, d)| format!("{}/{}", n, d))),
+            field(metadata.f_number.map(|n| format!("{:.1}", n))),
+            field(metadata.exposure_compensation.map(|e| format!("{:.2}", e))),
+            field(metadata.gain.map(|gain| match gain {
+                Gain::Auto => "auto".to_string(),
+                Gain::Decibels(db) => db.to_string(),
+            })),
+            field(metadata.exposure_program_name().map(str::to_string)),
+            field(metadata.white_balance_name().map(str::to_string)),
+            field(
+                metadata
+                    .focus
+                    .map(|f| if f.manual { "manual" } else { "auto" }.to_string()),
+            ),
+            field(metadata.focus.map(|f| format!("{:.2}", f.distance))),
+            field(position.map(|p| format!("{:.6}", p.latitude))),
+            field(position.map(|p| format!("{:.6}", p.longitude))),
+            field(
+                position
+                    .and_then(|p| p.altitude)
+                    .map(|a| format!("{:.1}", a)),
+            ),
+            field(metadata.make_name().map(str::to_string)),
+            field(metadata.model.as_ref().map(|m| csv_quote(m))),
+        ];
+        writeln!(self.writer, "{}", fields.join(","))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn csv_quote(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes the GPS fixes as one GPX 1.1 track; `finish` closes it
+pub struct GPXWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> GPXWriter<W> {
+    pub fn new(mut writer: W, name: &str) -> io::Result<Self> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<gpx version="1.1" creator="mts-parser" xmlns="http://www.topografix.com/GPX/1/1">"#
+        )?;
+        writeln!(writer, "<trk><name>{}</name><trkseg>", xml_escape(name))?;
+        Ok(Self { writer })
+    }
+
+    /// GOPs without a GPS fix are skipped
+    pub fn write_gop(&mut self, gop: &GOPMetadata) -> io::Result<()> {
+        let Some(position) = gop.metadata.position else {
+            return Ok(());
+        };
+        write!(
+            self.writer,
+            r#"<trkpt lat="{:.6}" lon="{:.6}">"#,
+            position.latitude, position.longitude
+        )?;
+        if let Some(altitude) = position.altitude {
+            write!(self.writer, "<ele>{:.1}</ele>", altitude)?;
+        }
+        if let Some(recorded) = gop.metadata.recorded {
+            write!(self.writer, "<time>{}</time>", recorded)?;
+        }
+        writeln!(self.writer, "</trkpt>")
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        writeln!(self.writer, "</trkseg></trk>")?;
+        writeln!(self.writer, "</gpx>")?;
+        Ok(self.writer)
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
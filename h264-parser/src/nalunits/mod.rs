@@ -1,11 +1,22 @@
+pub mod mvc;
+pub mod pps;
+pub mod sei;
+pub mod slice;
+pub mod sps;
+
+use super::startcode::{parse_start_code_unit, START_CODE_PREFIX};
 use super::stream::{stream, Stream, PartialStream};
+use mvc::{NALUnitHeaderExtension, SubsetSPS};
+use pps::{ParameterSets, PPS};
+use sei::{parse_sei_rbsp, SEIMessage};
+use slice::SliceHeader;
+use sps::SPS;
 use std::fmt;
 
-const SHORT_NAL_BOUNDARY: &[u8] = b"\x00\x00\x01";
-const LONG_NAL_BOUNDARY: &[u8] = b"\x00\x00\x00\x01";
 const EMULATION_PREVENTION_BYTES: &[u8] = b"\x00\x00\x03"; 
+const LONG_START_CODE: &[u8] = b"\x00\x00\x00\x01";
 
-use winnow::{binary::{bits,self}, combinator, error, token, IResult, Parser };
+use winnow::{binary::bits, combinator, error, token, IResult, Parser };
 
 //
 // enum NALUnitType {
@@ -22,19 +33,14 @@ use winnow::{binary::{bits,self}, combinator, error, token, IResult, Parser };
 //     EndOfStream = 11,
 //     // FilterData = 12,
 //     // SPSExt = 13,
-//     // PrefixNALUnit = 14,
-//     // SubsetSPS = 15,
+//     PrefixNALUnit = 14,
+//     SubsetSPS = 15,
 //     // SliceLayerWithoutPartitioning = 19,
-//     // CodedSliceExtension = 20,
+//     CodedSliceExtension = 20,
 // }
 
-fn parse_till_nal_unit_end(input: PartialStream) -> IResult<PartialStream, Vec<u8>> {
-    let (input, data) = combinator::alt((
-        Parser::complete_err(token::take_until0(LONG_NAL_BOUNDARY)),
-        token::take_until0(SHORT_NAL_BOUNDARY),
-        combinator::rest,
-    ))
-    .parse_next(input)?;
+/// Turns the payload of a NAL unit into its RBSP (removes the 0x03 of every 0x000003)
+pub fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
     let datastream = stream(data);
     let (suffix, mut parts): (Stream, Vec<&[u8]>) = combinator::repeat(
         0.., (
@@ -42,7 +48,26 @@ fn parse_till_nal_unit_end(input: PartialStream) -> IResult<PartialStream, Vec<u
             EMULATION_PREVENTION_BYTES,
             ).map(|x| x.0)).parse_next(datastream).unwrap();
     parts.push(suffix);
-    Ok((input, parts.join(&[0_u8; 2][..])))
+    parts.join(&[0_u8; 2][..])
+}
+
+/// Turns an RBSP into the payload of a NAL unit: inserts 0x03 after every two zero
+/// bytes followed by a byte up to 0x03, and after a zero byte at the end (7.4.1)
+pub fn add_emulation_prevention(rbsp: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(rbsp.len() + rbsp.len() / 256 + 1);
+    let mut zeros = 0;
+    for &byte in rbsp {
+        if zeros >= 2 && byte <= 3 {
+            data.push(3);
+            zeros = 0;
+        }
+        data.push(byte);
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+    }
+    if data.last() == Some(&0) {
+        data.push(3);
+    }
+    data
 }
 
 pub trait NALUnitBase {
@@ -62,23 +87,39 @@ pub trait KnownNALUnit {
         .parse_next(input)?;
         Ok((input, ref_idc))
     }
-    fn parse(input: Stream) -> IResult<Stream, NALUnit>;
+    /// Parameter sets and slice headers refer to parameter sets seen before
+    fn parse<'i>(
+        input: Stream<'i>,
+        parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit>;
 }
 
 pub struct NonIDRPictureNU {
     pub ref_idc: u8,
+    /// None if the header is broken or refers to an unknown PPS
+    pub header: Option<SliceHeader>,
     pub rest: Vec<u8>,
 }
 
 impl KnownNALUnit for NonIDRPictureNU {
     const NU_TYPE: u8 = 1;
-    fn parse(input: Stream) -> IResult<Stream, NALUnit> {
+    fn parse<'i>(
+        input: Stream<'i>,
+        parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
         let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let (input, header) = combinator::opt(combinator::peek(
+            bits::bits::<_, _, error::Error<(_, usize)>, _, _>(|input| {
+                SliceHeader::parse_bits(input, Self::NU_TYPE, ref_idc, parameter_sets)
+            }),
+        ))
+        .parse_next(input)?;
         let (input, rest) = combinator::rest.parse_next(input)?;
         Ok((
             input,
             NALUnit::NonIDRPicture(Self {
                 ref_idc,
+                header,
                 rest: rest.to_vec(),
             }),
         ))
@@ -89,8 +130,9 @@ impl fmt::Debug for NonIDRPictureNU {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "NonIDRPicture: {} {:x?}",
+            "NonIDRPicture: {} {:?} {:x?}",
             self.ref_idc,
+            self.header,
             &self.rest[..(16.min(self.rest.len()))]
         )
     }
@@ -98,19 +140,31 @@ impl fmt::Debug for NonIDRPictureNU {
 
 pub struct IDRPictureNU {
     pub ref_idc: u8,
+    /// None if the header is broken or refers to an unknown PPS
+    pub header: Option<SliceHeader>,
     pub rest: Vec<u8>,
 }
 
 impl KnownNALUnit for IDRPictureNU {
     const NU_TYPE: u8 = 5;
 
-    fn parse(input: Stream) -> IResult<Stream, NALUnit> {
+    fn parse<'i>(
+        input: Stream<'i>,
+        parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
         let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let (input, header) = combinator::opt(combinator::peek(
+            bits::bits::<_, _, error::Error<(_, usize)>, _, _>(|input| {
+                SliceHeader::parse_bits(input, Self::NU_TYPE, ref_idc, parameter_sets)
+            }),
+        ))
+        .parse_next(input)?;
         let (input, rest) = combinator::rest.parse_next(input)?;
         Ok((
             input,
             NALUnit::IDRPicture(Self {
                 ref_idc,
+                header,
                 rest: rest.to_vec(),
             }),
         ))
@@ -121,8 +175,307 @@ impl fmt::Debug for IDRPictureNU {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "IDRPicture: {} {:x?}",
+            "IDRPicture: {} {:?} {:x?}",
             self.ref_idc,
+            self.header,
+            &self.rest[..(16.min(self.rest.len()))]
+        )
+    }
+}
+
+pub struct SEINU {
+    pub ref_idc: u8,
+    pub messages: Vec<SEIMessage>,
+    /// The RBSP the messages were read from
+    pub rest: Vec<u8>,
+}
+
+impl KnownNALUnit for SEINU {
+    const NU_TYPE: u8 = 6;
+
+    fn parse<'i>(
+        input: Stream<'i>,
+        parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
+        let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let (input, rest) = combinator::rest.parse_next(input)?;
+        Ok((
+            input,
+            NALUnit::SEI(Self {
+                ref_idc,
+                messages: parse_sei_rbsp(rest, parameter_sets),
+                rest: rest.to_vec(),
+            }),
+        ))
+    }
+}
+
+impl fmt::Debug for SEINU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SEI: {} {:?}", self.ref_idc, self.messages)
+    }
+}
+
+pub struct SPSNU {
+    pub ref_idc: u8,
+    pub sps: Box<SPS>,
+    /// The RBSP the SPS was read from
+    pub rest: Vec<u8>,
+}
+
+impl SPSNU {
+    /// A NAL unit with the serialised `sps`, e.g. after changing some of its fields
+    pub fn new(ref_idc: u8, sps: SPS) -> Self {
+        Self {
+            ref_idc,
+            rest: sps.to_rbsp(),
+            sps: Box::new(sps),
+        }
+    }
+}
+
+impl KnownNALUnit for SPSNU {
+    const NU_TYPE: u8 = 7;
+
+    fn parse<'i>(
+        input: Stream<'i>,
+        _parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
+        let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let rest = input.to_vec();
+        let (input, sps) =
+            bits::bits::<_, _, error::Error<(_, usize)>, _, _>(SPS::parse_bits).parse_next(input)?;
+        let (input, _) = combinator::rest.parse_next(input)?;
+        Ok((
+            input,
+            NALUnit::SPS(Self {
+                ref_idc,
+                sps: Box::new(sps),
+                rest,
+            }),
+        ))
+    }
+}
+
+impl fmt::Debug for SPSNU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SPS: {} {:?}", self.ref_idc, self.sps)
+    }
+}
+
+pub struct PPSNU {
+    pub ref_idc: u8,
+    pub pps: Box<PPS>,
+    /// The RBSP the PPS was read from
+    pub rest: Vec<u8>,
+}
+
+impl PPSNU {
+    /// A NAL unit with the serialised `pps`; None if it can't be serialised
+    pub fn new(ref_idc: u8, pps: PPS) -> Option<Self> {
+        Some(Self {
+            ref_idc,
+            rest: pps.to_rbsp()?,
+            pps: Box::new(pps),
+        })
+    }
+}
+
+impl KnownNALUnit for PPSNU {
+    const NU_TYPE: u8 = 8;
+
+    fn parse<'i>(
+        input: Stream<'i>,
+        parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
+        let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let rest = input.to_vec();
+        let (input, pps) = bits::bits::<_, _, error::Error<(_, usize)>, _, _>(|input| {
+            PPS::parse_bits(input, parameter_sets)
+        })
+        .parse_next(input)?;
+        let (input, _) = combinator::rest.parse_next(input)?;
+        Ok((
+            input,
+            NALUnit::PPS(Self {
+                ref_idc,
+                pps: Box::new(pps),
+                rest,
+            }),
+        ))
+    }
+}
+
+impl fmt::Debug for PPSNU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PPS: {} {:?}", self.ref_idc, self.pps)
+    }
+}
+
+pub struct AUDNU {
+    pub ref_idc: u8,
+    /// Which slice types the primary coded picture may contain (Table 7-5)
+    pub primary_pic_type: u8,
+}
+
+impl KnownNALUnit for AUDNU {
+    const NU_TYPE: u8 = 9;
+
+    fn parse<'i>(
+        input: Stream<'i>,
+        _parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
+        let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let (input, primary_pic_type) =
+            bits::bits::<_, _, error::Error<(_, usize)>, _, _>(bits::take(3_usize))
+                .parse_next(input)?;
+        let (input, _) = combinator::rest.parse_next(input)?;
+        Ok((
+            input,
+            NALUnit::AUD(Self {
+                ref_idc,
+                primary_pic_type,
+            }),
+        ))
+    }
+}
+
+impl fmt::Debug for AUDNU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AUD: {} {}", self.ref_idc, self.primary_pic_type)
+    }
+}
+
+pub struct PrefixNU {
+    pub ref_idc: u8,
+    /// Of the base view or base layer slice that follows
+    pub extension: NALUnitHeaderExtension,
+    /// The RBSP after the header byte, with the header extension
+    pub rest: Vec<u8>,
+}
+
+impl KnownNALUnit for PrefixNU {
+    const NU_TYPE: u8 = 14;
+
+    fn parse<'i>(
+        input: Stream<'i>,
+        _parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
+        let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let rest = input.to_vec();
+        let (input, extension) = bits::bits::<_, _, error::Error<(_, usize)>, _, _>(
+            NALUnitHeaderExtension::parse_bits,
+        )
+        .parse_next(input)?;
+        let (input, _) = combinator::rest.parse_next(input)?;
+        Ok((
+            input,
+            NALUnit::Prefix(Self {
+                ref_idc,
+                extension,
+                rest,
+            }),
+        ))
+    }
+}
+
+impl fmt::Debug for PrefixNU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Prefix: {} {:?}", self.ref_idc, self.extension)
+    }
+}
+
+pub struct SubsetSPSNU {
+    pub ref_idc: u8,
+    pub subset_sps: Box<SubsetSPS>,
+    /// The RBSP the subset SPS was read from
+    pub rest: Vec<u8>,
+}
+
+impl KnownNALUnit for SubsetSPSNU {
+    const NU_TYPE: u8 = 15;
+
+    fn parse<'i>(
+        input: Stream<'i>,
+        _parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
+        let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let rest = input.to_vec();
+        let (input, subset_sps) =
+            bits::bits::<_, _, error::Error<(_, usize)>, _, _>(SubsetSPS::parse_bits)
+                .parse_next(input)?;
+        let (input, _) = combinator::rest.parse_next(input)?;
+        Ok((
+            input,
+            NALUnit::SubsetSPS(Self {
+                ref_idc,
+                subset_sps: Box::new(subset_sps),
+                rest,
+            }),
+        ))
+    }
+}
+
+impl fmt::Debug for SubsetSPSNU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SubsetSPS: {} {:?}", self.ref_idc, self.subset_sps)
+    }
+}
+
+/// A coded slice of a non-base view (MVC) or of an enhancement layer (SVC)
+pub struct SliceExtensionNU {
+    pub ref_idc: u8,
+    pub extension: NALUnitHeaderExtension,
+    /// Only read for MVC; None if it is broken or refers to an unknown PPS or subset SPS
+    pub header: Option<SliceHeader>,
+    /// The RBSP after the header byte, with the header extension
+    pub rest: Vec<u8>,
+}
+
+impl KnownNALUnit for SliceExtensionNU {
+    const NU_TYPE: u8 = 20;
+
+    fn parse<'i>(
+        input: Stream<'i>,
+        parameter_sets: &ParameterSets,
+    ) -> IResult<Stream<'i>, NALUnit> {
+        let (input, ref_idc) = Self::parse_idc_ref_and_check_nutype(input)?;
+        let rest = input.to_vec();
+        let (input, extension) = bits::bits::<_, _, error::Error<(_, usize)>, _, _>(
+            NALUnitHeaderExtension::parse_bits,
+        )
+        .parse_next(input)?;
+        let header = match extension {
+            NALUnitHeaderExtension::MVC(ref mvc) => combinator::opt(
+                bits::bits::<_, _, error::Error<(_, usize)>, _, _>(|input| {
+                    SliceHeader::parse_mvc_bits(input, mvc, ref_idc, parameter_sets)
+                }),
+            )
+            .parse_next(input)?
+            .1,
+            NALUnitHeaderExtension::SVC(_) => None,
+        };
+        let (input, _) = combinator::rest.parse_next(input)?;
+        Ok((
+            input,
+            NALUnit::SliceExtension(Self {
+                ref_idc,
+                extension,
+                header,
+                rest,
+            }),
+        ))
+    }
+}
+
+impl fmt::Debug for SliceExtensionNU {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SliceExtension: {} {:?} {:?} {:x?}",
+            self.ref_idc,
+            self.extension,
+            self.header,
             &self.rest[..(16.min(self.rest.len()))]
         )
     }
@@ -200,17 +553,112 @@ impl fmt::Debug for UnknownNU {
 pub enum NALUnit {
     NonIDRPicture(NonIDRPictureNU),
     IDRPicture(IDRPictureNU),
+    SEI(SEINU),
+    SPS(SPSNU),
+    PPS(PPSNU),
+    AUD(AUDNU),
+    Prefix(PrefixNU),
+    SubsetSPS(SubsetSPSNU),
+    SliceExtension(SliceExtensionNU),
     Unknown(UnknownNU),
     // IDR(IDRNALUnit),
-    // SEI(SEINALUnit),
-    // SPS(SPSNALUnit),
-    // PPS(PPSNALUnit),
     // AUD(AUDNALUnit),
     // EndOfSequence(),
     // EndOfStream(),
     // Unknown(u8, Vec<u8>),
 }
 
+impl NALUnit {
+    pub fn nal_unit_type(&self) -> u8 {
+        match self {
+            Self::NonIDRPicture(_) => NonIDRPictureNU::NU_TYPE,
+            Self::IDRPicture(_) => IDRPictureNU::NU_TYPE,
+            Self::SEI(_) => SEINU::NU_TYPE,
+            Self::SPS(_) => SPSNU::NU_TYPE,
+            Self::PPS(_) => PPSNU::NU_TYPE,
+            Self::AUD(_) => AUDNU::NU_TYPE,
+            Self::Prefix(_) => PrefixNU::NU_TYPE,
+            Self::SubsetSPS(_) => SubsetSPSNU::NU_TYPE,
+            Self::SliceExtension(_) => SliceExtensionNU::NU_TYPE,
+            Self::Unknown(nu) => nu.nal_unit_type,
+        }
+    }
+
+    pub fn ref_idc(&self) -> u8 {
+        match self {
+            Self::NonIDRPicture(nu) => nu.ref_idc,
+            Self::IDRPicture(nu) => nu.ref_idc,
+            Self::SEI(nu) => nu.ref_idc,
+            Self::SPS(nu) => nu.ref_idc,
+            Self::PPS(nu) => nu.ref_idc,
+            Self::AUD(nu) => nu.ref_idc,
+            Self::Prefix(nu) => nu.ref_idc,
+            Self::SubsetSPS(nu) => nu.ref_idc,
+            Self::SliceExtension(nu) => nu.ref_idc,
+            Self::Unknown(nu) => nu.ref_idc,
+        }
+    }
+
+    /// The RBSP after the NAL unit header
+    pub fn rbsp(&self) -> Vec<u8> {
+        match self {
+            Self::NonIDRPicture(nu) => nu.rest.clone(),
+            Self::IDRPicture(nu) => nu.rest.clone(),
+            Self::SEI(nu) => nu.rest.clone(),
+            Self::SPS(nu) => nu.rest.clone(),
+            Self::PPS(nu) => nu.rest.clone(),
+            // primary_pic_type and rbsp_trailing_bits
+            Self::AUD(nu) => vec![nu.primary_pic_type << 5 | 0x10],
+            Self::Prefix(nu) => nu.rest.clone(),
+            Self::SubsetSPS(nu) => nu.rest.clone(),
+            Self::SliceExtension(nu) => nu.rest.clone(),
+            Self::Unknown(nu) => nu.rest.clone(),
+        }
+    }
+
+    /// The NAL unit as it is between start codes: the header byte and the RBSP with
+    /// emulation prevention bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![self.ref_idc() << 5 | self.nal_unit_type()];
+        data.extend(add_emulation_prevention(&self.rbsp()));
+        data
+    }
+
+    /// The slice header of a coded slice, if it could be read
+    pub fn slice_header(&self) -> Option<&SliceHeader> {
+        match self {
+            Self::NonIDRPicture(nu) => nu.header.as_ref(),
+            Self::IDRPicture(nu) => nu.header.as_ref(),
+            Self::SliceExtension(nu) => nu.header.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Whether it is a slice without a readable header, or a slice, SEI, parameter set, AUD,
+    /// prefix NAL unit or slice extension that could not be parsed
+    pub fn is_unreadable(&self) -> bool {
+        match self {
+            Self::NonIDRPicture(nu) => nu.header.is_none(),
+            Self::IDRPicture(nu) => nu.header.is_none(),
+            // only MVC slice extensions have their header read
+            Self::SliceExtension(nu) => {
+                nu.header.is_none() && matches!(nu.extension, NALUnitHeaderExtension::MVC(_))
+            }
+            Self::Unknown(nu) => matches!(nu.nal_unit_type, 1 | 5 | 6 | 7 | 8 | 9 | 14 | 15 | 20),
+            _ => false,
+        }
+    }
+
+    /// The SVC or MVC header extension of prefix NAL units and coded slice extensions
+    pub fn header_extension(&self) -> Option<&NALUnitHeaderExtension> {
+        match self {
+            Self::Prefix(nu) => Some(&nu.extension),
+            Self::SliceExtension(nu) => Some(&nu.extension),
+            _ => None,
+        }
+    }
+}
+
 // fn parse_nal_unit_non_idr(input: &[u8]) -> IResult<&[u8], NALUnit> {
 //     let (input, nal_ref_idc) = take_nal_type(NALUnitType::SliceOfNonIDRPicture as u8)(input)?;
 //     Ok((&[], NALUnit::NonIDR(NonIDRNALUnit {ref_idc: nal_ref_idc, rest: input[..cmp::min(input.len(), 20)].to_vec() } )))
@@ -259,15 +707,75 @@ pub enum NALUnit {
 // }
 //
 
-pub fn parse_nal_unit(input: PartialStream) -> IResult<PartialStream, NALUnit> {
-    let (input, _) = combinator::alt((LONG_NAL_BOUNDARY, SHORT_NAL_BOUNDARY)).parse_next(input)?;
-    let (input, nudata) = parse_till_nal_unit_end(input)?;
-    let nudata = stream(&nudata[..]);
-    let (nudata, firstbyte) = combinator::peek(binary::u8::<_, ()>).parse_next(nudata).unwrap();
+/// Parses the NAL unit after the start code at the start of `input`; anything else there
+/// is an error rather than skipped
+pub fn parse_nal_unit<'i>(
+    input: PartialStream<'i>,
+    parameter_sets: &mut ParameterSets,
+) -> IResult<PartialStream<'i>, NALUnit> {
+    let (input, _) =
+        combinator::peek(combinator::alt((LONG_START_CODE, START_CODE_PREFIX))).parse_next(input)?;
+    let (input, data) = parse_start_code_unit(input)?;
+    Ok((input, parse_nal_unit_data(data, parameter_sets)))
+}
+
+/// Parses the bytes between two start codes (still with emulation prevention bytes);
+/// the SPS and PPS are added to `parameter_sets`
+pub fn parse_nal_unit_data(data: &[u8], parameter_sets: &mut ParameterSets) -> NALUnit {
+    // a NAL unit never ends in a zero byte; these are trailing_zero_8bits
+    let data = &data[..crate::annexb::nal_unit_end(data)];
+    let Some(&firstbyte) = data.first() else {
+        // nothing but zero bytes after a start code
+        return NALUnit::Unknown(UnknownNU {
+            nal_unit_type: 0,
+            ref_idc: 0,
+            rest: Vec::new(),
+        });
+    };
+    let rbsp = remove_emulation_prevention(data);
+    let nudata = stream(&rbsp[..]);
+    let ps = &*parameter_sets;
     let nal_unit = match firstbyte & 0b0001_1111_u8 {
-        IDRPictureNU::NU_TYPE => IDRPictureNU::parse.parse(nudata),
-        NonIDRPictureNU::NU_TYPE => NonIDRPictureNU::parse.parse(nudata),
+        IDRPictureNU::NU_TYPE => (|i| IDRPictureNU::parse(i, ps)).parse(nudata),
+        NonIDRPictureNU::NU_TYPE => (|i| NonIDRPictureNU::parse(i, ps)).parse(nudata),
+        SEINU::NU_TYPE => (|i| SEINU::parse(i, ps)).parse(nudata),
+        AUDNU::NU_TYPE => (|i| AUDNU::parse(i, ps)).parse(nudata),
+        SPSNU::NU_TYPE => (|i| SPSNU::parse(i, ps)).parse(nudata),
+        PPSNU::NU_TYPE => (|i| PPSNU::parse(i, ps)).parse(nudata),
+        PrefixNU::NU_TYPE => (|i| PrefixNU::parse(i, ps)).parse(nudata),
+        SubsetSPSNU::NU_TYPE => (|i| SubsetSPSNU::parse(i, ps)).parse(nudata),
+        SliceExtensionNU::NU_TYPE => (|i| SliceExtensionNU::parse(i, ps)).parse(nudata),
         _ => UnknownNU::parse.parse(nudata),
-    }.unwrap();
-    Ok((input, nal_unit))
+    };
+    // what can't be parsed, like a broken parameter set, a header extension that is too
+    // short or a forbidden_zero_bit that is set, is kept as an unknown NAL unit
+    let nal_unit = nal_unit.unwrap_or_else(|_| {
+        NALUnit::Unknown(UnknownNU {
+            nal_unit_type: firstbyte & 0b0001_1111,
+            ref_idc: firstbyte >> 5,
+            rest: rbsp[1..].to_vec(),
+        })
+    });
+    match nal_unit {
+        NALUnit::SPS(ref nu) => {
+            parameter_sets.sps.insert(nu.sps.seq_parameter_set_id, (*nu.sps).clone());
+            parameter_sets.active_sps_id = Some(nu.sps.seq_parameter_set_id);
+        }
+        NALUnit::PPS(ref nu) => {
+            parameter_sets.pps.insert(nu.pps.pic_parameter_set_id, (*nu.pps).clone());
+        }
+        NALUnit::SubsetSPS(ref nu) => {
+            let id = nu.subset_sps.sps.seq_parameter_set_id;
+            parameter_sets.subset_sps.insert(id, (*nu.subset_sps).clone());
+        }
+        NALUnit::IDRPicture(IDRPictureNU { header: Some(ref header), .. })
+        | NALUnit::NonIDRPicture(NonIDRPictureNU { header: Some(ref header), .. }) => {
+            parameter_sets.active_sps_id = parameter_sets
+                .pps
+                .get(&header.pic_parameter_set_id)
+                .map(|pps| pps.seq_parameter_set_id);
+        }
+        _ => (),
+    }
+    nal_unit
 }
@@ -0,0 +1,344 @@
+// The NAL unit header extensions of SVC (ITU-T H.264 Annex G) and MVC (Annex H), and
+// the subset SPS with the MVC extension that multiview (stereo 3D) streams have
+use super::sps::{HRDParameters, TimingInfo, SPS};
+use crate::bitstream::{flag, u, ue, verify_error, BitInput, BitResult};
+use winnow::{combinator, Parser};
+
+/// profile_idc values whose subset SPS has seq_parameter_set_mvc_extension() as is
+const MVC_PROFILES: [u8; 2] = [118, 128];
+
+/// nal_unit_header_svc_extension() (G.7.3.1.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SVCHeaderExtension {
+    pub idr: bool,
+    pub priority_id: u8,
+    pub no_inter_layer_pred: bool,
+    pub dependency_id: u8,
+    pub quality_id: u8,
+    pub temporal_id: u8,
+    pub use_ref_base_pic: bool,
+    pub discardable: bool,
+    pub output: bool,
+}
+
+/// nal_unit_header_mvc_extension() (H.7.3.1.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MVCHeaderExtension {
+    pub non_idr: bool,
+    pub priority_id: u8,
+    pub view_id: u16,
+    pub temporal_id: u8,
+    /// The view component only refers to other views of the same access unit
+    pub anchor_pic: bool,
+    /// Other views of the access unit refer to this view component
+    pub inter_view: bool,
+}
+
+/// The three bytes after the header byte of prefix NAL units and coded slice extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NALUnitHeaderExtension {
+    SVC(SVCHeaderExtension),
+    MVC(MVCHeaderExtension),
+}
+
+impl NALUnitHeaderExtension {
+    pub fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, svc_extension) = flag(input)?;
+        if svc_extension {
+            let (input, (idr, priority_id, no_inter_layer_pred, dependency_id, quality_id)) =
+                (flag, u(6), flag, u(3), u(4)).parse_next(input)?;
+            let (input, (temporal_id, use_ref_base_pic, discardable, output, _reserved)) =
+                (u(3), flag, flag, flag, u(2)).parse_next(input)?;
+            return Ok((
+                input,
+                Self::SVC(SVCHeaderExtension {
+                    idr,
+                    priority_id: priority_id as u8,
+                    no_inter_layer_pred,
+                    dependency_id: dependency_id as u8,
+                    quality_id: quality_id as u8,
+                    temporal_id: temporal_id as u8,
+                    use_ref_base_pic,
+                    discardable,
+                    output,
+                }),
+            ));
+        }
+        let (input, (non_idr, priority_id, view_id, temporal_id)) =
+            (flag, u(6), u(10), u(3)).parse_next(input)?;
+        let (input, (anchor_pic, inter_view, _reserved)) = (flag, flag, flag).parse_next(input)?;
+        Ok((
+            input,
+            Self::MVC(MVCHeaderExtension {
+                non_idr,
+                priority_id: priority_id as u8,
+                view_id: view_id as u16,
+                temporal_id: temporal_id as u8,
+                anchor_pic,
+                inter_view,
+            }),
+        ))
+    }
+
+    /// The view_id of an MVC extension
+    pub fn view_id(&self) -> Option<u16> {
+        match self {
+            Self::MVC(extension) => Some(extension.view_id),
+            Self::SVC(_) => None,
+        }
+    }
+
+    /// IdrPicFlag
+    pub fn idr(&self) -> bool {
+        match self {
+            Self::MVC(extension) => !extension.non_idr,
+            Self::SVC(extension) => extension.idr,
+        }
+    }
+}
+
+/// An operation point of a level in the MVC extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MVCOperationPoint {
+    pub temporal_id: u8,
+    pub target_view_ids: Vec<u32>,
+    /// The views needed to decode the target views
+    pub num_views: u32,
+}
+
+/// seq_parameter_set_mvc_extension() (H.7.3.2.1.4)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MVCExtension {
+    /// view_id of every view, in view order; the first is the base view
+    pub view_ids: Vec<u32>,
+    /// The inter-view references (view_ids) in list 0 and 1 of anchor and of non-anchor
+    /// view components of every view but the base view
+    pub anchor_refs: Vec<[Vec<u32>; 2]>,
+    pub non_anchor_refs: Vec<[Vec<u32>; 2]>,
+    /// level_idc and its operation points
+    pub levels: Vec<(u8, Vec<MVCOperationPoint>)>,
+}
+
+fn parse_view_ids(input: BitInput) -> BitResult<Vec<u32>> {
+    let (mut input, count) = ue(input)?;
+    if count > 15 {
+        return verify_error(input);
+    }
+    let mut view_ids = Vec::new();
+    for _ in 0..count {
+        let view_id;
+        (input, view_id) = ue(input)?;
+        view_ids.push(view_id);
+    }
+    Ok((input, view_ids))
+}
+
+/// The references of list 0 and 1 of every view but the base view
+fn parse_view_refs(mut input: BitInput, views: usize) -> BitResult<Vec<[Vec<u32>; 2]>> {
+    let mut refs = Vec::new();
+    for _ in 1..views {
+        let (l0, l1);
+        (input, (l0, l1)) = (parse_view_ids, parse_view_ids).parse_next(input)?;
+        refs.push([l0, l1]);
+    }
+    Ok((input, refs))
+}
+
+impl MVCExtension {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (mut input, num_views_minus1) = ue(input)?;
+        if num_views_minus1 > 1023 {
+            return verify_error(input);
+        }
+        let mut view_ids = Vec::new();
+        for _ in 0..=num_views_minus1 {
+            let view_id;
+            (input, view_id) = ue(input)?;
+            view_ids.push(view_id);
+        }
+        let views = view_ids.len();
+        let (input, anchor_refs) = parse_view_refs(input, views)?;
+        let (input, non_anchor_refs) = parse_view_refs(input, views)?;
+        let (mut input, num_level_values_signalled_minus1) = ue(input)?;
+        if num_level_values_signalled_minus1 > 63 {
+            return verify_error(input);
+        }
+        let mut levels = Vec::new();
+        for _ in 0..=num_level_values_signalled_minus1 {
+            let (level_idc, num_applicable_ops_minus1);
+            (input, (level_idc, num_applicable_ops_minus1)) = (u(8), ue).parse_next(input)?;
+            if num_applicable_ops_minus1 > 1023 {
+                return verify_error(input);
+            }
+            let mut operation_points = Vec::new();
+            for _ in 0..=num_applicable_ops_minus1 {
+                let (temporal_id, num_target_views_minus1);
+                (input, (temporal_id, num_target_views_minus1)) = (u(3), ue).parse_next(input)?;
+                if num_target_views_minus1 > 1023 {
+                    return verify_error(input);
+                }
+                let mut target_view_ids = Vec::new();
+                for _ in 0..=num_target_views_minus1 {
+                    let view_id;
+                    (input, view_id) = ue(input)?;
+                    target_view_ids.push(view_id);
+                }
+                let num_views_minus1;
+                (input, num_views_minus1) = ue(input)?;
+                operation_points.push(MVCOperationPoint {
+                    temporal_id: temporal_id as u8,
+                    target_view_ids,
+                    num_views: num_views_minus1 + 1,
+                });
+            }
+            levels.push((level_idc as u8, operation_points));
+        }
+        Ok((
+            input,
+            Self {
+                view_ids,
+                anchor_refs,
+                non_anchor_refs,
+                levels,
+            },
+        ))
+    }
+
+    /// The views that `view_id` refers to, in any list of anchor or non-anchor pictures
+    pub fn dependencies(&self, view_id: u32) -> Vec<u32> {
+        let Some(index) = self.view_ids.iter().position(|id| *id == view_id) else {
+            return Vec::new();
+        };
+        let mut dependencies = Vec::new();
+        if index > 0 {
+            let lists = self.anchor_refs[index - 1]
+                .iter()
+                .chain(self.non_anchor_refs[index - 1].iter());
+            for id in lists.flatten() {
+                if !dependencies.contains(id) {
+                    dependencies.push(*id);
+                }
+            }
+        }
+        dependencies
+    }
+}
+
+/// An operation point of mvc_vui_parameters_extension() (H.14.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MVCVUIOperationPoint {
+    pub temporal_id: u8,
+    pub target_output_view_ids: Vec<u32>,
+    pub timing: Option<TimingInfo>,
+    pub nal_hrd_parameters: Option<HRDParameters>,
+    pub vcl_hrd_parameters: Option<HRDParameters>,
+    /// Only present with HRD parameters
+    pub low_delay_hrd: Option<bool>,
+    pub pic_struct_present: bool,
+}
+
+impl MVCVUIOperationPoint {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (temporal_id, num_target_output_views_minus1)) =
+            (u(3), ue).parse_next(input)?;
+        if num_target_output_views_minus1 > 1023 {
+            return verify_error(input);
+        }
+        let mut input = input;
+        let mut target_output_view_ids = Vec::new();
+        for _ in 0..=num_target_output_views_minus1 {
+            let view_id;
+            (input, view_id) = ue(input)?;
+            target_output_view_ids.push(view_id);
+        }
+        let (input, timing_info_present) = flag(input)?;
+        let (input, timing) = combinator::cond(
+            timing_info_present,
+            (u(32), u(32), flag).map(|(num_units_in_tick, time_scale, fixed_frame_rate)| {
+                TimingInfo {
+                    num_units_in_tick,
+                    time_scale,
+                    fixed_frame_rate,
+                }
+            }),
+        )
+        .parse_next(input)?;
+        let (input, nal_hrd_present) = flag(input)?;
+        let (input, nal_hrd_parameters) =
+            combinator::cond(nal_hrd_present, HRDParameters::parse_bits).parse_next(input)?;
+        let (input, vcl_hrd_present) = flag(input)?;
+        let (input, vcl_hrd_parameters) =
+            combinator::cond(vcl_hrd_present, HRDParameters::parse_bits).parse_next(input)?;
+        let (input, low_delay_hrd) =
+            combinator::cond(nal_hrd_present || vcl_hrd_present, flag).parse_next(input)?;
+        let (input, pic_struct_present) = flag(input)?;
+        Ok((
+            input,
+            Self {
+                temporal_id: temporal_id as u8,
+                target_output_view_ids,
+                timing,
+                nal_hrd_parameters,
+                vcl_hrd_parameters,
+                low_delay_hrd,
+                pic_struct_present,
+            },
+        ))
+    }
+}
+
+/// subset_seq_parameter_set_rbsp() (7.3.2.1.3); the extensions are only read for the
+/// Multiview High and Stereo High profiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsetSPS {
+    pub sps: SPS,
+    pub mvc: Option<MVCExtension>,
+    /// The operation points of the MVC VUI extension, if present
+    pub mvc_vui: Option<Vec<MVCVUIOperationPoint>>,
+}
+
+impl SubsetSPS {
+    pub fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, sps) = SPS::parse_bits(input)?;
+        if !MVC_PROFILES.contains(&sps.profile_idc) {
+            return Ok((
+                input,
+                Self {
+                    sps,
+                    mvc: None,
+                    mvc_vui: None,
+                },
+            ));
+        }
+        let (input, (_bit_equal_to_one, mvc)) =
+            (flag, MVCExtension::parse_bits).parse_next(input)?;
+        let (input, mvc_vui_present) = flag(input)?;
+        let (input, mvc_vui) = combinator::cond(mvc_vui_present, |input| {
+            let (mut input, vui_mvc_num_ops_minus1) = ue(input)?;
+            if vui_mvc_num_ops_minus1 > 1023 {
+                return verify_error(input);
+            }
+            let mut operation_points = Vec::new();
+            for _ in 0..=vui_mvc_num_ops_minus1 {
+                let operation_point;
+                (input, operation_point) = MVCVUIOperationPoint::parse_bits(input)?;
+                operation_points.push(operation_point);
+            }
+            Ok((input, operation_points))
+        })
+        .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                sps,
+                mvc: Some(mvc),
+                mvc_vui,
+            },
+        ))
+    }
+
+    /// The number of views; 1 without the MVC extension
+    pub fn views(&self) -> usize {
+        self.mvc.as_ref().map_or(1, |mvc| mvc.view_ids.len())
+    }
+}
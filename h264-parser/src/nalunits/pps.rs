@@ -0,0 +1,358 @@
+// Picture parameter set (ITU-T H.264 7.3.2.2), and the store of parameter sets that
+// slice headers are resolved against
+use super::mvc::SubsetSPS;
+use super::sps::{write_scaling_lists, ScalingList, SPS};
+use crate::bitstream::{
+    flag, more_rbsp_data, se, u, ue, verify_error, BitInput, BitResult, BitWriter,
+};
+use std::collections::HashMap;
+use winnow::{combinator, Parser};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropyCodingMode {
+    CAVLC,
+    CABAC,
+}
+
+/// slice_group_map_type and the fields that come with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SliceGroupMap {
+    /// 0
+    Interleaved { run_length_minus1: Vec<u32> },
+    /// 1
+    Dispersed,
+    /// 2: top_left and bottom_right of every slice group but the last
+    Foreground { rectangles: Vec<(u32, u32)> },
+    /// 3 (box-out), 4 (raster scan) or 5 (wipe)
+    Evolving {
+        map_type: u32,
+        change_direction: bool,
+        change_rate: u32,
+    },
+    /// 6
+    Explicit { slice_group_id: Vec<u32> },
+}
+
+impl SliceGroupMap {
+    pub fn map_type(&self) -> u32 {
+        match self {
+            Self::Interleaved { .. } => 0,
+            Self::Dispersed => 1,
+            Self::Foreground { .. } => 2,
+            Self::Evolving { map_type, .. } => *map_type,
+            Self::Explicit { .. } => 6,
+        }
+    }
+}
+
+/// Flexible macroblock ordering, with more than one slice group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SliceGroups {
+    pub num_slice_groups: u32,
+    pub map: SliceGroupMap,
+}
+
+impl SliceGroups {
+    fn parse_bits(input: BitInput, num_slice_groups: u32) -> BitResult<Self> {
+        let (mut input, map_type) = ue(input)?;
+        let map = match map_type {
+            0 => {
+                let mut run_length_minus1 = Vec::new();
+                for _ in 0..num_slice_groups {
+                    let run_length;
+                    (input, run_length) = ue(input)?;
+                    run_length_minus1.push(run_length);
+                }
+                SliceGroupMap::Interleaved { run_length_minus1 }
+            }
+            1 => SliceGroupMap::Dispersed,
+            2 => {
+                let mut rectangles = Vec::new();
+                for _ in 1..num_slice_groups {
+                    let rectangle;
+                    (input, rectangle) = (ue, ue).parse_next(input)?;
+                    rectangles.push(rectangle);
+                }
+                SliceGroupMap::Foreground { rectangles }
+            }
+            3..=5 => {
+                let (change_direction, change_rate_minus1);
+                (input, (change_direction, change_rate_minus1)) = (flag, ue).parse_next(input)?;
+                SliceGroupMap::Evolving {
+                    map_type,
+                    change_direction,
+                    change_rate: change_rate_minus1 + 1,
+                }
+            }
+            6 => {
+                let pic_size_in_map_units_minus1;
+                (input, pic_size_in_map_units_minus1) = ue(input)?;
+                // Ceil(Log2(num_slice_groups))
+                let bits = u32::BITS - (num_slice_groups - 1).leading_zeros();
+                let mut slice_group_id = Vec::new();
+                for _ in 0..=pic_size_in_map_units_minus1 {
+                    let id;
+                    (input, id) = u(bits as usize)(input)?;
+                    slice_group_id.push(id);
+                }
+                SliceGroupMap::Explicit { slice_group_id }
+            }
+            _ => return verify_error(input),
+        };
+        Ok((
+            input,
+            Self {
+                num_slice_groups,
+                map,
+            },
+        ))
+    }
+
+    /// None if the map doesn't fit num_slice_groups or has nothing to write minus 1 of
+    fn write_bits(&self, writer: &mut BitWriter) -> Option<()> {
+        let num_slice_groups_minus1 = self.num_slice_groups.checked_sub(1)?;
+        writer.ue(self.map.map_type());
+        match &self.map {
+            SliceGroupMap::Interleaved { run_length_minus1 } => {
+                if run_length_minus1.len() != self.num_slice_groups as usize {
+                    return None;
+                }
+                for run_length in run_length_minus1.iter() {
+                    writer.ue(*run_length);
+                }
+            }
+            SliceGroupMap::Dispersed => (),
+            SliceGroupMap::Foreground { rectangles } => {
+                if rectangles.len() != num_slice_groups_minus1 as usize {
+                    return None;
+                }
+                for (top_left, bottom_right) in rectangles.iter() {
+                    writer.ue(*top_left);
+                    writer.ue(*bottom_right);
+                }
+            }
+            SliceGroupMap::Evolving {
+                change_direction,
+                change_rate,
+                ..
+            } => {
+                writer.flag(*change_direction);
+                writer.ue(change_rate.checked_sub(1)?);
+            }
+            SliceGroupMap::Explicit { slice_group_id } => {
+                writer.ue((slice_group_id.len() as u32).checked_sub(1)?);
+                let bits = u32::BITS - num_slice_groups_minus1.leading_zeros();
+                for id in slice_group_id.iter() {
+                    writer.u(bits as usize, *id);
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PPS {
+    pub pic_parameter_set_id: u32,
+    pub seq_parameter_set_id: u32,
+    pub entropy_coding_mode: EntropyCodingMode,
+    pub bottom_field_pic_order_in_frame_present: bool,
+    pub slice_groups: Option<SliceGroups>,
+    pub num_ref_idx_l0_default_active: u32,
+    pub num_ref_idx_l1_default_active: u32,
+    pub weighted_pred: bool,
+    /// 0: default, 1: explicit, 2: implicit weighted bi-prediction
+    pub weighted_bipred_idc: u8,
+    pub pic_init_qp: i32,
+    pub pic_init_qs: i32,
+    pub chroma_qp_index_offset: i32,
+    pub deblocking_filter_control_present: bool,
+    pub constrained_intra_pred: bool,
+    pub redundant_pic_cnt_present: bool,
+    pub transform_8x8_mode: bool,
+    /// pic_scaling_list_present_flag and scaling_list() of every list, if
+    /// pic_scaling_matrix_present_flag is set
+    pub scaling_lists: Option<Vec<Option<ScalingList>>>,
+    /// None if the PPS ends before it; chroma_qp_index_offset applies then
+    pub second_chroma_qp_index_offset: Option<i32>,
+}
+
+impl PPS {
+    /// The number of 8x8 scaling lists depends on the chroma format of the SPS; without
+    /// it 4:2:0 is assumed
+    pub fn parse_bits<'i>(
+        input: BitInput<'i>,
+        parameter_sets: &ParameterSets,
+    ) -> BitResult<'i, Self> {
+        let (input, (pic_parameter_set_id, seq_parameter_set_id)) = (ue, ue).parse_next(input)?;
+        if pic_parameter_set_id > 255 || seq_parameter_set_id > 31 {
+            return verify_error(input);
+        }
+        let (input, (entropy_coding_mode, bottom_field_pic_order_in_frame_present)) =
+            (flag, flag).parse_next(input)?;
+        let (input, num_slice_groups_minus1) = ue(input)?;
+        if num_slice_groups_minus1 > 7 {
+            return verify_error(input);
+        }
+        let (input, slice_groups) = combinator::cond(num_slice_groups_minus1 > 0, |input| {
+            SliceGroups::parse_bits(input, num_slice_groups_minus1 + 1)
+        })
+        .parse_next(input)?;
+        let (input, (num_ref_idx_l0_default_active_minus1, num_ref_idx_l1_default_active_minus1)) =
+            (ue, ue).parse_next(input)?;
+        if num_ref_idx_l0_default_active_minus1 > 31 || num_ref_idx_l1_default_active_minus1 > 31 {
+            return verify_error(input);
+        }
+        let (input, (weighted_pred, weighted_bipred_idc)) = (flag, u(2)).parse_next(input)?;
+        let (input, (pic_init_qp_minus26, pic_init_qs_minus26, chroma_qp_index_offset)) =
+            (se, se, se).parse_next(input)?;
+        let (input, flags) = (flag, flag, flag).parse_next(input)?;
+        let (deblocking_filter_control_present, constrained_intra_pred, redundant_pic_cnt_present) =
+            flags;
+        let mut input = input;
+        let mut transform_8x8_mode = false;
+        let mut scaling_lists = None;
+        let mut second_chroma_qp_index_offset = None;
+        if more_rbsp_data(&input) {
+            let scaling_matrix_present;
+            (input, (transform_8x8_mode, scaling_matrix_present)) =
+                (flag, flag).parse_next(input)?;
+            if scaling_matrix_present {
+                // a PPS of a non-base view may only have a subset SPS
+                let chroma_format_idc = parameter_sets
+                    .sps
+                    .get(&seq_parameter_set_id)
+                    .or_else(|| {
+                        let subset_sps = parameter_sets.subset_sps.get(&seq_parameter_set_id);
+                        subset_sps.map(|subset_sps| &subset_sps.sps)
+                    })
+                    .map_or(1, |sps| sps.chroma_format_idc);
+                let lists_8x8 = match (transform_8x8_mode, chroma_format_idc) {
+                    (false, _) => 0,
+                    (true, 3) => 6,
+                    (true, _) => 2,
+                };
+                let mut lists = Vec::new();
+                for i in 0..6 + lists_8x8 {
+                    let (present, list);
+                    (input, present) = flag(input)?;
+                    (input, list) = combinator::cond(present, |input| {
+                        ScalingList::parse_bits(input, if i < 6 { 16 } else { 64 })
+                    })
+                    .parse_next(input)?;
+                    lists.push(list);
+                }
+                scaling_lists = Some(lists);
+            }
+            let offset;
+            (input, offset) = se(input)?;
+            second_chroma_qp_index_offset = Some(offset);
+        }
+        Ok((
+            input,
+            Self {
+                pic_parameter_set_id,
+                seq_parameter_set_id,
+                entropy_coding_mode: match entropy_coding_mode {
+                    false => EntropyCodingMode::CAVLC,
+                    true => EntropyCodingMode::CABAC,
+                },
+                bottom_field_pic_order_in_frame_present,
+                slice_groups,
+                num_ref_idx_l0_default_active: num_ref_idx_l0_default_active_minus1 + 1,
+                num_ref_idx_l1_default_active: num_ref_idx_l1_default_active_minus1 + 1,
+                weighted_pred,
+                weighted_bipred_idc: weighted_bipred_idc as u8,
+                pic_init_qp: pic_init_qp_minus26 + 26,
+                pic_init_qs: pic_init_qs_minus26 + 26,
+                chroma_qp_index_offset,
+                deblocking_filter_control_present,
+                constrained_intra_pred,
+                redundant_pic_cnt_present,
+                transform_8x8_mode,
+                scaling_lists,
+                second_chroma_qp_index_offset,
+            },
+        ))
+    }
+
+    /// The inverse of `parse_bits`, without the rbsp_trailing_bits; the fields after
+    /// redundant_pic_cnt_present_flag are written if second_chroma_qp_index_offset is.
+    /// None if a count that is coded minus 1 is 0, or the slice group map doesn't match
+    /// the number of slice groups
+    pub fn write_bits(&self, writer: &mut BitWriter) -> Option<()> {
+        writer.ue(self.pic_parameter_set_id);
+        writer.ue(self.seq_parameter_set_id);
+        writer.flag(self.entropy_coding_mode == EntropyCodingMode::CABAC);
+        writer.flag(self.bottom_field_pic_order_in_frame_present);
+        match &self.slice_groups {
+            Some(slice_groups) => {
+                writer.ue(slice_groups.num_slice_groups.checked_sub(1)?);
+                slice_groups.write_bits(writer)?;
+            }
+            None => writer.ue(0),
+        }
+        writer.ue(self.num_ref_idx_l0_default_active.checked_sub(1)?);
+        writer.ue(self.num_ref_idx_l1_default_active.checked_sub(1)?);
+        writer.flag(self.weighted_pred);
+        writer.u(2, self.weighted_bipred_idc as u32);
+        writer.se(self.pic_init_qp - 26);
+        writer.se(self.pic_init_qs - 26);
+        writer.se(self.chroma_qp_index_offset);
+        writer.flag(self.deblocking_filter_control_present);
+        writer.flag(self.constrained_intra_pred);
+        writer.flag(self.redundant_pic_cnt_present);
+        if let Some(second_chroma_qp_index_offset) = self.second_chroma_qp_index_offset {
+            writer.flag(self.transform_8x8_mode);
+            write_scaling_lists(writer, self.scaling_lists.as_deref());
+            writer.se(second_chroma_qp_index_offset);
+        }
+        Some(())
+    }
+
+    /// pic_parameter_set_rbsp(), as after the NAL unit header; None if the fields can't
+    /// be coded, see `write_bits`
+    pub fn to_rbsp(&self) -> Option<Vec<u8>> {
+        let mut writer = BitWriter::new();
+        self.write_bits(&mut writer)?;
+        writer.rbsp_trailing_bits();
+        Some(writer.into_bytes())
+    }
+}
+
+/// The parameter sets seen so far, which picture parameter sets and slice headers
+/// refer to
+#[derive(Debug, Default)]
+pub struct ParameterSets {
+    pub sps: HashMap<u32, SPS>,
+    pub pps: HashMap<u32, PPS>,
+    /// Subset SPS, which coded slice extensions refer to instead of the SPS
+    pub subset_sps: HashMap<u32, SubsetSPS>,
+    /// The SPS of the last slice, or the last SPS received if it came after it
+    pub active_sps_id: Option<u32>,
+}
+
+impl ParameterSets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The PPS with the given id, and the SPS it refers to
+    pub fn get(&self, pic_parameter_set_id: u32) -> Option<(&PPS, &SPS)> {
+        let pps = self.pps.get(&pic_parameter_set_id)?;
+        let sps = self.sps.get(&pps.seq_parameter_set_id)?;
+        Some((pps, sps))
+    }
+
+    /// The PPS with the given id, and the subset SPS it refers to
+    pub fn get_subset(&self, pic_parameter_set_id: u32) -> Option<(&PPS, &SubsetSPS)> {
+        let pps = self.pps.get(&pic_parameter_set_id)?;
+        let subset_sps = self.subset_sps.get(&pps.seq_parameter_set_id)?;
+        Some((pps, subset_sps))
+    }
+
+    /// The SPS that SEI messages without a seq_parameter_set_id refer to
+    pub fn active_sps(&self) -> Option<&SPS> {
+        self.sps.get(&self.active_sps_id?)
+    }
+}
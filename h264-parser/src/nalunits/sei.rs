@@ -0,0 +1,491 @@
+// SEI messages (ITU-T H.264 7.3.2.3 and annex D)
+use super::pps::ParameterSets;
+use super::sps::{HRDParameters, SPS};
+use crate::bitstream::{flag, parse_rbsp, u, ue, verify_error, BitInput, BitResult};
+use crate::stream::{stream, Stream};
+use std::fmt;
+use winnow::{binary, combinator, token, IResult, Parser};
+
+pub const BUFFERING_PERIOD: u32 = 0;
+pub const PIC_TIMING: u32 = 1;
+pub const USER_DATA_REGISTERED_ITU_T_T35: u32 = 4;
+pub const USER_DATA_UNREGISTERED: u32 = 5;
+pub const RECOVERY_POINT: u32 = 6;
+pub const FRAME_PACKING_ARRANGEMENT: u32 = 45;
+pub const MASTERING_DISPLAY_COLOUR_VOLUME: u32 = 137;
+pub const CONTENT_LIGHT_LEVEL_INFO: u32 = 144;
+
+/// initial_cpb_removal_delay and initial_cpb_removal_delay_offset of one CPB
+pub type InitialCPBRemovalDelay = (u32, u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferingPeriod {
+    pub seq_parameter_set_id: u32,
+    pub nal_initial_cpb_removal_delays: Vec<InitialCPBRemovalDelay>,
+    pub vcl_initial_cpb_removal_delays: Vec<InitialCPBRemovalDelay>,
+}
+
+fn parse_initial_cpb_removal_delays<'i>(
+    mut input: BitInput<'i>,
+    hrd: Option<&HRDParameters>,
+) -> BitResult<'i, Vec<InitialCPBRemovalDelay>> {
+    let mut delays = Vec::new();
+    if let Some(hrd) = hrd {
+        let length = hrd.initial_cpb_removal_delay_length as usize;
+        for _ in hrd.cpb_specs.iter() {
+            let delay;
+            (input, delay) = (u(length), u(length)).parse_next(input)?;
+            delays.push(delay);
+        }
+    }
+    Ok((input, delays))
+}
+
+impl BufferingPeriod {
+    fn parse_bits<'i>(input: BitInput<'i>, parameter_sets: &ParameterSets) -> BitResult<'i, Self> {
+        let (input, seq_parameter_set_id) = ue(input)?;
+        let Some(vui) = parameter_sets
+            .sps
+            .get(&seq_parameter_set_id)
+            .and_then(|sps| sps.vui.as_ref())
+        else {
+            return verify_error(input);
+        };
+        let (input, nal_initial_cpb_removal_delays) =
+            parse_initial_cpb_removal_delays(input, vui.nal_hrd_parameters.as_ref())?;
+        let (input, vcl_initial_cpb_removal_delays) =
+            parse_initial_cpb_removal_delays(input, vui.vcl_hrd_parameters.as_ref())?;
+        Ok((
+            input,
+            Self {
+                seq_parameter_set_id,
+                nal_initial_cpb_removal_delays,
+                vcl_initial_cpb_removal_delays,
+            },
+        ))
+    }
+}
+
+/// clock_timestamp() of a field or frame of pic_timing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTimestamp {
+    /// 0: progressive, 1: interlaced, 2: unknown
+    pub ct_type: u8,
+    pub nuit_field_based: bool,
+    pub counting_type: u8,
+    pub discontinuity: bool,
+    pub cnt_dropped: bool,
+    pub n_frames: u8,
+    /// None if not sent, then it is the same as in the previous timestamp
+    pub seconds: Option<u8>,
+    pub minutes: Option<u8>,
+    pub hours: Option<u8>,
+    pub time_offset: i32,
+}
+
+impl ClockTimestamp {
+    fn parse_bits(input: BitInput, time_offset_length: usize) -> BitResult<Self> {
+        let (input, (ct_type, nuit_field_based, counting_type)) =
+            (u(2), flag, u(5)).parse_next(input)?;
+        let (input, (full_timestamp, discontinuity, cnt_dropped, n_frames)) =
+            (flag, flag, flag, u(8)).parse_next(input)?;
+        let (input, (seconds, minutes, hours)) = match full_timestamp {
+            true => (u(6), u(6), u(5))
+                .map(|(s, m, h)| (Some(s), Some(m), Some(h)))
+                .parse_next(input)?,
+            false => {
+                // seconds_flag, minutes_flag and hours_flag each enclose the next ones
+                let (input, seconds_present) = flag(input)?;
+                let (input, seconds) = combinator::cond(seconds_present, u(6)).parse_next(input)?;
+                let (input, minutes_present) =
+                    combinator::cond(seconds_present, flag).parse_next(input)?;
+                let (input, minutes) =
+                    combinator::cond(minutes_present == Some(true), u(6)).parse_next(input)?;
+                let (input, hours_present) =
+                    combinator::cond(minutes_present == Some(true), flag).parse_next(input)?;
+                let (input, hours) =
+                    combinator::cond(hours_present == Some(true), u(5)).parse_next(input)?;
+                (input, (seconds, minutes, hours))
+            }
+        };
+        let (input, time_offset) = match time_offset_length {
+            0 => (input, 0),
+            length => u(length)
+                .map(|offset| ((offset << (32 - length)) as i32) >> (32 - length))
+                .parse_next(input)?,
+        };
+        Ok((
+            input,
+            Self {
+                ct_type: ct_type as u8,
+                nuit_field_based,
+                counting_type: counting_type as u8,
+                discontinuity,
+                cnt_dropped,
+                n_frames: n_frames as u8,
+                seconds: seconds.map(|s| s as u8),
+                minutes: minutes.map(|m| m as u8),
+                hours: hours.map(|h| h as u8),
+                time_offset,
+            },
+        ))
+    }
+
+    /// hh:mm:ss:ff, if the timestamp is complete
+    pub fn timecode(&self) -> Option<String> {
+        Some(format!(
+            "{:02}:{:02}:{:02}:{:02}",
+            self.hours?, self.minutes?, self.seconds?, self.n_frames
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PicTiming {
+    /// cpb_removal_delay and dpb_output_delay, with HRD parameters
+    pub delays: Option<(u32, u32)>,
+    /// Table D-1, e.g. 0: frame, 1: top field, 3: top and bottom field, 7: frame doubling
+    pub pic_struct: Option<u8>,
+    /// One for every field or frame of pic_struct that has a timestamp
+    pub clock_timestamps: Vec<Option<ClockTimestamp>>,
+}
+
+impl PicTiming {
+    fn parse_bits<'i>(input: BitInput<'i>, sps: &SPS) -> BitResult<'i, Self> {
+        let Some(vui) = sps.vui.as_ref() else {
+            return verify_error(input);
+        };
+        let hrd = vui
+            .nal_hrd_parameters
+            .as_ref()
+            .or(vui.vcl_hrd_parameters.as_ref());
+        let (input, delays) = match hrd {
+            Some(hrd) => (
+                u(hrd.cpb_removal_delay_length as usize),
+                u(hrd.dpb_output_delay_length as usize),
+            )
+                .map(Some)
+                .parse_next(input)?,
+            None => (input, None),
+        };
+        let (mut input, pic_struct) =
+            combinator::cond(vui.pic_struct_present, u(4)).parse_next(input)?;
+        let mut clock_timestamps = Vec::new();
+        if let Some(pic_struct) = pic_struct {
+            let num_clock_ts = match pic_struct {
+                0..=2 => 1,
+                3 | 4 | 7 => 2,
+                5 | 6 | 8 => 3,
+                _ => return verify_error(input),
+            };
+            let time_offset_length = hrd.map_or(24, |hrd| hrd.time_offset_length as usize);
+            for _ in 0..num_clock_ts {
+                let (present, timestamp);
+                (input, present) = flag(input)?;
+                (input, timestamp) = combinator::cond(present, |input| {
+                    ClockTimestamp::parse_bits(input, time_offset_length)
+                })
+                .parse_next(input)?;
+                clock_timestamps.push(timestamp);
+            }
+        }
+        Ok((
+            input,
+            Self {
+                delays,
+                pic_struct: pic_struct.map(|p| p as u8),
+                clock_timestamps,
+            },
+        ))
+    }
+}
+
+/// Decoding from here on gives correct pictures after recovery_frame_cnt frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryPoint {
+    pub recovery_frame_cnt: u32,
+    pub exact_match: bool,
+    pub broken_link: bool,
+    pub changing_slice_group_idc: u8,
+}
+
+impl RecoveryPoint {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        (ue, flag, flag, u(2))
+            .map(
+                |(recovery_frame_cnt, exact_match, broken_link, changing_slice_group_idc)| Self {
+                    recovery_frame_cnt,
+                    exact_match,
+                    broken_link,
+                    changing_slice_group_idc: changing_slice_group_idc as u8,
+                },
+            )
+            .parse_next(input)
+    }
+}
+
+/// Stereo 3D packing of the two views in one picture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePackingArrangement {
+    pub id: u32,
+    /// None if the arrangement is cancelled
+    pub arrangement: Option<FramePacking>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramePacking {
+    /// 0: checkerboard, 1: column, 2: row, 3: side by side, 4: top bottom, 5: temporal
+    pub arrangement_type: u8,
+    pub quincunx_sampling: bool,
+    pub content_interpretation_type: u8,
+    pub spatial_flipping: bool,
+    pub frame0_flipped: bool,
+    pub field_views: bool,
+    pub current_frame_is_frame0: bool,
+    pub frame0_self_contained: bool,
+    pub frame1_self_contained: bool,
+    /// (x, y) of frame 0 and frame 1
+    pub grid_positions: Option<[(u8, u8); 2]>,
+    pub repetition_period: u32,
+}
+
+impl FramePacking {
+    pub fn arrangement_name(&self) -> &'static str {
+        match self.arrangement_type {
+            0 => "checkerboard",
+            1 => "column interleaved",
+            2 => "row interleaved",
+            3 => "side by side",
+            4 => "top bottom",
+            5 => "frame alternation",
+            _ => "unknown",
+        }
+    }
+}
+
+impl FramePackingArrangement {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (id, cancel)) = (ue, flag).parse_next(input)?;
+        let (input, arrangement) = combinator::cond(!cancel, |input| {
+            let (input, (arrangement_type, quincunx_sampling, content_interpretation_type)) =
+                (u(7), flag, u(6)).parse_next(input)?;
+            let (input, flags) = (flag, flag, flag, flag, flag, flag).parse_next(input)?;
+            let (input, grid_positions) = combinator::cond(
+                !quincunx_sampling && arrangement_type != 5,
+                (u(4), u(4), u(4), u(4))
+                    .map(|(x0, y0, x1, y1)| [(x0 as u8, y0 as u8), (x1 as u8, y1 as u8)]),
+            )
+            .parse_next(input)?;
+            // frame_packing_arrangement_reserved_byte
+            let (input, (_, repetition_period)) = (u(8), ue).parse_next(input)?;
+            Ok((
+                input,
+                FramePacking {
+                    arrangement_type: arrangement_type as u8,
+                    quincunx_sampling,
+                    content_interpretation_type: content_interpretation_type as u8,
+                    spatial_flipping: flags.0,
+                    frame0_flipped: flags.1,
+                    field_views: flags.2,
+                    current_frame_is_frame0: flags.3,
+                    frame0_self_contained: flags.4,
+                    frame1_self_contained: flags.5,
+                    grid_positions,
+                    repetition_period,
+                },
+            ))
+        })
+        .parse_next(input)?;
+        Ok((input, Self { id, arrangement }))
+    }
+}
+
+/// Chromaticities are in units of 0.00002, luminances in units of 0.0001 cd/m²
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasteringDisplayColourVolume {
+    /// (x, y) of the green, blue and red primaries, in that order
+    pub display_primaries: [(u16, u16); 3],
+    pub white_point: (u16, u16),
+    pub max_display_mastering_luminance: u32,
+    pub min_display_mastering_luminance: u32,
+}
+
+impl MasteringDisplayColourVolume {
+    /// The payload is the same in H.264 and H.265
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 24 {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([payload[i], payload[i + 1]]);
+        let u32_at = |i: usize| u32::from_be_bytes(payload[i..i + 4].try_into().unwrap());
+        Some(Self {
+            display_primaries: [
+                (u16_at(0), u16_at(2)),
+                (u16_at(4), u16_at(6)),
+                (u16_at(8), u16_at(10)),
+            ],
+            white_point: (u16_at(12), u16_at(14)),
+            max_display_mastering_luminance: u32_at(16),
+            min_display_mastering_luminance: u32_at(20),
+        })
+    }
+}
+
+impl fmt::Display for MasteringDisplayColourVolume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let xy =
+            |(x, y): (u16, u16)| format!("({:.4}, {:.4})", x as f32 / 50000.0, y as f32 / 50000.0);
+        let [g, b, r] = self.display_primaries;
+        write!(
+            f,
+            "R{} G{} B{} WP{}, {}-{} cd/m²",
+            xy(r),
+            xy(g),
+            xy(b),
+            xy(self.white_point),
+            self.min_display_mastering_luminance as f32 / 10000.0,
+            self.max_display_mastering_luminance as f32 / 10000.0,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    /// MaxCLL in cd/m²
+    pub max_content_light_level: u16,
+    /// MaxFALL in cd/m²
+    pub max_pic_average_light_level: u16,
+}
+
+impl ContentLightLevel {
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        if payload.len() < 4 {
+            return None;
+        }
+        Some(Self {
+            max_content_light_level: u16::from_be_bytes([payload[0], payload[1]]),
+            max_pic_average_light_level: u16::from_be_bytes([payload[2], payload[3]]),
+        })
+    }
+}
+
+pub enum SEIMessage {
+    BufferingPeriod(BufferingPeriod),
+    PicTiming(PicTiming),
+    RecoveryPoint(RecoveryPoint),
+    /// itu_t_t35_country_code (with the extension byte if it is 0xff) and the payload
+    /// after it, e.g. ATSC A/53 closed captions behind country code 0xb5
+    UserDataRegistered(Vec<u8>, Vec<u8>),
+    /// uuid_iso_iec_11578 and the payload after it
+    UserDataUnregistered([u8; 16], Vec<u8>),
+    FramePackingArrangement(FramePackingArrangement),
+    MasteringDisplayColourVolume(MasteringDisplayColourVolume),
+    ContentLightLevel(ContentLightLevel),
+    /// Messages not decoded, or that failed to decode
+    Other(u32, Vec<u8>),
+}
+
+impl fmt::Debug for SEIMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BufferingPeriod(b) => write!(f, "{:?}", b),
+            Self::PicTiming(p) => write!(f, "{:?}", p),
+            Self::RecoveryPoint(r) => write!(f, "{:?}", r),
+            Self::UserDataRegistered(country_code, data) => write!(
+                f,
+                "UserDataRegistered({:x?}, {:x?})",
+                country_code,
+                &data[..(16.min(data.len()))]
+            ),
+            Self::UserDataUnregistered(uuid, data) => write!(
+                f,
+                "UserDataUnregistered({:02x?}, {:x?})",
+                uuid,
+                &data[..(16.min(data.len()))]
+            ),
+            Self::FramePackingArrangement(a) => write!(f, "{:?}", a),
+            Self::MasteringDisplayColourVolume(m) => write!(f, "MasteringDisplay({})", m),
+            Self::ContentLightLevel(c) => write!(
+                f,
+                "ContentLightLevel(MaxCLL={}, MaxFALL={})",
+                c.max_content_light_level, c.max_pic_average_light_level
+            ),
+            Self::Other(payload_type, data) => write!(
+                f,
+                "SEI({}, {:x?})",
+                payload_type,
+                &data[..(16.min(data.len()))]
+            ),
+        }
+    }
+}
+
+/// payloadType and payloadSize: a run of 0xff bytes, each adding 255, and a last byte
+pub fn sei_value(input: Stream<'_>) -> IResult<Stream<'_>, u32> {
+    let (input, extension) = token::take_while(0.., 0xff).parse_next(input)?;
+    let (input, last) = binary::u8(input)?;
+    Ok((input, extension.len() as u32 * 255 + last as u32))
+}
+
+impl SEIMessage {
+    fn parse<'i>(input: Stream<'i>, parameter_sets: &ParameterSets) -> IResult<Stream<'i>, Self> {
+        let (input, (payload_type, payload_size)) = (sei_value, sei_value).parse_next(input)?;
+        let (input, payload) = token::take(payload_size).parse_next(input)?;
+        let message = match payload_type {
+            BUFFERING_PERIOD => parse_rbsp(payload, |input| {
+                BufferingPeriod::parse_bits(input, parameter_sets)
+            })
+            .ok()
+            .map(Self::BufferingPeriod),
+            PIC_TIMING => parameter_sets.active_sps().and_then(|sps| {
+                parse_rbsp(payload, |input| PicTiming::parse_bits(input, sps))
+                    .ok()
+                    .map(Self::PicTiming)
+            }),
+            RECOVERY_POINT => parse_rbsp(payload, RecoveryPoint::parse_bits)
+                .ok()
+                .map(Self::RecoveryPoint),
+            USER_DATA_REGISTERED_ITU_T_T35 if !payload.is_empty() => {
+                let country_code_length = if payload[0] == 0xff { 2 } else { 1 };
+                (payload.len() >= country_code_length).then(|| {
+                    let (country_code, data) = payload.split_at(country_code_length);
+                    Self::UserDataRegistered(country_code.to_vec(), data.to_vec())
+                })
+            }
+            USER_DATA_UNREGISTERED if payload.len() >= 16 => Some(Self::UserDataUnregistered(
+                payload[..16].try_into().unwrap(),
+                payload[16..].to_vec(),
+            )),
+            FRAME_PACKING_ARRANGEMENT => parse_rbsp(payload, FramePackingArrangement::parse_bits)
+                .ok()
+                .map(Self::FramePackingArrangement),
+            MASTERING_DISPLAY_COLOUR_VOLUME => MasteringDisplayColourVolume::from_payload(payload)
+                .map(Self::MasteringDisplayColourVolume),
+            CONTENT_LIGHT_LEVEL_INFO => {
+                ContentLightLevel::from_payload(payload).map(Self::ContentLightLevel)
+            }
+            _ => None,
+        };
+        Ok((
+            input,
+            message.unwrap_or_else(|| Self::Other(payload_type, payload.to_vec())),
+        ))
+    }
+}
+
+/// Parses the messages of an SEI RBSP, up to the rbsp_trailing_bits; pic_timing is read
+/// with the active SPS, buffering_period with the SPS it names
+pub fn parse_sei_rbsp(rbsp: &[u8], parameter_sets: &ParameterSets) -> Vec<SEIMessage> {
+    let mut input = stream(rbsp);
+    let mut messages = Vec::new();
+    while !input.is_empty() && **input != [0x80][..] {
+        match SEIMessage::parse(input, parameter_sets) {
+            Ok((remainder, message)) => {
+                messages.push(message);
+                input = remainder;
+            }
+            Err(_) => break,
+        }
+    }
+    messages
+}
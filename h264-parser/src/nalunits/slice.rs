@@ -0,0 +1,515 @@
+// slice_header() (ITU-T H.264 7.3.3), resolved against the SPS and PPS it refers to
+use super::mvc::MVCHeaderExtension;
+use super::pps::{EntropyCodingMode, ParameterSets, SliceGroupMap};
+use super::sps::{PicOrderCntType, SPS};
+use crate::bitstream::{flag, se, u, ue, verify_error, BitInput, BitResult};
+use winnow::{combinator, Parser};
+
+/// NAL unit type of a coded slice of an IDR picture
+const IDR_NU_TYPE: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceType {
+    P,
+    B,
+    I,
+    SP,
+    SI,
+}
+
+impl SliceType {
+    fn from_slice_type(slice_type: u32) -> Self {
+        match slice_type % 5 {
+            0 => Self::P,
+            1 => Self::B,
+            2 => Self::I,
+            3 => Self::SP,
+            _ => Self::SI,
+        }
+    }
+
+    pub fn is_intra(&self) -> bool {
+        matches!(self, Self::I | Self::SI)
+    }
+
+    pub fn is_b(&self) -> bool {
+        *self == Self::B
+    }
+}
+
+/// An entry of ref_pic_list_modification(), by modification_of_pic_nums_idc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefPicListModification {
+    /// 0: abs_diff_pic_num_minus1 to subtract
+    SubtractPicNum(u32),
+    /// 1: abs_diff_pic_num_minus1 to add
+    AddPicNum(u32),
+    /// 2: long_term_pic_num
+    LongTermPicNum(u32),
+    /// 4: abs_diff_view_idx_minus1 to subtract, in slices of non-base views (H.7.3.3.1.1)
+    SubtractViewIdx(u32),
+    /// 5: abs_diff_view_idx_minus1 to add
+    AddViewIdx(u32),
+}
+
+/// ref_pic_list_modification(), or ref_pic_list_mvc_modification() if `mvc`
+fn parse_ref_pic_list_modification(
+    input: BitInput,
+    mvc: bool,
+) -> BitResult<Option<Vec<RefPicListModification>>> {
+    let (mut input, modification_present) = flag(input)?;
+    if !modification_present {
+        return Ok((input, None));
+    }
+    let mut modifications = Vec::new();
+    loop {
+        let idc;
+        (input, idc) = ue(input)?;
+        let value;
+        match idc {
+            0..=2 => (input, value) = ue(input)?,
+            4 | 5 if mvc => (input, value) = ue(input)?,
+            3 => break,
+            _ => return verify_error(input),
+        }
+        modifications.push(match idc {
+            0 => RefPicListModification::SubtractPicNum(value),
+            1 => RefPicListModification::AddPicNum(value),
+            2 => RefPicListModification::LongTermPicNum(value),
+            4 => RefPicListModification::SubtractViewIdx(value),
+            _ => RefPicListModification::AddViewIdx(value),
+        });
+        // more entries than reference indices is a broken header
+        if modifications.len() > 32 {
+            return verify_error(input);
+        }
+    }
+    Ok((input, Some(modifications)))
+}
+
+/// Weights and offsets of one reference picture; None if the defaults apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PredWeight {
+    pub luma: Option<(i32, i32)>,
+    /// (weight, offset) of Cb and Cr
+    pub chroma: Option<[(i32, i32); 2]>,
+}
+
+/// pred_weight_table() (7.3.3.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PredWeightTable {
+    pub luma_log2_weight_denom: u32,
+    /// Not present without chroma (ChromaArrayType 0)
+    pub chroma_log2_weight_denom: Option<u32>,
+    pub l0: Vec<PredWeight>,
+    pub l1: Vec<PredWeight>,
+}
+
+impl PredWeightTable {
+    fn parse_bits(
+        input: BitInput,
+        has_chroma: bool,
+        num_ref_idx_l0_active: u32,
+        num_ref_idx_l1_active: u32,
+    ) -> BitResult<Self> {
+        let (input, luma_log2_weight_denom) = ue(input)?;
+        let (mut input, chroma_log2_weight_denom) =
+            combinator::cond(has_chroma, ue).parse_next(input)?;
+        let mut lists = [Vec::new(), Vec::new()];
+        for (list, count) in lists
+            .iter_mut()
+            .zip([num_ref_idx_l0_active, num_ref_idx_l1_active])
+        {
+            for _ in 0..count {
+                let (luma_present, luma);
+                (input, luma_present) = flag(input)?;
+                (input, luma) = combinator::cond(luma_present, (se, se)).parse_next(input)?;
+                let (chroma_present, chroma);
+                (input, chroma_present) = combinator::cond(has_chroma, flag).parse_next(input)?;
+                (input, chroma) = combinator::cond(
+                    chroma_present == Some(true),
+                    (se, se, se, se).map(|(cb_w, cb_o, cr_w, cr_o)| [(cb_w, cb_o), (cr_w, cr_o)]),
+                )
+                .parse_next(input)?;
+                list.push(PredWeight { luma, chroma });
+            }
+        }
+        let [l0, l1] = lists;
+        Ok((
+            input,
+            Self {
+                luma_log2_weight_denom,
+                chroma_log2_weight_denom,
+                l0,
+                l1,
+            },
+        ))
+    }
+}
+
+/// memory_management_control_operation and its arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryManagementControlOperation {
+    /// 1: difference_of_pic_nums_minus1
+    UnmarkShortTerm(u32),
+    /// 2: long_term_pic_num
+    UnmarkLongTerm(u32),
+    /// 3: difference_of_pic_nums_minus1 and long_term_frame_idx
+    ShortTermToLongTerm(u32, u32),
+    /// 4: max_long_term_frame_idx_plus1
+    MaxLongTermFrameIdx(u32),
+    /// 5
+    UnmarkAll,
+    /// 6: long_term_frame_idx
+    CurrentToLongTerm(u32),
+}
+
+/// dec_ref_pic_marking() (7.3.3.3), only in reference pictures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecRefPicMarking {
+    IDR {
+        no_output_of_prior_pics: bool,
+        long_term_reference: bool,
+    },
+    SlidingWindow,
+    Adaptive(Vec<MemoryManagementControlOperation>),
+}
+
+impl DecRefPicMarking {
+    fn parse_bits(input: BitInput, idr: bool) -> BitResult<Self> {
+        if idr {
+            return (flag, flag)
+                .map(|(no_output_of_prior_pics, long_term_reference)| Self::IDR {
+                    no_output_of_prior_pics,
+                    long_term_reference,
+                })
+                .parse_next(input);
+        }
+        let (mut input, adaptive) = flag(input)?;
+        if !adaptive {
+            return Ok((input, Self::SlidingWindow));
+        }
+        let mut operations = Vec::new();
+        loop {
+            let (operation, value);
+            (input, operation) = ue(input)?;
+            operations.push(match operation {
+                0 => break,
+                1 => {
+                    (input, value) = ue(input)?;
+                    MemoryManagementControlOperation::UnmarkShortTerm(value)
+                }
+                2 => {
+                    (input, value) = ue(input)?;
+                    MemoryManagementControlOperation::UnmarkLongTerm(value)
+                }
+                3 => {
+                    let long_term_frame_idx;
+                    (input, (value, long_term_frame_idx)) = (ue, ue).parse_next(input)?;
+                    MemoryManagementControlOperation::ShortTermToLongTerm(
+                        value,
+                        long_term_frame_idx,
+                    )
+                }
+                4 => {
+                    (input, value) = ue(input)?;
+                    MemoryManagementControlOperation::MaxLongTermFrameIdx(value)
+                }
+                5 => MemoryManagementControlOperation::UnmarkAll,
+                6 => {
+                    (input, value) = ue(input)?;
+                    MemoryManagementControlOperation::CurrentToLongTerm(value)
+                }
+                _ => return verify_error(input),
+            });
+            if operations.len() > 66 {
+                return verify_error(input);
+            }
+        }
+        Ok((input, Self::Adaptive(operations)))
+    }
+
+    /// memory_management_control_operation 5 is present
+    pub fn has_mmco5(&self) -> bool {
+        match self {
+            Self::Adaptive(operations) => {
+                operations.contains(&MemoryManagementControlOperation::UnmarkAll)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeblockingFilter {
+    /// 0: on, 1: off, 2: on but not across slice boundaries
+    pub disable_deblocking_filter_idc: u32,
+    pub slice_alpha_c0_offset_div2: i32,
+    pub slice_beta_offset_div2: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SliceHeader {
+    pub first_mb_in_slice: u32,
+    pub slice_type: SliceType,
+    /// slice_type above 4: all slices of the picture have this type
+    pub same_type_in_picture: bool,
+    pub pic_parameter_set_id: u32,
+    pub colour_plane_id: Option<u8>,
+    pub frame_num: u32,
+    pub field_pic: bool,
+    pub bottom_field: bool,
+    pub idr_pic_id: Option<u32>,
+    /// With pic_order_cnt_type 0
+    pub pic_order_cnt_lsb: Option<u32>,
+    pub delta_pic_order_cnt_bottom: i32,
+    pub delta_pic_order_cnt: [i32; 2],
+    pub redundant_pic_cnt: u32,
+    pub direct_spatial_mv_pred: Option<bool>,
+    /// num_ref_idx_l0_active_minus1 and _l1_active_minus1, if num_ref_idx_active_override_flag
+    /// is set
+    pub num_ref_idx_active_override: Option<(u32, Option<u32>)>,
+    /// The number of active reference indices of list 0 and 1, 0 for unused lists
+    pub num_ref_idx_l0_active: u32,
+    pub num_ref_idx_l1_active: u32,
+    pub ref_pic_list_modification_l0: Option<Vec<RefPicListModification>>,
+    pub ref_pic_list_modification_l1: Option<Vec<RefPicListModification>>,
+    pub pred_weight_table: Option<PredWeightTable>,
+    pub dec_ref_pic_marking: Option<DecRefPicMarking>,
+    pub cabac_init_idc: Option<u32>,
+    pub slice_qp_delta: i32,
+    pub sp_for_switch: Option<bool>,
+    pub slice_qs_delta: Option<i32>,
+    pub deblocking_filter: Option<DeblockingFilter>,
+    pub slice_group_change_cycle: Option<u32>,
+}
+
+impl SliceHeader {
+    /// Fails if the PPS or SPS the slice refers to is unknown
+    pub fn parse_bits<'i>(
+        input: BitInput<'i>,
+        nal_unit_type: u8,
+        nal_ref_idc: u8,
+        parameter_sets: &ParameterSets,
+    ) -> BitResult<'i, Self> {
+        let idr = nal_unit_type == IDR_NU_TYPE;
+        Self::parse_bits_with(input, idr, false, nal_ref_idc, parameter_sets)
+    }
+
+    /// The header of a coded slice extension of a non-base view, whose PPS refers to a
+    /// subset SPS
+    pub fn parse_mvc_bits<'i>(
+        input: BitInput<'i>,
+        extension: &MVCHeaderExtension,
+        nal_ref_idc: u8,
+        parameter_sets: &ParameterSets,
+    ) -> BitResult<'i, Self> {
+        let idr = !extension.non_idr;
+        Self::parse_bits_with(input, idr, true, nal_ref_idc, parameter_sets)
+    }
+
+    fn parse_bits_with<'i>(
+        input: BitInput<'i>,
+        idr: bool,
+        mvc: bool,
+        nal_ref_idc: u8,
+        parameter_sets: &ParameterSets,
+    ) -> BitResult<'i, Self> {
+        let (input, (first_mb_in_slice, raw_slice_type, pic_parameter_set_id)) =
+            (ue, ue, ue).parse_next(input)?;
+        if raw_slice_type > 9 {
+            return verify_error(input);
+        }
+        let slice_type = SliceType::from_slice_type(raw_slice_type);
+        let parameter_sets: Option<(_, &SPS)> = match mvc {
+            true => parameter_sets
+                .get_subset(pic_parameter_set_id)
+                .map(|(pps, subset_sps)| (pps, &subset_sps.sps)),
+            false => parameter_sets.get(pic_parameter_set_id),
+        };
+        let Some((pps, sps)) = parameter_sets else {
+            return verify_error(input);
+        };
+        let (input, colour_plane_id) =
+            combinator::cond(sps.separate_colour_plane, u(2)).parse_next(input)?;
+        let (input, frame_num) = u(sps.log2_max_frame_num as usize)(input)?;
+        let (input, field_pic) = combinator::cond(!sps.frame_mbs_only, flag)
+            .map(|flag| flag.unwrap_or(false))
+            .parse_next(input)?;
+        let (input, bottom_field) = combinator::cond(field_pic, flag)
+            .map(|flag| flag.unwrap_or(false))
+            .parse_next(input)?;
+        let (input, idr_pic_id) = combinator::cond(idr, ue).parse_next(input)?;
+        let bottom_present = pps.bottom_field_pic_order_in_frame_present && !field_pic;
+        let mut input = input;
+        let mut pic_order_cnt_lsb = None;
+        let mut delta_pic_order_cnt_bottom = 0;
+        let mut delta_pic_order_cnt = [0; 2];
+        match sps.pic_order_cnt_type {
+            PicOrderCntType::Type0 {
+                log2_max_pic_order_cnt_lsb,
+            } => {
+                let lsb;
+                (input, lsb) = u(log2_max_pic_order_cnt_lsb as usize)(input)?;
+                pic_order_cnt_lsb = Some(lsb);
+                if bottom_present {
+                    (input, delta_pic_order_cnt_bottom) = se(input)?;
+                }
+            }
+            PicOrderCntType::Type1 {
+                delta_pic_order_always_zero: false,
+                ..
+            } => {
+                (input, delta_pic_order_cnt[0]) = se(input)?;
+                if bottom_present {
+                    (input, delta_pic_order_cnt[1]) = se(input)?;
+                }
+            }
+            _ => (),
+        }
+        let (input, redundant_pic_cnt) = combinator::cond(pps.redundant_pic_cnt_present, ue)
+            .map(|count| count.unwrap_or(0))
+            .parse_next(input)?;
+        let (input, direct_spatial_mv_pred) =
+            combinator::cond(slice_type.is_b(), flag).parse_next(input)?;
+        let inter = !slice_type.is_intra();
+        let (input, override_present) = combinator::cond(inter, flag).parse_next(input)?;
+        let (input, num_ref_idx_active_override) = combinator::cond(
+            override_present == Some(true),
+            (ue, combinator::cond(slice_type.is_b(), ue)),
+        )
+        .parse_next(input)?;
+        let (num_ref_idx_l0_active, num_ref_idx_l1_active) = match num_ref_idx_active_override {
+            _ if !inter => (0, 0),
+            Some((l0, l1)) => (l0 + 1, l1.map_or(0, |l1| l1 + 1)),
+            None if slice_type.is_b() => (
+                pps.num_ref_idx_l0_default_active,
+                pps.num_ref_idx_l1_default_active,
+            ),
+            None => (pps.num_ref_idx_l0_default_active, 0),
+        };
+        if num_ref_idx_l0_active > 32 || num_ref_idx_l1_active > 32 {
+            return verify_error(input);
+        }
+        let modification = |input| parse_ref_pic_list_modification(input, mvc);
+        let (input, ref_pic_list_modification_l0) = combinator::cond(inter, modification)
+            .map(Option::flatten)
+            .parse_next(input)?;
+        let (input, ref_pic_list_modification_l1) =
+            combinator::cond(slice_type.is_b(), modification)
+                .map(Option::flatten)
+                .parse_next(input)?;
+        let weighted = match slice_type {
+            SliceType::P | SliceType::SP => pps.weighted_pred,
+            SliceType::B => pps.weighted_bipred_idc == 1,
+            _ => false,
+        };
+        let (input, pred_weight_table) = combinator::cond(weighted, |input| {
+            PredWeightTable::parse_bits(
+                input,
+                sps.chroma_array_type() != 0,
+                num_ref_idx_l0_active,
+                num_ref_idx_l1_active,
+            )
+        })
+        .parse_next(input)?;
+        let (input, dec_ref_pic_marking) = combinator::cond(nal_ref_idc != 0, |input| {
+            DecRefPicMarking::parse_bits(input, idr)
+        })
+        .parse_next(input)?;
+        let (input, cabac_init_idc) = combinator::cond(
+            pps.entropy_coding_mode == EntropyCodingMode::CABAC && inter,
+            ue,
+        )
+        .parse_next(input)?;
+        let (input, slice_qp_delta) = se(input)?;
+        let (input, sp_for_switch) =
+            combinator::cond(slice_type == SliceType::SP, flag).parse_next(input)?;
+        let (input, slice_qs_delta) =
+            combinator::cond(matches!(slice_type, SliceType::SP | SliceType::SI), se)
+                .parse_next(input)?;
+        let (input, deblocking_filter) =
+            combinator::cond(pps.deblocking_filter_control_present, |input| {
+                let (input, disable_deblocking_filter_idc) = ue(input)?;
+                let (input, offsets) =
+                    combinator::cond(disable_deblocking_filter_idc != 1, (se, se))
+                        .parse_next(input)?;
+                let (slice_alpha_c0_offset_div2, slice_beta_offset_div2) =
+                    offsets.unwrap_or((0, 0));
+                Ok((
+                    input,
+                    DeblockingFilter {
+                        disable_deblocking_filter_idc,
+                        slice_alpha_c0_offset_div2,
+                        slice_beta_offset_div2,
+                    },
+                ))
+            })
+            .parse_next(input)?;
+        let change_rate = match pps.slice_groups.as_ref().map(|groups| &groups.map) {
+            Some(SliceGroupMap::Evolving { change_rate, .. }) => Some(*change_rate),
+            _ => None,
+        };
+        let (input, slice_group_change_cycle) = match change_rate {
+            Some(change_rate) => {
+                // Ceil(Log2(PicSizeInMapUnits ÷ SliceGroupChangeRate + 1))
+                let value = sps
+                    .pic_width_in_mbs
+                    .checked_mul(sps.pic_height_in_map_units)
+                    .and_then(|size| size.div_ceil(change_rate).checked_add(1));
+                let Some(value) = value else {
+                    return verify_error(input);
+                };
+                let bits = u32::BITS - (value - 1).leading_zeros();
+                u(bits as usize).map(Some).parse_next(input)?
+            }
+            None => (input, None),
+        };
+        Ok((
+            input,
+            Self {
+                first_mb_in_slice,
+                slice_type,
+                same_type_in_picture: raw_slice_type > 4,
+                pic_parameter_set_id,
+                colour_plane_id: colour_plane_id.map(|id| id as u8),
+                frame_num,
+                field_pic,
+                bottom_field,
+                idr_pic_id,
+                pic_order_cnt_lsb,
+                delta_pic_order_cnt_bottom,
+                delta_pic_order_cnt,
+                redundant_pic_cnt,
+                direct_spatial_mv_pred,
+                num_ref_idx_active_override,
+                num_ref_idx_l0_active,
+                num_ref_idx_l1_active,
+                ref_pic_list_modification_l0,
+                ref_pic_list_modification_l1,
+                pred_weight_table,
+                dec_ref_pic_marking,
+                cabac_init_idc,
+                slice_qp_delta,
+                sp_for_switch,
+                slice_qs_delta,
+                deblocking_filter,
+                slice_group_change_cycle,
+            },
+        ))
+    }
+
+    /// SliceQPY, given the PPS of the slice
+    pub fn slice_qp(&self, pic_init_qp: i32) -> i32 {
+        pic_init_qp + self.slice_qp_delta
+    }
+
+    /// Whether this slice and `other` (the previous one) belong to the same field pair
+    /// or frame: the second field of a pair has the same frame_num and the other parity
+    pub fn is_second_field_of(&self, other: &SliceHeader) -> bool {
+        self.field_pic
+            && other.field_pic
+            && self.frame_num == other.frame_num
+            && self.bottom_field != other.bottom_field
+    }
+}
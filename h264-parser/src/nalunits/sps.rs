@@ -0,0 +1,823 @@
+// Sequence parameter set (ITU-T H.264 7.3.2.1.1) with its VUI (Annex E)
+use crate::bitstream::{flag, se, u, ue, verify_error, BitInput, BitResult, BitWriter};
+use winnow::{combinator, Parser};
+
+/// profile_idc values whose SPS has chroma_format_idc, bit depths and scaling matrices
+const HIGH_PROFILES: [u8; 13] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+/// scaling_list(): the coded delta_scale values, as they are in the bitstream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScalingList {
+    /// 16 for the 4x4 lists, 64 for the 8x8 ones
+    pub size: usize,
+    pub delta_scales: Vec<i32>,
+}
+
+impl ScalingList {
+    pub(crate) fn parse_bits(input: BitInput, size: usize) -> BitResult<Self> {
+        let (mut input, mut delta_scales) = (input, Vec::new());
+        let mut next_scale = 8;
+        for _ in 0..size {
+            let delta_scale;
+            (input, delta_scale) = se(input)?;
+            if !(-128..=127).contains(&delta_scale) {
+                return verify_error(input);
+            }
+            delta_scales.push(delta_scale);
+            next_scale = (next_scale + delta_scale + 256) % 256;
+            if next_scale == 0 {
+                break;
+            }
+        }
+        Ok((input, Self { size, delta_scales }))
+    }
+
+    pub(crate) fn write_bits(&self, writer: &mut BitWriter) {
+        for delta_scale in self.delta_scales.iter() {
+            writer.se(*delta_scale);
+        }
+    }
+
+    /// The scaling factors in zig-zag order, None if the default list is to be used
+    /// (useDefaultScalingMatrixFlag)
+    pub fn values(&self) -> Option<Vec<u8>> {
+        let mut values = Vec::with_capacity(self.size);
+        let mut last_scale = 8;
+        for delta_scale in self.delta_scales.iter() {
+            let next_scale = (last_scale + delta_scale + 256) % 256;
+            if next_scale == 0 {
+                break;
+            }
+            values.push(next_scale as u8);
+            last_scale = next_scale;
+        }
+        if values.is_empty() {
+            return None;
+        }
+        values.resize(self.size, last_scale as u8);
+        Some(values)
+    }
+}
+
+/// The scaling matrix present flag, and the present flag and list of every list
+pub(crate) fn write_scaling_lists(writer: &mut BitWriter, lists: Option<&[Option<ScalingList>]>) {
+    writer.flag(lists.is_some());
+    for list in lists.unwrap_or_default() {
+        writer.flag(list.is_some());
+        if let Some(list) = list {
+            list.write_bits(writer);
+        }
+    }
+}
+
+/// hrd_parameters() (E.1.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HRDParameters {
+    pub bit_rate_scale: u8,
+    pub cpb_size_scale: u8,
+    /// bit_rate_value_minus1, cpb_size_value_minus1 and cbr_flag of every CPB
+    pub cpb_specs: Vec<(u32, u32, bool)>,
+    pub initial_cpb_removal_delay_length: u8,
+    pub cpb_removal_delay_length: u8,
+    pub dpb_output_delay_length: u8,
+    pub time_offset_length: u8,
+}
+
+impl HRDParameters {
+    pub(crate) fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (mut input, (cpb_cnt_minus1, bit_rate_scale, cpb_size_scale)) =
+            (ue, u(4), u(4)).parse_next(input)?;
+        if cpb_cnt_minus1 > 31 {
+            return verify_error(input);
+        }
+        let mut cpb_specs = Vec::new();
+        for _ in 0..=cpb_cnt_minus1 {
+            let spec;
+            (input, spec) = (ue, ue, flag).parse_next(input)?;
+            cpb_specs.push(spec);
+        }
+        let (input, lengths) = (u(5), u(5), u(5), u(5)).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                bit_rate_scale: bit_rate_scale as u8,
+                cpb_size_scale: cpb_size_scale as u8,
+                cpb_specs,
+                initial_cpb_removal_delay_length: lengths.0 as u8 + 1,
+                cpb_removal_delay_length: lengths.1 as u8 + 1,
+                dpb_output_delay_length: lengths.2 as u8 + 1,
+                time_offset_length: lengths.3 as u8,
+            },
+        ))
+    }
+
+    fn write_bits(&self, writer: &mut BitWriter) {
+        writer.ue(self.cpb_specs.len() as u32 - 1);
+        writer.u(4, self.bit_rate_scale as u32);
+        writer.u(4, self.cpb_size_scale as u32);
+        for (bit_rate_value_minus1, cpb_size_value_minus1, cbr) in self.cpb_specs.iter() {
+            writer.ue(*bit_rate_value_minus1);
+            writer.ue(*cpb_size_value_minus1);
+            writer.flag(*cbr);
+        }
+        writer.u(5, self.initial_cpb_removal_delay_length as u32 - 1);
+        writer.u(5, self.cpb_removal_delay_length as u32 - 1);
+        writer.u(5, self.dpb_output_delay_length as u32 - 1);
+        writer.u(5, self.time_offset_length as u32);
+    }
+
+    /// Bit rate of every CPB in bits/s
+    pub fn bit_rates(&self) -> Vec<u64> {
+        self.cpb_specs
+            .iter()
+            .map(|(value, _, _)| (*value as u64 + 1) << (6 + self.bit_rate_scale))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColourDescription {
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+}
+
+impl ColourDescription {
+    pub fn primaries_name(&self) -> &'static str {
+        match self.colour_primaries {
+            1 => "BT.709",
+            4 => "BT.470 M",
+            5 => "BT.601 625",
+            6 | 7 => "BT.601 525",
+            9 => "BT.2020",
+            _ => "unknown",
+        }
+    }
+
+    pub fn transfer_name(&self) -> &'static str {
+        match self.transfer_characteristics {
+            1 | 6 | 14 | 15 => "SDR",
+            16 => "PQ",
+            18 => "HLG",
+            _ => "unknown",
+        }
+    }
+
+    pub fn matrix_name(&self) -> &'static str {
+        match self.matrix_coefficients {
+            0 => "GBR",
+            1 => "BT.709",
+            5 | 6 => "BT.601",
+            9 => "BT.2020 NCL",
+            10 => "BT.2020 CL",
+            _ => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoSignalType {
+    /// 0: component, 1: PAL, 2: NTSC, 3: SECAM, 4: MAC, 5: unspecified
+    pub video_format: u8,
+    pub full_range: bool,
+    pub colour_description: Option<ColourDescription>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingInfo {
+    pub num_units_in_tick: u32,
+    pub time_scale: u32,
+    pub fixed_frame_rate: bool,
+}
+
+impl TimingInfo {
+    /// Frames per second as (numerator, denominator); a tick is a field
+    pub fn frame_rate(&self) -> Option<(u64, u64)> {
+        match self.num_units_in_tick {
+            0 => None,
+            units => Some((self.time_scale as u64, units as u64 * 2)),
+        }
+    }
+}
+
+/// bitstream_restriction of the VUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitstreamRestriction {
+    pub motion_vectors_over_pic_boundaries: bool,
+    pub max_bytes_per_pic_denom: u32,
+    pub max_bits_per_mb_denom: u32,
+    pub log2_max_mv_length_horizontal: u32,
+    pub log2_max_mv_length_vertical: u32,
+    pub max_num_reorder_frames: u32,
+    pub max_dec_frame_buffering: u32,
+}
+
+/// vui_parameters() (E.1.1)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VUI {
+    pub aspect_ratio_idc: Option<u8>,
+    /// sar_width and sar_height, with aspect_ratio_idc Extended_SAR
+    pub extended_sar: Option<(u16, u16)>,
+    pub overscan_appropriate: Option<bool>,
+    pub video_signal_type: Option<VideoSignalType>,
+    /// chroma_sample_loc_type_top_field and _bottom_field
+    pub chroma_sample_loc_type: Option<(u32, u32)>,
+    pub timing: Option<TimingInfo>,
+    pub nal_hrd_parameters: Option<HRDParameters>,
+    pub vcl_hrd_parameters: Option<HRDParameters>,
+    /// Only present with HRD parameters
+    pub low_delay_hrd: Option<bool>,
+    pub pic_struct_present: bool,
+    pub bitstream_restriction: Option<BitstreamRestriction>,
+}
+
+/// Table E-1
+const SAMPLE_ASPECT_RATIOS: [(u16, u16); 16] = [
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+pub const EXTENDED_SAR: u8 = 255;
+
+impl VUI {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, aspect_ratio_info_present) = flag(input)?;
+        let (input, aspect_ratio_idc) =
+            combinator::cond(aspect_ratio_info_present, u(8)).parse_next(input)?;
+        let (input, extended_sar) = combinator::cond(
+            aspect_ratio_idc == Some(EXTENDED_SAR as u32),
+            (u(16), u(16)).map(|(width, height)| (width as u16, height as u16)),
+        )
+        .parse_next(input)?;
+        let (input, overscan_info_present) = flag(input)?;
+        let (input, overscan_appropriate) =
+            combinator::cond(overscan_info_present, flag).parse_next(input)?;
+        let (input, video_signal_type_present) = flag(input)?;
+        let (input, video_signal_type) = combinator::cond(video_signal_type_present, |input| {
+            let (input, (video_format, full_range, colour_description_present)) =
+                (u(3), flag, flag).parse_next(input)?;
+            let (input, colour_description) = combinator::cond(
+                colour_description_present,
+                (u(8), u(8), u(8)).map(|(primaries, transfer, matrix)| ColourDescription {
+                    colour_primaries: primaries as u8,
+                    transfer_characteristics: transfer as u8,
+                    matrix_coefficients: matrix as u8,
+                }),
+            )
+            .parse_next(input)?;
+            Ok((
+                input,
+                VideoSignalType {
+                    video_format: video_format as u8,
+                    full_range,
+                    colour_description,
+                },
+            ))
+        })
+        .parse_next(input)?;
+        let (input, chroma_loc_info_present) = flag(input)?;
+        let (input, chroma_sample_loc_type) =
+            combinator::cond(chroma_loc_info_present, (ue, ue)).parse_next(input)?;
+        let (input, timing_info_present) = flag(input)?;
+        let (input, timing) = combinator::cond(
+            timing_info_present,
+            (u(32), u(32), flag).map(|(num_units_in_tick, time_scale, fixed_frame_rate)| {
+                TimingInfo {
+                    num_units_in_tick,
+                    time_scale,
+                    fixed_frame_rate,
+                }
+            }),
+        )
+        .parse_next(input)?;
+        let (input, nal_hrd_present) = flag(input)?;
+        let (input, nal_hrd_parameters) =
+            combinator::cond(nal_hrd_present, HRDParameters::parse_bits).parse_next(input)?;
+        let (input, vcl_hrd_present) = flag(input)?;
+        let (input, vcl_hrd_parameters) =
+            combinator::cond(vcl_hrd_present, HRDParameters::parse_bits).parse_next(input)?;
+        let (input, low_delay_hrd) =
+            combinator::cond(nal_hrd_present || vcl_hrd_present, flag).parse_next(input)?;
+        let (input, (pic_struct_present, bitstream_restriction_present)) =
+            (flag, flag).parse_next(input)?;
+        let (input, bitstream_restriction) = combinator::cond(
+            bitstream_restriction_present,
+            (flag, ue, ue, ue, ue, ue, ue).map(|fields| BitstreamRestriction {
+                motion_vectors_over_pic_boundaries: fields.0,
+                max_bytes_per_pic_denom: fields.1,
+                max_bits_per_mb_denom: fields.2,
+                log2_max_mv_length_horizontal: fields.3,
+                log2_max_mv_length_vertical: fields.4,
+                max_num_reorder_frames: fields.5,
+                max_dec_frame_buffering: fields.6,
+            }),
+        )
+        .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                aspect_ratio_idc: aspect_ratio_idc.map(|idc| idc as u8),
+                extended_sar,
+                overscan_appropriate,
+                video_signal_type,
+                chroma_sample_loc_type,
+                timing,
+                nal_hrd_parameters,
+                vcl_hrd_parameters,
+                low_delay_hrd,
+                pic_struct_present,
+                bitstream_restriction,
+            },
+        ))
+    }
+
+    fn write_bits(&self, writer: &mut BitWriter) {
+        writer.flag(self.aspect_ratio_idc.is_some());
+        if let Some(aspect_ratio_idc) = self.aspect_ratio_idc {
+            writer.u(8, aspect_ratio_idc as u32);
+            if aspect_ratio_idc == EXTENDED_SAR {
+                let (sar_width, sar_height) = self.extended_sar.unwrap_or((0, 0));
+                writer.u(16, sar_width as u32);
+                writer.u(16, sar_height as u32);
+            }
+        }
+        writer.flag(self.overscan_appropriate.is_some());
+        if let Some(overscan_appropriate) = self.overscan_appropriate {
+            writer.flag(overscan_appropriate);
+        }
+        writer.flag(self.video_signal_type.is_some());
+        if let Some(video_signal_type) = self.video_signal_type {
+            writer.u(3, video_signal_type.video_format as u32);
+            writer.flag(video_signal_type.full_range);
+            writer.flag(video_signal_type.colour_description.is_some());
+            if let Some(colour_description) = video_signal_type.colour_description {
+                writer.u(8, colour_description.colour_primaries as u32);
+                writer.u(8, colour_description.transfer_characteristics as u32);
+                writer.u(8, colour_description.matrix_coefficients as u32);
+            }
+        }
+        writer.flag(self.chroma_sample_loc_type.is_some());
+        if let Some((top_field, bottom_field)) = self.chroma_sample_loc_type {
+            writer.ue(top_field);
+            writer.ue(bottom_field);
+        }
+        writer.flag(self.timing.is_some());
+        if let Some(timing) = self.timing {
+            writer.u(32, timing.num_units_in_tick);
+            writer.u(32, timing.time_scale);
+            writer.flag(timing.fixed_frame_rate);
+        }
+        for hrd_parameters in [&self.nal_hrd_parameters, &self.vcl_hrd_parameters] {
+            writer.flag(hrd_parameters.is_some());
+            if let Some(hrd_parameters) = hrd_parameters {
+                hrd_parameters.write_bits(writer);
+            }
+        }
+        if self.nal_hrd_parameters.is_some() || self.vcl_hrd_parameters.is_some() {
+            writer.flag(self.low_delay_hrd.unwrap_or(false));
+        }
+        writer.flag(self.pic_struct_present);
+        writer.flag(self.bitstream_restriction.is_some());
+        if let Some(restriction) = self.bitstream_restriction {
+            writer.flag(restriction.motion_vectors_over_pic_boundaries);
+            writer.ue(restriction.max_bytes_per_pic_denom);
+            writer.ue(restriction.max_bits_per_mb_denom);
+            writer.ue(restriction.log2_max_mv_length_horizontal);
+            writer.ue(restriction.log2_max_mv_length_vertical);
+            writer.ue(restriction.max_num_reorder_frames);
+            writer.ue(restriction.max_dec_frame_buffering);
+        }
+    }
+
+    /// Sample aspect ratio as (width, height)
+    pub fn sample_aspect_ratio(&self) -> Option<(u16, u16)> {
+        match self.aspect_ratio_idc? {
+            EXTENDED_SAR => self.extended_sar,
+            idc @ 1..=16 => Some(SAMPLE_ASPECT_RATIOS[idc as usize - 1]),
+            _ => None,
+        }
+    }
+
+    pub fn colour_description(&self) -> Option<&ColourDescription> {
+        self.video_signal_type
+            .as_ref()
+            .and_then(|v| v.colour_description.as_ref())
+    }
+}
+
+/// pic_order_cnt_type and the fields that come with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PicOrderCntType {
+    Type0 {
+        log2_max_pic_order_cnt_lsb: u32,
+    },
+    Type1 {
+        delta_pic_order_always_zero: bool,
+        offset_for_non_ref_pic: i32,
+        offset_for_top_to_bottom_field: i32,
+        offset_for_ref_frame: Vec<i32>,
+    },
+    Type2,
+}
+
+/// Frame cropping offsets, in crop units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCropping {
+    pub left_offset: u32,
+    pub right_offset: u32,
+    pub top_offset: u32,
+    pub bottom_offset: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SPS {
+    pub profile_idc: u8,
+    /// constraint_set0_flag to constraint_set5_flag from the most significant bit on,
+    /// followed by reserved_zero_2bits
+    pub constraint_flags: u8,
+    pub level_idc: u8,
+    pub seq_parameter_set_id: u32,
+    pub chroma_format_idc: u32,
+    pub separate_colour_plane: bool,
+    pub bit_depth_luma: u32,
+    pub bit_depth_chroma: u32,
+    pub qpprime_y_zero_transform_bypass: bool,
+    /// seq_scaling_list_present_flag and scaling_list() of every list, if
+    /// seq_scaling_matrix_present_flag is set
+    pub scaling_lists: Option<Vec<Option<ScalingList>>>,
+    pub log2_max_frame_num: u32,
+    pub pic_order_cnt_type: PicOrderCntType,
+    pub max_num_ref_frames: u32,
+    pub gaps_in_frame_num_value_allowed: bool,
+    pub pic_width_in_mbs: u32,
+    pub pic_height_in_map_units: u32,
+    pub frame_mbs_only: bool,
+    pub mb_adaptive_frame_field: bool,
+    pub direct_8x8_inference: bool,
+    pub frame_cropping: Option<FrameCropping>,
+    pub vui: Option<VUI>,
+}
+
+impl SPS {
+    pub fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (profile_idc, constraint_flags, level_idc, seq_parameter_set_id)) =
+            (u(8), u(8), u(8), ue).parse_next(input)?;
+        if seq_parameter_set_id > 31 {
+            return verify_error(input);
+        }
+        let profile_idc = profile_idc as u8;
+        let mut input = input;
+        let mut chroma_format_idc = 1;
+        let mut separate_colour_plane = false;
+        let (mut bit_depth_luma_minus8, mut bit_depth_chroma_minus8) = (0, 0);
+        let mut qpprime_y_zero_transform_bypass = false;
+        let mut scaling_lists = None;
+        if HIGH_PROFILES.contains(&profile_idc) {
+            (input, chroma_format_idc) = ue(input)?;
+            if chroma_format_idc > 3 {
+                return verify_error(input);
+            }
+            (input, separate_colour_plane) = combinator::cond(chroma_format_idc == 3, flag)
+                .map(|flag| flag.unwrap_or(false))
+                .parse_next(input)?;
+            let scaling_matrix_present;
+            (
+                input,
+                (
+                    bit_depth_luma_minus8,
+                    bit_depth_chroma_minus8,
+                    qpprime_y_zero_transform_bypass,
+                    scaling_matrix_present,
+                ),
+            ) = (ue, ue, flag, flag).parse_next(input)?;
+            if bit_depth_luma_minus8 > 6 || bit_depth_chroma_minus8 > 6 {
+                return verify_error(input);
+            }
+            if scaling_matrix_present {
+                let mut lists = Vec::new();
+                for i in 0..if chroma_format_idc != 3 { 8 } else { 12 } {
+                    let (present, list);
+                    (input, present) = flag(input)?;
+                    (input, list) = combinator::cond(present, |input| {
+                        ScalingList::parse_bits(input, if i < 6 { 16 } else { 64 })
+                    })
+                    .parse_next(input)?;
+                    lists.push(list);
+                }
+                scaling_lists = Some(lists);
+            }
+        }
+        let (input, (log2_max_frame_num_minus4, pic_order_cnt_type)) =
+            (ue, ue).parse_next(input)?;
+        if log2_max_frame_num_minus4 > 12 {
+            return verify_error(input);
+        }
+        let (input, pic_order_cnt_type) = match pic_order_cnt_type {
+            0 => {
+                let (input, log2_max_pic_order_cnt_lsb_minus4) = ue(input)?;
+                if log2_max_pic_order_cnt_lsb_minus4 > 12 {
+                    return verify_error(input);
+                }
+                (
+                    input,
+                    PicOrderCntType::Type0 {
+                        log2_max_pic_order_cnt_lsb: log2_max_pic_order_cnt_lsb_minus4 + 4,
+                    },
+                )
+            }
+            1 => {
+                let (mut input, fields) = (flag, se, se, ue).parse_next(input)?;
+                let (delta_pic_order_always_zero, offset_for_non_ref_pic) = (fields.0, fields.1);
+                let (offset_for_top_to_bottom_field, num_ref_frames_in_cycle) =
+                    (fields.2, fields.3);
+                if num_ref_frames_in_cycle > 255 {
+                    return verify_error(input);
+                }
+                let mut offset_for_ref_frame = Vec::new();
+                for _ in 0..num_ref_frames_in_cycle {
+                    let offset;
+                    (input, offset) = se(input)?;
+                    offset_for_ref_frame.push(offset);
+                }
+                (
+                    input,
+                    PicOrderCntType::Type1 {
+                        delta_pic_order_always_zero,
+                        offset_for_non_ref_pic,
+                        offset_for_top_to_bottom_field,
+                        offset_for_ref_frame,
+                    },
+                )
+            }
+            2 => (input, PicOrderCntType::Type2),
+            _ => return verify_error(input),
+        };
+        let (input, (max_num_ref_frames, gaps_in_frame_num_value_allowed)) =
+            (ue, flag).parse_next(input)?;
+        let (input, (pic_width_in_mbs_minus1, pic_height_in_map_units_minus1)) =
+            (ue, ue).parse_next(input)?;
+        let (input, frame_mbs_only) = flag(input)?;
+        let (input, mb_adaptive_frame_field) =
+            combinator::cond(!frame_mbs_only, flag).parse_next(input)?;
+        let (input, (direct_8x8_inference, frame_cropping_present)) =
+            (flag, flag).parse_next(input)?;
+        let (input, frame_cropping) = combinator::cond(
+            frame_cropping_present,
+            (ue, ue, ue, ue).map(|(left, right, top, bottom)| FrameCropping {
+                left_offset: left,
+                right_offset: right,
+                top_offset: top,
+                bottom_offset: bottom,
+            }),
+        )
+        .parse_next(input)?;
+        let (input, vui_present) = flag(input)?;
+        let (input, vui) = combinator::cond(vui_present, VUI::parse_bits).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                profile_idc,
+                constraint_flags: constraint_flags as u8,
+                level_idc: level_idc as u8,
+                seq_parameter_set_id,
+                chroma_format_idc,
+                separate_colour_plane,
+                bit_depth_luma: bit_depth_luma_minus8 + 8,
+                bit_depth_chroma: bit_depth_chroma_minus8 + 8,
+                qpprime_y_zero_transform_bypass,
+                scaling_lists,
+                log2_max_frame_num: log2_max_frame_num_minus4 + 4,
+                pic_order_cnt_type,
+                max_num_ref_frames,
+                gaps_in_frame_num_value_allowed,
+                pic_width_in_mbs: pic_width_in_mbs_minus1 + 1,
+                pic_height_in_map_units: pic_height_in_map_units_minus1 + 1,
+                frame_mbs_only,
+                mb_adaptive_frame_field: mb_adaptive_frame_field.unwrap_or(false),
+                direct_8x8_inference,
+                frame_cropping,
+                vui,
+            },
+        ))
+    }
+
+    /// The inverse of `parse_bits`, without the rbsp_trailing_bits
+    pub fn write_bits(&self, writer: &mut BitWriter) {
+        writer.u(8, self.profile_idc as u32);
+        writer.u(8, self.constraint_flags as u32);
+        writer.u(8, self.level_idc as u32);
+        writer.ue(self.seq_parameter_set_id);
+        if HIGH_PROFILES.contains(&self.profile_idc) {
+            writer.ue(self.chroma_format_idc);
+            if self.chroma_format_idc == 3 {
+                writer.flag(self.separate_colour_plane);
+            }
+            writer.ue(self.bit_depth_luma - 8);
+            writer.ue(self.bit_depth_chroma - 8);
+            writer.flag(self.qpprime_y_zero_transform_bypass);
+            write_scaling_lists(writer, self.scaling_lists.as_deref());
+        }
+        writer.ue(self.log2_max_frame_num - 4);
+        match &self.pic_order_cnt_type {
+            PicOrderCntType::Type0 {
+                log2_max_pic_order_cnt_lsb,
+            } => {
+                writer.ue(0);
+                writer.ue(log2_max_pic_order_cnt_lsb - 4);
+            }
+            PicOrderCntType::Type1 {
+                delta_pic_order_always_zero,
+                offset_for_non_ref_pic,
+                offset_for_top_to_bottom_field,
+                offset_for_ref_frame,
+            } => {
+                writer.ue(1);
+                writer.flag(*delta_pic_order_always_zero);
+                writer.se(*offset_for_non_ref_pic);
+                writer.se(*offset_for_top_to_bottom_field);
+                writer.ue(offset_for_ref_frame.len() as u32);
+                for offset in offset_for_ref_frame.iter() {
+                    writer.se(*offset);
+                }
+            }
+            PicOrderCntType::Type2 => writer.ue(2),
+        }
+        writer.ue(self.max_num_ref_frames);
+        writer.flag(self.gaps_in_frame_num_value_allowed);
+        writer.ue(self.pic_width_in_mbs - 1);
+        writer.ue(self.pic_height_in_map_units - 1);
+        writer.flag(self.frame_mbs_only);
+        if !self.frame_mbs_only {
+            writer.flag(self.mb_adaptive_frame_field);
+        }
+        writer.flag(self.direct_8x8_inference);
+        writer.flag(self.frame_cropping.is_some());
+        if let Some(cropping) = self.frame_cropping {
+            writer.ue(cropping.left_offset);
+            writer.ue(cropping.right_offset);
+            writer.ue(cropping.top_offset);
+            writer.ue(cropping.bottom_offset);
+        }
+        writer.flag(self.vui.is_some());
+        if let Some(vui) = &self.vui {
+            vui.write_bits(writer);
+        }
+    }
+
+    /// seq_parameter_set_rbsp(), as after the NAL unit header
+    pub fn to_rbsp(&self) -> Vec<u8> {
+        let mut writer = BitWriter::new();
+        self.write_bits(&mut writer);
+        writer.rbsp_trailing_bits();
+        writer.into_bytes()
+    }
+
+    /// constraint_set<n>_flag
+    pub fn constraint_set(&self, n: u8) -> bool {
+        n < 6 && self.constraint_flags & (0x80 >> n) != 0
+    }
+
+    pub fn profile_name(&self) -> &'static str {
+        match self.profile_idc {
+            66 if self.constraint_set(1) => "Constrained Baseline",
+            66 => "Baseline",
+            77 => "Main",
+            88 => "Extended",
+            100 => "High",
+            110 => "High 10",
+            122 => "High 4:2:2",
+            244 => "High 4:4:4 Predictive",
+            44 => "CAVLC 4:4:4 Intra",
+            83 => "Scalable Baseline",
+            86 => "Scalable High",
+            118 => "Multiview High",
+            128 => "Stereo High",
+            _ => "unknown",
+        }
+    }
+
+    /// The level number; level 1b is level_idc 9, or 11 with constraint_set3_flag in
+    /// the Baseline, Main and Extended profiles
+    pub fn level(&self) -> f32 {
+        match (self.profile_idc, self.level_idc) {
+            (66 | 77 | 88, 11) if self.constraint_set(3) => 1.05,
+            (_, 9) => 1.05,
+            (_, level_idc) => level_idc as f32 / 10.0,
+        }
+    }
+
+    /// ChromaArrayType
+    pub fn chroma_array_type(&self) -> u32 {
+        match self.separate_colour_plane {
+            true => 0,
+            false => self.chroma_format_idc,
+        }
+    }
+
+    pub fn chroma_format_name(&self) -> &'static str {
+        match self.chroma_format_idc {
+            0 => "4:0:0",
+            1 => "4:2:0",
+            2 => "4:2:2",
+            _ => "4:4:4",
+        }
+    }
+
+    /// FrameHeightInMbs
+    pub fn frame_height_in_mbs(&self) -> u32 {
+        (2 - self.frame_mbs_only as u32).saturating_mul(self.pic_height_in_map_units)
+    }
+
+    /// CropUnitX and CropUnitY
+    fn crop_units(&self) -> (u32, u32) {
+        let fields = 2 - self.frame_mbs_only as u32;
+        match self.chroma_array_type() {
+            1 => (2, 2 * fields),
+            2 => (2, fields),
+            _ => (1, fields),
+        }
+    }
+
+    /// Width after frame cropping
+    pub fn width(&self) -> u32 {
+        let (crop_unit_x, _) = self.crop_units();
+        let crop = self.frame_cropping.map_or(0, |c| {
+            c.left_offset
+                .saturating_add(c.right_offset)
+                .saturating_mul(crop_unit_x)
+        });
+        self.pic_width_in_mbs
+            .saturating_mul(16)
+            .saturating_sub(crop)
+    }
+
+    /// Height after frame cropping
+    pub fn height(&self) -> u32 {
+        let (_, crop_unit_y) = self.crop_units();
+        let crop = self.frame_cropping.map_or(0, |c| {
+            c.top_offset
+                .saturating_add(c.bottom_offset)
+                .saturating_mul(crop_unit_y)
+        });
+        self.frame_height_in_mbs()
+            .saturating_mul(16)
+            .saturating_sub(crop)
+    }
+
+    pub fn frame_rate(&self) -> Option<(u64, u64)> {
+        self.vui.as_ref()?.timing?.frame_rate()
+    }
+
+    /// MaxDpbFrames: the frames that fit in the MaxDpbMbs of the level (Table A-1)
+    pub fn max_dpb_frames(&self) -> u32 {
+        let max_dpb_mbs = match self.level() {
+            l if l <= 1.05 => 396,
+            l if l <= 1.1 => 900,
+            l if l <= 2.0 => 2376,
+            l if l <= 2.1 => 4752,
+            l if l <= 3.0 => 8100,
+            l if l <= 3.1 => 18000,
+            l if l <= 3.2 => 20480,
+            l if l <= 4.1 => 32768,
+            l if l <= 4.2 => 34816,
+            l if l <= 5.0 => 110400,
+            l if l <= 5.2 => 184320,
+            _ => 696320,
+        };
+        let frame_mbs = self
+            .pic_width_in_mbs
+            .saturating_mul(self.frame_height_in_mbs())
+            .max(1);
+        (max_dpb_mbs / frame_mbs).min(16)
+    }
+
+    /// max_num_reorder_frames of the VUI, or MaxDpbFrames when it is absent (E.2.1)
+    pub fn max_num_reorder_frames(&self) -> u32 {
+        self.vui
+            .as_ref()
+            .and_then(|vui| vui.bitstream_restriction)
+            .map_or(self.max_dpb_frames(), |r| r.max_num_reorder_frames)
+    }
+
+    pub fn sample_aspect_ratio(&self) -> Option<(u16, u16)> {
+        self.vui.as_ref()?.sample_aspect_ratio()
+    }
+
+    pub fn colour_description(&self) -> Option<&ColourDescription> {
+        self.vui.as_ref()?.colour_description()
+    }
+}
@@ -0,0 +1,194 @@
+// Picture order count (ITU-T H.264 8.2.1): the display order of the pictures of a coded
+// video sequence, from the slice headers of successive pictures
+use crate::nalunits::slice::SliceHeader;
+use crate::nalunits::sps::{PicOrderCntType, SPS};
+
+/// TopFieldOrderCnt and BottomFieldOrderCnt; a field only has its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PicOrderCnt {
+    pub top: Option<i32>,
+    pub bottom: Option<i32>,
+}
+
+impl PicOrderCnt {
+    /// PicOrderCnt(): the smaller of the two for frames
+    pub fn pic_order_cnt(&self) -> i32 {
+        match (self.top, self.bottom) {
+            (Some(top), Some(bottom)) => top.min(bottom),
+            (top, bottom) => top.or(bottom).unwrap_or(0),
+        }
+    }
+}
+
+/// TopFieldOrderCnt and BottomFieldOrderCnt while they are being derived, which keeps
+/// broken streams from overflowing
+type FieldOrderCnts = (Option<i64>, Option<i64>);
+
+/// Keeps what the derivation of the next picture needs of the previous ones
+#[derive(Debug, Default)]
+pub struct PicOrderCntDecoder {
+    /// prevPicOrderCntMsb and prevPicOrderCntLsb, of the previous reference picture
+    prev_pic_order_cnt_msb: i64,
+    prev_pic_order_cnt_lsb: i64,
+    /// prevFrameNumOffset and prevFrameNum, of the previous picture
+    prev_frame_num_offset: i64,
+    prev_frame_num: u32,
+}
+
+impl PicOrderCntDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The POC of the picture of `header`, whose SPS is `sps`; None if it doesn't fit in
+    /// an i32
+    pub fn decode(
+        &mut self,
+        header: &SliceHeader,
+        nal_ref_idc: u8,
+        sps: &SPS,
+    ) -> Option<PicOrderCnt> {
+        let mmco5 = header
+            .dec_ref_pic_marking
+            .as_ref()
+            .is_some_and(|m| m.has_mmco5());
+        let (mut top, mut bottom) = match &sps.pic_order_cnt_type {
+            PicOrderCntType::Type0 {
+                log2_max_pic_order_cnt_lsb,
+            } => self.decode_type0(header, nal_ref_idc, *log2_max_pic_order_cnt_lsb),
+            PicOrderCntType::Type1 { .. } | PicOrderCntType::Type2 => {
+                self.decode_frame_num_based(header, nal_ref_idc, sps)
+            }
+        };
+        if mmco5 {
+            // the picture becomes the first of a new sequence (8.2.1)
+            let temp = match (top, bottom) {
+                (Some(top), Some(bottom)) => top.min(bottom),
+                (top, bottom) => top.or(bottom).unwrap_or(0),
+            };
+            top = top.map(|top| top - temp);
+            bottom = bottom.map(|bottom| bottom - temp);
+            if nal_ref_idc != 0 {
+                self.prev_pic_order_cnt_msb = 0;
+                self.prev_pic_order_cnt_lsb = match header.bottom_field {
+                    false => top.unwrap_or(0),
+                    true => 0,
+                };
+            }
+            self.prev_frame_num_offset = 0;
+            self.prev_frame_num = 0;
+        } else {
+            self.prev_frame_num = header.frame_num;
+        }
+        Some(PicOrderCnt {
+            top: top.map(i32::try_from).transpose().ok()?,
+            bottom: bottom.map(i32::try_from).transpose().ok()?,
+        })
+    }
+
+    /// 8.2.1.1
+    fn decode_type0(
+        &mut self,
+        header: &SliceHeader,
+        nal_ref_idc: u8,
+        log2_max_pic_order_cnt_lsb: u32,
+    ) -> FieldOrderCnts {
+        if header.idr_pic_id.is_some() {
+            self.prev_pic_order_cnt_msb = 0;
+            self.prev_pic_order_cnt_lsb = 0;
+        }
+        let max_lsb = 1_i64 << log2_max_pic_order_cnt_lsb;
+        let lsb = header.pic_order_cnt_lsb.unwrap_or(0) as i64;
+        let (prev_msb, prev_lsb) = (self.prev_pic_order_cnt_msb, self.prev_pic_order_cnt_lsb);
+        let msb = if lsb < prev_lsb && prev_lsb - lsb >= max_lsb / 2 {
+            prev_msb + max_lsb
+        } else if lsb > prev_lsb && lsb - prev_lsb > max_lsb / 2 {
+            prev_msb - max_lsb
+        } else {
+            prev_msb
+        };
+        if nal_ref_idc != 0 {
+            self.prev_pic_order_cnt_msb = msb;
+            self.prev_pic_order_cnt_lsb = lsb;
+        }
+        let delta_bottom = header.delta_pic_order_cnt_bottom as i64;
+        match (header.field_pic, header.bottom_field) {
+            (false, _) => (Some(msb + lsb), Some(msb + lsb + delta_bottom)),
+            (true, false) => (Some(msb + lsb), None),
+            (true, true) => (None, Some(msb + lsb)),
+        }
+    }
+
+    /// 8.2.1.2 and 8.2.1.3
+    fn decode_frame_num_based(
+        &mut self,
+        header: &SliceHeader,
+        nal_ref_idc: u8,
+        sps: &SPS,
+    ) -> FieldOrderCnts {
+        let frame_num = header.frame_num as i64;
+        let frame_num_offset = if header.idr_pic_id.is_some() {
+            0
+        } else if self.prev_frame_num as i64 > frame_num {
+            self.prev_frame_num_offset + (1_i64 << sps.log2_max_frame_num)
+        } else {
+            self.prev_frame_num_offset
+        };
+        self.prev_frame_num_offset = frame_num_offset;
+        match &sps.pic_order_cnt_type {
+            PicOrderCntType::Type1 {
+                offset_for_non_ref_pic,
+                offset_for_top_to_bottom_field,
+                offset_for_ref_frame,
+                ..
+            } => {
+                let cycle = offset_for_ref_frame.len() as i64;
+                let mut abs_frame_num = match cycle {
+                    0 => 0,
+                    _ => frame_num_offset + frame_num,
+                };
+                if nal_ref_idc == 0 && abs_frame_num > 0 {
+                    abs_frame_num -= 1;
+                }
+                let mut expected = 0_i64;
+                if abs_frame_num > 0 {
+                    let cycle_cnt = (abs_frame_num - 1) / cycle;
+                    let frame_num_in_cycle = (abs_frame_num - 1) % cycle;
+                    let delta_per_cycle: i64 = offset_for_ref_frame.iter().map(|&o| o as i64).sum();
+                    // saturating, the result is out of range anyway then
+                    expected = cycle_cnt.saturating_mul(delta_per_cycle).saturating_add(
+                        offset_for_ref_frame[..=frame_num_in_cycle as usize]
+                            .iter()
+                            .map(|&o| o as i64)
+                            .sum::<i64>(),
+                    );
+                }
+                if nal_ref_idc == 0 {
+                    expected = expected.saturating_add(*offset_for_non_ref_pic as i64);
+                }
+                let [delta0, delta1] = header.delta_pic_order_cnt.map(|d| d as i64);
+                let to_bottom = *offset_for_top_to_bottom_field as i64;
+                let top = expected.saturating_add(delta0);
+                match (header.field_pic, header.bottom_field) {
+                    (false, _) => (Some(top), Some(top.saturating_add(to_bottom + delta1))),
+                    (true, false) => (Some(top), None),
+                    (true, true) => (None, Some(expected.saturating_add(to_bottom + delta0))),
+                }
+            }
+            _ => {
+                let temp = if header.idr_pic_id.is_some() {
+                    0
+                } else if nal_ref_idc == 0 {
+                    2 * (frame_num_offset + frame_num) - 1
+                } else {
+                    2 * (frame_num_offset + frame_num)
+                };
+                match (header.field_pic, header.bottom_field) {
+                    (false, _) => (Some(temp), Some(temp)),
+                    (true, false) => (Some(temp), None),
+                    (true, true) => (None, Some(temp)),
+                }
+            }
+        }
+    }
+}
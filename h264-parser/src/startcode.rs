@@ -0,0 +1,153 @@
+// Splitting a byte stream on 0x000001 start codes; shared by every codec that uses
+// them (H.264 Annex B, MPEG-2 video, ...)
+use super::stream::{partialstream, PartialStream};
+use std::io::Read;
+
+use circular::Buffer;
+use winnow::{combinator, error, stream::Offset, token, IResult, Parser};
+
+pub const START_CODE_PREFIX: &[u8] = b"\x00\x00\x01";
+
+/// We will read the input in chunks of this size
+const CHUNK_SIZE: usize = 10 * 1024;
+
+/// Skips to the next start code, and takes everything up to the start code after that.
+///
+/// The returned data starts right after the start code prefix. It may end in zero bytes
+/// that are stuffing or the first byte of a 4 byte start code, rather than part of the
+/// unit; whether a unit can end in a zero byte depends on the codec.
+pub fn parse_start_code_unit(input: PartialStream<'_>) -> IResult<PartialStream<'_>, &[u8]> {
+    let (input, _) =
+        (token::take_until0(START_CODE_PREFIX), START_CODE_PREFIX).parse_next(input)?;
+    let (input, data) = combinator::alt((token::take_until0(START_CODE_PREFIX), combinator::rest))
+        .parse_next(input)?;
+    Ok((input, data))
+}
+
+/// The length of `data` without the zero bytes at its end
+fn trimmed_len(data: &[u8]) -> usize {
+    data.iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |p| p + 1)
+}
+
+fn contains_start_code(data: &[u8]) -> bool {
+    data.windows(START_CODE_PREFIX.len())
+        .any(|bytes| bytes == START_CODE_PREFIX)
+}
+
+pub struct StartCodeUnit {
+    /// Offset in the input of the first byte after the start code prefix
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+/// Iterates over the units between start codes in a `Read`
+pub struct StartCodeIterator<R: Read> {
+    input_reader: R,
+    buffer: Buffer,
+    has_reached_eof: bool,
+    consumed: u64,
+    skipped_bytes: u64,
+    /// Zero bytes at the end of the skipped bytes, which count only if more of them follow
+    skipped_zero_bytes: u64,
+}
+
+impl<R: Read> StartCodeIterator<R> {
+    pub fn new(input_reader: R) -> Self {
+        Self {
+            input_reader,
+            buffer: Buffer::with_capacity(CHUNK_SIZE),
+            has_reached_eof: false,
+            consumed: 0,
+            skipped_bytes: 0,
+            skipped_zero_bytes: 0,
+        }
+    }
+
+    /// Number of bytes before the first start code, not counting the zero bytes right
+    /// before it (or all bytes of an input without start codes)
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Counts the first `size` bytes of the buffer as skipped
+    fn skip(&mut self, size: usize) {
+        let len = trimmed_len(&self.buffer.data()[..size]) as u64;
+        if len > 0 {
+            self.skipped_bytes += self.skipped_zero_bytes + len;
+            self.skipped_zero_bytes = size as u64 - len;
+        } else {
+            self.skipped_zero_bytes += size as u64;
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.input_reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.input_reader
+    }
+}
+
+impl<R: Read> Iterator for StartCodeIterator<R> {
+    type Item = StartCodeUnit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.has_reached_eof && self.buffer.available_data() == 0 {
+                return None;
+            }
+            let input = partialstream(self.buffer.data(), self.has_reached_eof);
+            match parse_start_code_unit(input) {
+                Ok((remainder, data)) => {
+                    let start = self.buffer.data().offset_to(data);
+                    let unit = StartCodeUnit {
+                        offset: self.consumed + start as u64,
+                        data: data.to_vec(),
+                    };
+                    let consumed = input.offset_to(&remainder);
+                    self.skip(start - 3);
+                    self.skipped_zero_bytes = 0;
+                    self.buffer.consume(consumed);
+                    self.consumed += consumed as u64;
+                    return Some(unit);
+                }
+                Err(error::ErrMode::Incomplete(_)) if !self.has_reached_eof => {
+                    // without a start code, all but the bytes that may begin one are
+                    // skipped, rather than kept until one comes
+                    let available = self.buffer.available_data();
+                    if available > CHUNK_SIZE && !contains_start_code(self.buffer.data()) {
+                        let size = available - (START_CODE_PREFIX.len() - 1);
+                        self.skip(size);
+                        self.buffer.consume(size);
+                        self.consumed += size as u64;
+                    }
+                    if self.buffer.position() + self.buffer.available_space() >= CHUNK_SIZE {
+                        self.buffer.shift();
+                    } else {
+                        self.buffer.grow(self.buffer.capacity() + CHUNK_SIZE);
+                    }
+                    match self.input_reader.read(self.buffer.space()) {
+                        Ok(read) => {
+                            self.buffer.fill(read);
+                            if read == 0 {
+                                self.has_reached_eof = true;
+                            }
+                        }
+                        Err(e) => panic!("error: {}", e),
+                    }
+                }
+                Err(_) => {
+                    // no start code in the rest of the input
+                    let remaining = self.buffer.available_data();
+                    self.skip(remaining);
+                    self.skipped_zero_bytes = 0;
+                    self.buffer.consume(remaining);
+                    self.consumed += remaining as u64;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Writing NAL units back out: as an Annex B byte stream with start codes, or with
+// length prefixes as in MP4/Matroska samples
+use crate::access_unit::AccessUnit;
+use crate::nalunits::{KnownNALUnit, NALUnit, AUDNU, PPSNU, SPSNU};
+use crate::startcode::START_CODE_PREFIX;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// 3 byte start codes, with the zero_byte before parameter sets, access unit
+    /// delimiters and the first NAL unit of an access unit (B.1.2)
+    AnnexB,
+    /// 4 byte start codes before every NAL unit
+    AnnexBLong,
+    /// Big endian length of this many bytes (1, 2 or 4) before every NAL unit
+    LengthPrefix(usize),
+}
+
+pub struct NALUnitWriter<W: Write> {
+    writer: W,
+    framing: Framing,
+}
+
+impl<W: Write> NALUnitWriter<W> {
+    pub fn new(writer: W, framing: Framing) -> Self {
+        Self { writer, framing }
+    }
+
+    pub fn write(&mut self, nal_unit: &NALUnit) -> io::Result<()> {
+        self.write_bytes(&nal_unit.to_bytes(), false)
+    }
+
+    pub fn write_access_unit(&mut self, access_unit: &AccessUnit) -> io::Result<()> {
+        for (i, nal_unit) in access_unit.nal_units.iter().enumerate() {
+            self.write_bytes(&nal_unit.to_bytes(), i == 0)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a NAL unit as `NALUnit::to_bytes` returns it: the header byte and the
+    /// payload with emulation prevention bytes
+    pub fn write_bytes(&mut self, data: &[u8], first_in_access_unit: bool) -> io::Result<()> {
+        let nal_unit_type = data.first().map(|header| header & 0x1f);
+        let zero_byte = first_in_access_unit
+            || matches!(
+                nal_unit_type,
+                Some(SPSNU::NU_TYPE | PPSNU::NU_TYPE | AUDNU::NU_TYPE)
+            );
+        match self.framing {
+            Framing::AnnexB | Framing::AnnexBLong => {
+                if zero_byte || self.framing == Framing::AnnexBLong {
+                    self.writer.write_all(&[0])?;
+                }
+                self.writer.write_all(START_CODE_PREFIX)?;
+            }
+            Framing::LengthPrefix(size) => {
+                let fits = matches!(size, 1 | 2 | 4) && (data.len() as u64) >> (size * 8) == 0;
+                if !fits {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "NAL unit of {} bytes with a {} byte length",
+                            data.len(),
+                            size
+                        ),
+                    ));
+                }
+                self.writer
+                    .write_all(&(data.len() as u32).to_be_bytes()[4 - size..])?;
+            }
+        }
+        self.writer.write_all(data)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
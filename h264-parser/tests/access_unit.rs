@@ -0,0 +1,146 @@
+use h264_parser::access_unit::{primary_pic_type_of, AccessUnit, AccessUnitIterator};
+use h264_parser::nalunits::slice::SliceType;
+use std::io::Cursor;
+
+/// primary_pic_type 0 and 1
+const AUD_I: &[u8] = &[0x09, 0x10];
+const AUD_P: &[u8] = &[0x09, 0x30];
+/// Main profile 1920x1088 with POC type 0, without a VUI
+const SPS: &[u8] = &[
+    0x67, 0x4d, 0x00, 0x28, 0xec, 0xa0, 0x3c, 0x01, 0x13, 0xf2, 0xa0,
+];
+const PPS: &[u8] = &[0x68, 0xeb, 0x83, 0xcb, 0x20];
+const PPS_1: &[u8] = &[0x68, 0x5a, 0xe0, 0xf2, 0xc8];
+/// A recovery point
+const SEI: &[u8] = &[0x06, 0x06, 0x02, 0x24, 0x40, 0x80];
+/// The slices of an IDR picture, from macroblock 0 and 40
+const IDR: &[u8] = &[0x65, 0x88, 0x80, 0x80, 0x02, 0xd3, 0xad, 0x2d];
+const IDR_40: &[u8] = &[0x65, 0x05, 0x22, 0x20, 0x20, 0x00, 0xb4, 0xeb, 0x4b, 0x40];
+/// frame_num 1, POC LSB 6, from macroblock 0 and 30
+const P: &[u8] = &[0x61, 0x9a, 0x23, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+const P_30: &[u8] = &[
+    0x61, 0x0f, 0x9a, 0x23, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68,
+];
+/// Non-reference pictures with frame_num 2: a B and an I slice with POC LSB 2, and a B
+/// slice with POC LSB 4
+const B_POC_2: &[u8] = &[0x01, 0x9e, 0x41, 0x6a, 0x22, 0xd3, 0xad, 0x2d];
+const I_50_POC_2: &[u8] = &[0x01, 0x06, 0x62, 0x24, 0x11, 0x69, 0xd6, 0x96, 0x80];
+const B_POC_4: &[u8] = &[0x01, 0x9e, 0x42, 0x6a, 0x22, 0xd3, 0xad, 0x2d];
+/// frame_num 3 from macroblock 0, then frame_num 4 from macroblock 30 with PPS 0 and 1
+const P_FRAME_3: &[u8] = &[0x61, 0x9a, 0x64, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+const P_30_FRAME_4: &[u8] = &[
+    0x61, 0x0f, 0x9a, 0x85, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68,
+];
+const P_30_FRAME_4_PPS_1: &[u8] = &[
+    0x61, 0x0f, 0x99, 0x21, 0x55, 0xd9, 0x88, 0x45, 0xa7, 0x5a, 0x5a,
+];
+
+/// An Annex B byte stream with three byte start codes
+fn byte_stream(nal_units: &[&[u8]]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for nal_unit in nal_units {
+        data.extend([0, 0, 1]);
+        data.extend(*nal_unit);
+    }
+    data
+}
+
+fn access_units(data: Vec<u8>) -> Vec<AccessUnit> {
+    AccessUnitIterator::new(Box::new(Cursor::new(data))).collect()
+}
+
+fn nal_unit_types(access_unit: &AccessUnit) -> Vec<u8> {
+    access_unit
+        .nal_units
+        .iter()
+        .map(|nal_unit| nal_unit.nal_unit_type())
+        .collect()
+}
+
+#[test]
+fn access_unit_boundaries() {
+    let data = byte_stream(&[
+        AUD_I,
+        SPS,
+        PPS,
+        SEI,
+        IDR,
+        IDR_40,
+        // the AUD starts an access unit
+        AUD_P,
+        P,
+        P_30,
+        // nal_ref_idc is 0 for both, and they differ in POC
+        B_POC_2,
+        I_50_POC_2,
+        B_POC_4,
+        // a new frame_num, then pic_parameter_set_id
+        P_FRAME_3,
+        P_30_FRAME_4,
+        PPS_1,
+        P_30_FRAME_4_PPS_1,
+        // the parameter sets go with the IDR picture after them
+        SPS,
+        PPS,
+        IDR,
+    ]);
+    let access_units = access_units(data.clone());
+    let types: Vec<Vec<u8>> = access_units.iter().map(nal_unit_types).collect();
+    assert_eq!(
+        types,
+        [
+            vec![9, 7, 8, 6, 5, 5],
+            vec![9, 1, 1],
+            vec![1, 1],
+            vec![1],
+            vec![1],
+            vec![1],
+            vec![8, 1],
+            vec![7, 8, 5],
+        ]
+    );
+    let idr: Vec<bool> = access_units.iter().map(|au| au.idr).collect();
+    assert_eq!(idr, [true, false, false, false, false, false, false, true]);
+    // the sizes include the start codes and add up to the stream
+    let mut offset = 0;
+    for access_unit in &access_units {
+        assert_eq!(access_unit.offset, offset);
+        offset += access_unit.size as u64;
+    }
+    assert_eq!(offset, data.len() as u64);
+    assert_eq!(
+        access_units[1].size,
+        3 * 3 + AUD_P.len() + P.len() + P_30.len()
+    );
+}
+
+#[test]
+fn primary_pic_types() {
+    let data = byte_stream(&[
+        AUD_I, SPS, PPS, IDR, IDR_40, AUD_P, P, B_POC_2, I_50_POC_2, P_FRAME_3,
+    ]);
+    let access_units = access_units(data);
+    let types: Vec<Option<u8>> = access_units.iter().map(|au| au.primary_pic_type).collect();
+    // from the delimiters, then from the slice types
+    assert_eq!(types, [Some(0), Some(1), Some(2), Some(1)]);
+    assert_eq!(access_units[2].slice_types(), [SliceType::B, SliceType::I]);
+    assert_eq!(
+        access_units[0]
+            .first_slice_header()
+            .unwrap()
+            .first_mb_in_slice,
+        0
+    );
+    assert_eq!(access_units[0].slice_headers().count(), 2);
+    // without an SPS and PPS the slice headers can't be read
+    let access_units = self::access_units(byte_stream(&[P, P_30]));
+    assert_eq!(access_units.len(), 1);
+    assert_eq!(access_units[0].primary_pic_type, None);
+
+    assert_eq!(primary_pic_type_of(&[SliceType::I]), Some(0));
+    assert_eq!(primary_pic_type_of(&[SliceType::P, SliceType::I]), Some(1));
+    assert_eq!(primary_pic_type_of(&[SliceType::SI]), Some(3));
+    assert_eq!(primary_pic_type_of(&[SliceType::SP, SliceType::I]), Some(6));
+    assert_eq!(primary_pic_type_of(&[SliceType::B, SliceType::SI]), Some(7));
+    assert_eq!(primary_pic_type_of(&[]), None);
+}
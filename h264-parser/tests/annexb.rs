@@ -0,0 +1,123 @@
+use h264_parser::annexb::ByteStreamProblem;
+use h264_parser::nalunits::NALUnit;
+use h264_parser::NALUnitIterator;
+use std::io::{Cursor, Read};
+
+/// The NAL units of `data` as bytes, and the problems of the stream
+fn split(data: &[u8]) -> (Vec<Vec<u8>>, Vec<ByteStreamProblem>) {
+    let mut nal_units = NALUnitIterator::new(Box::new(Cursor::new(data.to_vec())));
+    let bytes = nal_units
+        .by_ref()
+        .map(|nal_unit| nal_unit.to_bytes())
+        .collect();
+    (bytes, nal_units.problems().to_vec())
+}
+
+#[test]
+fn forbidden_zero_bit() {
+    // an IDR slice and an SEI with the forbidden_zero_bit set, each before an AUD
+    for header in [0xe5, 0x86] {
+        let data = [0, 0, 0, 1, header, 0x88, 0x80, 0, 0, 0, 1, 0x09, 0x10];
+        let mut nal_units = NALUnitIterator::new(Box::new(Cursor::new(data.to_vec())));
+        let nal_unit = nal_units.next().unwrap();
+        let NALUnit::Unknown(nu) = &nal_unit else {
+            panic!("{:x} not kept as an unknown NAL unit", header);
+        };
+        assert_eq!(nu.nal_unit_type, header & 0x1f);
+        assert_eq!(nal_unit.to_bytes(), [header, 0x88, 0x80]);
+        assert_eq!(nal_units.next().unwrap().nal_unit_type(), 9);
+        assert!(nal_units.next().is_none());
+        assert_eq!(nal_units.problems(), []);
+    }
+    // and as the last NAL unit, cut off
+    let (nal_units, problems) = split(b"\x00\x00\x00\x01\xe5\x88\x80");
+    assert_eq!(nal_units, [b"\xe5\x88\x80"]);
+    assert_eq!(
+        problems,
+        [ByteStreamProblem::Truncated { offset: 4, size: 3 }]
+    );
+}
+
+#[test]
+fn empty_nal_unit() {
+    let (nal_units, problems) =
+        split(b"\x00\x00\x00\x01\x09\x10\x00\x00\x00\x01\x00\x00\x01\x09\x30");
+    assert_eq!(nal_units, [b"\x09\x10", b"\x09\x30"]);
+    assert_eq!(problems, [ByteStreamProblem::EmptyNALUnit { offset: 6 }]);
+}
+
+#[test]
+fn leading_garbage() {
+    let (nal_units, problems) = split(b"\xff\xee\x00\x00\x01\x09\x10");
+    assert_eq!(nal_units, [b"\x09\x10"]);
+    assert_eq!(problems, [ByteStreamProblem::LeadingGarbage { size: 2 }]);
+    // leading_zero_8bits are not
+    let (nal_units, problems) = split(b"\x00\x00\x00\x00\x01\x09\x10");
+    assert_eq!(nal_units, [b"\x09\x10"]);
+    assert_eq!(problems, []);
+}
+
+#[test]
+fn stray_bytes() {
+    let (nal_units, problems) =
+        split(b"\x00\x00\x01\x09\x10\x00\x00\x00\xab\xcd\x00\x00\x01\x09\x30");
+    assert_eq!(nal_units, [b"\x09\x10", b"\x09\x30"]);
+    assert_eq!(
+        problems,
+        [ByteStreamProblem::StrayBytes { offset: 8, size: 2 }]
+    );
+}
+
+#[test]
+fn truncated_last_nal_unit() {
+    // an SPS cut off after profile_idc
+    let (nal_units, problems) = split(b"\x00\x00\x00\x01\x09\x10\x00\x00\x00\x01\x67\x64");
+    assert_eq!(nal_units, [&b"\x09\x10"[..], b"\x67\x64"]);
+    assert_eq!(
+        problems,
+        [ByteStreamProblem::Truncated {
+            offset: 10,
+            size: 2
+        }]
+    );
+    // the same SPS is not cut off when something follows it
+    let (_, problems) = split(b"\x00\x00\x00\x01\x67\x64\x00\x00\x00\x01\x09\x10");
+    assert_eq!(problems, []);
+}
+
+#[test]
+fn nal_unit_offsets() {
+    let data = b"\xff\x00\x00\x00\x01\x09\x10\x00\x00\x01\x09\x30\x00\x00\x00\x00\x01\x09\x50";
+    let mut nal_units = NALUnitIterator::new(Box::new(Cursor::new(data.to_vec())));
+    assert_eq!(nal_units.offset(), None);
+    let mut offsets = Vec::new();
+    while nal_units.next().is_some() {
+        offsets.push(nal_units.offset().unwrap());
+    }
+    assert_eq!(offsets, [5, 10, 17]);
+}
+
+#[test]
+fn long_leading_garbage() {
+    // more than a read chunk before the first start code, with zero bytes across chunks
+    let mut data = vec![0xff; 25_000];
+    data.extend([0; 10_000]);
+    data.extend([0xee; 3]);
+    data.extend([0; 5_000]);
+    data.extend(b"\x00\x00\x01\x09\x10");
+    let (nal_units, problems) = split(&data);
+    assert_eq!(nal_units, [b"\x09\x10"]);
+    assert_eq!(
+        problems,
+        [ByteStreamProblem::LeadingGarbage { size: 35_003 }]
+    );
+
+    // a long input without start codes is read in bounded memory
+    let input = std::io::repeat(0xff).take(8 << 20);
+    let mut nal_units = NALUnitIterator::new(Box::new(input));
+    assert!(nal_units.next().is_none());
+    assert_eq!(
+        nal_units.problems(),
+        [ByteStreamProblem::LeadingGarbage { size: 8 << 20 }]
+    );
+}
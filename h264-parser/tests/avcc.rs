@@ -0,0 +1,188 @@
+use h264_parser::access_unit::AccessUnitIterator;
+use h264_parser::avcc::{
+    split_length_prefixed, AVCCSample, AVCCSampleIterator, AVCDecoderConfigurationRecord,
+    AnnexBWriter,
+};
+use h264_parser::nalunits::{NALUnit, SPSNU};
+use h264_parser::stream::stream;
+use h264_parser::NALUnitIterator;
+use std::io::Cursor;
+use std::path::Path;
+
+/// primary_pic_type 0 and 1
+const AUD_I: &[u8] = &[0x09, 0x10];
+const AUD_P: &[u8] = &[0x09, 0x30];
+/// Main profile 1920x1088 with POC type 0, without a VUI
+const SPS: &[u8] = &[
+    0x67, 0x4d, 0x00, 0x28, 0xec, 0xa0, 0x3c, 0x01, 0x13, 0xf2, 0xa0,
+];
+const PPS: &[u8] = &[0x68, 0xeb, 0x83, 0xcb, 0x20];
+/// A recovery point
+const SEI: &[u8] = &[0x06, 0x06, 0x02, 0x24, 0x40, 0x80];
+/// The slices of an IDR picture, from macroblock 0 and 40
+const IDR: &[u8] = &[0x65, 0x88, 0x80, 0x80, 0x02, 0xd3, 0xad, 0x2d];
+const IDR_40: &[u8] = &[0x65, 0x05, 0x22, 0x20, 0x20, 0x00, 0xb4, 0xeb, 0x4b, 0x40];
+/// frame_num 1, POC LSB 6
+const P: &[u8] = &[0x61, 0x9a, 0x23, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+
+/// An Annex B byte stream of access units, with a four byte start code before the
+/// first NAL unit of each and before parameter sets, as `AnnexBWriter` writes them
+fn byte_stream(access_units: &[&[&[u8]]]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for nal_units in access_units {
+        for (i, nal_unit) in nal_units.iter().enumerate() {
+            if i == 0 || [SPS, PPS].contains(nal_unit) {
+                data.push(0);
+            }
+            data.extend([0, 0, 1]);
+            data.extend(*nal_unit);
+        }
+    }
+    data
+}
+
+fn avcc_samples(data: Vec<u8>, length_size: usize, in_band: bool) -> Vec<AVCCSample> {
+    let access_units = AccessUnitIterator::new(Box::new(Cursor::new(data)));
+    AVCCSampleIterator::new(access_units, length_size, in_band)
+        .map(Result::unwrap)
+        .collect()
+}
+
+fn annex_b(samples: &[AVCCSample], length_size: usize, parameter_sets: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut writer = AnnexBWriter::new(Vec::new(), length_size, parameter_sets);
+    for sample in samples {
+        writer.write_sample(&sample.data).unwrap();
+    }
+    writer.into_inner()
+}
+
+fn parameter_sets() -> Vec<NALUnit> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/parameter_sets.264");
+    let data = std::fs::read(path).unwrap();
+    NALUnitIterator::new(Box::new(Cursor::new(data))).collect()
+}
+
+#[test]
+fn record_reads_back() {
+    let nal_units = parameter_sets();
+    for length_size in [1, 2, 4] {
+        let record =
+            AVCDecoderConfigurationRecord::from_nal_units(&nal_units, length_size).unwrap();
+        assert_eq!(record.sequence_parameter_sets.len(), 6);
+        assert_eq!(record.picture_parameter_sets.len(), 7);
+        let data = record.to_bytes().unwrap();
+        let (rest, parsed) = AVCDecoderConfigurationRecord::parse(stream(&data)).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed, record);
+    }
+}
+
+#[test]
+fn length_size_other_than_1_2_or_4() {
+    let nal_units = parameter_sets();
+    for length_size in [0, 3, 5, 8] {
+        assert_eq!(
+            AVCDecoderConfigurationRecord::from_nal_units(&nal_units, length_size),
+            None
+        );
+    }
+    let mut record = AVCDecoderConfigurationRecord::from_nal_units(&nal_units, 4).unwrap();
+    record.length_size = 3;
+    assert_eq!(record.to_bytes(), None);
+    record.length_size = 0;
+    assert_eq!(record.to_bytes(), None);
+}
+
+#[test]
+fn more_parameter_sets_than_the_record_counts() {
+    // an SPS for every seq_parameter_set_id, one more than numOfSequenceParameterSets holds
+    let NALUnit::SPS(nu) = &parameter_sets()[0] else {
+        panic!("SPS first");
+    };
+    let nal_units: Vec<NALUnit> = (0..32)
+        .map(|id| {
+            let mut sps = (*nu.sps).clone();
+            sps.seq_parameter_set_id = id;
+            NALUnit::SPS(SPSNU::new(3, sps))
+        })
+        .collect();
+    assert_eq!(
+        AVCDecoderConfigurationRecord::from_nal_units(&nal_units, 4),
+        None
+    );
+    let record = AVCDecoderConfigurationRecord::from_nal_units(&nal_units[..31], 4).unwrap();
+    assert_eq!(record.to_bytes().unwrap()[5], 0b1111_1111);
+    let mut record = record;
+    record.sequence_parameter_sets.push(Vec::new());
+    assert_eq!(record.to_bytes(), None);
+    record.sequence_parameter_sets.truncate(1);
+    record.picture_parameter_sets = vec![Vec::new(); 256];
+    assert_eq!(record.to_bytes(), None);
+}
+
+#[test]
+fn annex_b_round_trip() {
+    let data = byte_stream(&[
+        &[AUD_I, SPS, PPS, SEI, IDR, IDR_40],
+        &[AUD_P, P],
+        &[AUD_I, SPS, PPS, IDR],
+    ]);
+    for length_size in [1, 2, 4] {
+        // the delimiters and parameter sets are gone from the samples
+        let samples = avcc_samples(data.clone(), length_size, false);
+        let units: Vec<Vec<&[u8]>> = samples
+            .iter()
+            .map(|sample| split_length_prefixed(&sample.data, length_size).unwrap())
+            .collect();
+        assert_eq!(units, [vec![SEI, IDR, IDR_40], vec![P], vec![IDR]]);
+        let idr: Vec<bool> = samples.iter().map(|sample| sample.idr).collect();
+        assert_eq!(idr, [true, false, true]);
+
+        // and the SPS and PPS come back before every IDR picture
+        let parameter_sets = vec![SPS.to_vec(), PPS.to_vec()];
+        assert_eq!(
+            annex_b(&samples, length_size, parameter_sets),
+            byte_stream(&[&[SPS, PPS, SEI, IDR, IDR_40], &[P], &[SPS, PPS, IDR]])
+        );
+    }
+
+    // in band parameter sets stay, and aren't inserted twice
+    let samples = avcc_samples(data, 4, true);
+    assert_eq!(
+        split_length_prefixed(&samples[2].data, 4).unwrap(),
+        [SPS, PPS, IDR]
+    );
+    assert_eq!(
+        annex_b(&samples, 4, vec![SPS.to_vec(), PPS.to_vec()]),
+        byte_stream(&[&[SPS, PPS, SEI, IDR, IDR_40], &[P], &[SPS, PPS, IDR]])
+    );
+}
+
+#[test]
+fn parameter_sets_after_the_delimiter() {
+    let mut sample = Vec::new();
+    for unit in [AUD_I, IDR] {
+        sample.extend((unit.len() as u16).to_be_bytes());
+        sample.extend(unit);
+    }
+    let mut writer = AnnexBWriter::new(Vec::new(), 2, vec![SPS.to_vec(), PPS.to_vec()]);
+    writer.write_sample(&sample).unwrap();
+    assert_eq!(writer.into_inner(), byte_stream(&[&[AUD_I, SPS, PPS, IDR]]));
+}
+
+#[test]
+fn split_with_length_size_other_than_1_2_or_4() {
+    let sample = [0, 0, 0, 2, 0x09, 0x10];
+    assert_eq!(split_length_prefixed(&sample, 4), Some(vec![AUD_I]));
+    for length_size in [0, 3, 5, 8] {
+        assert_eq!(split_length_prefixed(&sample, length_size), None);
+    }
+    // a length past the end
+    assert_eq!(split_length_prefixed(&sample[..5], 4), None);
+}
+
+#[test]
+#[should_panic(expected = "NAL unit length of 0 bytes")]
+fn annex_b_writer_with_length_size_0() {
+    AnnexBWriter::new(Vec::new(), 0, Vec::new());
+}
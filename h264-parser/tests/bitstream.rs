@@ -0,0 +1,114 @@
+use h264_parser::bitstream::{
+    bits_consumed, bits_remaining, byte_aligned, flag, more_rbsp_data, parse_rbsp,
+    rbsp_trailing_bits, se, skip_bits, te, u, ue, BitInput, BitWriter,
+};
+use h264_parser::stream::stream;
+use winnow::combinator::repeat;
+use winnow::Parser;
+
+fn input(data: &[u8]) -> BitInput<'_> {
+    (stream(data), 0)
+}
+
+#[test]
+fn exp_golomb() {
+    // 1 010 011 00100 0001000
+    let values: Vec<u32> = parse_rbsp(&[0xa6, 0x41, 0x00], repeat(5, ue)).unwrap();
+    assert_eq!(values, [0, 1, 2, 3, 7]);
+    // codes 0 to 4 are 0, 1, -1, 2, -2
+    let values: Vec<i32> = parse_rbsp(&[0xa6, 0x42, 0x80], repeat(5, se)).unwrap();
+    assert_eq!(values, [0, 1, -1, 2, -2]);
+}
+
+#[test]
+fn longest_exp_golomb_codes() {
+    // 31 leading zeros and 31 bits of suffix: 2^32 - 2
+    let data = [0x00, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xfe];
+    assert_eq!(parse_rbsp(&data, ue), Ok(u32::MAX - 1));
+    assert_eq!(parse_rbsp(&data, se), Ok(-i32::MAX));
+    let data = [0x00, 0x00, 0x00, 0x01, 0xff, 0xff, 0xff, 0xfc];
+    assert_eq!(parse_rbsp(&data, se), Ok(i32::MAX));
+    // a 32nd leading zero doesn't fit, except for se(v) of i32::MIN
+    let data = [0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00];
+    assert!(parse_rbsp(&data, ue).is_err());
+    // codes 2^32 - 1 and 2^32 + 1 are 2^31 and 2^31 + 1
+    assert!(parse_rbsp(&data, se).is_err());
+    let data = [0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x01, 0x00];
+    assert!(parse_rbsp(&data, se).is_err());
+    let data = [0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x80];
+    assert_eq!(parse_rbsp(&data, se), Ok(i32::MIN));
+    let data = [0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00];
+    assert!(parse_rbsp(&data, se).is_err());
+}
+
+#[test]
+fn written_exp_golomb_codes_read_back() {
+    let signed = [0, 1, -1, 1000, -1000, i32::MAX, -i32::MAX, i32::MIN];
+    let unsigned = [0, 1, 2, 1000, u32::MAX - 1];
+    let mut writer = BitWriter::new();
+    for value in signed {
+        writer.se(value);
+    }
+    for value in unsigned {
+        writer.ue(value);
+    }
+    writer.rbsp_trailing_bits();
+    let data = writer.into_bytes();
+    let (read_signed, read_unsigned): (Vec<i32>, Vec<u32>) = parse_rbsp(
+        &data,
+        (repeat(signed.len(), se), repeat(unsigned.len(), ue)),
+    )
+    .unwrap();
+    assert_eq!(read_signed, signed);
+    assert_eq!(read_unsigned, unsigned);
+}
+
+#[test]
+fn reading_past_the_end() {
+    assert!(parse_rbsp(&[0x00], ue).is_err());
+    // the suffix is cut off
+    assert!(parse_rbsp(&[0x00, 0x01], ue).is_err());
+    assert!(parse_rbsp(&[], flag).is_err());
+    assert!(parse_rbsp(&[0xff], u(9)).is_err());
+    assert!(skip_bits(input(&[0xff; 4]), 33).is_err());
+}
+
+#[test]
+fn fixed_length_fields() {
+    let data = [0xab, 0xcd, 0xef, 0x12, 0x34, 0x56];
+    let (rest, (high, low, word)) = (u(4), u(4), u(32)).parse_next(input(&data)).unwrap();
+    assert_eq!((high, low, word), (0xa, 0xb, 0xcdef_1234));
+    assert!(byte_aligned(&rest));
+    assert_eq!(bits_remaining(&rest), 8);
+    let (rest, _) = skip_bits(input(&data), 41).unwrap();
+    assert_eq!(bits_consumed(&input(&data), &rest), 41);
+    assert_eq!((bits_remaining(&rest), byte_aligned(&rest)), (7, false));
+    assert_eq!(u(7)(rest).unwrap().1, 0x56);
+    // te(v) with a range of 1 is the inverted bit, otherwise ue(v)
+    assert_eq!(parse_rbsp(&[0x80], te(1)), Ok(0));
+    assert_eq!(parse_rbsp(&[0x00], te(1)), Ok(1));
+    assert_eq!(parse_rbsp(&[0x60], te(2)), Ok(2));
+}
+
+#[test]
+fn rbsp_data_and_trailing_bits() {
+    // one bit of data before the stop bit
+    let data = [0b1100_0000];
+    assert!(more_rbsp_data(&input(&data)));
+    let (rest, _) = flag(input(&data)).unwrap();
+    assert!(!more_rbsp_data(&rest));
+    assert!(rbsp_trailing_bits(rest).unwrap().0 .0.is_empty());
+    // the stop bit can be in an earlier byte than cabac_zero_words
+    let data = [0x01, 0x80, 0x00, 0x00];
+    let (rest, _) = u(7)(input(&data)).unwrap();
+    assert!(more_rbsp_data(&rest));
+    let (rest, _) = u(1)(rest).unwrap();
+    assert!(!more_rbsp_data(&rest));
+    assert!(!more_rbsp_data(&input(&[0x00, 0x00])));
+    assert!(!more_rbsp_data(&input(&[])));
+    // the stop bit must be followed by zero bits up to the byte boundary
+    let (rest, _) = u(3)(input(&[0b1111_0000])).unwrap();
+    assert!(rbsp_trailing_bits(rest).is_ok());
+    assert!(rbsp_trailing_bits(input(&[0x81])).is_err());
+    assert!(rbsp_trailing_bits(input(&[0x00])).is_err());
+}
@@ -0,0 +1,30 @@
+use h264_parser::gop::{GOPPicture, GOPStart, GOP};
+use h264_parser::nalunits::slice::SliceType;
+
+fn picture(slice_type: Option<SliceType>, pic_order_cnt: Option<i32>) -> GOPPicture {
+    GOPPicture {
+        slice_type,
+        reference: slice_type != Some(SliceType::B),
+        pic_order_cnt,
+        fields: 2,
+        size: 0,
+    }
+}
+
+#[test]
+fn pictures_without_poc_keep_their_place() {
+    let gop = GOP {
+        offset: 0,
+        start: GOPStart::IDR,
+        closed: true,
+        pictures: vec![
+            picture(Some(SliceType::I), Some(0)),
+            picture(Some(SliceType::P), Some(6)),
+            picture(None, None),
+            picture(Some(SliceType::B), Some(2)),
+            picture(Some(SliceType::B), Some(4)),
+        ],
+    };
+    assert_eq!(gop.decode_pattern(), "IP?BB");
+    assert_eq!(gop.display_pattern(), "IB?BP");
+}
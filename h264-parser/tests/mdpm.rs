@@ -0,0 +1,259 @@
+use h264_parser::mdpm::{
+    CSVWriter, CameraMetadataIterator, ClipMetadata, GOPMetadata, GPXWriter, Gain, RecordingTime,
+    MDPM, MDPM_UUID,
+};
+use h264_parser::nalunits::pps::ParameterSets;
+use h264_parser::nalunits::{parse_nal_unit_data, NALUnit};
+
+/// Main profile 1920x1088 without a VUI
+const SPS: &[u8] = &[
+    0x67, 0x4d, 0x00, 0x28, 0xec, 0xa0, 0x3c, 0x01, 0x13, 0xf2, 0xa0,
+];
+const PPS: &[u8] = &[0x68, 0xeb, 0x83, 0xcb, 0x20];
+const IDR_SLICE: &[u8] = &[0x65, 0x88, 0x80, 0x80, 0x02, 0xd3, 0xad, 0x2d];
+const P_SLICE: &[u8] = &[
+    0x61, 0x9a, 0x21, 0x57, 0x66, 0x25, 0x4d, 0x45, 0xa7, 0x5a, 0x5a,
+];
+/// The second slice of the P picture, from macroblock 60
+const SECOND_P_SLICE: &[u8] = &[
+    0x61, 0x07, 0xa6, 0x88, 0x55, 0xd9, 0x89, 0x53, 0x51, 0x69, 0xd6, 0x96, 0x80,
+];
+
+fn record(tag: u8, value: [u8; 4]) -> (u8, [u8; 4]) {
+    (tag, value)
+}
+
+fn rational(tag: u8, numerator: u32, denominator: u32) -> [(u8, [u8; 4]); 2] {
+    [
+        record(tag, numerator.to_be_bytes()),
+        record(tag + 1, denominator.to_be_bytes()),
+    ]
+}
+
+/// The records of a Sony camcorder at 2014-05-30 12:34:`second`, in UTC-5 with
+/// daylight saving time, with a GPS fix in Paris if `gps`
+fn records(second: u8, gps: bool) -> Vec<(u8, [u8; 4])> {
+    let mut records = vec![
+        record(0x18, [0x6a, 0x20, 0x14, 0x05]),
+        record(0x19, [0x30, 0x12, 0x34, second]),
+        // gain 9 dB, shutter speed priority, daylight, manual focus at 0.4 m
+        record(0x70, [0xff, 0x24, 0x60, 0x91]),
+    ];
+    records.extend(rational(0xa0, 1, 60));
+    records.extend(rational(0xa2, 28, 10));
+    records.extend(rational(0xa4, -1_i32 as u32, 3));
+    if gps {
+        records.push(record(0xb1, *b"N\0\0\0"));
+        records.extend(rational(0xb2, 48, 1));
+        records.extend(rational(0xb4, 51, 1));
+        records.extend(rational(0xb6, 2400, 100));
+        records.push(record(0xb8, *b"W\0\0\0"));
+        records.extend(rational(0xb9, 2, 1));
+        records.extend(rational(0xbb, 21, 1));
+        records.extend(rational(0xbd, 0, 1));
+        records.push(record(0xbf, [0; 4]));
+        records.extend(rational(0xc0, 355, 10));
+    }
+    records.push(record(0xe0, [0x01, 0x08, 0x00, 0x00]));
+    records.push(record(0xe4, *b"HDR-"));
+    records.push(record(0xe5, *b"CX7,"));
+    records.push(record(0xe6, *b"\"E\"\0"));
+    records
+}
+
+fn payload(records: &[(u8, [u8; 4])]) -> Vec<u8> {
+    let mut data = b"MDPM".to_vec();
+    data.push(records.len() as u8);
+    for (tag, value) in records {
+        data.push(*tag);
+        data.extend(value);
+    }
+    data
+}
+
+/// A user_data_unregistered SEI NAL unit with the MDPM message
+fn sei(records: &[(u8, [u8; 4])]) -> Vec<u8> {
+    let mut rbsp = vec![5];
+    let mut size = MDPM_UUID.len() + payload(records).len();
+    while size >= 255 {
+        rbsp.push(0xff);
+        size -= 255;
+    }
+    rbsp.push(size as u8);
+    rbsp.extend(MDPM_UUID);
+    rbsp.extend(payload(records));
+    rbsp.push(0x80);
+    // emulation prevention
+    let mut data = vec![0x06];
+    for byte in rbsp {
+        if byte <= 3 && data.ends_with(&[0, 0]) {
+            data.push(3);
+        }
+        data.push(byte);
+    }
+    data
+}
+
+#[test]
+fn camera_metadata() {
+    let mdpm = MDPM::from_payload(&payload(&records(0x56, true))).unwrap();
+    assert_eq!(mdpm.records.len(), 30);
+    let metadata = mdpm.metadata();
+    let recorded = metadata.recorded.unwrap();
+    assert_eq!(
+        recorded,
+        RecordingTime {
+            year: 2014,
+            month: 5,
+            day: 30,
+            hour: 12,
+            minute: 34,
+            second: 56,
+            utc_offset: Some(-300),
+            daylight_saving: true,
+        }
+    );
+    assert_eq!(recorded.to_string(), "2014-05-30T12:34:56-05:00");
+    assert_eq!(metadata.exposure_time, Some((1, 60)));
+    assert_eq!(metadata.f_number, Some(2.8));
+    assert_eq!(metadata.exposure_compensation, Some(-1.0 / 3.0));
+    assert_eq!(metadata.gain, Some(Gain::Decibels(9)));
+    assert_eq!(
+        metadata.exposure_program_name(),
+        Some("shutter speed priority AE")
+    );
+    assert_eq!(metadata.white_balance_name(), Some("daylight"));
+    let focus = metadata.focus.unwrap();
+    assert!(focus.manual);
+    assert_eq!(focus.distance, 0.4);
+    let position = metadata.position.unwrap();
+    assert!((position.latitude - (48.0 + 51.0 / 60.0 + 24.0 / 3600.0)).abs() < 1e-9);
+    assert!((position.longitude + 2.35).abs() < 1e-9);
+    assert_eq!(position.altitude, Some(35.5));
+    assert_eq!(metadata.make_name(), Some("Sony"));
+    assert_eq!(metadata.model.as_deref(), Some("HDR-CX7,\"E\""));
+}
+
+#[test]
+fn missing_and_invalid_fields() {
+    // unknown time zone, a bad BCD digit and a GPS fix without its last record
+    let mut records = vec![
+        record(0x18, [0xff, 0x20, 0x14, 0x05]),
+        record(0x19, [0x30, 0x12, 0x34, 0x56]),
+        record(0x70, [0xff, 0xf0, 0xff, 0xff]),
+    ];
+    records.push(record(0xb1, *b"S\0\0\0"));
+    records.extend(rational(0xb2, 48, 1));
+    records.extend(rational(0xb4, 51, 1));
+    records.extend(rational(0xb6, 2400, 100));
+    records.push(record(0xb8, *b"E\0\0\0"));
+    records.extend(rational(0xb9, 2, 1));
+    records.extend(rational(0xbb, 21, 1));
+    records.push(record(0xbd, [0; 4]));
+    let metadata = MDPM::from_payload(&payload(&records)).unwrap().metadata();
+    let recorded = metadata.recorded.unwrap();
+    assert_eq!(
+        (recorded.utc_offset, recorded.daylight_saving),
+        (None, false)
+    );
+    assert_eq!(recorded.to_string(), "2014-05-30T12:34:56");
+    assert_eq!(
+        (metadata.gain, metadata.exposure_program),
+        (Some(Gain::Auto), None)
+    );
+    assert_eq!((metadata.white_balance, metadata.focus), (None, None));
+    assert_eq!(metadata.position, None);
+    assert_eq!((metadata.make, metadata.model), (None, None));
+    records[1] = record(0x19, [0x30, 0x1a, 0x34, 0x56]);
+    assert_eq!(
+        MDPM::from_payload(&payload(&records))
+            .unwrap()
+            .metadata()
+            .recorded,
+        None
+    );
+    // a count of more records than there are
+    let mut data = payload(&records[..2]);
+    data[4] = 10;
+    assert_eq!(MDPM::from_payload(&data).unwrap().records.len(), 2);
+    assert_eq!(MDPM::from_payload(b"MDPX\x00"), None);
+    assert_eq!(MDPM::from_payload(b"MDPM"), None);
+}
+
+type Summary = (usize, u64, Option<u8>, bool);
+
+#[test]
+fn gops_of_a_stream() {
+    let mut parameter_sets = ParameterSets::new();
+    let mut data = vec![SPS.to_vec(), PPS.to_vec()];
+    for (gop, second) in [0x56, 0x57, 0x58].into_iter().enumerate() {
+        data.push(sei(&records(second, gop != 1)));
+        data.push(IDR_SLICE.to_vec());
+        for _ in 0..3 {
+            data.push(P_SLICE.to_vec());
+            data.push(SECOND_P_SLICE.to_vec());
+        }
+    }
+    let nal_units: Vec<NALUnit> = data
+        .iter()
+        .map(|data| parse_nal_unit_data(data, &mut parameter_sets))
+        .collect();
+    let gops: Vec<GOPMetadata> = CameraMetadataIterator::new(nal_units.into_iter()).collect();
+    // pictures start at the slices with first_mb_in_slice 0
+    let summary: Vec<Summary> = gops
+        .iter()
+        .map(|gop| {
+            (
+                gop.gop,
+                gop.picture,
+                gop.metadata.recorded.map(|r| r.second),
+                gop.metadata.position.is_some(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (0, 0, Some(56), true),
+            (1, 4, Some(57), false),
+            (2, 8, Some(58), true)
+        ]
+    );
+    let mut clip = ClipMetadata::new();
+    for gop in &gops {
+        clip.add(gop);
+    }
+    assert_eq!((clip.gops, clip.positions), (3, 2));
+    assert_eq!(
+        clip.to_string(),
+        "3 GOPs, recorded 2014-05-30T12:34:56-05:00 to 2014-05-30T12:34:58-05:00, \
+         camera Sony HDR-CX7,\"E\", 2 GPS fixes from 48.856667,-2.350000"
+    );
+
+    let mut csv = CSVWriter::new(Vec::new()).unwrap();
+    for gop in &gops {
+        csv.write_gop(gop).unwrap();
+    }
+    let csv = String::from_utf8(csv.into_inner()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("gop,picture,recorded,"));
+    assert_eq!(
+        lines[1],
+        "0,0,2014-05-30T12:34:56-05:00,1/60,2.8,-0.33,9,shutter speed priority AE,daylight,\
+         manual,0.40,48.856667,-2.350000,35.5,Sony,\"HDR-CX7,\"\"E\"\"\""
+    );
+    assert!(lines[2].contains(",0.40,,,,Sony,"));
+
+    let mut gpx = GPXWriter::new(Vec::new(), "<clip>").unwrap();
+    for gop in &gops {
+        gpx.write_gop(gop).unwrap();
+    }
+    let gpx = String::from_utf8(gpx.finish().unwrap()).unwrap();
+    assert!(gpx.contains("<trk><name>&lt;clip&gt;</name><trkseg>"));
+    assert_eq!(gpx.matches("<trkpt ").count(), 2);
+    assert!(gpx.contains(
+        r#"<trkpt lat="48.856667" lon="-2.350000"><ele>35.5</ele><time>2014-05-30T12:34:58-05:00</time></trkpt>"#
+    ));
+    assert!(gpx.ends_with("</trkseg></trk>\n</gpx>\n"));
+}
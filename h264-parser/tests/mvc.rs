@@ -0,0 +1,261 @@
+use h264_parser::access_unit::{AccessUnit, AccessUnitIterator};
+use h264_parser::nalunits::mvc::{MVCOperationPoint, NALUnitHeaderExtension};
+use h264_parser::nalunits::pps::ParameterSets;
+use h264_parser::nalunits::slice::{RefPicListModification, SliceType};
+use h264_parser::nalunits::{parse_nal_unit_data, NALUnit};
+use std::io::Cursor;
+
+/// High profile with POC type 0, for the base view
+const SPS: &[u8] = &[
+    0x67, 0x64, 0x00, 0x28, 0xac, 0xd9, 0x40, 0x78, 0x02, 0x27, 0xe5, 0x40,
+];
+const PPS: &[u8] = &[0x68, 0xeb, 0x83, 0xcb, 0x20];
+/// Stereo High with id 1: views 0 and 1, view 1 refers to view 0, level 4.1 with one
+/// operation point, and an MVC VUI with the timing and NAL HRD of that operation point
+const SUBSET_SPS: &[u8] = &[
+    0x6f, 0x80, 0x00, 0x29, 0x4b, 0x36, 0x50, 0x1e, 0x00, 0x89, 0x95, 0x4b, 0x5c, 0xa6, 0x15, 0x2c,
+    0x54, 0x00, 0x00, 0x0f, 0xa4, 0x00, 0x02, 0xee, 0x03, 0x91, 0x80, 0x01, 0x86, 0xa0, 0x00, 0x18,
+    0x6a, 0x2f, 0x7b, 0xe0, 0x20,
+];
+/// PPS 1, of the subset SPS
+const PPS_1: &[u8] = &[0x68, 0x4a, 0xb8, 0x3c, 0xb2];
+/// MVC prefix NAL units of the base view, of an anchor IDR picture and of a P picture
+const PREFIX_IDR: &[u8] = &[0x6e, 0x00, 0x00, 0x07];
+const PREFIX: &[u8] = &[0x6e, 0x40, 0x00, 0x03];
+/// The two slices of the pictures of the base view, from macroblock 0 and 60
+const IDR: &[u8] = &[0x65, 0x88, 0x80, 0x80, 0x02, 0xd3, 0xad, 0x2d];
+const IDR_60: &[u8] = &[0x65, 0x07, 0xa2, 0x20, 0x20, 0x00, 0xb4, 0xeb, 0x4b, 0x40];
+const P: &[u8] = &[0x61, 0x9a, 0x22, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+const P_60: &[u8] = &[
+    0x61, 0x07, 0xa6, 0x88, 0x95, 0xd9, 0x88, 0x45, 0xa7, 0x5a, 0x5a,
+];
+/// Coded slice extensions of view 1
+const VIEW_1_I: &[u8] = &[
+    0x74, 0x00, 0x00, 0x47, 0x88, 0x40, 0x40, 0x07, 0xe7, 0x87, 0x80,
+];
+const VIEW_1_I_60: &[u8] = &[
+    0x74, 0x00, 0x00, 0x47, 0x07, 0xa2, 0x10, 0x10, 0x01, 0xf9, 0xe1, 0xe0,
+];
+const VIEW_1_P: &[u8] = &[
+    0x74, 0x40, 0x00, 0x43, 0x99, 0x08, 0x95, 0x2c, 0xdb, 0x22, 0xfc, 0xf0, 0xf0,
+];
+const VIEW_1_P_60: &[u8] = &[
+    0x74, 0x40, 0x00, 0x43, 0x07, 0xa6, 0x42, 0x25, 0x4b, 0x36, 0xc8, 0xbf, 0x3c, 0x3c,
+];
+/// An SVC prefix NAL unit and an enhancement layer slice with dependency_id 1
+const SVC_PREFIX: &[u8] = &[0x6e, 0xc5, 0x80, 0x07, 0x80];
+const SVC_SLICE: &[u8] = &[0x74, 0xc5, 0x90, 0x07, 0x12, 0x34, 0x80];
+/// The MVC delimiter of MPEG-2 systems, before the non-base views
+const MVCD: &[u8] = &[0x18, 0x80];
+const AUD_I: &[u8] = &[0x09, 0x10];
+const AUD_P: &[u8] = &[0x09, 0x30];
+
+fn parse(nal_units: &[&[u8]]) -> (Vec<NALUnit>, ParameterSets) {
+    let mut parameter_sets = ParameterSets::new();
+    let nal_units = nal_units
+        .iter()
+        .map(|data| parse_nal_unit_data(data, &mut parameter_sets))
+        .collect();
+    (nal_units, parameter_sets)
+}
+
+/// An Annex B byte stream with three byte start codes
+fn byte_stream(nal_units: &[&[u8]]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for nal_unit in nal_units {
+        data.extend([0, 0, 1]);
+        data.extend(*nal_unit);
+    }
+    data
+}
+
+fn access_units(nal_units: &[&[u8]]) -> Vec<AccessUnit> {
+    let data = byte_stream(nal_units);
+    AccessUnitIterator::new(Box::new(Cursor::new(data))).collect()
+}
+
+/// The view_ids of an access unit with the types of their NAL units
+fn views(access_unit: &AccessUnit) -> Vec<(u16, Vec<u8>)> {
+    access_unit
+        .views()
+        .into_iter()
+        .map(|(view_id, nal_units)| {
+            let types = nal_units.iter().map(|nu| nu.nal_unit_type()).collect();
+            (view_id, types)
+        })
+        .collect()
+}
+
+#[test]
+fn header_extensions() {
+    let (nal_units, _) = parse(&[PREFIX_IDR, PREFIX, SVC_PREFIX, SVC_SLICE]);
+    let extensions: Vec<NALUnitHeaderExtension> = nal_units
+        .iter()
+        .map(|nal_unit| *nal_unit.header_extension().unwrap())
+        .collect();
+    let [NALUnitHeaderExtension::MVC(idr), NALUnitHeaderExtension::MVC(non_idr), NALUnitHeaderExtension::SVC(base_layer), NALUnitHeaderExtension::SVC(layer)] =
+        extensions[..]
+    else {
+        panic!("{:?}", extensions);
+    };
+    assert!(!idr.non_idr && idr.anchor_pic && idr.inter_view);
+    assert_eq!((idr.view_id, idr.priority_id, idr.temporal_id), (0, 0, 0));
+    assert!(non_idr.non_idr && !non_idr.anchor_pic && non_idr.inter_view);
+    assert!(extensions[0].idr() && !extensions[1].idr());
+    assert_eq!(extensions[1].view_id(), Some(0));
+    assert_eq!(extensions[2].view_id(), None);
+    assert!(base_layer.idr && base_layer.no_inter_layer_pred && base_layer.output);
+    assert_eq!((base_layer.priority_id, base_layer.dependency_id), (5, 0));
+    assert_eq!(layer.dependency_id, 1);
+    // SVC slices are kept, but their header isn't read
+    assert!(nal_units[3].slice_header().is_none());
+    // too short for the header extension
+    let (nal_units, _) = parse(&[&[0x6e, 0x40]]);
+    assert!(matches!(nal_units[..], [NALUnit::Unknown(_)]));
+}
+
+#[test]
+fn subset_sps() {
+    let (nal_units, parameter_sets) = parse(&[SPS, SUBSET_SPS]);
+    let NALUnit::SubsetSPS(nu) = &nal_units[1] else {
+        panic!("{:?}", nal_units[1]);
+    };
+    let subset_sps = &nu.subset_sps;
+    assert_eq!(subset_sps.sps.profile_idc, 128);
+    assert_eq!(subset_sps.sps.seq_parameter_set_id, 1);
+    assert_eq!(subset_sps.views(), 2);
+    let mvc = subset_sps.mvc.as_ref().unwrap();
+    assert_eq!(mvc.view_ids, [0, 1]);
+    assert_eq!(mvc.anchor_refs, [[vec![0], vec![]]]);
+    assert_eq!(mvc.non_anchor_refs, [[vec![0], vec![]]]);
+    assert_eq!(
+        mvc.levels,
+        [(
+            41,
+            vec![MVCOperationPoint {
+                temporal_id: 0,
+                target_view_ids: vec![0, 1],
+                num_views: 2,
+            }]
+        )]
+    );
+    assert_eq!(
+        (mvc.dependencies(1), mvc.dependencies(0)),
+        (vec![0], vec![])
+    );
+    let [operation_point] = &subset_sps.mvc_vui.as_ref().unwrap()[..] else {
+        panic!("{:?}", subset_sps.mvc_vui);
+    };
+    assert_eq!(operation_point.target_output_view_ids, [1]);
+    let timing = operation_point.timing.unwrap();
+    assert_eq!((timing.num_units_in_tick, timing.time_scale), (1001, 48000));
+    assert!(operation_point.nal_hrd_parameters.is_some());
+    assert_eq!(operation_point.low_delay_hrd, Some(false));
+    // kept apart from the SPS
+    assert_eq!(parameter_sets.sps.keys().collect::<Vec<_>>(), [&0]);
+    assert_eq!(parameter_sets.subset_sps.keys().collect::<Vec<_>>(), [&1]);
+}
+
+#[test]
+fn coded_slice_extensions() {
+    let (nal_units, parameter_sets) = parse(&[SPS, PPS, SUBSET_SPS, PPS_1, VIEW_1_I, VIEW_1_P]);
+    assert!(parameter_sets.get_subset(1).is_some());
+    assert!(parameter_sets.get_subset(0).is_none());
+    let NALUnit::SliceExtension(anchor) = &nal_units[4] else {
+        panic!("{:?}", nal_units[4]);
+    };
+    assert_eq!(anchor.extension.view_id(), Some(1));
+    assert!(anchor.extension.idr());
+    let header = anchor.header.as_ref().unwrap();
+    assert_eq!(header.slice_type, SliceType::I);
+    assert_eq!(
+        (header.pic_parameter_set_id, header.idr_pic_id),
+        (1, Some(3))
+    );
+    let header = nal_units[5].slice_header().unwrap();
+    assert_eq!((header.frame_num, header.pic_order_cnt_lsb), (1, Some(4)));
+    // inter-view references are reordered by view index
+    assert_eq!(
+        header.ref_pic_list_modification_l0,
+        Some(vec![
+            RefPicListModification::SubtractViewIdx(0),
+            RefPicListModification::AddViewIdx(0),
+            RefPicListModification::SubtractPicNum(2),
+        ])
+    );
+    // without the subset SPS the header can't be read
+    let (nal_units, _) = parse(&[SPS, PPS, VIEW_1_P]);
+    let NALUnit::SliceExtension(nu) = &nal_units[2] else {
+        panic!("{:?}", nal_units[2]);
+    };
+    assert!(nu.header.is_none());
+}
+
+#[test]
+fn access_units_per_view() {
+    let access_units = access_units(&[
+        AUD_I,
+        SPS,
+        PPS,
+        PREFIX_IDR,
+        IDR,
+        PREFIX_IDR,
+        IDR_60,
+        MVCD,
+        SUBSET_SPS,
+        PPS_1,
+        VIEW_1_I,
+        VIEW_1_I_60,
+        AUD_P,
+        PREFIX,
+        P,
+        PREFIX,
+        P_60,
+        MVCD,
+        VIEW_1_P,
+        VIEW_1_P_60,
+    ]);
+    assert_eq!(access_units.len(), 2);
+    assert_eq!(
+        views(&access_units[0]),
+        [
+            (0, vec![9, 7, 8, 14, 5, 14, 5]),
+            (1, vec![24, 15, 8, 20, 20]),
+        ]
+    );
+    assert_eq!(
+        views(&access_units[1]),
+        [(0, vec![9, 14, 1, 14, 1]), (1, vec![24, 20, 20])]
+    );
+    assert!(access_units[0].idr && !access_units[1].idr);
+    // the primary coded picture is that of the base view
+    assert_eq!(access_units[0].slice_types(), [SliceType::I]);
+    assert_eq!(access_units[1].primary_slices().count(), 2);
+
+    // without delimiters, the access units start at the prefix of a new base view picture
+    let access_units = self::access_units(&[
+        SPS, PPS, SUBSET_SPS, PPS_1, PREFIX_IDR, IDR, VIEW_1_I, PREFIX, P, VIEW_1_P,
+    ]);
+    let views: Vec<_> = access_units.iter().map(views).collect();
+    assert_eq!(
+        views,
+        [
+            vec![(0, vec![7, 8, 15, 8, 14, 5]), (1, vec![20])],
+            vec![(0, vec![14, 1]), (1, vec![20])],
+        ]
+    );
+}
+
+#[test]
+fn svc_layers() {
+    // the enhancement layers belong to the access unit of the base layer, and the prefix
+    // to that of the slice after it
+    let access_units = access_units(&[
+        SPS, PPS, SVC_PREFIX, IDR, SVC_SLICE, SVC_PREFIX, IDR, SVC_SLICE,
+    ]);
+    let types: Vec<Vec<u8>> = access_units
+        .iter()
+        .map(|au| au.nal_units.iter().map(|nu| nu.nal_unit_type()).collect())
+        .collect();
+    assert_eq!(types, [vec![7, 8, 14, 5, 20], vec![14, 5, 20]]);
+}
@@ -0,0 +1,169 @@
+// SPS and PPS serialisation: tests/data/parameter_sets.264 has six SPS (High with VUI,
+// HRD and scaling lists, Baseline, Main with POC type 1, interlaced with POC type 2,
+// and two without VUI) and seven PPS (with and without the transform_8x8_mode_flag tail,
+// with scaling lists, slice groups and weighted bi-prediction)
+use h264_parser::nalunits::{
+    parse_nal_unit_data,
+    pps::{ParameterSets, SliceGroupMap, SliceGroups, PPS},
+    sps::{ColourDescription, PicOrderCntType, SPS},
+    NALUnit, PPSNU, SPSNU,
+};
+use h264_parser::NALUnitIterator;
+use std::io::Cursor;
+use std::path::Path;
+
+/// A parameter set with the RBSP it was parsed from
+type Parsed<T> = (T, Vec<u8>);
+
+fn parameter_sets() -> (Vec<Parsed<SPS>>, Vec<Parsed<PPS>>) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/parameter_sets.264");
+    let data = std::fs::read(path).unwrap();
+    let (mut sps, mut pps) = (Vec::new(), Vec::new());
+    for nal_unit in NALUnitIterator::new(Box::new(Cursor::new(data))) {
+        match nal_unit {
+            NALUnit::SPS(nu) => sps.push((*nu.sps, nu.rest)),
+            NALUnit::PPS(nu) => pps.push((*nu.pps, nu.rest)),
+            _ => panic!("not a parameter set"),
+        }
+    }
+    (sps, pps)
+}
+
+#[test]
+fn sps_to_rbsp_reproduces_the_original() {
+    let (sps, _) = parameter_sets();
+    assert_eq!(sps.len(), 6);
+    for (sps, rest) in sps.iter() {
+        assert_eq!(&sps.to_rbsp(), rest, "SPS {}", sps.seq_parameter_set_id);
+    }
+    let vui = sps[0].0.vui.as_ref().unwrap();
+    assert!(vui.nal_hrd_parameters.is_some());
+    assert!(vui.bitstream_restriction.is_some());
+    assert!(sps[0].0.scaling_lists.is_some());
+    assert!(matches!(
+        sps[2].0.pic_order_cnt_type,
+        PicOrderCntType::Type1 { .. }
+    ));
+    assert!(sps[4].0.vui.is_none());
+}
+
+#[test]
+fn pps_to_rbsp_reproduces_the_original() {
+    let (_, pps) = parameter_sets();
+    assert_eq!(pps.len(), 7);
+    for (pps, rest) in pps.iter() {
+        assert_eq!(
+            pps.to_rbsp().as_ref(),
+            Some(rest),
+            "PPS {}",
+            pps.pic_parameter_set_id
+        );
+    }
+    assert!(pps[0].0.transform_8x8_mode && pps[0].0.scaling_lists.is_some());
+    assert!(pps[1].0.second_chroma_qp_index_offset.is_none());
+    assert!(pps[2].0.second_chroma_qp_index_offset.is_some());
+}
+
+#[test]
+fn pps_fields_that_cant_be_coded() {
+    let (_, pps) = parameter_sets();
+    let mut pps = pps
+        .into_iter()
+        .find(|(pps, _)| pps.slice_groups.is_some())
+        .unwrap()
+        .0;
+    let with_map = |num_slice_groups, map| {
+        let mut pps = pps.clone();
+        pps.slice_groups = Some(SliceGroups {
+            num_slice_groups,
+            map,
+        });
+        pps.to_rbsp()
+    };
+    let evolving = |change_rate| SliceGroupMap::Evolving {
+        map_type: 4,
+        change_direction: true,
+        change_rate,
+    };
+    assert!(with_map(2, evolving(1)).is_some());
+    assert_eq!(with_map(2, evolving(0)), None);
+    assert_eq!(with_map(0, evolving(1)), None);
+    let explicit = |slice_group_id| SliceGroupMap::Explicit { slice_group_id };
+    assert!(with_map(3, explicit(vec![0, 2, 1])).is_some());
+    assert_eq!(with_map(3, explicit(Vec::new())), None);
+    let interleaved = |run_length_minus1| SliceGroupMap::Interleaved { run_length_minus1 };
+    assert!(with_map(2, interleaved(vec![3, 4])).is_some());
+    assert_eq!(with_map(3, interleaved(vec![3, 4])), None);
+    let foreground = |rectangles| SliceGroupMap::Foreground { rectangles };
+    assert!(with_map(2, foreground(vec![(0, 21)])).is_some());
+    assert_eq!(with_map(2, foreground(Vec::new())), None);
+    pps.num_ref_idx_l1_default_active = 0;
+    assert_eq!(pps.to_rbsp(), None);
+    assert!(PPSNU::new(3, pps).is_none());
+}
+
+/// Serialises `sps` into a NAL unit and reads it back
+fn reparse(sps: &SPS) -> (SPS, Vec<u8>) {
+    let data = NALUnit::SPS(SPSNU::new(3, sps.clone())).to_bytes();
+    match parse_nal_unit_data(&data, &mut ParameterSets::new()) {
+        NALUnit::SPS(nu) => (*nu.sps, data),
+        _ => panic!("SPS not parsed"),
+    }
+}
+
+#[test]
+fn patch_colour_description() {
+    let (mut sps, _) = parameter_sets().0.remove(0);
+    let video_signal_type = sps
+        .vui
+        .as_mut()
+        .unwrap()
+        .video_signal_type
+        .as_mut()
+        .unwrap();
+    video_signal_type.colour_description = Some(ColourDescription {
+        colour_primaries: 9,
+        transfer_characteristics: 16,
+        matrix_coefficients: 9,
+    });
+    assert_eq!(reparse(&sps).0, sps);
+}
+
+#[test]
+fn patch_aspect_ratio() {
+    let (mut sps, _) = parameter_sets().0.remove(0);
+    let vui = sps.vui.as_mut().unwrap();
+    assert_eq!(vui.extended_sar, Some((4, 3)));
+    vui.aspect_ratio_idc = Some(1);
+    vui.extended_sar = None;
+    let (patched, _) = reparse(&sps);
+    assert_eq!(patched, sps);
+    assert_eq!(patched.sample_aspect_ratio(), Some((1, 1)));
+}
+
+#[test]
+fn patch_level_idc() {
+    let (mut sps, rest) = parameter_sets().0.remove(0);
+    sps.level_idc = 41;
+    let (patched, data) = reparse(&sps);
+    assert_eq!(patched, sps);
+    // only the level_idc byte after the header, profile_idc and constraint flags changes
+    let mut original = vec![0x67];
+    original.extend(h264_parser::nalunits::add_emulation_prevention(&rest));
+    let changed: Vec<usize> = (0..data.len())
+        .filter(|&i| data[i] != original[i])
+        .collect();
+    assert_eq!(changed, [3]);
+}
+
+#[test]
+fn patch_max_num_reorder_frames() {
+    let (mut sps, _) = parameter_sets().0.remove(0);
+    let vui = sps.vui.as_mut().unwrap();
+    let restriction = vui.bitstream_restriction.as_mut().unwrap();
+    restriction.max_num_reorder_frames = 0;
+    restriction.max_dec_frame_buffering = 1;
+    let (patched, _) = reparse(&sps);
+    assert_eq!(patched, sps);
+    assert_eq!(patched.max_num_reorder_frames(), 0);
+}
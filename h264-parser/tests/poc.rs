@@ -0,0 +1,200 @@
+// Picture order count of hand-built slice headers, and the display order and timestamps
+// that come from it
+use h264_parser::access_unit::{AccessUnitIterator, DisplayOrderIterator};
+use h264_parser::nalunits::parse_nal_unit_data;
+use h264_parser::nalunits::pps::ParameterSets;
+use h264_parser::poc::PicOrderCntDecoder;
+use std::io::Cursor;
+
+/// High profile 1920x1088 with POC type 0, a log2_max_pic_order_cnt_lsb of 6, 29.97 frames
+/// per second and a max_num_reorder_frames of 2
+const SPS_0: &[u8] = &[
+    0x67, 0x64, 0x00, 0x28, 0xad, 0xa4, 0x92, 0x0d, 0x82, 0x11, 0x6c, 0xa0, 0x3c, 0x01, 0x13, 0xf2,
+    0xff, 0xe0, 0x00, 0x80, 0x00, 0x6d, 0x40, 0x40, 0x40, 0x50, 0x00, 0x00, 0x3e, 0x90, 0x00, 0x0e,
+    0xa6, 0x0e, 0x46, 0x00, 0x06, 0x1a, 0x80, 0x00, 0x61, 0xa8, 0xbd, 0xef, 0x83, 0xb4, 0x11, 0x08,
+    0xb2, 0xc0,
+];
+const PPS: &[u8] = &[0x68, 0xeb, 0x83, 0xcb, 0x30, 0x10, 0x80, 0x21, 0x70];
+/// Frames with their pic_order_cnt_lsb; the B frames are not reference pictures
+const IDR_0: &[u8] = &[0x65, 0x88, 0x80, 0x80, 0x02, 0xd3, 0xad, 0x2d];
+const P_6: &[u8] = &[0x61, 0x9a, 0x23, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+const B_2: &[u8] = &[0x01, 0x9e, 0x41, 0x6a, 0x22, 0xd3, 0xad, 0x2d];
+const B_4: &[u8] = &[0x01, 0x9e, 0x42, 0x6a, 0x22, 0xd3, 0xad, 0x2d];
+const P_12: &[u8] = &[0x61, 0x9a, 0x46, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+const B_8: &[u8] = &[0x01, 0x9e, 0x64, 0x6a, 0x22, 0xd3, 0xad, 0x2d];
+const B_10: &[u8] = &[0x01, 0x9e, 0x65, 0x6a, 0x22, 0xd3, 0xad, 0x2d];
+const P_40: &[u8] = &[0x61, 0x9a, 0x74, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+const P_2: &[u8] = &[0x61, 0x9a, 0x81, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+/// memory_management_control_operation 5
+const P_8_MMCO5: &[u8] = &[
+    0x61, 0x9a, 0xa4, 0x57, 0x66, 0x25, 0x4d, 0x45, 0xa7, 0x5a, 0x5a,
+];
+/// frame_num 1, pic_order_cnt_lsb 4
+const P_LSB_4: &[u8] = &[0x61, 0x9a, 0x22, 0x57, 0x66, 0x21, 0x16, 0x9d, 0x69, 0x68];
+/// The same without a VUI, with POC type 2
+const SPS_2: &[u8] = &[
+    0x67, 0x64, 0x00, 0x28, 0xac, 0xb2, 0x80, 0xf0, 0x04, 0x4f, 0xca, 0x80,
+];
+/// P frames with their frame_num
+const TYPE_2_P_0: &[u8] = &[0x61, 0x9a, 0x15, 0xd9, 0x88, 0x45, 0xa7, 0x5a, 0x5a];
+const TYPE_2_P_1: &[u8] = &[0x61, 0x9a, 0x35, 0xd9, 0x88, 0x45, 0xa7, 0x5a, 0x5a];
+const TYPE_2_P_2: &[u8] = &[0x61, 0x9a, 0x55, 0xd9, 0x88, 0x45, 0xa7, 0x5a, 0x5a];
+const TYPE_2_P_15: &[u8] = &[0x61, 0x9b, 0xf5, 0xd9, 0x88, 0x45, 0xa7, 0x5a, 0x5a];
+const TYPE_2_IDR: &[u8] = &[0x65, 0x88, 0x80, 0x80, 0xb4, 0xeb, 0x4b, 0x40];
+/// nal_ref_idc 0
+const TYPE_2_NON_REF_2: &[u8] = &[0x01, 0x9a, 0x55, 0xd9, 0x88, 0x8b, 0x4e, 0xb4, 0xb4];
+const TYPE_2_P_3_MMCO5: &[u8] = &[
+    0x61, 0x9a, 0x75, 0xd9, 0x89, 0x53, 0x51, 0x69, 0xd6, 0x96, 0x80,
+];
+/// POC type 1 with an offset_for_non_ref_pic of -2, an offset_for_top_to_bottom_field of 1
+/// and a cycle of two frames of 2
+const SPS_1: &[u8] = &[
+    0x67, 0x64, 0x00, 0x28, 0xac, 0xa1, 0x53, 0x21, 0x0a, 0x03, 0xc0, 0x11, 0x3f, 0x2a,
+];
+/// Frames with their frame_num and delta_pic_order_cnt[0]
+const TYPE_1_IDR: &[u8] = &[0x65, 0x88, 0x80, 0x88, 0x5a, 0x75, 0xa5, 0xa0];
+const TYPE_1_P_1: &[u8] = &[0x61, 0x9a, 0x22, 0x2b, 0xb3, 0x10, 0x8b, 0x4e, 0xb4, 0xb4];
+const TYPE_1_B_2: &[u8] = &[0x01, 0x9e, 0x5d, 0x44, 0x5a, 0x75, 0xa5, 0xa0];
+const TYPE_1_B_2_DELTA_2: &[u8] = &[0x01, 0x9e, 0x44, 0xd4, 0x45, 0xa7, 0x5a, 0x5a];
+const TYPE_1_P_2: &[u8] = &[0x61, 0x9a, 0x44, 0xae, 0xcc, 0x42, 0x2d, 0x3a, 0xd2, 0xd0];
+const TYPE_1_P_3: &[u8] = &[0x61, 0x9a, 0x7a, 0xec, 0xc4, 0x22, 0xd3, 0xad, 0x2d];
+/// POC type 1 with a cycle of one frame of 2^30
+const SPS_1_LARGE_OFFSET: &[u8] = &[
+    0x67, 0x64, 0x00, 0x28, 0xac, 0xa6, 0x80, 0x00, 0x00, 0x03, 0x00, 0x40, 0x00, 0x00, 0x03, 0x00,
+    0x14, 0x07, 0x80, 0x22, 0x64,
+];
+
+type FieldOrderCnts = Option<(Option<i32>, Option<i32>)>;
+
+/// TopFieldOrderCnt and BottomFieldOrderCnt of every slice
+fn decode(nal_units: &[&[u8]]) -> Vec<FieldOrderCnts> {
+    let mut parameter_sets = ParameterSets::new();
+    let mut decoder = PicOrderCntDecoder::new();
+    let mut pocs = Vec::new();
+    for data in nal_units {
+        let nal_unit = parse_nal_unit_data(data, &mut parameter_sets);
+        if let Some(header) = nal_unit.slice_header() {
+            let (_, sps) = parameter_sets.get(header.pic_parameter_set_id).unwrap();
+            let poc = decoder.decode(header, nal_unit.ref_idc(), sps);
+            pocs.push(poc.map(|poc| (poc.top, poc.bottom)));
+        }
+    }
+    pocs
+}
+
+fn frames(pocs: &[i32]) -> Vec<FieldOrderCnts> {
+    pocs.iter()
+        .map(|poc| Some((Some(*poc), Some(*poc))))
+        .collect()
+}
+
+fn byte_stream(nal_units: &[&[u8]]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for nal_unit in nal_units {
+        data.extend([0, 0, 1]);
+        data.extend(*nal_unit);
+    }
+    data
+}
+
+#[test]
+fn type_0() {
+    let pocs = decode(&[
+        SPS_0, PPS, IDR_0, P_6, B_2, B_4, P_12, B_8, B_10, P_40,
+        // pic_order_cnt_lsb wraps
+        P_2, // an IDR picture starts from 0
+        IDR_0, P_LSB_4,
+        // so does a picture with MMCO 5, after it is decoded with 4 as the previous LSB
+        P_8_MMCO5, P_40,
+    ]);
+    assert_eq!(pocs, frames(&[0, 6, 2, 4, 12, 8, 10, 40, 66, 0, 4, 0, -24]));
+}
+
+#[test]
+fn type_1() {
+    let pocs = decode(&[
+        SPS_1,
+        PPS,
+        TYPE_1_IDR,
+        TYPE_1_P_1,
+        TYPE_1_B_2,
+        TYPE_1_B_2_DELTA_2,
+        TYPE_1_P_2,
+        TYPE_1_P_3,
+    ]);
+    let expected: Vec<FieldOrderCnts> = [(0, 1), (6, 7), (0, 1), (2, 3), (6, 7), (6, 7)]
+        .into_iter()
+        .map(|(top, bottom)| Some((Some(top), Some(bottom))))
+        .collect();
+    assert_eq!(pocs, expected);
+}
+
+#[test]
+fn type_1_out_of_range() {
+    let pocs = decode(&[
+        SPS_1_LARGE_OFFSET,
+        PPS,
+        TYPE_1_IDR,
+        TYPE_1_P_1,
+        TYPE_1_P_2,
+        TYPE_1_P_3,
+        TYPE_1_IDR,
+    ]);
+    let mut expected = frames(&[0, (1 << 30) + 4]);
+    expected.extend([None, None]);
+    expected.extend(frames(&[0]));
+    assert_eq!(pocs, expected);
+}
+
+#[test]
+fn type_2() {
+    let pocs = decode(&[
+        SPS_2,
+        PPS,
+        TYPE_2_IDR,
+        TYPE_2_P_1,
+        TYPE_2_NON_REF_2,
+        TYPE_2_P_2,
+        TYPE_2_P_15,
+        // frame_num wraps
+        TYPE_2_P_0,
+        TYPE_2_P_3_MMCO5,
+        TYPE_2_P_1,
+    ]);
+    assert_eq!(pocs, frames(&[0, 2, 3, 4, 30, 32, 0, 2]));
+}
+
+#[test]
+fn display_order() {
+    let data = byte_stream(&[
+        SPS_0, PPS, IDR_0, P_6, B_2, B_4, P_12, B_8, B_10, P_40, P_8_MMCO5, P_LSB_4, IDR_0,
+    ]);
+    let access_units = AccessUnitIterator::new(Box::new(Cursor::new(data)));
+    let order: Vec<(u64, u64, i32)> = DisplayOrderIterator::new(access_units, None)
+        .map(|timed| {
+            // a frame lasts 3003 ticks; pictures are shown two frames after they would
+            // be without reordering
+            assert_eq!(timed.dts, Some(timed.decode_index * 3003));
+            assert_eq!(timed.pts, Some((timed.display_index + 2) * 3003));
+            let poc = timed.access_unit.pic_order_cnt.unwrap();
+            (timed.decode_index, timed.display_index, poc.pic_order_cnt())
+        })
+        .collect();
+    // the pictures before MMCO 5 and the IDR picture are shown first
+    assert_eq!(
+        order,
+        [
+            (0, 0, 0),
+            (2, 1, 2),
+            (3, 2, 4),
+            (1, 3, 6),
+            (5, 4, 8),
+            (6, 5, 10),
+            (4, 6, 12),
+            (7, 7, 40),
+            (8, 8, 0),
+            (9, 9, 4),
+            (10, 10, 0)
+        ]
+    );
+}
@@ -0,0 +1,223 @@
+use h264_parser::nalunits::pps::ParameterSets;
+use h264_parser::nalunits::sei::{
+    ClockTimestamp, FramePacking, RecoveryPoint, SEIMessage, PIC_TIMING, USER_DATA_UNREGISTERED,
+};
+use h264_parser::nalunits::{parse_nal_unit_data, NALUnit};
+
+/// Main profile, with a VUI with NAL HRD parameters (24 bit delays and time offsets,
+/// one CPB) and pic_struct_present_flag
+const SPS: &[u8] = &[
+    0x67, 0x4d, 0x00, 0x28, 0xec, 0xa0, 0x3c, 0x01, 0x13, 0xf2, 0xff, 0xe0, 0x00, 0x80, 0x00, 0x6d,
+    0x40, 0x40, 0x40, 0x50, 0x00, 0x00, 0x3e, 0x90, 0x00, 0x0e, 0xa6, 0x0e, 0x46, 0x00, 0x06, 0x1a,
+    0x80, 0x00, 0x61, 0xa8, 0xbd, 0xef, 0x83, 0xb4, 0x11, 0x08, 0xb2, 0xc0,
+];
+
+/// seq_parameter_set_id 0, initial_cpb_removal_delay 90000 and offset 0
+const BUFFERING_PERIOD: &[u8] = &[0x80, 0xaf, 0xc8, 0x00, 0x00, 0x00, 0x40];
+
+/// Delays 2 and 4, pic_struct 3 (top and bottom field) with a full timestamp of
+/// 23:59:59:29 and time offset -2 for the first field, and only seconds for the second
+const PIC_TIMING_PAYLOAD: &[u8] = &[
+    0x00, 0x00, 0x02, 0x00, 0x00, 0x04, 0x3a, 0x24, 0x1d, 0xef, 0xbb, 0xff, 0xff, 0xff, 0x51, 0x00,
+    0x04, 0x00, 0x00, 0x00, 0x04,
+];
+
+/// recovery_frame_cnt 3, exact_match
+const RECOVERY_POINT: &[u8] = &[0x24, 0x40];
+
+/// Side by side, frame 0 is the left view, repetition period 1
+const FRAME_PACKING: &[u8] = &[0x81, 0x81, 0x00, 0x00, 0x00, 0x01, 0x20];
+
+/// payloadType and payloadSize in 0xff extension bytes, and the payload
+fn message(payload_type: u32, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for mut value in [payload_type, payload.len() as u32] {
+        while value >= 255 {
+            data.push(0xff);
+            value -= 255;
+        }
+        data.push(value as u8);
+    }
+    data.extend(payload);
+    data
+}
+
+/// An SEI NAL unit with the messages, parsed with `parameter_sets`
+fn parse(messages: &[Vec<u8>], parameter_sets: &mut ParameterSets) -> Vec<SEIMessage> {
+    let mut rbsp = messages.concat();
+    rbsp.push(0x80);
+    // emulation prevention
+    let mut data = vec![0x06];
+    for byte in rbsp {
+        if byte <= 3 && data.ends_with(&[0, 0]) {
+            data.push(3);
+        }
+        data.push(byte);
+    }
+    match parse_nal_unit_data(&data, parameter_sets) {
+        NALUnit::SEI(nu) => nu.messages,
+        nal_unit => panic!("{:?} is not an SEI", nal_unit),
+    }
+}
+
+fn parameter_sets() -> ParameterSets {
+    let mut parameter_sets = ParameterSets::new();
+    assert!(matches!(
+        parse_nal_unit_data(SPS, &mut parameter_sets),
+        NALUnit::SPS(_)
+    ));
+    parameter_sets
+}
+
+#[test]
+fn timing_messages() {
+    let messages = parse(
+        &[
+            message(0, BUFFERING_PERIOD),
+            message(1, PIC_TIMING_PAYLOAD),
+            message(6, RECOVERY_POINT),
+        ],
+        &mut parameter_sets(),
+    );
+    let [SEIMessage::BufferingPeriod(buffering_period), SEIMessage::PicTiming(pic_timing), SEIMessage::RecoveryPoint(recovery_point)] =
+        &messages[..]
+    else {
+        panic!("{:?}", messages);
+    };
+    assert_eq!(buffering_period.seq_parameter_set_id, 0);
+    assert_eq!(
+        buffering_period.nal_initial_cpb_removal_delays,
+        [(90000, 0)]
+    );
+    assert!(buffering_period.vcl_initial_cpb_removal_delays.is_empty());
+    assert_eq!(pic_timing.delays, Some((2, 4)));
+    assert_eq!(pic_timing.pic_struct, Some(3));
+    let [Some(top), Some(bottom)] = pic_timing.clock_timestamps[..] else {
+        panic!("{:?}", pic_timing.clock_timestamps);
+    };
+    assert_eq!(
+        top,
+        ClockTimestamp {
+            ct_type: 1,
+            nuit_field_based: false,
+            counting_type: 4,
+            discontinuity: false,
+            cnt_dropped: false,
+            n_frames: 29,
+            seconds: Some(59),
+            minutes: Some(59),
+            hours: Some(23),
+            time_offset: -2,
+        }
+    );
+    assert_eq!(top.timecode().as_deref(), Some("23:59:59:29"));
+    assert_eq!((bottom.seconds, bottom.minutes), (Some(0), None));
+    assert_eq!(bottom.timecode(), None);
+    assert_eq!(
+        *recovery_point,
+        RecoveryPoint {
+            recovery_frame_cnt: 3,
+            exact_match: true,
+            broken_link: false,
+            changing_slice_group_idc: 0,
+        }
+    );
+}
+
+#[test]
+fn timing_needs_the_sps() {
+    // without an SPS the HRD and pic_struct fields can't be read
+    let messages = parse(
+        &[message(0, BUFFERING_PERIOD), message(1, PIC_TIMING_PAYLOAD)],
+        &mut ParameterSets::new(),
+    );
+    let [SEIMessage::Other(0, _), SEIMessage::Other(PIC_TIMING, payload)] = &messages[..] else {
+        panic!("{:?}", messages);
+    };
+    assert_eq!(payload, PIC_TIMING_PAYLOAD);
+}
+
+#[test]
+fn user_data() {
+    let mut unregistered =
+        b"\xdc\x45\xe9\xbd\xe6\xd9\x48\xb7\x96\x2c\xd8\x20\xd9\x23\xee\xef".to_vec();
+    unregistered.extend(b"x264 - core 164");
+    let messages = parse(
+        &[
+            message(4, b"\xb5\x00\x31GA94\x03"),
+            message(4, b"\xff\x01\x02"),
+            message(5, &unregistered),
+            // too short for a UUID
+            message(5, b"\x01\x02"),
+        ],
+        &mut ParameterSets::new(),
+    );
+    let [SEIMessage::UserDataRegistered(atsc, captions), SEIMessage::UserDataRegistered(extended, rest), SEIMessage::UserDataUnregistered(uuid, text), SEIMessage::Other(USER_DATA_UNREGISTERED, _)] =
+        &messages[..]
+    else {
+        panic!("{:?}", messages);
+    };
+    assert_eq!(
+        (&atsc[..], &captions[..]),
+        (&[0xb5][..], &b"\x00\x31GA94\x03"[..])
+    );
+    assert_eq!((&extended[..], &rest[..]), (&[0xff, 0x01][..], &[0x02][..]));
+    assert_eq!(uuid[..], unregistered[..16]);
+    assert_eq!(text, b"x264 - core 164");
+}
+
+#[test]
+fn frame_packing_and_hdr() {
+    let mastering_display = [
+        0x33, 0xc2, 0x86, 0xc4, 0x1d, 0x4c, 0x0b, 0xb8, 0x84, 0xd0, 0x3e, 0x80, 0x3d, 0x13, 0x40,
+        0x42, 0x00, 0x98, 0x96, 0x80, 0x00, 0x00, 0x00, 0x32,
+    ];
+    let messages = parse(
+        &[
+            message(45, FRAME_PACKING),
+            message(137, &mastering_display),
+            message(144, &[0x03, 0xe8, 0x01, 0x90]),
+        ],
+        &mut ParameterSets::new(),
+    );
+    let [SEIMessage::FramePackingArrangement(arrangement), SEIMessage::MasteringDisplayColourVolume(mdcv), SEIMessage::ContentLightLevel(cll)] =
+        &messages[..]
+    else {
+        panic!("{:?}", messages);
+    };
+    assert_eq!(arrangement.id, 0);
+    let packing: FramePacking = arrangement.arrangement.unwrap();
+    assert_eq!(packing.arrangement_name(), "side by side");
+    assert_eq!(packing.content_interpretation_type, 1);
+    assert_eq!(packing.grid_positions, Some([(0, 0), (0, 0)]));
+    assert_eq!(packing.repetition_period, 1);
+    assert_eq!(mdcv.white_point, (15635, 16450));
+    assert_eq!(mdcv.min_display_mastering_luminance, 50);
+    assert_eq!(cll.max_content_light_level, 1000);
+    assert_eq!(cll.max_pic_average_light_level, 400);
+}
+
+#[test]
+fn extension_bytes() {
+    // payloadType 300 and a payloadSize of 255 and 600
+    let messages = parse(
+        &[
+            message(300, &[0x12]),
+            message(5, &[0x34; 255]),
+            message(5, &[0x56; 600]),
+        ],
+        &mut ParameterSets::new(),
+    );
+    let [SEIMessage::Other(300, other), SEIMessage::UserDataUnregistered(_, first), SEIMessage::UserDataUnregistered(_, second)] =
+        &messages[..]
+    else {
+        panic!("{:?}", messages);
+    };
+    assert_eq!(other, &[0x12]);
+    assert_eq!((first.len(), second.len()), (255 - 16, 600 - 16));
+    // a message that claims more bytes than are left ends the list
+    let mut truncated = message(6, RECOVERY_POINT);
+    truncated.extend([0x06, 0x10, 0x24]);
+    let messages = parse(&[truncated], &mut ParameterSets::new());
+    assert!(matches!(messages[..], [SEIMessage::RecoveryPoint(_)]));
+}
@@ -0,0 +1,73 @@
+use h264_parser::nalunits::pps::{ParameterSets, SliceGroupMap};
+use h264_parser::nalunits::slice::SliceHeader;
+use h264_parser::nalunits::{parse_nal_unit_data, NALUnit};
+
+/// Baseline 120x68 macroblocks, and 65536x65536, whose picture size doesn't fit in a u32
+const SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1e, 0xed, 0x00, 0xf0, 0x04, 0x4c, 0x80];
+const SPS_OVERFLOWING_SIZE: &[u8] = &[
+    0x67, 0x42, 0xc0, 0x1e, 0xed, 0x00, 0x00, 0x20, 0x00, 0x00, 0x03, 0x00, 0x10, 0x00, 0x0c, 0x80,
+];
+/// Two slice groups with slice_group_map_type 4 and a change rate of 10
+const PPS: &[u8] = &[0x68, 0xc4, 0x58, 0xac, 0x79];
+/// P slices with a 10 and a 32 bit slice_group_change_cycle of 5
+const SLICE: &[u8] = &[0x61, 0x9a, 0x21, 0x0f, 0x01, 0x6b, 0x4b, 0x50];
+const SLICE_32: &[u8] = &[
+    0x61, 0x9a, 0x21, 0x0f, 0x00, 0x00, 0x03, 0x00, 0x05, 0xad, 0x2d, 0x40,
+];
+
+fn parameter_sets(sps: &[u8]) -> ParameterSets {
+    let mut parameter_sets = ParameterSets::new();
+    parse_nal_unit_data(sps, &mut parameter_sets);
+    parse_nal_unit_data(PPS, &mut parameter_sets);
+    parameter_sets
+}
+
+/// Changes the map type and change rate of the PPS
+fn set_evolving(parameter_sets: &mut ParameterSets, new_map_type: u32, new_change_rate: u32) {
+    let pps = parameter_sets.pps.get_mut(&0).unwrap();
+    match &mut pps.slice_groups.as_mut().unwrap().map {
+        SliceGroupMap::Evolving {
+            map_type,
+            change_rate,
+            ..
+        } => {
+            *map_type = new_map_type;
+            *change_rate = new_change_rate;
+        }
+        map => panic!("unexpected {:?}", map),
+    }
+}
+
+fn slice_header(data: &[u8], parameter_sets: &mut ParameterSets) -> Option<SliceHeader> {
+    match parse_nal_unit_data(data, parameter_sets) {
+        NALUnit::NonIDRPicture(nu) => nu.header,
+        nal_unit => panic!("not a slice: {:?}", nal_unit),
+    }
+}
+
+#[test]
+fn slice_group_change_cycle() {
+    for map_type in 3..=5 {
+        let mut parameter_sets = parameter_sets(SPS);
+        set_evolving(&mut parameter_sets, map_type, 10);
+        // Ceil(Log2(8160 ÷ 10 + 1)) bits
+        let header = slice_header(SLICE, &mut parameter_sets).unwrap();
+        assert_eq!(header.slice_group_change_cycle, Some(5));
+
+        // the picture size overflows
+        let mut parameter_sets = self::parameter_sets(SPS_OVERFLOWING_SIZE);
+        assert_eq!(parameter_sets.sps[&0].pic_width_in_mbs, 65536);
+        set_evolving(&mut parameter_sets, map_type, 10);
+        assert!(slice_header(SLICE, &mut parameter_sets).is_none());
+
+        // u32::MAX map units, so only adding 1 overflows
+        let sps = parameter_sets.sps.get_mut(&0).unwrap();
+        sps.pic_width_in_mbs = 65535;
+        sps.pic_height_in_map_units = 65537;
+        set_evolving(&mut parameter_sets, map_type, 1);
+        assert!(slice_header(SLICE_32, &mut parameter_sets).is_none());
+        set_evolving(&mut parameter_sets, map_type, 2);
+        let header = slice_header(SLICE_32, &mut parameter_sets).unwrap();
+        assert_eq!(header.slice_group_change_cycle, Some(5));
+    }
+}
@@ -0,0 +1,45 @@
+use h264_parser::bitstream::BitWriter;
+use h264_parser::nalunits::{
+    add_emulation_prevention, parse_nal_unit_data, pps::ParameterSets, sps::TimingInfo, NALUnit,
+};
+
+#[test]
+fn frame_rate_with_a_32_bit_tick() {
+    let timing = TimingInfo {
+        num_units_in_tick: u32::MAX,
+        time_scale: 60000,
+        fixed_frame_rate: true,
+    };
+    assert_eq!(timing.frame_rate(), Some((60000, 2 * u32::MAX as u64)));
+}
+
+#[test]
+fn largest_picture_size_saturates() {
+    // Baseline SPS with the largest pic_width_in_mbs_minus1 and
+    // pic_height_in_map_units_minus1 that ue(v) can hold, and field coding
+    let mut writer = BitWriter::new();
+    writer.u(8, 66);
+    writer.u(8, 0);
+    writer.u(8, 30);
+    writer.ue(0);
+    writer.ue(0);
+    writer.ue(2);
+    writer.ue(1);
+    writer.flag(false);
+    writer.ue(u32::MAX - 1);
+    writer.ue(u32::MAX - 1);
+    writer.flag(false);
+    writer.flag(false);
+    writer.flag(true);
+    writer.flag(false);
+    writer.flag(false);
+    writer.rbsp_trailing_bits();
+    let mut data = vec![0x67];
+    data.extend(add_emulation_prevention(&writer.into_bytes()));
+    let NALUnit::SPS(nu) = parse_nal_unit_data(&data, &mut ParameterSets::new()) else {
+        panic!("SPS not parsed");
+    };
+    assert_eq!(nu.sps.width(), u32::MAX);
+    assert_eq!(nu.sps.height(), u32::MAX);
+    assert_eq!(nu.sps.max_dpb_frames(), 0);
+}
@@ -0,0 +1,23 @@
+use h264_parser::nalunits::{parse_nal_unit, pps::ParameterSets};
+use h264_parser::stream::partialstream;
+use winnow::stream::Offset;
+
+#[test]
+fn parse_nal_unit_after_3_and_4_byte_start_codes() {
+    let data = b"\x00\x00\x00\x01\x09\x10\x00\x00\x01\x09\x30";
+    let mut parameter_sets = ParameterSets::new();
+    let input = partialstream(data, true);
+    let (rest, nal_unit) = parse_nal_unit(input, &mut parameter_sets).unwrap();
+    assert_eq!(nal_unit.nal_unit_type(), 9);
+    assert_eq!(input.offset_to(&rest), 6);
+    let (rest, nal_unit) = parse_nal_unit(rest, &mut parameter_sets).unwrap();
+    assert_eq!(nal_unit.to_bytes(), [0x09, 0x30]);
+    assert_eq!(input.offset_to(&rest), data.len());
+}
+
+#[test]
+fn parse_nal_unit_needs_a_start_code_first() {
+    let data = b"\xff\x00\x00\x01\x09\x10";
+    let mut parameter_sets = ParameterSets::new();
+    assert!(parse_nal_unit(partialstream(data, true), &mut parameter_sets).is_err());
+}
@@ -0,0 +1,90 @@
+// Round trips of the streams in tests/data: parsed, serialised with `NALUnit::to_bytes`
+// and written back. They are the same NAL units in four framings: 4 byte start codes,
+// 3 byte start codes with the zero_byte where B.1.2 puts it, and 4 and 2 byte lengths.
+// Besides AUDs, an SPS with VUI, HRD and scaling lists, a PPS and SEI, they have slices
+// whose data needs every kind of emulation prevention and ends in cabac_zero_words.
+use h264_parser::access_unit::AccessUnitIterator;
+use h264_parser::avcc::LengthPrefixedIterator;
+use h264_parser::nalunits::{add_emulation_prevention, parse_nal_unit_data, pps::ParameterSets};
+use h264_parser::writer::{Framing, NALUnitWriter};
+use h264_parser::NALUnitIterator;
+use std::io::Cursor;
+use std::path::Path;
+
+fn read(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name);
+    std::fs::read(path).unwrap()
+}
+
+#[test]
+fn long_start_codes() {
+    let data = read("long_start_codes.264");
+    let mut writer = NALUnitWriter::new(Vec::new(), Framing::AnnexBLong);
+    let mut nal_units = 0;
+    for nal_unit in NALUnitIterator::new(Box::new(Cursor::new(data.clone()))) {
+        writer.write(&nal_unit).unwrap();
+        nal_units += 1;
+    }
+    assert_eq!(nal_units, 12);
+    assert_eq!(writer.into_inner(), data);
+}
+
+#[test]
+fn short_start_codes() {
+    let data = read("short_start_codes.264");
+    let mut writer = NALUnitWriter::new(Vec::new(), Framing::AnnexB);
+    let mut access_units = 0;
+    for access_unit in AccessUnitIterator::new(Box::new(Cursor::new(data.clone()))) {
+        writer.write_access_unit(&access_unit).unwrap();
+        access_units += 1;
+    }
+    assert_eq!(access_units, 4);
+    assert_eq!(writer.into_inner(), data);
+}
+
+#[test]
+fn length_prefixes() {
+    for (name, length_size) in [("length_prefixed_4.avcc", 4), ("length_prefixed_2.avcc", 2)] {
+        let data = read(name);
+        let mut writer = NALUnitWriter::new(Vec::new(), Framing::LengthPrefix(length_size));
+        for nal_unit in LengthPrefixedIterator::new(Cursor::new(&data), length_size) {
+            writer.write(&nal_unit).unwrap();
+        }
+        assert_eq!(writer.into_inner(), data, "{}", name);
+    }
+}
+
+#[test]
+fn start_codes_to_length_prefixes() {
+    let mut writer = NALUnitWriter::new(Vec::new(), Framing::LengthPrefix(4));
+    let data = read("long_start_codes.264");
+    for nal_unit in NALUnitIterator::new(Box::new(Cursor::new(data))) {
+        writer.write(&nal_unit).unwrap();
+    }
+    assert_eq!(writer.into_inner(), read("length_prefixed_4.avcc"));
+}
+
+#[test]
+fn emulation_prevention() {
+    let cases: [(&[u8], &[u8]); 7] = [
+        (b"\x12\x00\x00\x00\x34", b"\x12\x00\x00\x03\x00\x34"),
+        (b"\x12\x00\x00\x01\x34", b"\x12\x00\x00\x03\x01\x34"),
+        (b"\x12\x00\x00\x02\x34", b"\x12\x00\x00\x03\x02\x34"),
+        (b"\x12\x00\x00\x03\x34", b"\x12\x00\x00\x03\x03\x34"),
+        (b"\x12\x00\x00\x04\x34", b"\x12\x00\x00\x04\x34"),
+        // cabac_zero_words after the stop bit
+        (b"\x80\x00\x00", b"\x80\x00\x00\x03"),
+        (b"\x80\x00\x00\x00\x00", b"\x80\x00\x00\x03\x00\x00\x03"),
+    ];
+    for (rbsp, payload) in cases {
+        assert_eq!(add_emulation_prevention(rbsp), payload);
+        // and back, as the payload of a filler data NAL unit
+        let mut data = vec![0x0c];
+        data.extend_from_slice(payload);
+        let nal_unit = parse_nal_unit_data(&data, &mut ParameterSets::new());
+        assert_eq!(nal_unit.rbsp(), rbsp);
+        assert_eq!(nal_unit.to_bytes(), data);
+    }
+}
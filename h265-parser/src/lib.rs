@@ -0,0 +1,45 @@
+// HEVC / H.265 (ITU-T H.265) Annex B byte streams, stream_type 0x24
+pub mod nalunits;
+pub mod parameter_sets;
+pub mod sei;
+pub mod slice;
+pub mod stream;
+
+use h264_parser::startcode::StartCodeIterator;
+use parameter_sets::ParameterSets;
+use std::io::Read;
+
+pub struct NALUnitIterator {
+    units: StartCodeIterator<Box<dyn Read>>,
+    parameter_sets: ParameterSets,
+}
+
+impl NALUnitIterator {
+    pub fn new(input_reader: Box<dyn Read>) -> NALUnitIterator {
+        Self {
+            units: StartCodeIterator::new(input_reader),
+            parameter_sets: ParameterSets::new(),
+        }
+    }
+
+    /// The VPS, SPS and PPS seen so far
+    pub fn parameter_sets(&self) -> &ParameterSets {
+        &self.parameter_sets
+    }
+}
+
+impl Iterator for NALUnitIterator {
+    type Item = nalunits::NALUnit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let unit = self.units.next()?;
+            // skip units without a valid NAL unit header
+            if let Some(nal_unit) =
+                nalunits::parse_nal_unit_data(&unit.data, &mut self.parameter_sets)
+            {
+                return Some(nal_unit);
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use super::parameter_sets::{ParameterSets, PPS, SPS, VPS};
+use super::sei::{parse_sei_rbsp, SEIMessage};
+use super::slice::SliceSegmentHeader;
+use super::stream::{stream, Stream};
+use h264_parser::{bitstream::parse_rbsp, nalunits::remove_emulation_prevention};
+use std::fmt;
+use winnow::{binary::bits, error, IResult, Parser};
+
+// nal_unit_type values (table 7-1)
+pub const TRAIL_N: u8 = 0;
+pub const TRAIL_R: u8 = 1;
+pub const TSA_N: u8 = 2;
+pub const TSA_R: u8 = 3;
+pub const STSA_N: u8 = 4;
+pub const STSA_R: u8 = 5;
+pub const RADL_N: u8 = 6;
+pub const RADL_R: u8 = 7;
+pub const RASL_N: u8 = 8;
+pub const RASL_R: u8 = 9;
+pub const BLA_W_LP: u8 = 16;
+pub const BLA_W_RADL: u8 = 17;
+pub const BLA_N_LP: u8 = 18;
+pub const IDR_W_RADL: u8 = 19;
+pub const IDR_N_LP: u8 = 20;
+pub const CRA_NUT: u8 = 21;
+pub const VPS_NUT: u8 = 32;
+pub const SPS_NUT: u8 = 33;
+pub const PPS_NUT: u8 = 34;
+pub const AUD_NUT: u8 = 35;
+pub const EOS_NUT: u8 = 36;
+pub const EOB_NUT: u8 = 37;
+pub const FD_NUT: u8 = 38;
+pub const PREFIX_SEI_NUT: u8 = 39;
+pub const SUFFIX_SEI_NUT: u8 = 40;
+
+/// Slice segment headers are short; this is more than enough of the NAL unit to parse
+/// one, and saves unescaping the whole slice
+const SLICE_HEADER_BYTES: usize = 64;
+
+/// What a coded picture is, from the type of its VCL NAL units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictureClass {
+    IDR,
+    CRA,
+    BLA,
+    /// Random access skipped leading picture, not decodable when decoding starts at the
+    /// associated IRAP picture
+    RASL,
+    /// Random access decodable leading picture
+    RADL,
+    /// TRAIL, TSA and STSA pictures
+    Trailing,
+}
+
+impl PictureClass {
+    /// Intra random access point
+    pub fn is_irap(&self) -> bool {
+        matches!(self, Self::IDR | Self::CRA | Self::BLA)
+    }
+
+    pub fn is_leading(&self) -> bool {
+        matches!(self, Self::RASL | Self::RADL)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NALHeader {
+    pub nal_unit_type: u8,
+    pub nuh_layer_id: u8,
+    /// TemporalId, i.e. nuh_temporal_id_plus1 - 1
+    pub temporal_id: u8,
+}
+
+impl NALHeader {
+    pub fn parse(input: Stream<'_>) -> IResult<Stream<'_>, Self> {
+        let (input, (_, nal_unit_type, nuh_layer_id, temporal_id_plus1)) =
+            bits::bits::<_, _, error::Error<(_, usize)>, _, _>((
+                bits::tag(0_u8, 1_usize),
+                bits::take(6_usize),
+                bits::take(6_usize),
+                bits::take::<_, u8, _, _>(3_usize).verify(|t| *t != 0),
+            ))
+            .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                nal_unit_type,
+                nuh_layer_id,
+                temporal_id: temporal_id_plus1 - 1,
+            },
+        ))
+    }
+
+    /// Video coding layer, i.e. a slice segment
+    pub fn is_vcl(&self) -> bool {
+        self.nal_unit_type < VPS_NUT
+    }
+
+    pub fn is_irap(&self) -> bool {
+        (BLA_W_LP..=23).contains(&self.nal_unit_type)
+    }
+
+    pub fn is_idr(&self) -> bool {
+        matches!(self.nal_unit_type, IDR_W_RADL | IDR_N_LP)
+    }
+
+    /// Sub-layer non-reference pictures (the _N types) are not used for prediction of
+    /// pictures in the same sub-layer
+    pub fn is_sub_layer_non_reference(&self) -> bool {
+        self.nal_unit_type <= 14 && self.nal_unit_type.is_multiple_of(2)
+    }
+
+    pub fn picture_class(&self) -> Option<PictureClass> {
+        Some(match self.nal_unit_type {
+            TRAIL_N..=STSA_R => PictureClass::Trailing,
+            RADL_N | RADL_R => PictureClass::RADL,
+            RASL_N | RASL_R => PictureClass::RASL,
+            BLA_W_LP..=BLA_N_LP => PictureClass::BLA,
+            IDR_W_RADL | IDR_N_LP => PictureClass::IDR,
+            CRA_NUT => PictureClass::CRA,
+            _ => return None,
+        })
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.nal_unit_type {
+            TRAIL_N => "TRAIL_N",
+            TRAIL_R => "TRAIL_R",
+            TSA_N => "TSA_N",
+            TSA_R => "TSA_R",
+            STSA_N => "STSA_N",
+            STSA_R => "STSA_R",
+            RADL_N => "RADL_N",
+            RADL_R => "RADL_R",
+            RASL_N => "RASL_N",
+            RASL_R => "RASL_R",
+            BLA_W_LP => "BLA_W_LP",
+            BLA_W_RADL => "BLA_W_RADL",
+            BLA_N_LP => "BLA_N_LP",
+            IDR_W_RADL => "IDR_W_RADL",
+            IDR_N_LP => "IDR_N_LP",
+            CRA_NUT => "CRA",
+            VPS_NUT => "VPS",
+            SPS_NUT => "SPS",
+            PPS_NUT => "PPS",
+            AUD_NUT => "AUD",
+            EOS_NUT => "EOS",
+            EOB_NUT => "EOB",
+            FD_NUT => "FD",
+            PREFIX_SEI_NUT => "PREFIX_SEI",
+            SUFFIX_SEI_NUT => "SUFFIX_SEI",
+            10..=15 | 22..=31 => "reserved VCL",
+            41..=47 => "reserved",
+            _ => "unspecified",
+        }
+    }
+}
+
+pub enum Payload {
+    VPS(VPS),
+    SPS(SPS),
+    PPS(PPS),
+    /// None if the slice segment header refers to a missing parameter set, or is broken
+    Slice(Option<SliceSegmentHeader>),
+    /// pic_type: which slice types the access unit may contain
+    AUD(u8),
+    SEI(Vec<SEIMessage>),
+    EndOfSequence,
+    EndOfBitstream,
+    /// The RBSP of NAL units of other types, and of parameter sets that failed to parse
+    Other(Vec<u8>),
+}
+
+impl fmt::Debug for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VPS(vps) => write!(f, "{:?}", vps),
+            Self::SPS(sps) => write!(f, "{:?}", sps),
+            Self::PPS(pps) => write!(f, "{:?}", pps),
+            Self::Slice(Some(slice)) => write!(f, "{:?}", slice),
+            Self::Slice(None) => write!(f, "unparsed slice"),
+            Self::AUD(pic_type) => write!(f, "pic_type={}", pic_type),
+            Self::SEI(messages) => write!(f, "{:?}", messages),
+            Self::EndOfSequence | Self::EndOfBitstream => Ok(()),
+            Self::Other(rbsp) => write!(f, "{:x?}", &rbsp[..(16.min(rbsp.len()))]),
+        }
+    }
+}
+
+pub struct NALUnit {
+    pub header: NALHeader,
+    /// Size of the NAL unit in bytes, including the header and emulation prevention
+    pub size: usize,
+    pub payload: Payload,
+}
+
+impl fmt::Debug for NALUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.header.type_name())?;
+        if self.header.nuh_layer_id != 0 || self.header.temporal_id != 0 {
+            write!(
+                f,
+                " (layer {}, temporal id {})",
+                self.header.nuh_layer_id, self.header.temporal_id
+            )?;
+        }
+        write!(f, ": {} bytes {:?}", self.size, self.payload)
+    }
+}
+
+/// Parses the bytes between two start codes (still with emulation prevention bytes).
+///
+/// Parameter sets are added to `parameter_sets`, and slice segment headers are parsed with
+/// the ones already there. Returns None if there is no valid NAL unit header.
+pub fn parse_nal_unit_data(data: &[u8], parameter_sets: &mut ParameterSets) -> Option<NALUnit> {
+    // a NAL unit never ends in a zero byte; these are trailing_zero_8bits
+    let end = data
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |p| p + 1);
+    let data = &data[..end];
+    let (rest, header) = NALHeader::parse(stream(data)).ok()?;
+    let payload = match header.nal_unit_type {
+        _ if header.is_vcl() => {
+            let rbsp = remove_emulation_prevention(&rest[..SLICE_HEADER_BYTES.min(rest.len())]);
+            Payload::Slice(
+                parse_rbsp(&rbsp, |input| {
+                    SliceSegmentHeader::parse_bits(input, &header, parameter_sets)
+                })
+                .ok(),
+            )
+        }
+        VPS_NUT => {
+            let rbsp = remove_emulation_prevention(rest);
+            match parse_rbsp(&rbsp, VPS::parse_bits).ok() {
+                Some(vps) => {
+                    parameter_sets
+                        .vps
+                        .insert(vps.video_parameter_set_id, vps.clone());
+                    Payload::VPS(vps)
+                }
+                None => Payload::Other(rbsp),
+            }
+        }
+        SPS_NUT => {
+            let rbsp = remove_emulation_prevention(rest);
+            match parse_rbsp(&rbsp, SPS::parse_bits).ok() {
+                Some(sps) => {
+                    parameter_sets
+                        .sps
+                        .insert(sps.seq_parameter_set_id, sps.clone());
+                    Payload::SPS(sps)
+                }
+                None => Payload::Other(rbsp),
+            }
+        }
+        PPS_NUT => {
+            let rbsp = remove_emulation_prevention(rest);
+            match parse_rbsp(&rbsp, PPS::parse_bits).ok() {
+                Some(pps) => {
+                    parameter_sets
+                        .pps
+                        .insert(pps.pic_parameter_set_id, pps.clone());
+                    Payload::PPS(pps)
+                }
+                None => Payload::Other(rbsp),
+            }
+        }
+        AUD_NUT if !rest.is_empty() => Payload::AUD(rest[0] >> 5),
+        PREFIX_SEI_NUT | SUFFIX_SEI_NUT => {
+            Payload::SEI(parse_sei_rbsp(&remove_emulation_prevention(rest)))
+        }
+        EOS_NUT => Payload::EndOfSequence,
+        EOB_NUT => Payload::EndOfBitstream,
+        _ => Payload::Other(remove_emulation_prevention(rest)),
+    };
+    Some(NALUnit {
+        header,
+        size: data.len(),
+        payload,
+    })
+}
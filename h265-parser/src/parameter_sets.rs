@@ -0,0 +1,956 @@
+// Video, sequence and picture parameter sets (ITU-T H.265 7.3.2)
+//
+// Only the parts needed to describe the stream and to parse slice segment headers are
+// kept; everything after the VUI timing information in the SPS (HRD parameters, range
+// and multilayer extensions) is not parsed.
+use h264_parser::bitstream::{flag, se, skip_bits, u, ue, verify_error, BitInput, BitResult};
+use std::collections::HashMap;
+use winnow::{combinator, Parser};
+
+/// general_profile_tier_level; the sub-layer levels are skipped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileTierLevel {
+    pub profile_space: u8,
+    pub tier_flag: bool,
+    pub profile_idc: u8,
+    pub profile_compatibility_flags: u32,
+    pub progressive_source: bool,
+    pub interlaced_source: bool,
+    pub non_packed_constraint: bool,
+    pub frame_only_constraint: bool,
+    pub level_idc: u8,
+}
+
+impl ProfileTierLevel {
+    fn parse_bits(input: BitInput, max_sub_layers_minus1: u8) -> BitResult<Self> {
+        let (input, (profile_space, tier_flag, profile_idc, profile_compatibility_flags)) =
+            (u(2), flag, u(5), u(32)).parse_next(input)?;
+        let (input, (progressive_source, interlaced_source, non_packed_constraint)) =
+            (flag, flag, flag).parse_next(input)?;
+        let (input, frame_only_constraint) = flag(input)?;
+        // 43 bits of constraint flags and general_inbld_flag
+        let (input, _) = skip_bits(input, 44)?;
+        let (mut input, level_idc) = u(8)(input)?;
+        let mut present = Vec::new();
+        for _ in 0..max_sub_layers_minus1 {
+            let flags;
+            (input, flags) = (flag, flag).parse_next(input)?;
+            present.push(flags);
+        }
+        if max_sub_layers_minus1 > 0 {
+            (input, _) = skip_bits(input, (8 - max_sub_layers_minus1 as usize) * 2)?;
+        }
+        for (profile_present, level_present) in present {
+            if profile_present {
+                (input, _) = skip_bits(input, 88)?;
+            }
+            if level_present {
+                (input, _) = skip_bits(input, 8)?;
+            }
+        }
+        Ok((
+            input,
+            Self {
+                profile_space: profile_space as u8,
+                tier_flag,
+                profile_idc: profile_idc as u8,
+                profile_compatibility_flags,
+                progressive_source,
+                interlaced_source,
+                non_packed_constraint,
+                frame_only_constraint,
+                level_idc: level_idc as u8,
+            },
+        ))
+    }
+
+    pub fn profile_name(&self) -> &'static str {
+        // with profile_idc 0, the first compatible profile tells what the stream is
+        let profile = match self.profile_idc {
+            0 => self.profile_compatibility_flags.leading_zeros() as u8,
+            idc => idc,
+        };
+        match profile {
+            1 => "Main",
+            2 => "Main 10",
+            3 => "Main Still Picture",
+            4 => "Range Extensions",
+            5 => "High Throughput",
+            6 => "Multiview Main",
+            7 => "Scalable Main",
+            8 => "3D Main",
+            9 => "Screen Content Coding",
+            10 => "Scalable Range Extensions",
+            11 => "High Throughput Screen Content Coding",
+            _ => "unknown",
+        }
+    }
+
+    pub fn tier_name(&self) -> &'static str {
+        match self.tier_flag {
+            false => "Main",
+            true => "High",
+        }
+    }
+
+    /// general_level_idc is 30 times the level number
+    pub fn level(&self) -> f32 {
+        self.level_idc as f32 / 30.0
+    }
+}
+
+/// DPB size and reordering for the highest sub-layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubLayerOrdering {
+    pub max_dec_pic_buffering: u32,
+    pub max_num_reorder_pics: u32,
+    pub max_latency_increase_plus1: u32,
+}
+
+impl SubLayerOrdering {
+    /// Parses the sub_layer_ordering_info loop and keeps the last entry
+    fn parse_bits(input: BitInput, max_sub_layers_minus1: u8) -> BitResult<Self> {
+        let (mut input, info_present) = flag(input)?;
+        let first = match info_present {
+            true => 0,
+            false => max_sub_layers_minus1,
+        };
+        let mut ordering = None;
+        for _ in first..=max_sub_layers_minus1 {
+            let (max_dec_pic_buffering_minus1, max_num_reorder_pics, max_latency_increase_plus1);
+            (
+                input,
+                (
+                    max_dec_pic_buffering_minus1,
+                    max_num_reorder_pics,
+                    max_latency_increase_plus1,
+                ),
+            ) = (ue, ue, ue).parse_next(input)?;
+            ordering = Some(Self {
+                max_dec_pic_buffering: max_dec_pic_buffering_minus1 + 1,
+                max_num_reorder_pics,
+                max_latency_increase_plus1,
+            });
+        }
+        match ordering {
+            Some(ordering) => Ok((input, ordering)),
+            None => verify_error(input),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingInfo {
+    pub num_units_in_tick: u32,
+    pub time_scale: u32,
+    pub num_ticks_poc_diff_one: Option<u32>,
+}
+
+impl TimingInfo {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (num_units_in_tick, time_scale, poc_proportional_to_timing)) =
+            (u(32), u(32), flag).parse_next(input)?;
+        let (input, num_ticks_poc_diff_one_minus1) =
+            combinator::cond(poc_proportional_to_timing, ue).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                num_units_in_tick,
+                time_scale,
+                num_ticks_poc_diff_one: num_ticks_poc_diff_one_minus1.map(|n| n + 1),
+            },
+        ))
+    }
+
+    /// Pictures per second as (numerator, denominator)
+    pub fn frame_rate(&self) -> Option<(u32, u32)> {
+        match self.num_units_in_tick {
+            0 => None,
+            n => Some((self.time_scale, n)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VPS {
+    pub video_parameter_set_id: u8,
+    pub base_layer_internal: bool,
+    pub base_layer_available: bool,
+    pub max_layers: u8,
+    pub max_sub_layers: u8,
+    pub temporal_id_nesting: bool,
+    pub profile_tier_level: ProfileTierLevel,
+    pub sub_layer_ordering: SubLayerOrdering,
+    pub max_layer_id: u8,
+    pub num_layer_sets: u32,
+    pub timing: Option<TimingInfo>,
+}
+
+impl VPS {
+    pub(crate) fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (video_parameter_set_id, base_layer_internal, base_layer_available)) =
+            (u(4), flag, flag).parse_next(input)?;
+        let (input, (max_layers_minus1, max_sub_layers_minus1, temporal_id_nesting, _)) =
+            (u(6), u(3), flag, u(16)).parse_next(input)?;
+        let max_sub_layers_minus1 = max_sub_layers_minus1 as u8;
+        let (input, profile_tier_level) =
+            ProfileTierLevel::parse_bits(input, max_sub_layers_minus1)?;
+        let (input, sub_layer_ordering) =
+            SubLayerOrdering::parse_bits(input, max_sub_layers_minus1)?;
+        let (input, (max_layer_id, num_layer_sets_minus1)) = (u(6), ue).parse_next(input)?;
+        if num_layer_sets_minus1 > 1023 {
+            return verify_error(input);
+        }
+        // layer_id_included_flag
+        let (input, _) = skip_bits(
+            input,
+            num_layer_sets_minus1 as usize * (max_layer_id as usize + 1),
+        )?;
+        let (input, timing_present) = flag(input)?;
+        let (input, timing) =
+            combinator::cond(timing_present, TimingInfo::parse_bits).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                video_parameter_set_id: video_parameter_set_id as u8,
+                base_layer_internal,
+                base_layer_available,
+                max_layers: max_layers_minus1 as u8 + 1,
+                max_sub_layers: max_sub_layers_minus1 + 1,
+                temporal_id_nesting,
+                profile_tier_level,
+                sub_layer_ordering,
+                max_layer_id: max_layer_id as u8,
+                num_layer_sets: num_layer_sets_minus1 + 1,
+                timing,
+            },
+        ))
+    }
+}
+
+/// Skips scaling_list_data(), which has no effect on anything we look at
+fn skip_scaling_list_data(mut input: BitInput) -> BitResult<()> {
+    for size_id in 0..4 {
+        let step = if size_id == 3 { 3 } else { 1 };
+        for _ in (0..6).step_by(step) {
+            let pred_mode;
+            (input, pred_mode) = flag(input)?;
+            if !pred_mode {
+                // scaling_list_pred_matrix_id_delta
+                (input, _) = ue(input)?;
+                continue;
+            }
+            let coefficients = 64.min(1 << (4 + (size_id << 1)));
+            if size_id > 1 {
+                // scaling_list_dc_coef_minus8
+                (input, _) = se(input)?;
+            }
+            for _ in 0..coefficients {
+                (input, _) = se(input)?;
+            }
+        }
+    }
+    Ok((input, ()))
+}
+
+/// st_ref_pic_set(), resolved to the POC differences of the reference pictures
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShortTermRefPicSet {
+    /// Negative POC differences, closest first
+    pub delta_poc_s0: Vec<i32>,
+    pub used_by_curr_pic_s0: Vec<bool>,
+    /// Positive POC differences, closest first
+    pub delta_poc_s1: Vec<i32>,
+    pub used_by_curr_pic_s1: Vec<bool>,
+}
+
+impl ShortTermRefPicSet {
+    pub fn num_delta_pocs(&self) -> usize {
+        self.delta_poc_s0.len() + self.delta_poc_s1.len()
+    }
+
+    /// Parses the set with index `sets.len()`, which may be predicted from one of `sets`.
+    /// In a slice header, the reference set is signalled with delta_idx_minus1.
+    pub(crate) fn parse_bits<'i>(
+        input: BitInput<'i>,
+        sets: &[ShortTermRefPicSet],
+        in_slice_header: bool,
+    ) -> BitResult<'i, Self> {
+        let (input, inter_ref_pic_set_prediction) = match sets.is_empty() {
+            true => (input, false),
+            false => flag(input)?,
+        };
+        if inter_ref_pic_set_prediction {
+            return Self::parse_predicted(input, sets, in_slice_header);
+        }
+        let (mut input, (num_negative_pics, num_positive_pics)) = (ue, ue).parse_next(input)?;
+        if num_negative_pics > 16 || num_positive_pics > 16 {
+            return verify_error(input);
+        }
+        let mut set = Self::default();
+        let mut poc = 0;
+        for _ in 0..num_negative_pics {
+            let (delta_poc_minus1, used);
+            (input, (delta_poc_minus1, used)) = (ue, flag).parse_next(input)?;
+            poc -= delta_poc_minus1 as i32 + 1;
+            set.delta_poc_s0.push(poc);
+            set.used_by_curr_pic_s0.push(used);
+        }
+        poc = 0;
+        for _ in 0..num_positive_pics {
+            let (delta_poc_minus1, used);
+            (input, (delta_poc_minus1, used)) = (ue, flag).parse_next(input)?;
+            poc += delta_poc_minus1 as i32 + 1;
+            set.delta_poc_s1.push(poc);
+            set.used_by_curr_pic_s1.push(used);
+        }
+        Ok((input, set))
+    }
+
+    /// Derivation of equations 7-61 and 7-62
+    fn parse_predicted<'i>(
+        input: BitInput<'i>,
+        sets: &[ShortTermRefPicSet],
+        in_slice_header: bool,
+    ) -> BitResult<'i, Self> {
+        let (input, delta_idx_minus1) = match in_slice_header {
+            true => ue(input)?,
+            false => (input, 0),
+        };
+        let Some(reference) = (sets.len())
+            .checked_sub(delta_idx_minus1 as usize + 1)
+            .map(|index| &sets[index])
+        else {
+            return verify_error(input);
+        };
+        let (mut input, (delta_rps_sign, abs_delta_rps_minus1)) = (flag, ue).parse_next(input)?;
+        let delta_rps = match delta_rps_sign {
+            false => abs_delta_rps_minus1 as i32 + 1,
+            true => -(abs_delta_rps_minus1 as i32) - 1,
+        };
+        // (used_by_curr_pic_flag, use_delta_flag) for every picture of the reference set,
+        // plus one for the reference picture itself
+        let mut flags = Vec::new();
+        for _ in 0..=reference.num_delta_pocs() {
+            let used;
+            (input, used) = flag(input)?;
+            let use_delta = match used {
+                true => true,
+                false => {
+                    let use_delta;
+                    (input, use_delta) = flag(input)?;
+                    use_delta
+                }
+            };
+            flags.push((used, use_delta));
+        }
+        let negatives = reference.delta_poc_s0.len();
+        let (s0, s1) = (&reference.delta_poc_s0, &reference.delta_poc_s1);
+        let mut set = Self::default();
+        let add = |set: &mut Self, poc: i32, (used, use_delta): (bool, bool)| {
+            if use_delta && poc < 0 {
+                set.delta_poc_s0.push(poc);
+                set.used_by_curr_pic_s0.push(used);
+            } else if use_delta && poc > 0 {
+                set.delta_poc_s1.push(poc);
+                set.used_by_curr_pic_s1.push(used);
+            }
+        };
+        // negative differences, closest first
+        for j in (0..s1.len()).rev() {
+            if s1[j] + delta_rps < 0 {
+                add(&mut set, s1[j] + delta_rps, flags[negatives + j]);
+            }
+        }
+        if delta_rps < 0 {
+            add(&mut set, delta_rps, flags[reference.num_delta_pocs()]);
+        }
+        for j in 0..negatives {
+            if s0[j] + delta_rps < 0 {
+                add(&mut set, s0[j] + delta_rps, flags[j]);
+            }
+        }
+        // positive differences, closest first
+        for j in (0..negatives).rev() {
+            if s0[j] + delta_rps > 0 {
+                add(&mut set, s0[j] + delta_rps, flags[j]);
+            }
+        }
+        if delta_rps > 0 {
+            add(&mut set, delta_rps, flags[reference.num_delta_pocs()]);
+        }
+        for j in 0..s1.len() {
+            if s1[j] + delta_rps > 0 {
+                add(&mut set, s1[j] + delta_rps, flags[negatives + j]);
+            }
+        }
+        Ok((input, set))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub left_offset: u32,
+    pub right_offset: u32,
+    pub top_offset: u32,
+    pub bottom_offset: u32,
+}
+
+impl Window {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (left_offset, right_offset, top_offset, bottom_offset)) =
+            (ue, ue, ue, ue).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                left_offset,
+                right_offset,
+                top_offset,
+                bottom_offset,
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColourDescription {
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+}
+
+impl ColourDescription {
+    pub fn primaries_name(&self) -> &'static str {
+        match self.colour_primaries {
+            1 => "BT.709",
+            5 => "BT.601 625",
+            6 => "BT.601 525",
+            9 => "BT.2020",
+            11 => "DCI-P3",
+            12 => "Display P3",
+            _ => "unknown",
+        }
+    }
+
+    pub fn transfer_name(&self) -> &'static str {
+        match self.transfer_characteristics {
+            1 | 6 | 14 | 15 => "SDR",
+            16 => "PQ",
+            18 => "HLG",
+            _ => "unknown",
+        }
+    }
+
+    pub fn matrix_name(&self) -> &'static str {
+        match self.matrix_coefficients {
+            0 => "GBR",
+            1 => "BT.709",
+            5 | 6 => "BT.601",
+            9 => "BT.2020 NCL",
+            10 => "BT.2020 CL",
+            _ => "unknown",
+        }
+    }
+
+    /// PQ (SMPTE ST 2084) or HLG transfer
+    pub fn is_hdr(&self) -> bool {
+        matches!(self.transfer_characteristics, 16 | 18)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoSignalType {
+    pub video_format: u8,
+    pub full_range: bool,
+    pub colour_description: Option<ColourDescription>,
+}
+
+/// vui_parameters() up to and including the timing information
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VUI {
+    /// Sample aspect ratio as (width, height)
+    pub sample_aspect_ratio: Option<(u16, u16)>,
+    pub overscan_appropriate: Option<bool>,
+    pub video_signal_type: Option<VideoSignalType>,
+    pub chroma_sample_loc_type: Option<(u32, u32)>,
+    pub neutral_chroma_indication: bool,
+    pub field_seq: bool,
+    pub frame_field_info_present: bool,
+    pub default_display_window: Option<Window>,
+    pub timing: Option<TimingInfo>,
+}
+
+/// Table E.1
+const SAMPLE_ASPECT_RATIOS: [(u16, u16); 16] = [
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+const EXTENDED_SAR: u32 = 255;
+
+impl VUI {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (mut input, aspect_ratio_info_present) = flag(input)?;
+        let mut sample_aspect_ratio = None;
+        if aspect_ratio_info_present {
+            let aspect_ratio_idc;
+            (input, aspect_ratio_idc) = u(8)(input)?;
+            sample_aspect_ratio = match aspect_ratio_idc {
+                EXTENDED_SAR => {
+                    let (width, height);
+                    (input, (width, height)) = (u(16), u(16)).parse_next(input)?;
+                    Some((width as u16, height as u16))
+                }
+                idc @ 1..=16 => Some(SAMPLE_ASPECT_RATIOS[idc as usize - 1]),
+                _ => None,
+            };
+        }
+        let (input, overscan_info_present) = flag(input)?;
+        let (input, overscan_appropriate) =
+            combinator::cond(overscan_info_present, flag).parse_next(input)?;
+        let (input, video_signal_type_present) = flag(input)?;
+        let (input, video_signal_type) = combinator::cond(video_signal_type_present, |input| {
+            let (input, (video_format, full_range, colour_description_present)) =
+                (u(3), flag, flag).parse_next(input)?;
+            let (input, colour_description) = combinator::cond(
+                colour_description_present,
+                (u(8), u(8), u(8)).map(|(primaries, transfer, matrix)| ColourDescription {
+                    colour_primaries: primaries as u8,
+                    transfer_characteristics: transfer as u8,
+                    matrix_coefficients: matrix as u8,
+                }),
+            )
+            .parse_next(input)?;
+            Ok((
+                input,
+                VideoSignalType {
+                    video_format: video_format as u8,
+                    full_range,
+                    colour_description,
+                },
+            ))
+        })
+        .parse_next(input)?;
+        let (input, chroma_loc_info_present) = flag(input)?;
+        let (input, chroma_sample_loc_type) =
+            combinator::cond(chroma_loc_info_present, (ue, ue)).parse_next(input)?;
+        let (input, (neutral_chroma_indication, field_seq, frame_field_info_present)) =
+            (flag, flag, flag).parse_next(input)?;
+        let (input, default_display_window_present) = flag(input)?;
+        let (input, default_display_window) =
+            combinator::cond(default_display_window_present, Window::parse_bits)
+                .parse_next(input)?;
+        let (input, timing_present) = flag(input)?;
+        let (input, timing) =
+            combinator::cond(timing_present, TimingInfo::parse_bits).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                sample_aspect_ratio,
+                overscan_appropriate,
+                video_signal_type,
+                chroma_sample_loc_type,
+                neutral_chroma_indication,
+                field_seq,
+                frame_field_info_present,
+                default_display_window,
+                timing,
+            },
+        ))
+    }
+
+    pub fn colour_description(&self) -> Option<&ColourDescription> {
+        self.video_signal_type
+            .as_ref()
+            .and_then(|v| v.colour_description.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SPS {
+    pub video_parameter_set_id: u8,
+    pub max_sub_layers: u8,
+    pub temporal_id_nesting: bool,
+    pub profile_tier_level: ProfileTierLevel,
+    pub seq_parameter_set_id: u32,
+    pub chroma_format_idc: u32,
+    pub separate_colour_plane: bool,
+    pub pic_width_in_luma_samples: u32,
+    pub pic_height_in_luma_samples: u32,
+    pub conformance_window: Option<Window>,
+    pub bit_depth_luma: u32,
+    pub bit_depth_chroma: u32,
+    pub log2_max_pic_order_cnt_lsb: u32,
+    pub sub_layer_ordering: SubLayerOrdering,
+    pub log2_min_luma_coding_block_size: u32,
+    pub log2_ctb_size: u32,
+    pub log2_min_luma_transform_block_size: u32,
+    pub log2_max_luma_transform_block_size: u32,
+    pub scaling_list_enabled: bool,
+    pub amp_enabled: bool,
+    pub sample_adaptive_offset_enabled: bool,
+    pub pcm_enabled: bool,
+    pub short_term_ref_pic_sets: Vec<ShortTermRefPicSet>,
+    /// lt_ref_pic_poc_lsb_sps and used_by_curr_pic_lt_sps_flag, if long term reference
+    /// pictures are used
+    pub long_term_ref_pics: Option<Vec<(u32, bool)>>,
+    pub temporal_mvp_enabled: bool,
+    pub strong_intra_smoothing_enabled: bool,
+    pub vui: Option<VUI>,
+}
+
+impl SPS {
+    pub(crate) fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (video_parameter_set_id, max_sub_layers_minus1, temporal_id_nesting)) =
+            (u(4), u(3), flag).parse_next(input)?;
+        let max_sub_layers_minus1 = max_sub_layers_minus1 as u8;
+        let (input, profile_tier_level) =
+            ProfileTierLevel::parse_bits(input, max_sub_layers_minus1)?;
+        let (input, (seq_parameter_set_id, chroma_format_idc)) = (ue, ue).parse_next(input)?;
+        if seq_parameter_set_id > 15 || chroma_format_idc > 3 {
+            return verify_error(input);
+        }
+        let (input, separate_colour_plane) =
+            combinator::cond(chroma_format_idc == 3, flag).parse_next(input)?;
+        let (input, (pic_width_in_luma_samples, pic_height_in_luma_samples)) =
+            (ue, ue).parse_next(input)?;
+        let (input, conformance_window_present) = flag(input)?;
+        let (input, conformance_window) =
+            combinator::cond(conformance_window_present, Window::parse_bits).parse_next(input)?;
+        let (input, (bit_depth_luma_minus8, bit_depth_chroma_minus8)) =
+            (ue, ue).parse_next(input)?;
+        let (input, log2_max_pic_order_cnt_lsb_minus4) = ue(input)?;
+        if log2_max_pic_order_cnt_lsb_minus4 > 12 {
+            return verify_error(input);
+        }
+        let (input, sub_layer_ordering) =
+            SubLayerOrdering::parse_bits(input, max_sub_layers_minus1)?;
+        let (input, (log2_min_luma_coding_block_size_minus3, log2_diff_max_min_coding_block)) =
+            (ue, ue).parse_next(input)?;
+        let (input, (log2_min_luma_transform_block_size_minus2, log2_diff_max_min_transform)) =
+            (ue, ue).parse_next(input)?;
+        if log2_min_luma_coding_block_size_minus3 + log2_diff_max_min_coding_block > 3 {
+            return verify_error(input);
+        }
+        // max_transform_hierarchy_depth_inter and _intra
+        let (input, _) = (ue, ue).parse_next(input)?;
+        let (input, scaling_list_enabled) = flag(input)?;
+        let (input, scaling_list_data_present) =
+            combinator::cond(scaling_list_enabled, flag).parse_next(input)?;
+        let (input, _) = combinator::cond(
+            scaling_list_data_present == Some(true),
+            skip_scaling_list_data,
+        )
+        .parse_next(input)?;
+        let (input, (amp_enabled, sample_adaptive_offset_enabled, pcm_enabled)) =
+            (flag, flag, flag).parse_next(input)?;
+        // bit depths and sizes of PCM samples, pcm_loop_filter_disabled_flag
+        let (input, _) =
+            combinator::cond(pcm_enabled, (u(4), u(4), ue, ue, flag)).parse_next(input)?;
+        let (mut input, num_short_term_ref_pic_sets) = ue(input)?;
+        if num_short_term_ref_pic_sets > 64 {
+            return verify_error(input);
+        }
+        let mut short_term_ref_pic_sets = Vec::new();
+        for _ in 0..num_short_term_ref_pic_sets {
+            let set;
+            (input, set) = ShortTermRefPicSet::parse_bits(input, &short_term_ref_pic_sets, false)?;
+            short_term_ref_pic_sets.push(set);
+        }
+        let (mut input, long_term_ref_pics_present) = flag(input)?;
+        let mut long_term_ref_pics = None;
+        if long_term_ref_pics_present {
+            let count;
+            (input, count) = ue(input)?;
+            if count > 32 {
+                return verify_error(input);
+            }
+            let mut pictures = Vec::new();
+            for _ in 0..count {
+                let picture;
+                (input, picture) =
+                    (u(log2_max_pic_order_cnt_lsb_minus4 as usize + 4), flag).parse_next(input)?;
+                pictures.push(picture);
+            }
+            long_term_ref_pics = Some(pictures);
+        }
+        let (input, (temporal_mvp_enabled, strong_intra_smoothing_enabled)) =
+            (flag, flag).parse_next(input)?;
+        let (input, vui_present) = flag(input)?;
+        let (input, vui) = combinator::cond(vui_present, VUI::parse_bits).parse_next(input)?;
+        let log2_min_luma_coding_block_size = log2_min_luma_coding_block_size_minus3 + 3;
+        let log2_min_luma_transform_block_size = log2_min_luma_transform_block_size_minus2 + 2;
+        Ok((
+            input,
+            Self {
+                video_parameter_set_id: video_parameter_set_id as u8,
+                max_sub_layers: max_sub_layers_minus1 + 1,
+                temporal_id_nesting,
+                profile_tier_level,
+                seq_parameter_set_id,
+                chroma_format_idc,
+                separate_colour_plane: separate_colour_plane.unwrap_or(false),
+                pic_width_in_luma_samples,
+                pic_height_in_luma_samples,
+                conformance_window,
+                bit_depth_luma: bit_depth_luma_minus8 + 8,
+                bit_depth_chroma: bit_depth_chroma_minus8 + 8,
+                log2_max_pic_order_cnt_lsb: log2_max_pic_order_cnt_lsb_minus4 + 4,
+                sub_layer_ordering,
+                log2_min_luma_coding_block_size,
+                log2_ctb_size: log2_min_luma_coding_block_size + log2_diff_max_min_coding_block,
+                log2_min_luma_transform_block_size,
+                log2_max_luma_transform_block_size: log2_min_luma_transform_block_size
+                    + log2_diff_max_min_transform,
+                scaling_list_enabled,
+                amp_enabled,
+                sample_adaptive_offset_enabled,
+                pcm_enabled,
+                short_term_ref_pic_sets,
+                long_term_ref_pics,
+                temporal_mvp_enabled,
+                strong_intra_smoothing_enabled,
+                vui,
+            },
+        ))
+    }
+
+    pub fn chroma_format_name(&self) -> &'static str {
+        match self.chroma_format_idc {
+            0 => "4:0:0",
+            1 => "4:2:0",
+            2 => "4:2:2",
+            _ => "4:4:4",
+        }
+    }
+
+    /// SubWidthC and SubHeightC (table 6-1), the unit of the conformance window offsets
+    fn chroma_subsampling(&self) -> (u32, u32) {
+        match (self.chroma_format_idc, self.separate_colour_plane) {
+            (1, _) => (2, 2),
+            (2, _) => (2, 1),
+            _ => (1, 1),
+        }
+    }
+
+    /// Width after cropping to the conformance window
+    pub fn width(&self) -> u32 {
+        let (sub_width, _) = self.chroma_subsampling();
+        let crop = self
+            .conformance_window
+            .map_or(0, |w| (w.left_offset + w.right_offset) * sub_width);
+        self.pic_width_in_luma_samples.saturating_sub(crop)
+    }
+
+    /// Height after cropping to the conformance window
+    pub fn height(&self) -> u32 {
+        let (_, sub_height) = self.chroma_subsampling();
+        let crop = self
+            .conformance_window
+            .map_or(0, |w| (w.top_offset + w.bottom_offset) * sub_height);
+        self.pic_height_in_luma_samples.saturating_sub(crop)
+    }
+
+    /// PicSizeInCtbsY
+    pub fn pic_size_in_ctbs(&self) -> u32 {
+        let ctb_size = 1 << self.log2_ctb_size;
+        self.pic_width_in_luma_samples.div_ceil(ctb_size)
+            * self.pic_height_in_luma_samples.div_ceil(ctb_size)
+    }
+
+    pub fn frame_rate(&self) -> Option<(u32, u32)> {
+        self.vui.as_ref()?.timing?.frame_rate()
+    }
+
+    pub fn colour_description(&self) -> Option<&ColourDescription> {
+        self.vui.as_ref()?.colour_description()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tiles {
+    pub num_tile_columns: u32,
+    pub num_tile_rows: u32,
+    pub uniform_spacing: bool,
+    pub loop_filter_across_tiles_enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeblockingFilterControl {
+    pub override_enabled: bool,
+    pub disabled: bool,
+    pub beta_offset_div2: i32,
+    pub tc_offset_div2: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PPS {
+    pub pic_parameter_set_id: u32,
+    pub seq_parameter_set_id: u32,
+    pub dependent_slice_segments_enabled: bool,
+    pub output_flag_present: bool,
+    pub num_extra_slice_header_bits: u8,
+    pub sign_data_hiding_enabled: bool,
+    pub cabac_init_present: bool,
+    pub num_ref_idx_l0_default_active: u32,
+    pub num_ref_idx_l1_default_active: u32,
+    pub init_qp: i32,
+    pub constrained_intra_pred: bool,
+    pub transform_skip_enabled: bool,
+    pub diff_cu_qp_delta_depth: Option<u32>,
+    pub cb_qp_offset: i32,
+    pub cr_qp_offset: i32,
+    pub slice_chroma_qp_offsets_present: bool,
+    pub weighted_pred: bool,
+    pub weighted_bipred: bool,
+    pub transquant_bypass_enabled: bool,
+    pub tiles: Option<Tiles>,
+    pub entropy_coding_sync_enabled: bool,
+    pub loop_filter_across_slices_enabled: bool,
+    pub deblocking_filter_control: Option<DeblockingFilterControl>,
+    pub scaling_list_data_present: bool,
+    pub lists_modification_present: bool,
+    pub log2_parallel_merge_level: u32,
+    pub slice_segment_header_extension_present: bool,
+}
+
+impl PPS {
+    pub(crate) fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (pic_parameter_set_id, seq_parameter_set_id)) = (ue, ue).parse_next(input)?;
+        if pic_parameter_set_id > 63 || seq_parameter_set_id > 15 {
+            return verify_error(input);
+        }
+        let (input, (dependent_slice_segments_enabled, output_flag_present)) =
+            (flag, flag).parse_next(input)?;
+        let (input, (num_extra_slice_header_bits, sign_data_hiding_enabled, cabac_init_present)) =
+            (u(3), flag, flag).parse_next(input)?;
+        let (input, (num_ref_idx_l0_default_active_minus1, num_ref_idx_l1_default_active_minus1)) =
+            (ue, ue).parse_next(input)?;
+        let (input, (init_qp_minus26, constrained_intra_pred, transform_skip_enabled)) =
+            (se, flag, flag).parse_next(input)?;
+        let (input, cu_qp_delta_enabled) = flag(input)?;
+        let (input, diff_cu_qp_delta_depth) =
+            combinator::cond(cu_qp_delta_enabled, ue).parse_next(input)?;
+        let (input, (cb_qp_offset, cr_qp_offset, slice_chroma_qp_offsets_present)) =
+            (se, se, flag).parse_next(input)?;
+        let (input, (weighted_pred, weighted_bipred, transquant_bypass_enabled)) =
+            (flag, flag, flag).parse_next(input)?;
+        let (input, (tiles_enabled, entropy_coding_sync_enabled)) =
+            (flag, flag).parse_next(input)?;
+        let (input, tiles) = combinator::cond(tiles_enabled, |input| {
+            let (mut input, (columns_minus1, rows_minus1, uniform_spacing)) =
+                (ue, ue, flag).parse_next(input)?;
+            if columns_minus1 > 63 || rows_minus1 > 63 {
+                return verify_error(input);
+            }
+            if !uniform_spacing {
+                // column_width_minus1 and row_height_minus1
+                for _ in 0..columns_minus1 + rows_minus1 {
+                    (input, _) = ue(input)?;
+                }
+            }
+            let (input, loop_filter_across_tiles_enabled) = flag(input)?;
+            Ok((
+                input,
+                Tiles {
+                    num_tile_columns: columns_minus1 + 1,
+                    num_tile_rows: rows_minus1 + 1,
+                    uniform_spacing,
+                    loop_filter_across_tiles_enabled,
+                },
+            ))
+        })
+        .parse_next(input)?;
+        let (input, (loop_filter_across_slices_enabled, deblocking_filter_control_present)) =
+            (flag, flag).parse_next(input)?;
+        let (input, deblocking_filter_control) =
+            combinator::cond(deblocking_filter_control_present, |input| {
+                let (input, (override_enabled, disabled)) = (flag, flag).parse_next(input)?;
+                let (input, offsets) = combinator::cond(!disabled, (se, se)).parse_next(input)?;
+                let (beta_offset_div2, tc_offset_div2) = offsets.unwrap_or((0, 0));
+                Ok((
+                    input,
+                    DeblockingFilterControl {
+                        override_enabled,
+                        disabled,
+                        beta_offset_div2,
+                        tc_offset_div2,
+                    },
+                ))
+            })
+            .parse_next(input)?;
+        let (input, scaling_list_data_present) = flag(input)?;
+        let (input, _) = combinator::cond(scaling_list_data_present, skip_scaling_list_data)
+            .parse_next(input)?;
+        let (input, (lists_modification_present, log2_parallel_merge_level_minus2)) =
+            (flag, ue).parse_next(input)?;
+        let (input, slice_segment_header_extension_present) = flag(input)?;
+        Ok((
+            input,
+            Self {
+                pic_parameter_set_id,
+                seq_parameter_set_id,
+                dependent_slice_segments_enabled,
+                output_flag_present,
+                num_extra_slice_header_bits: num_extra_slice_header_bits as u8,
+                sign_data_hiding_enabled,
+                cabac_init_present,
+                num_ref_idx_l0_default_active: num_ref_idx_l0_default_active_minus1 + 1,
+                num_ref_idx_l1_default_active: num_ref_idx_l1_default_active_minus1 + 1,
+                init_qp: init_qp_minus26 + 26,
+                constrained_intra_pred,
+                transform_skip_enabled,
+                diff_cu_qp_delta_depth,
+                cb_qp_offset,
+                cr_qp_offset,
+                slice_chroma_qp_offsets_present,
+                weighted_pred,
+                weighted_bipred,
+                transquant_bypass_enabled,
+                tiles,
+                entropy_coding_sync_enabled,
+                loop_filter_across_slices_enabled,
+                deblocking_filter_control,
+                scaling_list_data_present,
+                lists_modification_present,
+                log2_parallel_merge_level: log2_parallel_merge_level_minus2 + 2,
+                slice_segment_header_extension_present,
+            },
+        ))
+    }
+}
+
+/// The parameter sets seen so far, which slice segment headers refer to
+#[derive(Debug, Default)]
+pub struct ParameterSets {
+    pub vps: HashMap<u8, VPS>,
+    pub sps: HashMap<u32, SPS>,
+    pub pps: HashMap<u32, PPS>,
+}
+
+impl ParameterSets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The PPS with the given id, and the SPS it refers to
+    pub fn get(&self, pic_parameter_set_id: u32) -> Option<(&PPS, &SPS)> {
+        let pps = self.pps.get(&pic_parameter_set_id)?;
+        let sps = self.sps.get(&pps.seq_parameter_set_id)?;
+        Some((pps, sps))
+    }
+}
@@ -0,0 +1,82 @@
+// SEI messages (7.3.5 and annex D); only the HDR related ones are decoded
+use super::stream::{stream, Stream};
+pub use h264_parser::nalunits::sei::{ContentLightLevel, MasteringDisplayColourVolume};
+use std::fmt;
+use winnow::{binary, token, IResult, Parser};
+
+pub const MASTERING_DISPLAY_COLOUR_VOLUME: u32 = 137;
+pub const CONTENT_LIGHT_LEVEL_INFO: u32 = 144;
+pub const ALTERNATIVE_TRANSFER_CHARACTERISTICS: u32 = 147;
+
+pub enum SEIMessage {
+    MasteringDisplayColourVolume(MasteringDisplayColourVolume),
+    ContentLightLevel(ContentLightLevel),
+    /// preferred_transfer_characteristics, e.g. 18 (HLG) in streams signalling BT.2020
+    AlternativeTransferCharacteristics(u8),
+    Other(u32, Vec<u8>),
+}
+
+impl fmt::Debug for SEIMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MasteringDisplayColourVolume(m) => write!(f, "MasteringDisplay({})", m),
+            Self::ContentLightLevel(c) => write!(
+                f,
+                "ContentLightLevel(MaxCLL={}, MaxFALL={})",
+                c.max_content_light_level, c.max_pic_average_light_level
+            ),
+            Self::AlternativeTransferCharacteristics(t) => {
+                write!(f, "AlternativeTransferCharacteristics({})", t)
+            }
+            Self::Other(payload_type, data) => write!(
+                f,
+                "SEI({}, {:x?})",
+                payload_type,
+                &data[..(16.min(data.len()))]
+            ),
+        }
+    }
+}
+
+/// payloadType and payloadSize: a run of 0xff bytes, each adding 255, and a last byte
+fn sei_value(input: Stream<'_>) -> IResult<Stream<'_>, u32> {
+    let (input, extension) = token::take_while(0.., 0xff).parse_next(input)?;
+    let (input, last) = binary::u8(input)?;
+    Ok((input, extension.len() as u32 * 255 + last as u32))
+}
+
+impl SEIMessage {
+    fn parse(input: Stream<'_>) -> IResult<Stream<'_>, Self> {
+        let (input, (payload_type, payload_size)) = (sei_value, sei_value).parse_next(input)?;
+        let (input, payload) = token::take(payload_size).parse_next(input)?;
+        let message = match payload_type {
+            MASTERING_DISPLAY_COLOUR_VOLUME => MasteringDisplayColourVolume::from_payload(payload)
+                .map(Self::MasteringDisplayColourVolume),
+            CONTENT_LIGHT_LEVEL_INFO => {
+                ContentLightLevel::from_payload(payload).map(Self::ContentLightLevel)
+            }
+            ALTERNATIVE_TRANSFER_CHARACTERISTICS => payload
+                .first()
+                .map(|transfer| Self::AlternativeTransferCharacteristics(*transfer)),
+            _ => None,
+        }
+        .unwrap_or_else(|| Self::Other(payload_type, payload.to_vec()));
+        Ok((input, message))
+    }
+}
+
+/// Parses the messages of an SEI RBSP, up to the rbsp_trailing_bits
+pub fn parse_sei_rbsp(rbsp: &[u8]) -> Vec<SEIMessage> {
+    let mut input = stream(rbsp);
+    let mut messages = Vec::new();
+    while !input.is_empty() && **input != [0x80][..] {
+        match SEIMessage::parse(input) {
+            Ok((remainder, message)) => {
+                messages.push(message);
+                input = remainder;
+            }
+            Err(_) => break,
+        }
+    }
+    messages
+}
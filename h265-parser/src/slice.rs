@@ -0,0 +1,97 @@
+// The start of slice_segment_header() (7.3.6.1), as far as the picture order count
+use super::nalunits::NALHeader;
+use super::parameter_sets::ParameterSets;
+use h264_parser::bitstream::{flag, u, ue, verify_error, BitInput, BitResult};
+use winnow::{combinator, Parser};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceType {
+    B,
+    P,
+    I,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SliceSegmentHeader {
+    pub first_slice_segment_in_pic: bool,
+    /// Only present in IRAP pictures
+    pub no_output_of_prior_pics: Option<bool>,
+    pub pic_parameter_set_id: u32,
+    pub dependent_slice_segment: bool,
+    pub slice_segment_address: u32,
+    /// The fields below are only present in independent slice segments; dependent ones
+    /// take them from the slice segment before them
+    pub slice_type: Option<SliceType>,
+    pub pic_output: bool,
+    pub colour_plane_id: Option<u8>,
+    /// slice_pic_order_cnt_lsb; absent (zero) in IDR pictures
+    pub pic_order_cnt_lsb: Option<u32>,
+}
+
+impl SliceSegmentHeader {
+    pub(crate) fn parse_bits<'i>(
+        input: BitInput<'i>,
+        header: &NALHeader,
+        parameter_sets: &ParameterSets,
+    ) -> BitResult<'i, Self> {
+        let (input, first_slice_segment_in_pic) = flag(input)?;
+        let (input, no_output_of_prior_pics) =
+            combinator::cond(header.is_irap(), flag).parse_next(input)?;
+        let (input, pic_parameter_set_id) = ue(input)?;
+        let Some((pps, sps)) = parameter_sets.get(pic_parameter_set_id) else {
+            return verify_error(input);
+        };
+        let (input, dependent_slice_segment, slice_segment_address) =
+            match first_slice_segment_in_pic {
+                true => (input, false, 0),
+                false => {
+                    let (input, dependent) =
+                        combinator::cond(pps.dependent_slice_segments_enabled, flag)
+                            .parse_next(input)?;
+                    // Ceil(Log2(PicSizeInCtbsY)) bits
+                    let address_bits =
+                        32 - sps.pic_size_in_ctbs().saturating_sub(1).leading_zeros();
+                    let (input, address) = u(address_bits as usize)(input)?;
+                    (input, dependent.unwrap_or(false), address)
+                }
+            };
+        let mut slice = Self {
+            first_slice_segment_in_pic,
+            no_output_of_prior_pics,
+            pic_parameter_set_id,
+            dependent_slice_segment,
+            slice_segment_address,
+            slice_type: None,
+            pic_output: true,
+            colour_plane_id: None,
+            pic_order_cnt_lsb: None,
+        };
+        if dependent_slice_segment {
+            return Ok((input, slice));
+        }
+        // slice_reserved_flag
+        let (mut input, _) = u(pps.num_extra_slice_header_bits as usize)(input)?;
+        let slice_type;
+        (input, slice_type) = ue(input)?;
+        slice.slice_type = Some(match slice_type {
+            0 => SliceType::B,
+            1 => SliceType::P,
+            2 => SliceType::I,
+            _ => return verify_error(input),
+        });
+        if pps.output_flag_present {
+            (input, slice.pic_output) = flag(input)?;
+        }
+        if sps.separate_colour_plane {
+            let colour_plane_id;
+            (input, colour_plane_id) = u(2)(input)?;
+            slice.colour_plane_id = Some(colour_plane_id as u8);
+        }
+        if !header.is_idr() {
+            let pic_order_cnt_lsb;
+            (input, pic_order_cnt_lsb) = u(sps.log2_max_pic_order_cnt_lsb as usize)(input)?;
+            slice.pic_order_cnt_lsb = Some(pic_order_cnt_lsb);
+        }
+        Ok((input, slice))
+    }
+}
@@ -0,0 +1,284 @@
+use h265_parser::nalunits::{self, parse_nal_unit_data, NALHeader, NALUnit, Payload, PictureClass};
+use h265_parser::parameter_sets::{ParameterSets, ShortTermRefPicSet, Window};
+use h265_parser::sei::SEIMessage;
+use h265_parser::slice::SliceType;
+use h265_parser::stream::stream;
+use h265_parser::NALUnitIterator;
+use std::io::Cursor;
+
+/// Main profile, level 3.1, one layer, 60000/1001 Hz timing
+const VPS: &[u8] = &[
+    0x40, 0x01, 0x0c, 0x01, 0xff, 0xff, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03,
+    0x00, 0x00, 0x03, 0x00, 0x5d, 0x95, 0xc0, 0xc0, 0x00, 0x00, 0xfa, 0x40, 0x00, 0x3a, 0x98, 0x14,
+];
+
+/// Main 10, level 5.1, 1920x1088 cropped to 1080, 8 bit POC LSB, 64x64 CTBs, two short
+/// term reference picture sets (the second predicted from the first) and a VUI with PQ
+/// BT.2020 colour and 60000/1001 Hz timing
+const SPS: &[u8] = &[
+    0x42, 0x01, 0x01, 0x02, 0x20, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03,
+    0x00, 0x99, 0xa0, 0x03, 0xc0, 0x80, 0x11, 0x07, 0xca, 0xd9, 0x65, 0x79, 0x24, 0x49, 0x9a, 0xff,
+    0x78, 0x0b, 0x50, 0x91, 0x00, 0x90, 0x40, 0x00, 0x00, 0xfa, 0x40, 0x00, 0x3a, 0x98, 0x02,
+];
+
+/// Dependent slice segments, init_qp 22, 2x1 uniform tiles and deblocking offsets
+const PPS: &[u8] = &[0x44, 0x01, 0xe1, 0x62, 0x4a, 0x2a, 0x09, 0x7c, 0x64, 0x24];
+
+/// The first slice segment of an IDR_W_RADL I picture
+const IDR_SLICE: &[u8] = &[0x26, 0x01, 0xae, 0x96, 0x96];
+
+/// A dependent slice segment of a TRAIL_R picture at CTB 300
+const DEPENDENT_SLICE: &[u8] = &[0x02, 0x01, 0x72, 0xc5, 0xa5, 0xa8];
+
+/// The first slice segment of a TRAIL_R B picture with temporal id 1 and POC LSB 5
+const TRAIL_SLICE: &[u8] = &[0x02, 0x02, 0xe0, 0xa0, 0x00, 0x10];
+
+/// Like TRAIL_SLICE, but with pic_parameter_set_id 3
+const SLICE_WITHOUT_PPS: &[u8] = &[0x02, 0x01, 0x92, 0x0a, 0x00, 0x01];
+
+/// Mastering display colour volume, content light level and a payloadType of 300
+const SEI: &[u8] = &[
+    0x4e, 0x01, 0x89, 0x18, 0x33, 0xc2, 0x86, 0xc4, 0x1d, 0x4c, 0x0b, 0xb8, 0x84, 0xd0, 0x3e, 0x80,
+    0x3d, 0x13, 0x40, 0x42, 0x00, 0x98, 0x96, 0x80, 0x00, 0x00, 0x03, 0x00, 0x32, 0x90, 0x04, 0x03,
+    0xe8, 0x01, 0x90, 0xff, 0x2d, 0x01, 0x12, 0x80,
+];
+
+fn parse(data: &[u8], parameter_sets: &mut ParameterSets) -> NALUnit {
+    parse_nal_unit_data(data, parameter_sets).unwrap()
+}
+
+#[test]
+fn nal_unit_headers() {
+    let header = |data: &[u8]| NALHeader::parse(stream(data)).unwrap().1;
+    let idr = header(&[0x26, 0x01]);
+    assert_eq!(idr.nal_unit_type, nalunits::IDR_W_RADL);
+    assert!(idr.is_vcl() && idr.is_irap() && idr.is_idr());
+    assert_eq!(idr.picture_class(), Some(PictureClass::IDR));
+    assert_eq!(idr.type_name(), "IDR_W_RADL");
+    // CRA and BLA are random access points but not IDR
+    let cra = header(&[0x2a, 0x01]);
+    assert!(cra.is_irap() && !cra.is_idr());
+    assert_eq!(cra.picture_class(), Some(PictureClass::CRA));
+    assert!(header(&[0x20, 0x01]).picture_class().unwrap().is_irap());
+    // RASL_N in layer 1, temporal id 2
+    let rasl = header(&[0x10, 0x0b]);
+    assert_eq!((rasl.nuh_layer_id, rasl.temporal_id), (1, 2));
+    assert!(rasl.is_sub_layer_non_reference());
+    assert!(rasl.picture_class().unwrap().is_leading());
+    let sps = header(&[0x42, 0x01]);
+    assert!(!sps.is_vcl());
+    assert_eq!((sps.picture_class(), sps.type_name()), (None, "SPS"));
+    assert_eq!(header(&[0x54, 0x01]).type_name(), "reserved");
+    // forbidden_zero_bit set, and nuh_temporal_id_plus1 of 0
+    assert!(NALHeader::parse(stream(&[0xc2, 0x01])).is_err());
+    assert!(NALHeader::parse(stream(&[0x42, 0x00])).is_err());
+}
+
+#[test]
+fn vps() {
+    let nal_unit = parse(VPS, &mut ParameterSets::new());
+    assert_eq!(nal_unit.size, VPS.len());
+    let Payload::VPS(vps) = nal_unit.payload else {
+        panic!("not a VPS");
+    };
+    assert_eq!(vps.video_parameter_set_id, 0);
+    assert!(vps.base_layer_internal && vps.base_layer_available);
+    assert_eq!((vps.max_layers, vps.max_sub_layers), (1, 1));
+    let ptl = &vps.profile_tier_level;
+    assert_eq!((ptl.profile_idc, ptl.profile_name()), (1, "Main"));
+    assert_eq!(ptl.profile_compatibility_flags, 0x6000_0000);
+    assert!(ptl.progressive_source && ptl.frame_only_constraint);
+    assert_eq!(ptl.tier_name(), "Main");
+    assert_eq!((ptl.level_idc, ptl.level()), (93, 3.1));
+    assert_eq!(vps.sub_layer_ordering.max_dec_pic_buffering, 5);
+    assert_eq!(vps.sub_layer_ordering.max_num_reorder_pics, 2);
+    assert_eq!(vps.num_layer_sets, 1);
+    assert_eq!(vps.timing.unwrap().frame_rate(), Some((60000, 1001)));
+}
+
+#[test]
+fn sps() {
+    let mut parameter_sets = ParameterSets::new();
+    let Payload::SPS(sps) = parse(SPS, &mut parameter_sets).payload else {
+        panic!("not an SPS");
+    };
+    assert_eq!(parameter_sets.sps.get(&0), Some(&sps));
+    assert_eq!(sps.profile_tier_level.profile_name(), "Main 10");
+    assert_eq!(sps.profile_tier_level.level(), 5.1);
+    assert_eq!(sps.chroma_format_name(), "4:2:0");
+    assert_eq!(
+        (
+            sps.pic_width_in_luma_samples,
+            sps.pic_height_in_luma_samples
+        ),
+        (1920, 1088)
+    );
+    assert_eq!(
+        sps.conformance_window,
+        Some(Window {
+            left_offset: 0,
+            right_offset: 0,
+            top_offset: 0,
+            bottom_offset: 4
+        })
+    );
+    assert_eq!((sps.width(), sps.height()), (1920, 1080));
+    assert_eq!((sps.bit_depth_luma, sps.bit_depth_chroma), (10, 10));
+    assert_eq!(sps.log2_max_pic_order_cnt_lsb, 8);
+    assert_eq!(sps.log2_ctb_size, 6);
+    assert_eq!(sps.pic_size_in_ctbs(), 30 * 17);
+    assert!(sps.amp_enabled && sps.sample_adaptive_offset_enabled && !sps.pcm_enabled);
+    assert_eq!(
+        sps.short_term_ref_pic_sets,
+        [
+            ShortTermRefPicSet {
+                delta_poc_s0: vec![-1],
+                used_by_curr_pic_s0: vec![true],
+                ..Default::default()
+            },
+            // predicted from the first with deltaRps -1
+            ShortTermRefPicSet {
+                delta_poc_s0: vec![-1, -2],
+                used_by_curr_pic_s0: vec![true, true],
+                ..Default::default()
+            },
+        ]
+    );
+    assert_eq!(sps.long_term_ref_pics, None);
+    let vui = sps.vui.as_ref().unwrap();
+    assert_eq!(vui.sample_aspect_ratio, Some((1, 1)));
+    let colour = sps.colour_description().unwrap();
+    assert_eq!(
+        (colour.primaries_name(), colour.transfer_name()),
+        ("BT.2020", "PQ")
+    );
+    assert_eq!(colour.matrix_name(), "BT.2020 NCL");
+    assert!(colour.is_hdr());
+    assert_eq!(sps.frame_rate(), Some((60000, 1001)));
+}
+
+#[test]
+fn pps() {
+    let Payload::PPS(pps) = parse(PPS, &mut ParameterSets::new()).payload else {
+        panic!("not a PPS");
+    };
+    assert_eq!((pps.pic_parameter_set_id, pps.seq_parameter_set_id), (0, 0));
+    assert!(pps.dependent_slice_segments_enabled && !pps.output_flag_present);
+    assert!(pps.sign_data_hiding_enabled);
+    assert_eq!(pps.init_qp, 22);
+    assert_eq!(pps.diff_cu_qp_delta_depth, Some(1));
+    assert_eq!((pps.cb_qp_offset, pps.cr_qp_offset), (-2, 1));
+    let tiles = pps.tiles.as_ref().unwrap();
+    assert_eq!((tiles.num_tile_columns, tiles.num_tile_rows), (2, 1));
+    assert!(tiles.uniform_spacing);
+    let deblocking = pps.deblocking_filter_control.as_ref().unwrap();
+    assert_eq!(
+        (deblocking.beta_offset_div2, deblocking.tc_offset_div2),
+        (-1, 2)
+    );
+    assert_eq!(pps.log2_parallel_merge_level, 2);
+}
+
+#[test]
+fn broken_parameter_sets_are_kept_as_rbsp() {
+    let mut parameter_sets = ParameterSets::new();
+    let nal_unit = parse(&SPS[..12], &mut parameter_sets);
+    assert!(matches!(nal_unit.payload, Payload::Other(_)));
+    assert!(parameter_sets.sps.is_empty());
+}
+
+#[test]
+fn slice_segment_headers() {
+    let mut parameter_sets = ParameterSets::new();
+    for data in [VPS, SPS, PPS] {
+        parse(data, &mut parameter_sets);
+    }
+    let slice = |data: &[u8], parameter_sets: &mut ParameterSets| match parse(data, parameter_sets)
+        .payload
+    {
+        Payload::Slice(slice) => slice,
+        payload => panic!("{:?} is not a slice", payload),
+    };
+    let idr = slice(IDR_SLICE, &mut parameter_sets).unwrap();
+    assert!(idr.first_slice_segment_in_pic);
+    assert_eq!(idr.no_output_of_prior_pics, Some(false));
+    assert_eq!(idr.slice_type, Some(SliceType::I));
+    assert_eq!(idr.pic_order_cnt_lsb, None);
+    // the address takes Ceil(Log2(510)) bits
+    let dependent = slice(DEPENDENT_SLICE, &mut parameter_sets).unwrap();
+    assert!(dependent.dependent_slice_segment);
+    assert_eq!(dependent.slice_segment_address, 300);
+    assert_eq!(dependent.slice_type, None);
+    let trail = slice(TRAIL_SLICE, &mut parameter_sets).unwrap();
+    assert_eq!(trail.no_output_of_prior_pics, None);
+    assert_eq!(trail.slice_type, Some(SliceType::B));
+    assert_eq!(trail.pic_order_cnt_lsb, Some(5));
+    assert!(trail.pic_output);
+    assert_eq!(slice(SLICE_WITHOUT_PPS, &mut parameter_sets), None);
+    // before the parameter sets arrive, slices can't be parsed
+    assert_eq!(slice(IDR_SLICE, &mut ParameterSets::new()), None);
+}
+
+#[test]
+fn sei_messages() {
+    let nal_unit = parse(SEI, &mut ParameterSets::new());
+    let Payload::SEI(messages) = nal_unit.payload else {
+        panic!("not an SEI");
+    };
+    let [SEIMessage::MasteringDisplayColourVolume(mdcv), SEIMessage::ContentLightLevel(cll), SEIMessage::Other(300, other)] =
+        &messages[..]
+    else {
+        panic!("{:?}", messages);
+    };
+    assert_eq!(
+        mdcv.display_primaries,
+        [(13250, 34500), (7500, 3000), (34000, 16000)]
+    );
+    assert_eq!(mdcv.white_point, (15635, 16450));
+    assert_eq!(
+        (
+            mdcv.max_display_mastering_luminance,
+            mdcv.min_display_mastering_luminance
+        ),
+        (10_000_000, 50)
+    );
+    assert_eq!(
+        (cll.max_content_light_level, cll.max_pic_average_light_level),
+        (1000, 400)
+    );
+    assert_eq!(other, &[0x12]);
+}
+
+#[test]
+fn byte_stream() {
+    // an access unit delimiter, the parameter sets and two pictures, with trailing zeros
+    let mut data = Vec::new();
+    for nal_unit in [
+        &[0x46, 0x01, 0x50][..],
+        VPS,
+        SPS,
+        PPS,
+        IDR_SLICE,
+        TRAIL_SLICE,
+        DEPENDENT_SLICE,
+    ] {
+        data.extend([0, 0, 0, 1]);
+        data.extend(nal_unit);
+    }
+    data.extend([0, 0]);
+    let mut nal_units = NALUnitIterator::new(Box::new(Cursor::new(data)));
+    let first = nal_units.next().unwrap();
+    assert!(matches!(first.payload, Payload::AUD(2)));
+    let rest: Vec<NALUnit> = nal_units.by_ref().collect();
+    let types: Vec<&str> = rest.iter().map(|n| n.header.type_name()).collect();
+    assert_eq!(
+        types,
+        ["VPS", "SPS", "PPS", "IDR_W_RADL", "TRAIL_R", "TRAIL_R"]
+    );
+    let sizes: Vec<usize> = rest.iter().map(|n| n.size).collect();
+    assert_eq!(sizes, [VPS.len(), SPS.len(), PPS.len(), 5, 6, 6]);
+    assert!(rest[3..]
+        .iter()
+        .all(|n| matches!(n.payload, Payload::Slice(Some(_)))));
+    assert_eq!(nal_units.parameter_sets().get(0).unwrap().1.width(), 1920);
+}
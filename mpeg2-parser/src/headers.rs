@@ -0,0 +1,577 @@
+// The headers of an MPEG-2 video (ISO/IEC 13818-2) elementary stream; every unit starts
+// with the byte following the 0x000001 start code prefix
+use super::stream::{stream, Stream};
+use std::fmt;
+use winnow::{binary::bits, combinator, error, token, IResult, Parser};
+
+type BitInput<'i> = (Stream<'i>, usize);
+type BitResult<'i, O> = IResult<BitInput<'i>, O, error::Error<BitInput<'i>>>;
+
+pub const PICTURE_START_CODE: u8 = 0x00;
+pub const USER_DATA_START_CODE: u8 = 0xb2;
+pub const SEQUENCE_HEADER_CODE: u8 = 0xb3;
+pub const SEQUENCE_ERROR_CODE: u8 = 0xb4;
+pub const EXTENSION_START_CODE: u8 = 0xb5;
+pub const SEQUENCE_END_CODE: u8 = 0xb7;
+pub const GROUP_START_CODE: u8 = 0xb8;
+
+const FRAME_RATES: [(u32, u32); 8] = [
+    (24000, 1001),
+    (24, 1),
+    (25, 1),
+    (30000, 1001),
+    (30, 1),
+    (50, 1),
+    (60000, 1001),
+    (60, 1),
+];
+
+fn marker_bit(input: BitInput) -> BitResult<()> {
+    bits::tag(1_u8, 1_usize).void().parse_next(input)
+}
+
+fn quantiser_matrix(input: BitInput) -> BitResult<Option<Vec<u8>>> {
+    let (input, load) = bits::bool.parse_next(input)?;
+    combinator::cond(
+        load,
+        combinator::repeat(64, bits::take::<_, u8, _, _>(8_usize)),
+    )
+    .parse_next(input)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceHeader {
+    pub horizontal_size: u16,
+    pub vertical_size: u16,
+    pub aspect_ratio_information: u8,
+    pub frame_rate_code: u8,
+    /// In units of 400 bit/s
+    pub bit_rate_value: u32,
+    /// In units of 16 kbit
+    pub vbv_buffer_size_value: u16,
+    pub constrained_parameters: bool,
+    pub intra_quantiser_matrix: Option<Vec<u8>>,
+    pub non_intra_quantiser_matrix: Option<Vec<u8>>,
+}
+
+impl SequenceHeader {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (
+            input,
+            (
+                horizontal_size,
+                vertical_size,
+                aspect_ratio_information,
+                frame_rate_code,
+                bit_rate_value,
+                _,
+                vbv_buffer_size_value,
+                constrained_parameters,
+                intra_quantiser_matrix,
+                non_intra_quantiser_matrix,
+            ),
+        ) = (
+            bits::take(12_usize),
+            bits::take(12_usize),
+            bits::take(4_usize),
+            bits::take(4_usize),
+            bits::take(18_usize),
+            marker_bit,
+            bits::take(10_usize),
+            bits::bool,
+            quantiser_matrix,
+            quantiser_matrix,
+        )
+            .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                horizontal_size,
+                vertical_size,
+                aspect_ratio_information,
+                frame_rate_code,
+                bit_rate_value,
+                vbv_buffer_size_value,
+                constrained_parameters,
+                intra_quantiser_matrix,
+                non_intra_quantiser_matrix,
+            },
+        ))
+    }
+
+    /// (numerator, denominator) without the sequence extension's frame_rate_extension
+    pub fn frame_rate(&self) -> Option<(u32, u32)> {
+        FRAME_RATES
+            .get((self.frame_rate_code as usize).checked_sub(1)?)
+            .copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaFormat {
+    Reserved,
+    YUV420,
+    YUV422,
+    YUV444,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceExtension {
+    pub profile_and_level_indication: u8,
+    pub progressive_sequence: bool,
+    pub chroma_format: ChromaFormat,
+    pub horizontal_size_extension: u8,
+    pub vertical_size_extension: u8,
+    pub bit_rate_extension: u16,
+    pub vbv_buffer_size_extension: u8,
+    pub low_delay: bool,
+    pub frame_rate_extension_n: u8,
+    pub frame_rate_extension_d: u8,
+}
+
+impl SequenceExtension {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (
+            input,
+            (
+                profile_and_level_indication,
+                progressive_sequence,
+                chroma_format,
+                horizontal_size_extension,
+                vertical_size_extension,
+                bit_rate_extension,
+                _,
+                vbv_buffer_size_extension,
+                low_delay,
+                frame_rate_extension_n,
+                frame_rate_extension_d,
+            ),
+        ) = (
+            bits::take(8_usize),
+            bits::bool,
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::take(2_usize),
+            bits::take(2_usize),
+            bits::take(12_usize),
+            marker_bit,
+            bits::take(8_usize),
+            bits::bool,
+            bits::take(2_usize),
+            bits::take(5_usize),
+        )
+            .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                profile_and_level_indication,
+                progressive_sequence,
+                chroma_format: match chroma_format {
+                    1 => ChromaFormat::YUV420,
+                    2 => ChromaFormat::YUV422,
+                    3 => ChromaFormat::YUV444,
+                    _ => ChromaFormat::Reserved,
+                },
+                horizontal_size_extension,
+                vertical_size_extension,
+                bit_rate_extension,
+                vbv_buffer_size_extension,
+                low_delay,
+                frame_rate_extension_n,
+                frame_rate_extension_d,
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColourDescription {
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceDisplayExtension {
+    pub video_format: u8,
+    pub colour_description: Option<ColourDescription>,
+    pub display_horizontal_size: u16,
+    pub display_vertical_size: u16,
+}
+
+impl SequenceDisplayExtension {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (video_format, colour_description)) =
+            (bits::take(3_usize), bits::bool).parse_next(input)?;
+        let (input, colour_description) = combinator::cond(
+            colour_description,
+            (
+                bits::take(8_usize),
+                bits::take(8_usize),
+                bits::take(8_usize),
+            )
+                .map(
+                    |(colour_primaries, transfer_characteristics, matrix_coefficients)| {
+                        ColourDescription {
+                            colour_primaries,
+                            transfer_characteristics,
+                            matrix_coefficients,
+                        }
+                    },
+                ),
+        )
+        .parse_next(input)?;
+        let (input, (display_horizontal_size, _, display_vertical_size)) =
+            (bits::take(14_usize), marker_bit, bits::take(14_usize)).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                video_format,
+                colour_description,
+                display_horizontal_size,
+                display_vertical_size,
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictureStructure {
+    Reserved,
+    TopField,
+    BottomField,
+    Frame,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureCodingExtension {
+    /// [forward horizontal, forward vertical, backward horizontal, backward vertical]
+    pub f_code: [u8; 4],
+    pub intra_dc_precision: u8,
+    pub picture_structure: PictureStructure,
+    pub top_field_first: bool,
+    pub frame_pred_frame_dct: bool,
+    pub concealment_motion_vectors: bool,
+    pub q_scale_type: bool,
+    pub intra_vlc_format: bool,
+    pub alternate_scan: bool,
+    pub repeat_first_field: bool,
+    pub chroma_420_type: bool,
+    pub progressive_frame: bool,
+    pub composite_display_flag: bool,
+}
+
+impl PictureCodingExtension {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (
+            input,
+            (
+                f_code,
+                intra_dc_precision,
+                picture_structure,
+                top_field_first,
+                frame_pred_frame_dct,
+                concealment_motion_vectors,
+                q_scale_type,
+                intra_vlc_format,
+                alternate_scan,
+                repeat_first_field,
+                chroma_420_type,
+                progressive_frame,
+                composite_display_flag,
+            ),
+        ) = (
+            (
+                bits::take(4_usize),
+                bits::take(4_usize),
+                bits::take(4_usize),
+                bits::take(4_usize),
+            ),
+            bits::take(2_usize),
+            bits::take::<_, u8, _, _>(2_usize),
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+            bits::bool,
+        )
+            .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                f_code: [f_code.0, f_code.1, f_code.2, f_code.3],
+                intra_dc_precision,
+                picture_structure: match picture_structure {
+                    1 => PictureStructure::TopField,
+                    2 => PictureStructure::BottomField,
+                    3 => PictureStructure::Frame,
+                    _ => PictureStructure::Reserved,
+                },
+                top_field_first,
+                frame_pred_frame_dct,
+                concealment_motion_vectors,
+                q_scale_type,
+                intra_vlc_format,
+                alternate_scan,
+                repeat_first_field,
+                chroma_420_type,
+                progressive_frame,
+                composite_display_flag,
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeCode {
+    pub drop_frame: bool,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub pictures: u8,
+}
+
+impl fmt::Display for TimeCode {
+    /// SMPTE notation, `;` before the frames for drop frame time codes
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours,
+            self.minutes,
+            self.seconds,
+            if self.drop_frame { ';' } else { ':' },
+            self.pictures
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GOPHeader {
+    pub time_code: TimeCode,
+    pub closed_gop: bool,
+    pub broken_link: bool,
+}
+
+impl GOPHeader {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (drop_frame, hours, minutes, _, seconds, pictures, closed_gop, broken_link)) =
+            (
+                bits::bool,
+                bits::take(5_usize),
+                bits::take(6_usize),
+                marker_bit,
+                bits::take(6_usize),
+                bits::take(6_usize),
+                bits::bool,
+                bits::bool,
+            )
+                .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                time_code: TimeCode {
+                    drop_frame,
+                    hours,
+                    minutes,
+                    seconds,
+                    pictures,
+                },
+                closed_gop,
+                broken_link,
+            },
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictureCodingType {
+    I,
+    P,
+    B,
+    /// D-pictures (MPEG-1 only) and reserved values
+    Other(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PictureHeader {
+    pub temporal_reference: u16,
+    pub picture_coding_type: PictureCodingType,
+    pub vbv_delay: u16,
+    /// (full_pel_forward_vector, forward_f_code), P and B pictures only
+    pub forward: Option<(bool, u8)>,
+    /// (full_pel_backward_vector, backward_f_code), B pictures only
+    pub backward: Option<(bool, u8)>,
+}
+
+impl PictureHeader {
+    fn parse_bits(input: BitInput) -> BitResult<Self> {
+        let (input, (temporal_reference, picture_coding_type, vbv_delay)) = (
+            bits::take(10_usize),
+            bits::take::<_, u8, _, _>(3_usize),
+            bits::take(16_usize),
+        )
+            .parse_next(input)?;
+        let picture_coding_type = match picture_coding_type {
+            1 => PictureCodingType::I,
+            2 => PictureCodingType::P,
+            3 => PictureCodingType::B,
+            other => PictureCodingType::Other(other),
+        };
+        let (input, forward) = combinator::cond(
+            matches!(
+                picture_coding_type,
+                PictureCodingType::P | PictureCodingType::B
+            ),
+            (bits::bool, bits::take(3_usize)),
+        )
+        .parse_next(input)?;
+        let (input, backward) = combinator::cond(
+            picture_coding_type == PictureCodingType::B,
+            (bits::bool, bits::take(3_usize)),
+        )
+        .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                temporal_reference,
+                picture_coding_type,
+                vbv_delay,
+                forward,
+                backward,
+            },
+        ))
+    }
+}
+
+/// Skips the extension_start_code_identifier and parses the rest of the extension
+fn extension_bits<O>(
+    input: Stream,
+    parser: fn(BitInput) -> BitResult<O>,
+    to_unit: fn(O) -> Unit,
+) -> IResult<Stream, Unit> {
+    bits::bits::<_, _, error::Error<(_, usize)>, _, _>(
+        (bits::take::<_, u8, _, _>(4_usize), parser).map(|(_, o)| to_unit(o)),
+    )
+    .parse_next(input)
+}
+
+pub enum Unit {
+    Picture(PictureHeader),
+    /// slice_vertical_position (the start code value, 0x01..=0xaf) and the slice size
+    Slice(u8, usize),
+    UserData(Vec<u8>),
+    SequenceHeader(SequenceHeader),
+    SequenceExtension(SequenceExtension),
+    SequenceDisplayExtension(SequenceDisplayExtension),
+    PictureCodingExtension(PictureCodingExtension),
+    /// extension_start_code_identifier and the rest of the extension
+    OtherExtension(u8, Vec<u8>),
+    SequenceError,
+    SequenceEnd,
+    GOP(GOPHeader),
+    /// Start code value and data of units this parser doesn't know (or couldn't parse)
+    Unknown(u8, Vec<u8>),
+}
+
+impl Unit {
+    const SEQUENCE_EXTENSION_ID: u8 = 1;
+    const SEQUENCE_DISPLAY_EXTENSION_ID: u8 = 2;
+    const PICTURE_CODING_EXTENSION_ID: u8 = 8;
+
+    fn parse_extension(input: Stream) -> IResult<Stream, Self> {
+        let (_, id) = combinator::peek(winnow::binary::u8)
+            .map(|byte| byte >> 4)
+            .parse_next(input)?;
+        match id {
+            Self::SEQUENCE_EXTENSION_ID => extension_bits(
+                input,
+                SequenceExtension::parse_bits,
+                Self::SequenceExtension,
+            ),
+            Self::SEQUENCE_DISPLAY_EXTENSION_ID => extension_bits(
+                input,
+                SequenceDisplayExtension::parse_bits,
+                Self::SequenceDisplayExtension,
+            ),
+            Self::PICTURE_CODING_EXTENSION_ID => extension_bits(
+                input,
+                PictureCodingExtension::parse_bits,
+                Self::PictureCodingExtension,
+            ),
+            _ => {
+                let (input, rest) = combinator::rest.parse_next(input)?;
+                Ok((input, Self::OtherExtension(id, rest.to_vec())))
+            }
+        }
+    }
+
+    /// Parses the data of a start code unit (starting with the start code value)
+    pub fn parse(data: &[u8]) -> Self {
+        let Some((&code, rest)) = data.split_first() else {
+            return Self::Unknown(PICTURE_START_CODE, Vec::new());
+        };
+        let input = stream(rest);
+        let result: IResult<Stream, Self> = match code {
+            PICTURE_START_CODE => bits::bits::<_, _, error::Error<(_, usize)>, _, _>(
+                PictureHeader::parse_bits.map(Self::Picture),
+            )
+            .parse_next(input),
+            0x01..=0xaf => Ok((input, Self::Slice(code, rest.len()))),
+            USER_DATA_START_CODE => token::take_while(0.., |_| true)
+                .map(|data: &[u8]| Self::UserData(data.to_vec()))
+                .parse_next(input),
+            SEQUENCE_HEADER_CODE => bits::bits::<_, _, error::Error<(_, usize)>, _, _>(
+                SequenceHeader::parse_bits.map(Self::SequenceHeader),
+            )
+            .parse_next(input),
+            EXTENSION_START_CODE => Self::parse_extension(input),
+            SEQUENCE_ERROR_CODE => Ok((input, Self::SequenceError)),
+            SEQUENCE_END_CODE => Ok((input, Self::SequenceEnd)),
+            GROUP_START_CODE => bits::bits::<_, _, error::Error<(_, usize)>, _, _>(
+                GOPHeader::parse_bits.map(Self::GOP),
+            )
+            .parse_next(input),
+            _ => Ok((input, Self::Unknown(code, rest.to_vec()))),
+        };
+        match result {
+            Ok((_, unit)) => unit,
+            Err(_) => Self::Unknown(code, rest.to_vec()),
+        }
+    }
+}
+
+impl fmt::Debug for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Picture(header) => write!(f, "Picture({:?})", header),
+            Self::Slice(position, size) => write!(f, "Slice({}, {} bytes)", position, size),
+            Self::UserData(data) => {
+                write!(f, "UserData({:x?})", &data[..(16.min(data.len()))])
+            }
+            Self::SequenceHeader(header) => write!(f, "SequenceHeader({:?})", header),
+            Self::SequenceExtension(extension) => write!(f, "{:?}", extension),
+            Self::SequenceDisplayExtension(extension) => write!(f, "{:?}", extension),
+            Self::PictureCodingExtension(extension) => write!(f, "{:?}", extension),
+            Self::OtherExtension(id, data) => write!(
+                f,
+                "OtherExtension({}, {:x?})",
+                id,
+                &data[..(16.min(data.len()))]
+            ),
+            Self::SequenceError => write!(f, "SequenceError"),
+            Self::SequenceEnd => write!(f, "SequenceEnd"),
+            Self::GOP(header) => write!(f, "{:?}", header),
+            Self::Unknown(code, data) => write!(
+                f,
+                "Unknown(0x{:02x}, {:x?})",
+                code,
+                &data[..(16.min(data.len()))]
+            ),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// MPEG-2 video (ISO/IEC 13818-2), stream_type 0x02 (and MPEG-1 video, 0x01)
+pub mod headers;
+pub mod stream;
+
+use h264_parser::startcode::StartCodeIterator;
+use headers::{
+    GOPHeader, PictureCodingExtension, PictureHeader, PictureStructure, SequenceDisplayExtension,
+    SequenceExtension, SequenceHeader, Unit,
+};
+use mts_parser::pes_reader::PESReader;
+use mts_parser::stream_packet::PESPacket;
+use mts_parser::timestamp::{Timestamp, Unwrapper};
+use std::fmt;
+
+/// The sequence header and its extensions, as they apply to the following pictures
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    pub header: SequenceHeader,
+    /// Absent in MPEG-1 streams
+    pub extension: Option<SequenceExtension>,
+    pub display_extension: Option<SequenceDisplayExtension>,
+}
+
+impl Sequence {
+    pub fn width(&self) -> u32 {
+        let extension = self
+            .extension
+            .as_ref()
+            .map_or(0, |e| e.horizontal_size_extension as u32);
+        (extension << 12) | self.header.horizontal_size as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        let extension = self
+            .extension
+            .as_ref()
+            .map_or(0, |e| e.vertical_size_extension as u32);
+        (extension << 12) | self.header.vertical_size as u32
+    }
+
+    /// Frames per second as (numerator, denominator)
+    pub fn frame_rate(&self) -> Option<(u32, u32)> {
+        let (numerator, denominator) = self.header.frame_rate()?;
+        Some(match &self.extension {
+            Some(e) => (
+                numerator * (e.frame_rate_extension_n as u32 + 1),
+                denominator * (e.frame_rate_extension_d as u32 + 1),
+            ),
+            None => (numerator, denominator),
+        })
+    }
+
+    /// Bits per second
+    pub fn bit_rate(&self) -> u64 {
+        let extension = self
+            .extension
+            .as_ref()
+            .map_or(0, |e| e.bit_rate_extension as u64);
+        ((extension << 18) | self.header.bit_rate_value as u64) * 400
+    }
+
+    pub fn progressive(&self) -> bool {
+        match &self.extension {
+            Some(extension) => extension.progressive_sequence,
+            // MPEG-1 has no interlaced pictures
+            None => true,
+        }
+    }
+}
+
+/// One coded picture (frame or field) with the headers that came with it
+#[derive(Clone)]
+pub struct Picture {
+    /// Offset of the first start code of the picture (including its sequence and GOP
+    /// headers) in the elementary stream
+    pub offset: u64,
+    /// Unwrapped PTS / DTS of the PES packet this picture is the first picture of
+    pub pts: Option<Timestamp>,
+    pub dts: Option<Timestamp>,
+    /// Set if a sequence header came right before this picture
+    pub sequence: Option<Sequence>,
+    pub gop: Option<GOPHeader>,
+    pub header: PictureHeader,
+    pub coding_extension: Option<PictureCodingExtension>,
+    pub user_data: Vec<Vec<u8>>,
+    pub slices: usize,
+    /// Total size of the slices in bytes
+    pub size: usize,
+}
+
+impl Picture {
+    pub fn structure(&self) -> PictureStructure {
+        self.coding_extension
+            .as_ref()
+            .map_or(PictureStructure::Frame, |e| e.picture_structure)
+    }
+
+    /// Number of fields this picture is displayed for (2 or 3 for frames, 1 for fields),
+    /// ignoring the doubling and tripling of repeat_first_field in progressive sequences
+    pub fn fields(&self) -> u8 {
+        match (self.structure(), &self.coding_extension) {
+            (PictureStructure::Frame, Some(e)) if e.repeat_first_field => 3,
+            (PictureStructure::Frame, _) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Debug for Picture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let timestamp = |t: Option<Timestamp>| t.map_or("-".to_string(), |t| t.to_string());
+        write!(
+            f,
+            "Picture({:?}, temporal_reference={}, {:?}, pts={}, dts={}, slices={}, size={}",
+            self.header.picture_coding_type,
+            self.header.temporal_reference,
+            self.structure(),
+            timestamp(self.pts),
+            timestamp(self.dts),
+            self.slices,
+            self.size,
+        )?;
+        if let Some(e) = &self.coding_extension {
+            write!(
+                f,
+                ", top_field_first={}, repeat_first_field={}, progressive_frame={}",
+                e.top_field_first, e.repeat_first_field, e.progressive_frame
+            )?;
+        }
+        if let Some(gop) = &self.gop {
+            write!(f, ", GOP {} closed={}", gop.time_code, gop.closed_gop)?;
+        }
+        if self.sequence.is_some() {
+            write!(f, ", new sequence")?;
+        }
+        if !self.user_data.is_empty() {
+            write!(f, ", {} user data", self.user_data.len())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Collects the start code units of an MPEG-2 video stream into pictures
+pub struct PictureIterator<I: Iterator<Item = PESPacket>> {
+    units: StartCodeIterator<PESReader<I>>,
+    unwrapper: Unwrapper,
+    /// The sequence that the following pictures belong to
+    sequence: Option<Sequence>,
+    /// Headers seen since the last picture
+    pending_sequence: Option<Sequence>,
+    pending_gop: Option<GOPHeader>,
+    pending_user_data: Vec<Vec<u8>>,
+    /// Offset of the first unit of the next picture
+    pending_offset: Option<u64>,
+    current: Option<Picture>,
+}
+
+impl<I: Iterator<Item = PESPacket>> PictureIterator<I> {
+    pub fn new(packets: I) -> Self {
+        Self {
+            units: StartCodeIterator::new(PESReader::new(packets)),
+            unwrapper: Unwrapper::new(),
+            sequence: None,
+            pending_sequence: None,
+            pending_gop: None,
+            pending_user_data: Vec::new(),
+            pending_offset: None,
+            current: None,
+        }
+    }
+
+    /// The most recent sequence header and extensions
+    pub fn sequence(&self) -> Option<&Sequence> {
+        self.sequence.as_ref()
+    }
+
+    fn start_picture(&mut self, offset: u64, header: PictureHeader) -> Option<Picture> {
+        let offset = self.pending_offset.take().unwrap_or(offset);
+        let mark = self.units.get_mut().take_timestamps_until(offset);
+        let dts = mark
+            .and_then(|m| m.dts)
+            .map(|dts| self.unwrapper.unwrap_90khz(dts));
+        let pts = mark
+            .and_then(|m| m.pts)
+            .map(|pts| self.unwrapper.unwrap_90khz(pts));
+        let picture = Picture {
+            offset,
+            pts,
+            dts,
+            sequence: self.pending_sequence.take(),
+            gop: self.pending_gop.take(),
+            header,
+            coding_extension: None,
+            user_data: std::mem::take(&mut self.pending_user_data),
+            slices: 0,
+            size: 0,
+        };
+        self.current.replace(picture)
+    }
+
+    /// Ends the current picture, since a header that starts a new access unit showed up
+    fn end_picture(&mut self, offset: u64) -> Option<Picture> {
+        self.pending_offset.get_or_insert(offset);
+        self.current.take()
+    }
+}
+
+impl<I: Iterator<Item = PESPacket>> Iterator for PictureIterator<I> {
+    type Item = Picture;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(unit) = self.units.next() else {
+                return self.current.take();
+            };
+            // offset of the start code prefix
+            let offset = unit.offset.saturating_sub(3);
+            let finished = match Unit::parse(&unit.data) {
+                Unit::SequenceHeader(header) => {
+                    let finished = self.end_picture(offset);
+                    self.pending_sequence = Some(Sequence {
+                        header,
+                        extension: None,
+                        display_extension: None,
+                    });
+                    self.sequence = self.pending_sequence.clone();
+                    finished
+                }
+                Unit::SequenceExtension(extension) => {
+                    for sequence in [&mut self.pending_sequence, &mut self.sequence] {
+                        if let Some(sequence) = sequence.as_mut() {
+                            sequence.extension = Some(extension.clone());
+                        }
+                    }
+                    None
+                }
+                Unit::SequenceDisplayExtension(extension) => {
+                    for sequence in [&mut self.pending_sequence, &mut self.sequence] {
+                        if let Some(sequence) = sequence.as_mut() {
+                            sequence.display_extension = Some(extension.clone());
+                        }
+                    }
+                    None
+                }
+                Unit::GOP(header) => {
+                    let finished = self.end_picture(offset);
+                    self.pending_gop = Some(header);
+                    finished
+                }
+                Unit::Picture(header) => self.start_picture(offset, header),
+                Unit::PictureCodingExtension(extension) => {
+                    if let Some(picture) = self.current.as_mut() {
+                        picture.coding_extension = Some(extension);
+                    }
+                    None
+                }
+                Unit::UserData(data) => {
+                    // user data after a sequence or GOP header goes with the next picture
+                    match self.current.as_mut() {
+                        Some(picture) => picture.user_data.push(data),
+                        None => self.pending_user_data.push(data),
+                    }
+                    None
+                }
+                Unit::Slice(_, size) => {
+                    if let Some(picture) = self.current.as_mut() {
+                        picture.slices += 1;
+                        picture.size += size + 4;
+                    }
+                    None
+                }
+                Unit::SequenceEnd => self.end_picture(offset),
+                _ => None,
+            };
+            if finished.is_some() {
+                return finished;
+            }
+        }
+    }
+}
@@ -0,0 +1,561 @@
+//
+// Audio/video synchronisation and interleaving analysis, based on PES timestamps only.
+//
+// Every audio stream is compared against the first video stream of its program:
+// - interleaving: the PTS of the most recent audio PES minus the PTS of the most recent video
+//   PES, at each point in the multiplex. A large value means a decoder starves on the stream
+//   that lags behind.
+// - drift: the decode time of the most recent audio PES minus that of the most recent video
+//   PES. The interleaving keeps this offset within bounds; its largest value in a window is
+//   when the audio is freshest, which is not affected by gaps in the audio. The A/V drift is
+//   how far that moves away from the value of the first window.
+// - discontinuity: a decode timestamp going backwards or jumping forward, per stream. The
+//   drift tracking of the streams involved restarts after a discontinuity.
+//
+use super::stream_packet::{PESPacket, StreamKind, StreamPacket};
+use super::timestamp::{MediaTimeline, Timestamp};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AVSyncConfig {
+    /// Length (in video time) of the windows the interleaving is summarised over
+    pub window: Timestamp,
+    /// A/V drift that gets reported
+    pub drift_threshold: Timestamp,
+    /// How far one stream may lag behind the other in the multiplex
+    pub interleave_gap_threshold: Timestamp,
+    /// Largest forward step between two consecutive decode timestamps of a stream
+    pub discontinuity_threshold: Timestamp,
+}
+
+impl Default for AVSyncConfig {
+    fn default() -> Self {
+        Self {
+            window: Timestamp::from_90khz(10 * 90_000),
+            drift_threshold: Timestamp::from_90khz(40 * 90),
+            interleave_gap_threshold: Timestamp::from_90khz(500 * 90),
+            discontinuity_threshold: Timestamp::from_90khz(1000 * 90),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AVSyncEvent {
+    Drift {
+        audio_pid: u16,
+        video_pid: u16,
+        at: Timestamp,
+        drift: Timestamp,
+    },
+    InterleaveGap {
+        starving_pid: u16,
+        leading_pid: u16,
+        at: Timestamp,
+        gap: Timestamp,
+    },
+    Discontinuity {
+        pid: u16,
+        at: Timestamp,
+        previous: Timestamp,
+    },
+}
+
+impl fmt::Display for AVSyncEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AVSyncEvent::Drift {
+                audio_pid,
+                video_pid,
+                at,
+                drift,
+            } => write!(
+                f,
+                "{} drift: audio pid(0x{:x}) is {:+.3}s off video pid(0x{:x})",
+                at,
+                audio_pid,
+                drift.as_secs_f64(),
+                video_pid,
+            ),
+            AVSyncEvent::InterleaveGap {
+                starving_pid,
+                leading_pid,
+                at,
+                gap,
+            } => write!(
+                f,
+                "{} interleaving: pid(0x{:x}) is {:.3}s behind pid(0x{:x})",
+                at,
+                starving_pid,
+                gap.as_secs_f64(),
+                leading_pid,
+            ),
+            AVSyncEvent::Discontinuity { pid, at, previous } => write!(
+                f,
+                "{} discontinuity: pid(0x{:x}) jumped {:+.3}s (from {})",
+                at,
+                pid,
+                (*at - *previous).as_secs_f64(),
+                previous,
+            ),
+        }
+    }
+}
+
+/// The decode timestamps of a stream
+#[derive(Debug, Clone, Default)]
+struct StreamClock {
+    last: Option<Timestamp>,
+    /// Position in the multiplex of the last PES and of the last discontinuity
+    updated: u64,
+    restarted: u64,
+    steps: Vec<Timestamp>,
+    nominal_step: Option<Timestamp>,
+}
+
+impl StreamClock {
+    const STEPS_FOR_NOMINAL: usize = 32;
+
+    /// Returns the previous decode time
+    fn add(&mut self, decode_time: Timestamp, position: u64) -> Option<Timestamp> {
+        self.updated = position;
+        self.last.replace(decode_time)
+    }
+
+    fn add_step(&mut self, step: Timestamp) {
+        if self.nominal_step.is_some() {
+            return;
+        }
+        self.steps.push(step);
+        if self.steps.len() == Self::STEPS_FOR_NOMINAL {
+            let mut counts: HashMap<Timestamp, usize> = HashMap::new();
+            for step in self.steps.iter() {
+                *counts.entry(*step).or_default() += 1;
+            }
+            self.nominal_step = counts
+                .into_iter()
+                .max_by_key(|(step, count)| (*count, *step))
+                .map(|(step, _)| step);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamSummary {
+    pub pid: u16,
+    pub kind: StreamKind,
+    pub stream_type: u8,
+    pub pes_count: usize,
+    pub first_pts: Option<Timestamp>,
+    pub last_pts: Option<Timestamp>,
+    pub discontinuities: usize,
+    /// Most common decode time step between PES packets
+    pub nominal_pes_duration: Option<Timestamp>,
+    clock: StreamClock,
+}
+
+/// Interleaving and drift of one audio stream against its video stream over one window
+#[derive(Debug, Clone)]
+pub struct OffsetWindow {
+    pub audio_pid: u16,
+    pub start: Timestamp,
+    pub min_interleave: Timestamp,
+    pub max_interleave: Timestamp,
+    pub mean_interleave: Timestamp,
+    /// Largest audio minus video decode time
+    pub offset: Timestamp,
+    /// Change of the offset since the first window after the start or a discontinuity
+    pub drift: Timestamp,
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone)]
+struct OffsetAccumulator {
+    start: Timestamp,
+    end: Timestamp,
+    min: Timestamp,
+    max: Timestamp,
+    sum_27mhz: i64,
+    offset: Timestamp,
+    samples: usize,
+}
+
+impl OffsetAccumulator {
+    fn new(start: Timestamp, interleave: Timestamp, offset: Timestamp) -> Self {
+        Self {
+            start,
+            end: start,
+            min: interleave,
+            max: interleave,
+            sum_27mhz: 0,
+            offset,
+            samples: 0,
+        }
+    }
+
+    fn add(&mut self, at: Timestamp, interleave: Timestamp, offset: Timestamp) {
+        self.end = at;
+        self.min = self.min.min(interleave);
+        self.max = self.max.max(interleave);
+        self.sum_27mhz += interleave.as_27mhz();
+        self.offset = self.offset.max(offset);
+        self.samples += 1;
+    }
+
+    fn finish(&self, audio_pid: u16, drift: Timestamp) -> OffsetWindow {
+        OffsetWindow {
+            audio_pid,
+            start: self.start,
+            min_interleave: self.min,
+            max_interleave: self.max,
+            mean_interleave: Timestamp::from_27mhz(self.sum_27mhz / self.samples.max(1) as i64),
+            offset: self.offset,
+            drift,
+            samples: self.samples,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AudioTrack {
+    video_pid: u16,
+    current_window: Option<OffsetAccumulator>,
+    /// Offset of the first window since the start or the last discontinuity
+    reference_offset: Option<Timestamp>,
+    drifting: bool,
+    audio_starving: bool,
+    video_starving: bool,
+}
+
+impl AudioTrack {
+    fn close_window(
+        &mut self,
+        audio_pid: u16,
+        config: &AVSyncConfig,
+        windows: &mut Vec<OffsetWindow>,
+        events: &mut Vec<AVSyncEvent>,
+    ) {
+        let Some(window) = self.current_window.take() else {
+            return;
+        };
+        if window.samples == 0 {
+            return;
+        }
+        let drift = window.offset - *self.reference_offset.get_or_insert(window.offset);
+        let drifting = drift.abs_diff(Timestamp::ZERO) > config.drift_threshold;
+        if drifting && !self.drifting {
+            events.push(AVSyncEvent::Drift {
+                audio_pid,
+                video_pid: self.video_pid,
+                at: window.end,
+                drift,
+            });
+        }
+        self.drifting = drifting;
+        windows.push(window.finish(audio_pid, drift));
+    }
+}
+
+pub struct AVSyncAnalyzer {
+    config: AVSyncConfig,
+    timeline: MediaTimeline,
+    streams: HashMap<u16, StreamSummary>,
+    audio_tracks: HashMap<u16, AudioTrack>,
+    video_for_program: HashMap<u16, u16>,
+    windows: Vec<OffsetWindow>,
+    events: Vec<AVSyncEvent>,
+    /// Number of PES packets seen
+    position: u64,
+}
+
+impl AVSyncAnalyzer {
+    pub fn new(config: AVSyncConfig) -> Self {
+        Self {
+            config,
+            timeline: MediaTimeline::new(),
+            streams: HashMap::new(),
+            audio_tracks: HashMap::new(),
+            video_for_program: HashMap::new(),
+            windows: Vec::new(),
+            events: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Feed every element coming out of `ElementIterator`
+    pub fn add_stream_packet(&mut self, pid: u16, stream_packet: &StreamPacket) {
+        match stream_packet {
+            StreamPacket::PMT(pmt) => {
+                self.timeline.add_pmt(pmt);
+                let program_number = pmt.psi_data.table_id_extension;
+                for esi in pmt.elementary_stream_info_data.iter() {
+                    let kind = esi.kind();
+                    self.streams
+                        .entry(esi.pid)
+                        .or_insert_with(|| StreamSummary {
+                            pid: esi.pid,
+                            kind,
+                            stream_type: esi.stream_type,
+                            pes_count: 0,
+                            first_pts: None,
+                            last_pts: None,
+                            discontinuities: 0,
+                            nominal_pes_duration: None,
+                            clock: StreamClock::default(),
+                        });
+                    if kind == StreamKind::Video {
+                        self.video_for_program
+                            .entry(program_number)
+                            .or_insert(esi.pid);
+                    }
+                }
+                let Some(video_pid) = self.video_for_program.get(&program_number) else {
+                    return;
+                };
+                for esi in pmt.elementary_stream_info_data.iter() {
+                    if esi.kind() == StreamKind::Audio {
+                        self.audio_tracks.entry(esi.pid).or_insert(AudioTrack {
+                            video_pid: *video_pid,
+                            current_window: None,
+                            reference_offset: None,
+                            drifting: false,
+                            audio_starving: false,
+                            video_starving: false,
+                        });
+                    }
+                }
+            }
+            StreamPacket::PES(pes) => self.add_pes(pid, pes),
+            _ => (),
+        }
+    }
+
+    fn add_pes(&mut self, pid: u16, pes: &PESPacket) {
+        let Some(entry) = self.timeline.add_pes(pid, pes) else {
+            return;
+        };
+        let Some(stream) = self.streams.get_mut(&pid) else {
+            return;
+        };
+        self.position += 1;
+        stream.pes_count += 1;
+        if let Some(pts) = entry.pts {
+            stream.first_pts.get_or_insert(pts);
+            stream.last_pts = Some(pts);
+        }
+        let Some(decode_time) = entry.decode_time() else {
+            return;
+        };
+        let kind = stream.kind;
+        if let Some(previous) = stream.clock.add(decode_time, self.position) {
+            let step = decode_time - previous;
+            if step < Timestamp::ZERO || step > self.config.discontinuity_threshold {
+                stream.discontinuities += 1;
+                stream.clock.restarted = self.position;
+                self.events.push(AVSyncEvent::Discontinuity {
+                    pid,
+                    at: decode_time,
+                    previous,
+                });
+                for (audio_pid, track) in self.audio_tracks.iter_mut() {
+                    if *audio_pid == pid || track.video_pid == pid {
+                        track.close_window(
+                            *audio_pid,
+                            &self.config,
+                            &mut self.windows,
+                            &mut self.events,
+                        );
+                        track.reference_offset = None;
+                    }
+                }
+            } else if step > Timestamp::ZERO {
+                stream.clock.add_step(step);
+            }
+            stream.nominal_pes_duration = stream.clock.nominal_step;
+        }
+        let Some(pts) = entry.pts else {
+            return;
+        };
+        match kind {
+            StreamKind::Video => self.add_video_pts(pid, pts, decode_time),
+            StreamKind::Audio => self.add_audio_pts(pid, pts),
+            _ => (),
+        }
+    }
+
+    fn add_video_pts(&mut self, video_pid: u16, pts: Timestamp, decode_time: Timestamp) {
+        let Some(video) = self.streams.get(&video_pid) else {
+            return;
+        };
+        for (audio_pid, track) in self.audio_tracks.iter_mut() {
+            if track.video_pid != video_pid {
+                continue;
+            }
+            let Some(audio) = self.streams.get(audio_pid) else {
+                continue;
+            };
+            let (Some(audio_pts), Some(audio_decode_time)) = (audio.last_pts, audio.clock.last)
+            else {
+                continue;
+            };
+            // the audio from before a discontinuity of the video is not comparable
+            if audio.clock.updated < video.clock.restarted {
+                continue;
+            }
+            let interleave = audio_pts - pts;
+            let audio_starving = -interleave > self.config.interleave_gap_threshold;
+            if audio_starving && !track.audio_starving {
+                self.events.push(AVSyncEvent::InterleaveGap {
+                    starving_pid: *audio_pid,
+                    leading_pid: video_pid,
+                    at: pts,
+                    gap: -interleave,
+                });
+            }
+            track.audio_starving = audio_starving;
+
+            let window_ended = match &track.current_window {
+                Some(window) => pts - window.start >= self.config.window,
+                None => false,
+            };
+            if window_ended {
+                track.close_window(
+                    *audio_pid,
+                    &self.config,
+                    &mut self.windows,
+                    &mut self.events,
+                );
+            }
+            let offset = audio_decode_time - decode_time;
+            track
+                .current_window
+                .get_or_insert_with(|| OffsetAccumulator::new(pts, interleave, offset))
+                .add(pts, interleave, offset);
+        }
+    }
+
+    fn add_audio_pts(&mut self, audio_pid: u16, pts: Timestamp) {
+        let Some(track) = self.audio_tracks.get_mut(&audio_pid) else {
+            return;
+        };
+        let (Some(audio), Some(video)) = (
+            self.streams.get(&audio_pid),
+            self.streams.get(&track.video_pid),
+        ) else {
+            return;
+        };
+        let Some(video_pts) = video.last_pts else {
+            return;
+        };
+        if video.clock.updated < audio.clock.restarted {
+            return;
+        }
+        let gap = pts - video_pts;
+        let video_starving = gap > self.config.interleave_gap_threshold;
+        if video_starving && !track.video_starving {
+            self.events.push(AVSyncEvent::InterleaveGap {
+                starving_pid: track.video_pid,
+                leading_pid: audio_pid,
+                at: pts,
+                gap,
+            });
+        }
+        track.video_starving = video_starving;
+    }
+
+    pub fn finish(mut self) -> AVSyncReport {
+        for (audio_pid, track) in self.audio_tracks.iter_mut() {
+            track.close_window(
+                *audio_pid,
+                &self.config,
+                &mut self.windows,
+                &mut self.events,
+            );
+        }
+        self.windows.sort_by_key(|w| (w.audio_pid, w.start));
+        let mut start_offsets = Vec::new();
+        for (audio_pid, track) in self.audio_tracks.iter() {
+            let first_pts = |pid| self.streams.get(&pid).and_then(|s| s.first_pts);
+            if let (Some(audio), Some(video)) = (first_pts(*audio_pid), first_pts(track.video_pid))
+            {
+                start_offsets.push((*audio_pid, track.video_pid, audio - video));
+            }
+        }
+        start_offsets.sort_by_key(|o| o.0);
+        let mut streams: Vec<StreamSummary> = self.streams.into_values().collect();
+        streams.sort_by_key(|s| s.pid);
+        AVSyncReport {
+            streams,
+            start_offsets,
+            windows: self.windows,
+            events: self.events,
+        }
+    }
+}
+
+impl Default for AVSyncAnalyzer {
+    fn default() -> Self {
+        Self::new(AVSyncConfig::default())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AVSyncReport {
+    pub streams: Vec<StreamSummary>,
+    /// (audio pid, video pid, first audio PTS - first video PTS)
+    pub start_offsets: Vec<(u16, u16, Timestamp)>,
+    pub windows: Vec<OffsetWindow>,
+    pub events: Vec<AVSyncEvent>,
+}
+
+impl fmt::Display for AVSyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs_or_dash = |ts: Option<Timestamp>| {
+            ts.map_or("-".to_string(), |ts| format!("{:.3}s", ts.as_secs_f64()))
+        };
+        writeln!(f, "Streams:")?;
+        for stream in self.streams.iter() {
+            let or_dash = |ts: Option<Timestamp>| ts.map_or("-".to_string(), |ts| ts.to_string());
+            writeln!(
+                f,
+                "  pid(0x{:x}) type 0x{:02x} {:?}: {} PES of {}, PTS {} .. {}, {} discontinuities",
+                stream.pid,
+                stream.stream_type,
+                stream.kind,
+                stream.pes_count,
+                secs_or_dash(stream.nominal_pes_duration),
+                or_dash(stream.first_pts),
+                or_dash(stream.last_pts),
+                stream.discontinuities,
+            )?;
+        }
+        writeln!(f, "Start offsets:")?;
+        for (audio_pid, video_pid, offset) in self.start_offsets.iter() {
+            writeln!(
+                f,
+                "  audio pid(0x{:x}) - video pid(0x{:x}): {:+.3}s",
+                audio_pid,
+                video_pid,
+                offset.as_secs_f64()
+            )?;
+        }
+        writeln!(f, "Per window:")?;
+        for window in self.windows.iter() {
+            writeln!(
+                f,
+                "  {} audio pid(0x{:x}): interleave mean {:+.3}s, min {:+.3}s, max {:+.3}s, drift {:+.3}s",
+                window.start,
+                window.audio_pid,
+                window.mean_interleave.as_secs_f64(),
+                window.min_interleave.as_secs_f64(),
+                window.max_interleave.as_secs_f64(),
+                window.drift.as_secs_f64(),
+            )?;
+        }
+        writeln!(f, "Events:")?;
+        for event in self.events.iter() {
+            writeln!(f, "  {}", event)?;
+        }
+        Ok(())
+    }
+}
@@ -43,7 +43,7 @@ const CRC32_TABLE: [u32; 0x100] = [
 pub fn crc(data: &[u8]) -> u32 {
     let mut crc: u32 = 0xFFFFFFFF;
     for byte in data {
-        crc = (crc << 8) ^ CRC32_TABLE[(((crc >> 24) ^ ((byte & 0xff) as u32)) & 0xff) as usize];
+        crc = (crc << 8) ^ CRC32_TABLE[(((crc >> 24) ^ (*byte as u32)) & 0xff) as usize];
     }
     crc
 }
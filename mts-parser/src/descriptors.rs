@@ -0,0 +1,184 @@
+// Descriptors of the PMT (ISO/IEC 13818-1 2.6); only the metadata ones (2.6.58 - 2.6.61)
+// are decoded
+use super::stream::{stream, Stream};
+use winnow::{binary, combinator, error, IResult, Parser};
+
+pub const METADATA_POINTER_DESCRIPTOR: u8 = 0x25;
+pub const METADATA_DESCRIPTOR: u8 = 0x26;
+
+/// metadata_application_format and metadata_format values that say the 32 bit
+/// identifier after them is what counts
+const APPLICATION_FORMAT_IDENTIFIER: u16 = 0xffff;
+const FORMAT_IDENTIFIER: u8 = 0xff;
+/// metadata_format_identifier of ID3 timed metadata
+pub const ID3_FORMAT_IDENTIFIER: u32 = u32::from_be_bytes(*b"ID3 ");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Descriptor {
+    pub tag: u8,
+    pub data: Vec<u8>,
+}
+
+impl Descriptor {
+    pub fn parse(input: Stream) -> IResult<Stream, Self> {
+        let (input, (tag, data)) =
+            (binary::u8, binary::length_data(binary::u8)).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                tag,
+                data: data.to_vec(),
+            },
+        ))
+    }
+
+    /// Splits the descriptor loop of a PMT; a truncated last descriptor is dropped
+    pub fn parse_all(data: &[u8]) -> Vec<Self> {
+        combinator::repeat::<_, _, _, error::Error<_>, _>(0.., Self::parse)
+            .parse_next(stream(data))
+            .map_or(Vec::new(), |(_, descriptors)| descriptors)
+    }
+}
+
+/// The application and format fields shared by the metadata descriptors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataFormat {
+    pub application_format: u16,
+    pub application_format_identifier: Option<u32>,
+    pub format: u8,
+    pub format_identifier: Option<u32>,
+    pub service_id: u8,
+}
+
+impl MetadataFormat {
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, application_format) = binary::be_u16(input)?;
+        let (input, application_format_identifier) = combinator::cond(
+            application_format == APPLICATION_FORMAT_IDENTIFIER,
+            binary::be_u32,
+        )
+        .parse_next(input)?;
+        let (input, format) = binary::u8(input)?;
+        let (input, format_identifier) =
+            combinator::cond(format == FORMAT_IDENTIFIER, binary::be_u32).parse_next(input)?;
+        let (input, service_id) = binary::u8(input)?;
+        Ok((
+            input,
+            Self {
+                application_format,
+                application_format_identifier,
+                format,
+                format_identifier,
+                service_id,
+            },
+        ))
+    }
+
+    pub fn is_id3(&self) -> bool {
+        self.format_identifier == Some(ID3_FORMAT_IDENTIFIER)
+    }
+}
+
+/// Where a metadata service is carried (in the program descriptors)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataPointerDescriptor {
+    pub format: MetadataFormat,
+    pub locator_record: Option<Vec<u8>>,
+    /// 0: same transport stream, 1: other transport stream, 2: program stream,
+    /// 3: not MPEG-2 carried
+    pub mpeg_carriage_flags: u8,
+    pub program_number: Option<u16>,
+    /// (transport_stream_location, transport_stream_id)
+    pub transport_stream: Option<(u16, u16)>,
+    pub private_data: Vec<u8>,
+}
+
+impl MetadataPointerDescriptor {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, format) = MetadataFormat::parse(input)?;
+        let (input, flags) = binary::u8(input)?;
+        let mpeg_carriage_flags = (flags >> 5) & 0x3;
+        let (input, locator_record) =
+            combinator::cond(flags & 0x80 != 0, binary::length_data(binary::u8))
+                .parse_next(input)?;
+        let (input, program_number) =
+            combinator::cond(mpeg_carriage_flags <= 2, binary::be_u16).parse_next(input)?;
+        let (input, transport_stream) =
+            combinator::cond(mpeg_carriage_flags == 1, (binary::be_u16, binary::be_u16))
+                .parse_next(input)?;
+        let (input, private_data) = combinator::rest(input)?;
+        Ok((
+            input,
+            Self {
+                format,
+                locator_record: locator_record.map(|r| r.to_vec()),
+                mpeg_carriage_flags,
+                program_number,
+                transport_stream,
+                private_data: private_data.to_vec(),
+            },
+        ))
+    }
+}
+
+/// Describes the metadata service of an elementary stream (in its ES info descriptors)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataDescriptor {
+    pub format: MetadataFormat,
+    pub decoder_config_flags: u8,
+    pub service_identification_record: Option<Vec<u8>>,
+    /// decoder_config, dec_config_identification_record or reserved_data, depending on
+    /// decoder_config_flags
+    pub decoder_config: Option<Vec<u8>>,
+    pub decoder_config_metadata_service_id: Option<u8>,
+    pub private_data: Vec<u8>,
+}
+
+impl MetadataDescriptor {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, format) = MetadataFormat::parse(input)?;
+        let (input, flags) = binary::u8(input)?;
+        let decoder_config_flags = flags >> 5;
+        let (input, service_identification_record) =
+            combinator::cond(flags & 0x10 != 0, binary::length_data(binary::u8))
+                .parse_next(input)?;
+        let (input, decoder_config) = combinator::cond(
+            matches!(decoder_config_flags, 1 | 3 | 5 | 6),
+            binary::length_data(binary::u8),
+        )
+        .parse_next(input)?;
+        let (input, decoder_config_metadata_service_id) =
+            combinator::cond(decoder_config_flags == 4, binary::u8).parse_next(input)?;
+        let (input, private_data) = combinator::rest(input)?;
+        Ok((
+            input,
+            Self {
+                format,
+                decoder_config_flags,
+                service_identification_record: service_identification_record.map(|r| r.to_vec()),
+                decoder_config: decoder_config.map(|c| c.to_vec()),
+                decoder_config_metadata_service_id,
+                private_data: private_data.to_vec(),
+            },
+        ))
+    }
+}
+
+/// The metadata descriptors found in a descriptor loop
+pub fn metadata_pointer_descriptors(descriptors: &[u8]) -> Vec<MetadataPointerDescriptor> {
+    Descriptor::parse_all(descriptors)
+        .iter()
+        .filter(|d| d.tag == METADATA_POINTER_DESCRIPTOR)
+        .filter_map(|d| MetadataPointerDescriptor::parse(&d.data).ok())
+        .map(|(_, descriptor)| descriptor)
+        .collect()
+}
+
+pub fn metadata_descriptors(descriptors: &[u8]) -> Vec<MetadataDescriptor> {
+    Descriptor::parse_all(descriptors)
+        .iter()
+        .filter(|d| d.tag == METADATA_DESCRIPTOR)
+        .filter_map(|d| MetadataDescriptor::parse(&d.data).ok())
+        .map(|(_, descriptor)| descriptor)
+        .collect()
+}
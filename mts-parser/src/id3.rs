@@ -0,0 +1,436 @@
+// ID3v2 tags carried as timed metadata (stream_type 0x15), as used by HLS: one or more
+// complete tags per PES packet, either directly in a private_stream_1 PES or wrapped in
+// metadata access unit cells (stream_id 0xfc, ISO/IEC 13818-1 2.12.4)
+use super::stream_packet::PESPacket;
+use super::timestamp::{Timestamp, Unwrapper};
+use std::collections::VecDeque;
+use std::fmt;
+use winnow::{binary, combinator, error, token, IResult, Parser};
+
+const METADATA_STREAM_ID: u8 = 0xfc;
+const ID3_MAGIC: &[u8] = b"ID3";
+const HEADER_SIZE: usize = 10;
+const FLAG_UNSYNCHRONISATION: u8 = 0x80;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+// ID3v2.4 frame format flags
+const FRAME_FLAG_GROUPING: u16 = 0x0040;
+const FRAME_FLAG_UNSYNCHRONISATION: u16 = 0x0002;
+const FRAME_FLAG_DATA_LENGTH: u16 = 0x0001;
+
+/// 28 bit integer stored in the low 7 bits of 4 bytes
+fn synchsafe_u32(input: &[u8]) -> IResult<&[u8], u32> {
+    let (input, value) = binary::be_u32(input)?;
+    if value & 0x8080_8080 != 0 {
+        return Err(error::ErrMode::Backtrack(
+            error::ParseError::from_error_kind(input, error::ErrorKind::Verify),
+        ));
+    }
+    let value = (value & 0x7f)
+        | (value >> 1 & 0x3f80)
+        | (value >> 2 & 0x1f_c000)
+        | (value >> 3 & 0xfe0_0000);
+    Ok((input, value))
+}
+
+/// Reverts unsynchronisation, which inserted a zero byte after every 0xff
+fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut previous = 0;
+    for byte in data.iter().copied() {
+        if !(previous == 0xff && byte == 0) {
+            result.push(byte);
+        }
+        previous = byte;
+    }
+    result
+}
+
+/// Decodes a string in the given text encoding (0: ISO-8859-1, 1: UTF-16 with BOM,
+/// 2: UTF-16BE, 3: UTF-8)
+fn decode_text(encoding: u8, data: &[u8]) -> String {
+    match encoding {
+        1 | 2 => {
+            let (big_endian, data) = match data {
+                [0xff, 0xfe, rest @ ..] => (false, rest),
+                [0xfe, 0xff, rest @ ..] => (true, rest),
+                _ => (true, data),
+            };
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|c| match big_endian {
+                    true => u16::from_be_bytes([c[0], c[1]]),
+                    false => u16::from_le_bytes([c[0], c[1]]),
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        3 => String::from_utf8_lossy(data).into_owned(),
+        _ => data.iter().map(|b| *b as char).collect(),
+    }
+}
+
+/// Splits off a terminated string; the terminator is two (aligned) zero bytes in UTF-16
+fn split_text(encoding: u8, data: &[u8]) -> (String, &[u8]) {
+    let end = match encoding {
+        1 | 2 => data
+            .chunks_exact(2)
+            .position(|c| c == [0, 0])
+            .map(|p| (p * 2, p * 2 + 2)),
+        _ => data.iter().position(|b| *b == 0).map(|p| (p, p + 1)),
+    };
+    match end {
+        Some((end, next)) => (decode_text(encoding, &data[..end]), &data[next..]),
+        None => (decode_text(encoding, data), &[]),
+    }
+}
+
+pub enum ID3Frame {
+    /// T??? frames; ID3v2.4 allows several zero separated values
+    Text {
+        id: String,
+        values: Vec<String>,
+    },
+    /// TXXX
+    UserText {
+        description: String,
+        value: String,
+    },
+    /// W??? frames
+    URL {
+        id: String,
+        url: String,
+    },
+    /// WXXX
+    UserURL {
+        description: String,
+        url: String,
+    },
+    /// COMM
+    Comment {
+        language: String,
+        description: String,
+        text: String,
+    },
+    /// PRIV, e.g. com.apple.streaming.transportStreamTimestamp
+    Private {
+        owner: String,
+        data: Vec<u8>,
+    },
+    /// GEOB
+    Object {
+        mime_type: String,
+        filename: String,
+        description: String,
+        data: Vec<u8>,
+    },
+    Unknown {
+        id: String,
+        data: Vec<u8>,
+    },
+}
+
+impl ID3Frame {
+    fn decode(id: &str, data: &[u8]) -> Self {
+        let (encoding, text) = match data.split_first() {
+            Some((encoding, text)) => (*encoding, text),
+            None => (0, data),
+        };
+        match id {
+            "TXXX" => {
+                let (description, rest) = split_text(encoding, text);
+                let (value, _) = split_text(encoding, rest);
+                Self::UserText { description, value }
+            }
+            "WXXX" => {
+                let (description, rest) = split_text(encoding, text);
+                let (url, _) = split_text(0, rest);
+                Self::UserURL { description, url }
+            }
+            "COMM" if text.len() >= 3 => {
+                let (description, rest) = split_text(encoding, &text[3..]);
+                let (text_value, _) = split_text(encoding, rest);
+                Self::Comment {
+                    language: decode_text(0, &text[..3]),
+                    description,
+                    text: text_value,
+                }
+            }
+            "PRIV" => {
+                let (owner, rest) = split_text(0, data);
+                Self::Private {
+                    owner,
+                    data: rest.to_vec(),
+                }
+            }
+            "GEOB" => {
+                let (mime_type, rest) = split_text(0, text);
+                let (filename, rest) = split_text(encoding, rest);
+                let (description, rest) = split_text(encoding, rest);
+                Self::Object {
+                    mime_type,
+                    filename,
+                    description,
+                    data: rest.to_vec(),
+                }
+            }
+            _ if id.starts_with('T') => {
+                let mut values = Vec::new();
+                let mut rest = text;
+                while !rest.is_empty() {
+                    let value;
+                    (value, rest) = split_text(encoding, rest);
+                    values.push(value);
+                }
+                Self::Text {
+                    id: id.to_string(),
+                    values,
+                }
+            }
+            _ if id.starts_with('W') => Self::URL {
+                id: id.to_string(),
+                url: split_text(0, data).0,
+            },
+            _ => Self::Unknown {
+                id: id.to_string(),
+                data: data.to_vec(),
+            },
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Text { id, .. } | Self::URL { id, .. } | Self::Unknown { id, .. } => id,
+            Self::UserText { .. } => "TXXX",
+            Self::UserURL { .. } => "WXXX",
+            Self::Comment { .. } => "COMM",
+            Self::Private { .. } => "PRIV",
+            Self::Object { .. } => "GEOB",
+        }
+    }
+}
+
+impl fmt::Debug for ID3Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let truncated = |data: &[u8]| format!("{:x?}", &data[..(16.min(data.len()))]);
+        match self {
+            Self::Text { id, values } => write!(f, "{} {:?}", id, values),
+            Self::UserText { description, value } => {
+                write!(f, "TXXX {:?}={:?}", description, value)
+            }
+            Self::URL { id, url } => write!(f, "{} {:?}", id, url),
+            Self::UserURL { description, url } => write!(f, "WXXX {:?}={:?}", description, url),
+            Self::Comment {
+                language,
+                description,
+                text,
+            } => write!(f, "COMM [{}] {:?}={:?}", language, description, text),
+            Self::Private { owner, data } => {
+                write!(
+                    f,
+                    "PRIV {:?} {} bytes {}",
+                    owner,
+                    data.len(),
+                    truncated(data)
+                )
+            }
+            Self::Object {
+                mime_type,
+                filename,
+                description,
+                data,
+            } => write!(
+                f,
+                "GEOB {:?} {:?} {:?} {} bytes {}",
+                mime_type,
+                filename,
+                description,
+                data.len(),
+                truncated(data)
+            ),
+            Self::Unknown { id, data } => {
+                write!(f, "{} {} bytes {}", id, data.len(), truncated(data))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ID3Tag {
+    /// (major version, revision), e.g. (4, 0) for ID3v2.4.0
+    pub version: (u8, u8),
+    pub flags: u8,
+    pub frames: Vec<ID3Frame>,
+}
+
+impl ID3Tag {
+    /// Parses a whole ID3v2.3 or ID3v2.4 tag, including its footer if it has one
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, (_, major, revision, flags, size)) = (
+            ID3_MAGIC,
+            binary::u8.verify(|major| matches!(major, 3 | 4)),
+            binary::u8,
+            binary::u8,
+            synchsafe_u32,
+        )
+            .parse_next(input)?;
+        let (input, body) = token::take(size).parse_next(input)?;
+        let (input, _) = combinator::cond(flags & FLAG_FOOTER != 0, token::take(HEADER_SIZE))
+            .parse_next(input)?;
+        // in ID3v2.3 the whole tag is unsynchronised, in ID3v2.4 every frame on its own
+        let body = match major == 3 && flags & FLAG_UNSYNCHRONISATION != 0 {
+            true => remove_unsynchronisation(body),
+            false => body.to_vec(),
+        };
+        let mut frames_data = &body[..];
+        if flags & FLAG_EXTENDED_HEADER != 0 {
+            // the size excludes itself in ID3v2.3, and includes itself in ID3v2.4
+            let extended_size = match major {
+                3 => binary::be_u32::<_, error::Error<_>>
+                    .map(|s| s + 4)
+                    .parse_next(frames_data),
+                _ => synchsafe_u32(frames_data),
+            };
+            let Some(rest) = extended_size
+                .ok()
+                .and_then(|(_, size)| frames_data.get(size as usize..))
+            else {
+                return combinator::fail(input);
+            };
+            frames_data = rest;
+        }
+        let mut frames = Vec::new();
+        // frames end at the padding (zero bytes) or the end of the tag
+        while frames_data.len() > HEADER_SIZE && frames_data[0] != 0 {
+            let Ok((rest, frame)) = Self::parse_frame(frames_data, major) else {
+                break;
+            };
+            frames.push(frame);
+            frames_data = rest;
+        }
+        Ok((
+            input,
+            Self {
+                version: (major, revision),
+                flags,
+                frames,
+            },
+        ))
+    }
+
+    fn parse_frame(input: &[u8], major: u8) -> IResult<&[u8], ID3Frame> {
+        let (input, id) = token::take(4_usize).parse_next(input)?;
+        let (input, size) = match major {
+            3 => binary::be_u32(input)?,
+            _ => synchsafe_u32(input)?,
+        };
+        let (input, (flags, data)) = (binary::be_u16, token::take(size)).parse_next(input)?;
+        let id = String::from_utf8_lossy(id).into_owned();
+        if major == 3 {
+            return Ok((input, ID3Frame::decode(&id, data)));
+        }
+        let mut data = data;
+        if flags & FRAME_FLAG_GROUPING != 0 {
+            data = data.get(1..).unwrap_or_default();
+        }
+        if flags & FRAME_FLAG_DATA_LENGTH != 0 {
+            data = data.get(4..).unwrap_or_default();
+        }
+        let frame = match flags & FRAME_FLAG_UNSYNCHRONISATION {
+            0 => ID3Frame::decode(&id, data),
+            _ => ID3Frame::decode(&id, &remove_unsynchronisation(data)),
+        };
+        Ok((input, frame))
+    }
+
+    pub fn frame(&self, id: &str) -> Option<&ID3Frame> {
+        self.frames.iter().find(|frame| frame.id() == id)
+    }
+}
+
+/// An ID3 tag with the PTS of the PES packet it came in
+#[derive(Debug)]
+pub struct TimedID3Tag {
+    pub pts: Option<Timestamp>,
+    pub tag: ID3Tag,
+}
+
+/// Concatenates the AU_cell_data of the metadata access unit cells in a PES payload
+fn metadata_au_cells(mut data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    // metadata_service_id, sequence_number, flags, AU_cell_data_length
+    while let Ok((rest, (_, _, _, cell))) = (
+        binary::u8::<_, error::Error<_>>,
+        binary::u8,
+        binary::u8,
+        binary::length_data(binary::be_u16),
+    )
+        .parse_next(data)
+    {
+        result.extend_from_slice(cell);
+        data = rest;
+    }
+    result
+}
+
+/// Decodes the ID3 tags in the PES packets of a timed metadata stream
+pub struct ID3TagIterator<I: Iterator<Item = PESPacket>> {
+    packets: I,
+    unwrapper: Unwrapper,
+    pending: VecDeque<TimedID3Tag>,
+    skipped_bytes: u64,
+}
+
+impl<I: Iterator<Item = PESPacket>> ID3TagIterator<I> {
+    pub fn new(packets: I) -> Self {
+        Self {
+            packets,
+            unwrapper: Unwrapper::new(),
+            pending: VecDeque::new(),
+            skipped_bytes: 0,
+        }
+    }
+
+    /// Bytes of PES payload that weren't part of a valid ID3 tag
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+}
+
+impl<I: Iterator<Item = PESPacket>> Iterator for ID3TagIterator<I> {
+    type Item = TimedID3Tag;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let packet = self.packets.next()?;
+            let pts = packet
+                .header
+                .as_ref()
+                .and_then(|h| h.pts)
+                .map(|pts| self.unwrapper.unwrap_90khz(pts));
+            let payload = match packet.stream_id {
+                METADATA_STREAM_ID => metadata_au_cells(&packet.data),
+                _ => packet.data,
+            };
+            let mut data = &payload[..];
+            while !data.is_empty() {
+                match ID3Tag::parse(data) {
+                    Ok((rest, tag)) => {
+                        self.pending.push_back(TimedID3Tag { pts, tag });
+                        data = rest;
+                    }
+                    Err(_) => {
+                        // resynchronise on the next tag header, if any
+                        let skip = data[1..]
+                            .windows(ID3_MAGIC.len())
+                            .position(|w| w == ID3_MAGIC)
+                            .map_or(data.len(), |p| p + 1);
+                        self.skipped_bytes += skip as u64;
+                        data = &data[skip..];
+                    }
+                }
+            }
+        }
+        self.pending.pop_front()
+    }
+}
@@ -2,8 +2,13 @@ pub mod packets;
 pub mod crc;
 pub mod stream_packet;
 pub mod stream;
+pub mod timestamp;
+pub mod av_sync;
+pub mod pes_reader;
+pub mod descriptors;
+pub mod id3;
 use circular::Buffer;
-use std::{collections::{HashMap, HashSet}, io::Read};
+use std::{collections::{hash_map::Entry, HashMap, HashSet}, io::Read};
 use stream_packet::{Parsable, StreamPacket, PATTable, PMTTable, PESPacket};
 
 use winnow::{error, stream::Offset};
@@ -18,11 +23,11 @@ pub struct MTSPacketIterator {
 
 impl MTSPacketIterator {
     pub fn new(input_reader: Box<dyn Read>) -> MTSPacketIterator {
-        return Self {
+        Self {
             input_reader,
             buffer: Buffer::with_capacity(CHUNK_SIZE),
             has_reached_eof: false,
-        };
+        }
     }
 }
 
@@ -98,6 +103,14 @@ impl ElementIterator {
             pes_stream_pids: HashSet::new(),
         }
     }
+
+    /// Only the PES packets of one PID, e.g. as input for an elementary stream parser
+    pub fn pes_packets(self, pid: u16) -> impl Iterator<Item = PESPacket> {
+        self.filter_map(move |(element_pid, element)| match element {
+            StreamPacket::PES(pes) if element_pid == pid => Some(pes),
+            _ => None,
+        })
+    }
 }
 
 impl Iterator for ElementIterator {
@@ -115,31 +128,27 @@ impl Iterator for ElementIterator {
             match self.packet_iterator.next() {
                 None => {
                     // no more data; parse the data items still waiting
-                    let Some((pid, entry)) = self.packet_stream_map.iter_mut().next() else {
-                        return None;
-                    };
+                    let (pid, entry) = self.packet_stream_map.iter_mut().next()?;
                     let pid = &pid.clone();
                     entry.complete_element_cutoff = Some(entry.buffer.available_data());
-                    return match self.parse_pid_data_for_pid(pid) {
-                        Some(stream_packet) => Some((*pid, stream_packet)),
-                        None => None,
-                    };
+                    return self
+                        .parse_pid_data_for_pid(pid)
+                        .map(|stream_packet| (*pid, stream_packet));
                 }
                 Some(packet) => {
                     if packet.pid == Self::PADDING_PID {
                         continue;
                     }
                     self.last_pid = Some(packet.pid);
-                    if !self.packet_stream_map.contains_key(&packet.pid)
-                    {
+                    if let Entry::Vacant(vacant) = self.packet_stream_map.entry(packet.pid) {
                         if packet.payload_unit_start_indicator {
-                            self.packet_stream_map.insert(packet.pid, MapEntry::new());
+                            vacant.insert(MapEntry::new());
                         } else {
                             // skipping since not start
                             continue;
                         }
                     }
-                    let mut entry = self
+                    let entry = self
                         .packet_stream_map
                         .get_mut(&packet.pid)
                         .expect("Pid should exist");
@@ -178,9 +187,7 @@ impl Iterator for ElementIterator {
 
 impl ElementIterator {
     fn parse_pid_data_for_pid(&mut self, pid: &u16) -> Option<StreamPacket> {
-        let Some(entry) = self.packet_stream_map.get_mut(pid) else {
-            return None;
-        };
+        let entry = self.packet_stream_map.get_mut(pid)?;
         let input = match entry.complete_element_cutoff {
             Some(cutoff) => stream::partialstream(&entry.buffer.data()[..cutoff], true),
             None => stream::partialstream(entry.buffer.data(), false),
@@ -200,7 +207,7 @@ impl ElementIterator {
                 let consumed = input.offset_to(&remainder);
                 entry.buffer.consume(consumed);
                 if entry.buffer.empty() {
-                    self.packet_stream_map.remove(&pid);
+                    self.packet_stream_map.remove(pid);
                 } else {
                     entry.complete_element_cutoff = None;
                 }
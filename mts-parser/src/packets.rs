@@ -233,9 +233,9 @@ impl Packet {
 
 impl fmt::Debug for Packet {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
+        writeln!(
             f,
-            "{} {} {} {} {} {:x} {} {} {:?} {:?}\n",
+            "{} {} {} {} {} {:x} {} {} {:?} {:?}",
             self.copy_protection,
             self.arrival_timestamp,
             self.transport_error_indicator,
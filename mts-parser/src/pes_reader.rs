@@ -0,0 +1,80 @@
+// Presents the payloads of consecutive PES packets as one elementary stream, while
+// remembering where every PES packet started so its PTS / DTS can be matched to the
+// access unit it belongs to.
+use super::stream_packet::PESPacket;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+/// Position of the start of a PES packet in the elementary stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PESMark {
+    pub position: u64,
+    pub pts: Option<u64>,
+    pub dts: Option<u64>,
+}
+
+pub struct PESReader<I: Iterator<Item = PESPacket>> {
+    packets: I,
+    current: Vec<u8>,
+    current_position: usize,
+    position: u64,
+    marks: VecDeque<PESMark>,
+}
+
+impl<I: Iterator<Item = PESPacket>> PESReader<I> {
+    pub fn new(packets: I) -> Self {
+        Self {
+            packets,
+            current: Vec::new(),
+            current_position: 0,
+            position: 0,
+            marks: VecDeque::new(),
+        }
+    }
+
+    /// Removes all marks at or before `position`, and returns the last one that had a
+    /// timestamp.
+    ///
+    /// Per ISO/IEC 13818-1 the PTS of a PES packet belongs to the first access unit that
+    /// starts in it, so call this with the position of each access unit start in turn.
+    pub fn take_timestamps_until(&mut self, position: u64) -> Option<PESMark> {
+        let mut last = None;
+        while let Some(mark) = self.marks.front() {
+            if mark.position > position {
+                break;
+            }
+            if mark.pts.is_some() || mark.dts.is_some() {
+                last = Some(*mark);
+            }
+            self.marks.pop_front();
+        }
+        last
+    }
+}
+
+impl<I: Iterator<Item = PESPacket>> Read for PESReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current_position == self.current.len() {
+            let Some(packet) = self.packets.next() else {
+                return Ok(0);
+            };
+            let (pts, dts) = packet
+                .header
+                .as_ref()
+                .map_or((None, None), |h| (h.pts, h.dts));
+            self.marks.push_back(PESMark {
+                position: self.position,
+                pts,
+                dts,
+            });
+            self.current = packet.data;
+            self.current_position = 0;
+        }
+        let count = buf.len().min(self.current.len() - self.current_position);
+        buf[..count]
+            .copy_from_slice(&self.current[self.current_position..self.current_position + count]);
+        self.current_position += count;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
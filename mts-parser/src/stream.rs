@@ -12,6 +12,6 @@ pub fn partialstream(b: &[u8], complete: bool) -> PartialStream<'_> {
 
 pub type Stream<'i> = &'i Bytes;
 
-pub fn stream(b: &[u8]) -> Stream {
+pub fn stream(b: &[u8]) -> Stream<'_> {
     Bytes::new(b)
 }
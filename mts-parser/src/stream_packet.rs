@@ -60,7 +60,7 @@ pub struct PSISharedTableInfo {
 
 impl PSISharedTableInfo {
     const PADDING: u8 = 0xFF;
-    pub fn parse(input: PartialStream) -> IResult<PartialStream, (Self, &[u8])> {
+    pub fn parse(input: PartialStream<'_>) -> IResult<PartialStream<'_>, (Self, &[u8])> {
         let (input, (((table_id, rest), table_data), crc32)) = (
             (
                 binary::be_u8,
@@ -168,6 +168,15 @@ impl Parsable for PATTable {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Metadata,
+    Other,
+}
+
 #[derive(Debug)]
 pub struct ElementaryStreamInfo {
     pub stream_type: u8,
@@ -176,6 +185,18 @@ pub struct ElementaryStreamInfo {
 }
 
 impl ElementaryStreamInfo {
+    // see https://en.wikipedia.org/wiki/Program-specific_information#Elementary_stream_types
+    // 0x80 and up are the HDMV (AVCHD / Blu-ray) assignments
+    pub fn kind(&self) -> StreamKind {
+        match self.stream_type {
+            0x01 | 0x02 | 0x10 | 0x1b | 0x20 | 0x24 | 0xea => StreamKind::Video,
+            0x03 | 0x04 | 0x0f | 0x11 | 0x80..=0x87 | 0xa1 | 0xa2 => StreamKind::Audio,
+            0x90..=0x92 => StreamKind::Subtitle,
+            0x15 => StreamKind::Metadata,
+            _ => StreamKind::Other,
+        }
+    }
+
     pub fn parse(input: PartialStream) -> IResult<PartialStream, Self> {
         let (input, (stream_type, pid, descriptors)) = (
             binary::be_u8,
@@ -0,0 +1,325 @@
+//
+// Timestamps in a transport stream come in two flavours: PTS/DTS (and PCR base) count at
+// 90 kHz, the PCR (base * 300 + extension) counts at 27 MHz. Both are 33 bits wide (in 90 kHz
+// units), so they wrap every 2^33 / 90000 seconds (~26.5 hours).
+//
+// A `Timestamp` is stored in 27 MHz ticks and is signed, so that it can hold both flavours
+// without losing precision, and can express positions before the zero of a timeline.
+//
+use super::packets::PCR;
+use super::stream_packet::{PESPacket, PMTTable, StreamPacket};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+use std::time::Duration;
+
+pub const CLOCK_90KHZ: i64 = 90_000;
+pub const CLOCK_27MHZ: i64 = 27_000_000;
+const TICKS_27MHZ_PER_90KHZ: i64 = CLOCK_27MHZ / CLOCK_90KHZ;
+const WRAP_90KHZ: i64 = 1 << 33;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp {
+    ticks_27mhz: i64,
+}
+
+impl Timestamp {
+    pub const ZERO: Timestamp = Timestamp { ticks_27mhz: 0 };
+
+    pub fn from_90khz(ticks: i64) -> Self {
+        Self {
+            ticks_27mhz: ticks * TICKS_27MHZ_PER_90KHZ,
+        }
+    }
+
+    pub fn from_27mhz(ticks: i64) -> Self {
+        Self { ticks_27mhz: ticks }
+    }
+
+    pub fn from_pcr(pcr: &PCR) -> Self {
+        Self::from_27mhz(pcr.base as i64 * TICKS_27MHZ_PER_90KHZ + pcr.extension as i64)
+    }
+
+    pub fn from_secs_f64(secs: f64) -> Self {
+        Self::from_27mhz((secs * CLOCK_27MHZ as f64).round() as i64)
+    }
+
+    /// Value in 90 kHz ticks, rounded towards negative infinity
+    pub fn as_90khz(&self) -> i64 {
+        self.ticks_27mhz.div_euclid(TICKS_27MHZ_PER_90KHZ)
+    }
+
+    pub fn as_27mhz(&self) -> i64 {
+        self.ticks_27mhz
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        self.ticks_27mhz as f64 / CLOCK_27MHZ as f64
+    }
+
+    /// Wall-clock duration since zero; `None` for negative timestamps
+    pub fn as_duration(&self) -> Option<Duration> {
+        if self.ticks_27mhz < 0 {
+            return None;
+        }
+        let ticks = self.ticks_27mhz as u64;
+        let secs = ticks / CLOCK_27MHZ as u64;
+        let nanos = (ticks % CLOCK_27MHZ as u64) * 1_000 / 27;
+        Some(Duration::new(secs, nanos as u32))
+    }
+
+    /// The raw 33 bit value as it would appear in a PES header
+    pub fn as_wrapped_90khz(&self) -> u64 {
+        self.as_90khz().rem_euclid(WRAP_90KHZ) as u64
+    }
+
+    pub fn abs_diff(&self, other: Timestamp) -> Timestamp {
+        Self::from_27mhz((self.ticks_27mhz - other.ticks_27mhz).abs())
+    }
+}
+
+impl From<Duration> for Timestamp {
+    fn from(value: Duration) -> Self {
+        Self::from_27mhz(
+            value.as_secs() as i64 * CLOCK_27MHZ + value.subsec_nanos() as i64 * 27 / 1_000,
+        )
+    }
+}
+
+impl Add for Timestamp {
+    type Output = Timestamp;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::from_27mhz(self.ticks_27mhz + rhs.ticks_27mhz)
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Timestamp;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::from_27mhz(self.ticks_27mhz - rhs.ticks_27mhz)
+    }
+}
+
+impl Neg for Timestamp {
+    type Output = Timestamp;
+    fn neg(self) -> Self::Output {
+        Self::from_27mhz(-self.ticks_27mhz)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Formats as `[-]HH:MM:SS.mmm`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.ticks_27mhz < 0 { "-" } else { "" };
+        let total_millis = self.ticks_27mhz.unsigned_abs() / (CLOCK_27MHZ as u64 / 1_000);
+        let millis = total_millis % 1_000;
+        let total_secs = total_millis / 1_000;
+        write!(
+            f,
+            "{}{:02}:{:02}:{:02}.{:03}",
+            sign,
+            total_secs / 3600,
+            (total_secs / 60) % 60,
+            total_secs % 60,
+            millis,
+        )
+    }
+}
+
+/// Turns a sequence of 33 bit (wrapping) 90 kHz values into monotonic-ish `Timestamp`s.
+///
+/// Each new value is placed at the position closest to the previous one, so both forward
+/// wraps and small backward steps (B-frame PTS, audio/video interleaving) come out right.
+#[derive(Debug, Clone, Default)]
+pub struct Unwrapper {
+    last: Option<i64>,
+}
+
+impl Unwrapper {
+    pub fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// Start unwrapping close to `reference` rather than in the first wrap period
+    pub fn with_reference(reference: Timestamp) -> Self {
+        Self {
+            last: Some(reference.as_90khz()),
+        }
+    }
+
+    pub fn unwrap_90khz(&mut self, raw: u64) -> Timestamp {
+        let raw = (raw as i64).rem_euclid(WRAP_90KHZ);
+        let unwrapped = match self.last {
+            None => raw,
+            Some(last) => {
+                let half = WRAP_90KHZ / 2;
+                let delta =
+                    (raw - last.rem_euclid(WRAP_90KHZ) + half).rem_euclid(WRAP_90KHZ) - half;
+                last + delta
+            }
+        };
+        self.last = Some(unwrapped);
+        Timestamp::from_90khz(unwrapped)
+    }
+
+    pub fn unwrap_pcr(&mut self, pcr: &PCR) -> Timestamp {
+        self.unwrap_90khz(pcr.base) + Timestamp::from_27mhz(pcr.extension as i64)
+    }
+
+    pub fn last(&self) -> Option<Timestamp> {
+        self.last.map(Timestamp::from_90khz)
+    }
+}
+
+/// Shared clock for all streams in one program.
+///
+/// Every PID gets its own `Unwrapper`, seeded from the most recent timestamp seen in the
+/// program so all streams agree on the wrap period. All returned timestamps are relative to
+/// the zero of the program, which is the first PCR or PTS/DTS seen (unless set explicitly).
+#[derive(Debug, Clone)]
+pub struct ProgramTimeline {
+    pub program_number: u16,
+    pub pcr_pid: Option<u16>,
+    streams: HashMap<u16, Unwrapper>,
+    latest: Option<Timestamp>,
+    zero: Option<Timestamp>,
+}
+
+impl ProgramTimeline {
+    pub fn new(program_number: u16) -> Self {
+        Self {
+            program_number,
+            pcr_pid: None,
+            streams: HashMap::new(),
+            latest: None,
+            zero: None,
+        }
+    }
+
+    pub fn zero(&self) -> Option<Timestamp> {
+        self.zero
+    }
+
+    /// Overrides the zero; timestamps already returned are not adjusted
+    pub fn set_zero(&mut self, zero: Timestamp) {
+        self.zero = Some(zero);
+    }
+
+    fn unwrapper_for_pid(&mut self, pid: u16) -> &mut Unwrapper {
+        let latest = self.latest;
+        self.streams.entry(pid).or_insert_with(|| match latest {
+            Some(latest) => Unwrapper::with_reference(latest),
+            None => Unwrapper::new(),
+        })
+    }
+
+    fn make_relative(&mut self, absolute: Timestamp) -> Timestamp {
+        self.latest = Some(absolute);
+        let zero = *self.zero.get_or_insert(absolute);
+        absolute - zero
+    }
+
+    /// Absolute (unwrapped, but not zero-adjusted) timestamp for a raw PTS/DTS on a PID
+    pub fn unwrap_90khz(&mut self, pid: u16, raw: u64) -> Timestamp {
+        self.unwrapper_for_pid(pid).unwrap_90khz(raw)
+    }
+
+    pub fn add_pcr(&mut self, pid: u16, pcr: &PCR) -> Timestamp {
+        let absolute = self.unwrapper_for_pid(pid).unwrap_pcr(pcr);
+        self.make_relative(absolute)
+    }
+
+    pub fn add_pes_timestamp(&mut self, pid: u16, raw: u64) -> Timestamp {
+        let absolute = self.unwrap_90khz(pid, raw);
+        self.make_relative(absolute)
+    }
+}
+
+/// Position of one PES packet on the timeline of its program
+#[derive(Debug, Clone, Copy)]
+pub struct TimelineEntry {
+    pub program_number: u16,
+    pub pts: Option<Timestamp>,
+    pub dts: Option<Timestamp>,
+}
+
+impl TimelineEntry {
+    /// The decode time; equal to the PTS when no DTS is present
+    pub fn decode_time(&self) -> Option<Timestamp> {
+        self.dts.or(self.pts)
+    }
+}
+
+/// Keeps a `ProgramTimeline` for every program announced in the PMTs of a stream
+#[derive(Debug, Clone, Default)]
+pub struct MediaTimeline {
+    programs: HashMap<u16, ProgramTimeline>,
+    pid_to_program: HashMap<u16, u16>,
+}
+
+impl MediaTimeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn program(&self, program_number: u16) -> Option<&ProgramTimeline> {
+        self.programs.get(&program_number)
+    }
+
+    pub fn program_for_pid(&self, pid: u16) -> Option<&ProgramTimeline> {
+        self.programs.get(self.pid_to_program.get(&pid)?)
+    }
+
+    pub fn add_pmt(&mut self, pmt: &PMTTable) {
+        let program_number = pmt.psi_data.table_id_extension;
+        let timeline = self
+            .programs
+            .entry(program_number)
+            .or_insert_with(|| ProgramTimeline::new(program_number));
+        timeline.pcr_pid = Some(pmt.pcr_pid);
+        self.pid_to_program.insert(pmt.pcr_pid, program_number);
+        for esi in pmt.elementary_stream_info_data.iter() {
+            self.pid_to_program.insert(esi.pid, program_number);
+        }
+    }
+
+    /// Feed the PCR found in the adaptation field of a packet; ignored for unknown PIDs
+    pub fn add_pcr(&mut self, pid: u16, pcr: &PCR) -> Option<Timestamp> {
+        let program_number = self.pid_to_program.get(&pid)?;
+        let timeline = self.programs.get_mut(program_number)?;
+        if timeline.pcr_pid != Some(pid) {
+            return None;
+        }
+        Some(timeline.add_pcr(pid, pcr))
+    }
+
+    pub fn add_pes(&mut self, pid: u16, pes: &PESPacket) -> Option<TimelineEntry> {
+        let program_number = *self.pid_to_program.get(&pid)?;
+        let timeline = self.programs.get_mut(&program_number)?;
+        let header = pes.header.as_ref()?;
+        // DTS goes first, since it is never later than the PTS it belongs to
+        let dts = header.dts.map(|dts| timeline.add_pes_timestamp(pid, dts));
+        let pts = header.pts.map(|pts| timeline.add_pes_timestamp(pid, pts));
+        Some(TimelineEntry {
+            program_number,
+            pts,
+            dts,
+        })
+    }
+
+    /// Convenience for feeding the output of `ElementIterator` directly
+    pub fn add_stream_packet(
+        &mut self,
+        pid: u16,
+        stream_packet: &StreamPacket,
+    ) -> Option<TimelineEntry> {
+        match stream_packet {
+            StreamPacket::PMT(pmt) => {
+                self.add_pmt(pmt);
+                None
+            }
+            StreamPacket::PES(pes) => self.add_pes(pid, pes),
+            _ => None,
+        }
+    }
+}
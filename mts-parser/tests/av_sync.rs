@@ -0,0 +1,293 @@
+use mts_parser::av_sync::{AVSyncAnalyzer, AVSyncConfig, AVSyncEvent, AVSyncReport};
+use mts_parser::stream_packet::{
+    ElementaryStreamInfo, PESHeader, PESPacket, PMTTable, PSISharedTableInfo, StreamPacket,
+};
+use mts_parser::timestamp::Timestamp;
+
+const VIDEO: u16 = 0x1011;
+const AUDIO: u16 = 0x1100;
+const SECOND: i64 = 90_000;
+/// Time between entering the multiplex and decoding
+const DELAY: i64 = SECOND / 2;
+/// 25 fps video and 48 kHz AC-3 audio
+const FRAME: i64 = 3600;
+const AUDIO_FRAME: i64 = 2880;
+
+/// A PES packet of `pid` entering the multiplex at `arrival`
+#[derive(Debug, Clone, Copy)]
+struct Pes {
+    arrival: i64,
+    pid: u16,
+    pts: i64,
+    dts: Option<i64>,
+}
+
+fn esi(stream_type: u8, pid: u16) -> ElementaryStreamInfo {
+    ElementaryStreamInfo {
+        stream_type,
+        pid,
+        descriptors: Vec::new(),
+    }
+}
+
+fn pmt() -> StreamPacket {
+    StreamPacket::PMT(PMTTable {
+        psi_data: PSISharedTableInfo {
+            table_id: 2,
+            table_id_extension: 1,
+            version_number: 0,
+            current: true,
+            section_number: 0,
+            last_section_number: 0,
+        },
+        pcr_pid: VIDEO,
+        program_descriptiors: Vec::new(),
+        elementary_stream_info_data: vec![esi(0x1b, VIDEO), esi(0x81, AUDIO)],
+    })
+}
+
+fn pes(pts: i64, dts: Option<i64>) -> StreamPacket {
+    StreamPacket::PES(PESPacket {
+        stream_id: 0xe0,
+        header: Some(PESHeader {
+            scrambling_control: 0,
+            priority: false,
+            data_alignment_indicator: true,
+            copyright: false,
+            is_original: true,
+            pts: Some(pts as u64),
+            dts: dts.map(|dts| dts as u64),
+            escr: None,
+            es_rate: None,
+            dsm_trick_mode: None,
+            additional_copy_info: None,
+            previous_pes_packet_crc: None,
+            pes_extension: None,
+        }),
+        data: Vec::new(),
+    })
+}
+
+/// Video with an I P B B P B B ... decode order
+fn video(duration: i64) -> Vec<Pes> {
+    (0..duration / FRAME)
+        .map(|frame| {
+            let display = match frame {
+                0 => 0,
+                _ => 3 * ((frame - 1) / 3) + [3, 1, 2][(frame - 1) as usize % 3],
+            };
+            Pes {
+                arrival: frame * FRAME,
+                pid: VIDEO,
+                pts: DELAY + (display + 1) * FRAME,
+                dts: Some(DELAY + frame * FRAME),
+            }
+        })
+        .collect()
+}
+
+/// Audio with one, one and then two frames per PES, whose timestamps run `rate` times as
+/// fast as the multiplex
+fn audio(duration: i64, rate: f64) -> Vec<Pes> {
+    let mut packets = Vec::new();
+    let mut frame = 0;
+    while frame * AUDIO_FRAME < duration {
+        let arrival = frame * AUDIO_FRAME;
+        packets.push(Pes {
+            arrival,
+            pid: AUDIO,
+            pts: DELAY + (arrival as f64 * rate) as i64,
+            dts: None,
+        });
+        frame += [1, 1, 2][packets.len() % 3];
+    }
+    packets
+}
+
+fn analyze(streams: &[Vec<Pes>]) -> AVSyncReport {
+    let mut packets: Vec<Pes> = streams.concat();
+    packets.sort_by_key(|packet| packet.arrival);
+    let mut analyzer = AVSyncAnalyzer::new(AVSyncConfig {
+        window: Timestamp::from_90khz(SECOND),
+        ..AVSyncConfig::default()
+    });
+    analyzer.add_stream_packet(0x100, &pmt());
+    for packet in packets {
+        analyzer.add_stream_packet(packet.pid, &pes(packet.pts, packet.dts));
+    }
+    analyzer.finish()
+}
+
+fn without(packets: Vec<Pes>, from: i64, to: i64) -> Vec<Pes> {
+    packets
+        .into_iter()
+        .filter(|packet| packet.arrival < from || packet.arrival >= to)
+        .collect()
+}
+
+fn largest_drift(report: &AVSyncReport) -> Timestamp {
+    report
+        .windows
+        .iter()
+        .map(|window| window.drift.abs_diff(Timestamp::ZERO))
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn steady_streams() {
+    let report = analyze(&[video(30 * SECOND), audio(30 * SECOND, 1.0)]);
+    assert!(report.events.is_empty(), "{:?}", report.events);
+    let durations: Vec<_> = report
+        .streams
+        .iter()
+        .map(|stream| {
+            (
+                stream.pid,
+                stream.nominal_pes_duration,
+                stream.discontinuities,
+            )
+        })
+        .collect();
+    assert_eq!(
+        durations,
+        [
+            (VIDEO, Some(Timestamp::from_90khz(FRAME)), 0),
+            (AUDIO, Some(Timestamp::from_90khz(AUDIO_FRAME)), 0)
+        ]
+    );
+    assert_eq!(
+        report.start_offsets,
+        [(AUDIO, VIDEO, -Timestamp::from_90khz(FRAME))]
+    );
+    assert!(report.windows.len() >= 27);
+    // a varying number of frames per PES is not drift
+    assert!(largest_drift(&report) <= Timestamp::from_90khz(SECOND / 100));
+}
+
+#[test]
+fn drift() {
+    // the audio timestamps gain 2 ms per second
+    let report = analyze(&[video(60 * SECOND), audio(60 * SECOND, 1.002)]);
+    let drifts: Vec<(u16, u16, Timestamp, Timestamp)> = report
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            AVSyncEvent::Drift {
+                audio_pid,
+                video_pid,
+                at,
+                drift,
+            } => Some((*audio_pid, *video_pid, *at, *drift)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(drifts.len(), 1, "{:?}", report.events);
+    let (audio_pid, video_pid, at, drift) = drifts[0];
+    assert_eq!((audio_pid, video_pid), (AUDIO, VIDEO));
+    assert!(at > Timestamp::from_90khz(20 * SECOND) && at < Timestamp::from_90khz(23 * SECOND));
+    assert!(drift > Timestamp::from_90khz(40 * 90) && drift < Timestamp::from_90khz(50 * 90));
+    let last = report.windows.last().unwrap();
+    assert!(last.drift > Timestamp::from_90khz(110 * 90));
+    assert!(report
+        .to_string()
+        .contains(&format!("drift: audio pid(0x{:x}) is +0.04", AUDIO)));
+}
+
+#[test]
+fn interleave_gaps() {
+    let video = without(
+        video(30 * SECOND),
+        20 * SECOND,
+        20 * SECOND + 8 * SECOND / 10,
+    );
+    let audio = without(
+        audio(30 * SECOND, 1.0),
+        10 * SECOND,
+        10 * SECOND + 8 * SECOND / 10,
+    );
+    let report = analyze(&[video, audio]);
+    let gaps: Vec<(u16, u16)> = report
+        .events
+        .iter()
+        .map(|event| match event {
+            AVSyncEvent::InterleaveGap {
+                starving_pid,
+                leading_pid,
+                gap,
+                ..
+            } => {
+                assert!(*gap > Timestamp::from_90khz(SECOND / 2));
+                (*starving_pid, *leading_pid)
+            }
+            event => panic!("unexpected {}", event),
+        })
+        .collect();
+    assert_eq!(gaps, [(AUDIO, VIDEO), (VIDEO, AUDIO)]);
+    // the freshest audio of a window is still in sync
+    assert!(largest_drift(&report) <= Timestamp::from_90khz(SECOND / 100));
+}
+
+#[test]
+fn discontinuities() {
+    let jump = |packets: Vec<Pes>, from: i64, by: i64| -> Vec<Pes> {
+        packets
+            .into_iter()
+            .map(|mut packet| {
+                if packet.arrival >= from {
+                    packet.pts += by;
+                    packet.dts = packet.dts.map(|dts| dts + by);
+                }
+                packet
+            })
+            .collect()
+    };
+    // the audio alone goes back 2 s, then both streams jump 100 s ahead
+    let audio = jump(audio(40 * SECOND, 1.0), 4 * SECOND / 10, -2 * SECOND);
+    let audio = jump(audio, 15 * SECOND, 100 * SECOND);
+    let video = jump(video(40 * SECOND), 15 * SECOND, 100 * SECOND);
+    let report = analyze(&[video, audio]);
+    let events: Vec<(&str, u16, i64)> = report
+        .events
+        .iter()
+        .map(|event| match event {
+            AVSyncEvent::Discontinuity { pid, at, previous } => (
+                "discontinuity",
+                *pid,
+                (*at - *previous).as_secs_f64().round() as i64,
+            ),
+            AVSyncEvent::InterleaveGap {
+                starving_pid, gap, ..
+            } => ("gap", *starving_pid, gap.as_secs_f64().round() as i64),
+            event => panic!("unexpected {}", event),
+        })
+        .collect();
+    assert_eq!(
+        events,
+        [
+            ("discontinuity", AUDIO, -2),
+            ("gap", AUDIO, 2),
+            ("discontinuity", VIDEO, 100),
+            ("discontinuity", AUDIO, 100)
+        ]
+    );
+    let counts: Vec<usize> = report.streams.iter().map(|s| s.discontinuities).collect();
+    assert_eq!(counts, [1, 2]);
+    // the drift restarts from the first window after every discontinuity
+    assert!(largest_drift(&report) <= Timestamp::from_90khz(SECOND / 100));
+    let last = report.windows.last().unwrap();
+    assert_eq!((last.offset.as_secs_f64()).round(), -2.0);
+    // the step back is not one of the steps the nominal durations come from
+    let durations: Vec<_> = report
+        .streams
+        .iter()
+        .map(|stream| stream.nominal_pes_duration)
+        .collect();
+    assert_eq!(
+        durations,
+        [
+            Some(Timestamp::from_90khz(FRAME)),
+            Some(Timestamp::from_90khz(AUDIO_FRAME))
+        ]
+    );
+}
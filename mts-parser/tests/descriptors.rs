@@ -0,0 +1,122 @@
+use mts_parser::descriptors::{
+    metadata_descriptors, metadata_pointer_descriptors, Descriptor, MetadataDescriptor,
+    MetadataFormat, MetadataPointerDescriptor, ID3_FORMAT_IDENTIFIER,
+};
+
+/// The application and format fields of the ID3 timed metadata of HLS
+const ID3_FORMAT: [u8; 12] = [
+    0xff, 0xff, b'I', b'D', b'3', b' ', 0xff, b'I', b'D', b'3', b' ', 0x00,
+];
+
+fn descriptor(tag: u8, data: &[u8]) -> Vec<u8> {
+    let mut descriptor = vec![tag, data.len() as u8];
+    descriptor.extend(data);
+    descriptor
+}
+
+#[test]
+fn descriptor_loop() {
+    let mut data = descriptor(0x05, b"HDMV");
+    data.extend(descriptor(0x0a, b"eng\x00"));
+    // cut off one byte short
+    data.extend([0x26, 0x04, 0x00, 0x00, 0x00]);
+    assert_eq!(
+        Descriptor::parse_all(&data),
+        [
+            Descriptor {
+                tag: 0x05,
+                data: b"HDMV".to_vec()
+            },
+            Descriptor {
+                tag: 0x0a,
+                data: b"eng\x00".to_vec()
+            },
+        ]
+    );
+    assert_eq!(Descriptor::parse_all(&[]), []);
+}
+
+#[test]
+fn metadata_pointer_descriptor() {
+    // in this transport stream, program 1
+    let mut data = ID3_FORMAT.to_vec();
+    data.extend([0x1f, 0x00, 0x01]);
+    let mut descriptors = descriptor(0x05, b"ID3 ");
+    descriptors.extend(descriptor(0x25, &data));
+    let [pointer] = &metadata_pointer_descriptors(&descriptors)[..] else {
+        panic!("one metadata_pointer_descriptor");
+    };
+    assert_eq!(
+        pointer.format,
+        MetadataFormat {
+            application_format: 0xffff,
+            application_format_identifier: Some(ID3_FORMAT_IDENTIFIER),
+            format: 0xff,
+            format_identifier: Some(ID3_FORMAT_IDENTIFIER),
+            service_id: 0,
+        }
+    );
+    assert!(pointer.format.is_id3());
+    assert_eq!(pointer.mpeg_carriage_flags, 0);
+    assert_eq!(pointer.program_number, Some(1));
+    assert_eq!(pointer.transport_stream, None);
+    assert!(metadata_descriptors(&descriptors).is_empty());
+}
+
+#[test]
+fn metadata_pointer_to_another_transport_stream() {
+    // application and format without identifiers, a locator record and private data
+    let data = [
+        0x01, 0x00, 0x3f, 0x07, 0xbf, 0x02, 0xaa, 0xbb, 0x00, 0x02, 0x12, 0x34, 0x56, 0x78, 0xcc,
+    ];
+    let (rest, pointer) = MetadataPointerDescriptor::parse(&data).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(pointer.format.application_format, 0x0100);
+    assert_eq!(pointer.format.application_format_identifier, None);
+    assert_eq!(
+        (pointer.format.format, pointer.format.service_id),
+        (0x3f, 7)
+    );
+    assert!(!pointer.format.is_id3());
+    assert_eq!(pointer.locator_record, Some(vec![0xaa, 0xbb]));
+    assert_eq!(pointer.mpeg_carriage_flags, 1);
+    assert_eq!(pointer.program_number, Some(2));
+    assert_eq!(pointer.transport_stream, Some((0x1234, 0x5678)));
+    assert_eq!(pointer.private_data, [0xcc]);
+    // not carried in MPEG-2: no program number
+    let data = [0x01, 0x00, 0x3f, 0x07, 0x7f, 0xcc];
+    let (_, pointer) = MetadataPointerDescriptor::parse(&data).unwrap();
+    assert_eq!(pointer.mpeg_carriage_flags, 3);
+    assert_eq!(
+        (pointer.program_number, pointer.private_data),
+        (None, vec![0xcc])
+    );
+}
+
+#[test]
+fn metadata_descriptor() {
+    let mut data = ID3_FORMAT.to_vec();
+    data.push(0x0f);
+    let [metadata] = &metadata_descriptors(&descriptor(0x26, &data))[..] else {
+        panic!("one metadata_descriptor");
+    };
+    assert!(metadata.format.is_id3());
+    assert_eq!(metadata.decoder_config_flags, 0);
+    assert_eq!(metadata.service_identification_record, None);
+    assert_eq!(metadata.decoder_config, None);
+    // a service identification record and a decoder_config
+    let mut data = ID3_FORMAT.to_vec();
+    data.extend([0x3f, 0x01, 0xaa, 0x02, 0xbb, 0xcc, 0xdd]);
+    let (_, metadata) = MetadataDescriptor::parse(&data).unwrap();
+    assert_eq!(metadata.decoder_config_flags, 1);
+    assert_eq!(metadata.service_identification_record, Some(vec![0xaa]));
+    assert_eq!(metadata.decoder_config, Some(vec![0xbb, 0xcc]));
+    assert_eq!(metadata.private_data, [0xdd]);
+    // the decoder config is in another metadata service
+    let mut data = ID3_FORMAT.to_vec();
+    data.extend([0x8f, 0x05]);
+    let (_, metadata) = MetadataDescriptor::parse(&data).unwrap();
+    assert_eq!(metadata.decoder_config_metadata_service_id, Some(5));
+    // a truncated descriptor isn't parsed
+    assert!(metadata_descriptors(&descriptor(0x26, &ID3_FORMAT[..8])).is_empty());
+}
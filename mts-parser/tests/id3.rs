@@ -0,0 +1,234 @@
+use mts_parser::id3::{ID3Frame, ID3Tag, ID3TagIterator};
+use mts_parser::stream_packet::{PESHeader, PESPacket};
+
+const TIMESTAMP_OWNER: &[u8] = b"com.apple.streaming.transportStreamTimestamp\x00";
+
+fn synchsafe(size: usize) -> [u8; 4] {
+    [21, 14, 7, 0].map(|shift| (size >> shift) as u8 & 0x7f)
+}
+
+/// An ID3v2.4 frame
+fn frame(id: &[u8; 4], flags: u16, data: &[u8]) -> Vec<u8> {
+    let mut frame = id.to_vec();
+    frame.extend(synchsafe(data.len()));
+    frame.extend(flags.to_be_bytes());
+    frame.extend(data);
+    frame
+}
+
+fn tag(major: u8, flags: u8, body: &[u8]) -> Vec<u8> {
+    let mut tag = vec![b'I', b'D', b'3', major, 0, flags];
+    tag.extend(synchsafe(body.len()));
+    tag.extend(body);
+    tag
+}
+
+/// The tag HLS segmenters put at the start of every segment
+fn timestamp_tag(pts: u64) -> Vec<u8> {
+    let mut data = TIMESTAMP_OWNER.to_vec();
+    data.extend(pts.to_be_bytes());
+    tag(4, 0, &frame(b"PRIV", 0, &data))
+}
+
+fn parse(data: &[u8]) -> ID3Tag {
+    let (rest, tag) = ID3Tag::parse(data).unwrap();
+    assert!(rest.is_empty());
+    tag
+}
+
+#[test]
+fn text_frames() {
+    let mut body = frame(b"TXXX", 0, b"\x03title\x00caf\xc3\xa9");
+    // ID3v2.4 text frames can have several values
+    body.extend(frame(b"TPE1", 0, b"\x00one\x00two"));
+    // UTF-16 with a little endian BOM, and UTF-16BE without one
+    body.extend(frame(b"TIT2", 0, b"\x01\xff\xfeh\x00i\x00"));
+    body.extend(frame(b"TALB", 0, b"\x02\x00h\x00i"));
+    body.extend(frame(b"COMM", 0, b"\x00engdesc\x00text"));
+    body.extend(frame(b"WXXX", 0, b"\x00home\x00https://example.com"));
+    body.extend(frame(b"WOAR", 0, b"https://example.org"));
+    let tag = parse(&tag(4, 0, &body));
+    assert_eq!(tag.version, (4, 0));
+    assert_eq!(tag.frames.len(), 7);
+    let ids: Vec<&str> = tag.frames.iter().map(|f| f.id()).collect();
+    assert_eq!(
+        ids,
+        ["TXXX", "TPE1", "TIT2", "TALB", "COMM", "WXXX", "WOAR"]
+    );
+    let Some(ID3Frame::UserText { description, value }) = tag.frame("TXXX") else {
+        panic!("no TXXX");
+    };
+    assert_eq!((description.as_str(), value.as_str()), ("title", "café"));
+    let Some(ID3Frame::Text { values, .. }) = tag.frame("TPE1") else {
+        panic!("no TPE1");
+    };
+    assert_eq!(values, &["one", "two"]);
+    for id in ["TIT2", "TALB"] {
+        let Some(ID3Frame::Text { values, .. }) = tag.frame(id) else {
+            panic!("no {}", id);
+        };
+        assert_eq!(values, &["hi"]);
+    }
+    let Some(ID3Frame::Comment {
+        language,
+        description,
+        text,
+    }) = tag.frame("COMM")
+    else {
+        panic!("no COMM");
+    };
+    assert_eq!(
+        (language.as_str(), description.as_str(), text.as_str()),
+        ("eng", "desc", "text")
+    );
+    let Some(ID3Frame::UserURL { description, url }) = tag.frame("WXXX") else {
+        panic!("no WXXX");
+    };
+    assert_eq!(
+        (description.as_str(), url.as_str()),
+        ("home", "https://example.com")
+    );
+    let Some(ID3Frame::URL { url, .. }) = tag.frame("WOAR") else {
+        panic!("no WOAR");
+    };
+    assert_eq!(url, "https://example.org");
+}
+
+#[test]
+fn binary_frames() {
+    let mut priv_data = TIMESTAMP_OWNER.to_vec();
+    priv_data.extend(900_000_u64.to_be_bytes());
+    let mut body = frame(b"PRIV", 0, &priv_data);
+    body.extend(frame(
+        b"GEOB",
+        0,
+        b"\x00application/json\x00data.json\x00config\x00{}",
+    ));
+    body.extend(frame(b"APIC", 0, &[1, 2, 3]));
+    let tag = parse(&tag(4, 0, &body));
+    let Some(ID3Frame::Private { owner, data }) = tag.frame("PRIV") else {
+        panic!("no PRIV");
+    };
+    assert_eq!(owner, "com.apple.streaming.transportStreamTimestamp");
+    assert_eq!(data, &900_000_u64.to_be_bytes());
+    let Some(ID3Frame::Object {
+        mime_type,
+        filename,
+        description,
+        data,
+    }) = tag.frame("GEOB")
+    else {
+        panic!("no GEOB");
+    };
+    assert_eq!(
+        (mime_type.as_str(), filename.as_str(), description.as_str()),
+        ("application/json", "data.json", "config")
+    );
+    assert_eq!(data, b"{}");
+    let Some(ID3Frame::Unknown { data, .. }) = tag.frame("APIC") else {
+        panic!("no APIC");
+    };
+    assert_eq!(data, &[1, 2, 3]);
+}
+
+#[test]
+fn tag_structure() {
+    // extended header, padding and a footer
+    let mut body = vec![0, 0, 0, 6, 1, 0];
+    body.extend(frame(b"TIT2", 0, b"\x00hi"));
+    body.extend([0; 200]);
+    let mut data = tag(4, 0x50, &body);
+    data.extend(b"3DI\x04\x00\x50");
+    data.extend(synchsafe(body.len()));
+    // 219 bytes of body take two bytes of the synchsafe size
+    assert_eq!(data[8..10], [0x01, 0x5b]);
+    let tag = parse(&data);
+    assert_eq!((tag.flags, tag.frames.len()), (0x50, 1));
+    assert!(matches!(tag.frame("TIT2"), Some(ID3Frame::Text { .. })));
+    // sizes with the top bit of a byte set aren't synchsafe
+    assert!(ID3Tag::parse(b"ID3\x04\x00\x00\x00\x00\x00\x80").is_err());
+    // only ID3v2.3 and ID3v2.4
+    let mut data = timestamp_tag(0);
+    data[3] = 2;
+    assert!(ID3Tag::parse(&data).is_err());
+}
+
+#[test]
+fn unsynchronisation() {
+    // ID3v2.3: the whole tag, with plain frame sizes
+    let mut body = b"TIT2\x00\x00\x00\x04\x00\x00\x00\xff\x00\xfeA".to_vec();
+    body.extend(b"TPE1\x00\x00\x00\x02\x00\x00\x00B");
+    let id3v23 = parse(&tag(3, 0x80, &body));
+    assert_eq!(id3v23.version, (3, 0));
+    assert_eq!(id3v23.frames.len(), 2);
+    let Some(ID3Frame::Text { values, .. }) = id3v23.frame("TIT2") else {
+        panic!("no TIT2");
+    };
+    assert_eq!(values, &["\u{ff}\u{fe}A"]);
+    // ID3v2.4: per frame, here with a data length indicator
+    let data = frame(b"PRIV", 0x0003, b"\x00\x00\x00\x04x\x00\xff\x00\x01");
+    let id3v24 = parse(&tag(4, 0, &data));
+    let Some(ID3Frame::Private { owner, data }) = id3v24.frame("PRIV") else {
+        panic!("no PRIV");
+    };
+    assert_eq!((owner.as_str(), &data[..]), ("x", &[0xff, 0x01][..]));
+}
+
+fn pes(stream_id: u8, pts: Option<u64>, data: Vec<u8>) -> PESPacket {
+    PESPacket {
+        stream_id,
+        header: Some(PESHeader {
+            scrambling_control: 0,
+            priority: false,
+            data_alignment_indicator: true,
+            copyright: false,
+            is_original: false,
+            pts,
+            dts: None,
+            escr: None,
+            es_rate: None,
+            dsm_trick_mode: None,
+            additional_copy_info: None,
+            previous_pes_packet_crc: None,
+            pes_extension: None,
+        }),
+        data,
+    }
+}
+
+#[test]
+fn timed_tags() {
+    // two tags with garbage between them in a private_stream_1 PES
+    let mut first = timestamp_tag(1);
+    first.extend(b"garbage");
+    first.extend(timestamp_tag(2));
+    // one tag in two metadata access unit cells, across the PTS wrap
+    let cells = timestamp_tag(3);
+    let (start, end) = cells.split_at(20);
+    let mut second = Vec::new();
+    for (sequence_number, cell) in [start, end].into_iter().enumerate() {
+        second.extend([0, sequence_number as u8, 0xdf]);
+        second.extend((cell.len() as u16).to_be_bytes());
+        second.extend(cell);
+    }
+    let packets = vec![
+        pes(0xbd, Some((1 << 33) - 90_000), first),
+        pes(0xfc, Some(90_000), second),
+    ];
+    let mut tags = ID3TagIterator::new(packets.into_iter());
+    let timed: Vec<(i64, u8)> = tags
+        .by_ref()
+        .map(|timed| {
+            let Some(ID3Frame::Private { data, .. }) = timed.tag.frame("PRIV") else {
+                panic!("no PRIV");
+            };
+            (timed.pts.unwrap().as_90khz(), data[7])
+        })
+        .collect();
+    let wrap = 1_i64 << 33;
+    assert_eq!(
+        timed,
+        [(wrap - 90_000, 1), (wrap - 90_000, 2), (wrap + 90_000, 3)]
+    );
+    assert_eq!(tags.skipped_bytes(), 7);
+}
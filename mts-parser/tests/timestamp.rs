@@ -0,0 +1,148 @@
+use mts_parser::packets::PCR;
+use mts_parser::timestamp::{ProgramTimeline, Timestamp, Unwrapper};
+use std::time::Duration;
+
+const WRAP: u64 = 1 << 33;
+
+fn pcr(base: u64, extension: u16) -> PCR {
+    PCR {
+        base,
+        reserved: 0x3f,
+        extension,
+    }
+}
+
+#[test]
+fn clock_conversions() {
+    let second = Timestamp::from_90khz(90_000);
+    assert_eq!(second.as_27mhz(), 27_000_000);
+    assert_eq!(second.as_secs_f64(), 1.0);
+    assert_eq!(Timestamp::from_27mhz(27_000_000), second);
+    assert_eq!(Timestamp::from_secs_f64(1.5).as_90khz(), 135_000);
+    assert_eq!(
+        Timestamp::from_pcr(&pcr(90_000, 150)).as_27mhz(),
+        27_000_150
+    );
+    // 90 kHz values are rounded towards negative infinity
+    assert_eq!(Timestamp::from_27mhz(299).as_90khz(), 0);
+    assert_eq!(Timestamp::from_27mhz(-1).as_90khz(), -1);
+    assert_eq!(Timestamp::from_27mhz(-1).as_wrapped_90khz(), WRAP - 1);
+    assert_eq!(Timestamp::from_90khz(WRAP as i64 + 5).as_wrapped_90khz(), 5);
+}
+
+#[test]
+fn wall_clock_conversions() {
+    let timestamp = Timestamp::from_27mhz(27_000_000 + 13_500_027);
+    assert_eq!(timestamp.as_duration(), Some(Duration::new(1, 500_001_000)));
+    assert_eq!(Timestamp::from(Duration::new(1, 500_001_000)), timestamp);
+    assert_eq!(Timestamp::from_90khz(-1).as_duration(), None);
+    assert_eq!(
+        Timestamp::from_90khz(90_000 * 3_661).to_string(),
+        "01:01:01.000"
+    );
+    assert_eq!(Timestamp::from_90khz(-135_000).to_string(), "-00:00:01.500");
+    // 2^33 90 kHz ticks are a little over 26.5 hours
+    assert_eq!(
+        Timestamp::from_90khz(WRAP as i64).to_string(),
+        "26:30:43.717"
+    );
+}
+
+#[test]
+fn unwrap_forward_across_the_wrap() {
+    let mut unwrapper = Unwrapper::new();
+    assert_eq!(
+        unwrapper.unwrap_90khz(WRAP - 90_000).as_90khz(),
+        (WRAP - 90_000) as i64
+    );
+    assert_eq!(
+        unwrapper.unwrap_90khz(90_000).as_90khz(),
+        (WRAP + 90_000) as i64
+    );
+    // and on through the next period
+    assert_eq!(
+        unwrapper.unwrap_90khz(WRAP / 2).as_90khz(),
+        (WRAP + WRAP / 2) as i64
+    );
+    assert_eq!(
+        unwrapper.unwrap_90khz(WRAP - 1).as_90khz(),
+        (2 * WRAP - 1) as i64
+    );
+    assert_eq!(unwrapper.unwrap_90khz(0).as_90khz(), (2 * WRAP) as i64);
+    assert_eq!(
+        unwrapper.last(),
+        Some(Timestamp::from_90khz((2 * WRAP) as i64))
+    );
+}
+
+#[test]
+fn unwrap_small_backward_steps() {
+    // B-frame PTS go back a few frames
+    let mut unwrapper = Unwrapper::new();
+    assert_eq!(unwrapper.unwrap_90khz(18_000).as_90khz(), 18_000);
+    assert_eq!(unwrapper.unwrap_90khz(10_500).as_90khz(), 10_500);
+    assert_eq!(unwrapper.unwrap_90khz(14_250).as_90khz(), 14_250);
+    // also right after a wrap, back into the previous period
+    let mut unwrapper = Unwrapper::new();
+    unwrapper.unwrap_90khz(WRAP - 1_000);
+    assert_eq!(
+        unwrapper.unwrap_90khz(2_000).as_90khz(),
+        (WRAP + 2_000) as i64
+    );
+    assert_eq!(
+        unwrapper.unwrap_90khz(WRAP - 500).as_90khz(),
+        (WRAP - 500) as i64
+    );
+}
+
+#[test]
+fn unwrap_large_discontinuities() {
+    // jumps of less than half the period go forward, larger ones backward
+    let mut unwrapper = Unwrapper::new();
+    unwrapper.unwrap_90khz(1_000);
+    let forward = 1_000 + WRAP / 2 - 1;
+    assert_eq!(unwrapper.unwrap_90khz(forward).as_90khz(), forward as i64);
+    let mut unwrapper = Unwrapper::new();
+    unwrapper.unwrap_90khz(1_000);
+    let backward = 1_000 + WRAP / 2 + 1;
+    assert_eq!(
+        unwrapper.unwrap_90khz(backward).as_90khz(),
+        backward as i64 - WRAP as i64
+    );
+    // after a jump, values follow on from the new position
+    assert_eq!(
+        unwrapper.unwrap_90khz(backward + 3_600).as_90khz(),
+        backward as i64 + 3_600 - WRAP as i64
+    );
+}
+
+#[test]
+fn unwrap_near_a_reference() {
+    let reference = Timestamp::from_90khz(3 * WRAP as i64 + 100);
+    let mut unwrapper = Unwrapper::with_reference(reference);
+    assert_eq!(unwrapper.unwrap_90khz(50).as_90khz(), 3 * WRAP as i64 + 50);
+    // PCR extension on top of the unwrapped base
+    let mut unwrapper = Unwrapper::new();
+    unwrapper.unwrap_90khz(WRAP - 10);
+    assert_eq!(
+        unwrapper.unwrap_pcr(&pcr(10, 299)).as_27mhz(),
+        (WRAP as i64 + 10) * 300 + 299
+    );
+}
+
+#[test]
+fn program_timeline_shares_zero_and_wrap_period() {
+    let mut timeline = ProgramTimeline::new(1);
+    assert_eq!(timeline.zero(), None);
+    // video starts just before the wrap, audio just after it
+    let video = timeline.add_pes_timestamp(0x1011, WRAP - 9_000);
+    assert_eq!(video, Timestamp::ZERO);
+    assert_eq!(
+        timeline.zero(),
+        Some(Timestamp::from_90khz((WRAP - 9_000) as i64))
+    );
+    let audio = timeline.add_pes_timestamp(0x1100, 900);
+    assert_eq!(audio, Timestamp::from_90khz(9_900));
+    let pcr = timeline.add_pcr(0x1001, &pcr(WRAP - 18_000, 0));
+    assert_eq!(pcr, Timestamp::from_90khz(-9_000));
+}
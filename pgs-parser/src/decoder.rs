@@ -0,0 +1,226 @@
+// Turns display sets into RGBA bitmaps.
+//
+// Palettes and objects stay valid for the whole epoch (until the next epoch start), so a
+// display set may show objects that were defined in an earlier display set.
+use super::segments::{CompositionState, PaletteDefinition, Segment};
+use super::DisplaySet;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Y'CbCr (BT.709, limited range) to RGBA
+fn to_rgba(y: u8, cr: u8, cb: u8, alpha: u8) -> [u8; 4] {
+    let y = (y as f32 - 16.0) * 255.0 / 219.0;
+    let cb = (cb as f32 - 128.0) * 255.0 / 224.0;
+    let cr = (cr as f32 - 128.0) * 255.0 / 224.0;
+    let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    [
+        clamp(y + 1.5748 * cr),
+        clamp(y - 0.1873 * cb - 0.4681 * cr),
+        clamp(y + 1.8556 * cb),
+        alpha,
+    ]
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Object data ends in the middle of a run
+    Truncated,
+    /// More pixels in a line (or more lines) than the object size allows
+    Overflow,
+    MissingPalette(u8),
+    MissingObject(u16),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "object data is truncated"),
+            Self::Overflow => write!(f, "object data doesn't fit the object size"),
+            Self::MissingPalette(id) => write!(f, "palette {} is not defined", id),
+            Self::MissingObject(id) => write!(f, "object {} is not defined", id),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decodes run length encoded object data to one palette index per pixel
+pub fn decode_rle(data: &[u8], width: u16, height: u16) -> Result<Vec<u8>, DecodeError> {
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels = vec![0; width * height];
+    let (mut x, mut y) = (0, 0);
+    let mut bytes = data.iter().copied();
+    let mut next = || bytes.next().ok_or(DecodeError::Truncated);
+    while y < height {
+        let (length, color) = match next()? {
+            0 => match next()? {
+                0 => {
+                    // end of line
+                    x = 0;
+                    y += 1;
+                    continue;
+                }
+                flags => {
+                    let mut length = (flags & 0x3f) as usize;
+                    if flags & 0x40 != 0 {
+                        length = (length << 8) | next()? as usize;
+                    }
+                    let color = match flags & 0x80 {
+                        0 => 0,
+                        _ => next()?,
+                    };
+                    (length, color)
+                }
+            },
+            color => (1, color),
+        };
+        if x + length > width {
+            return Err(DecodeError::Overflow);
+        }
+        pixels[y * width + x..y * width + x + length].fill(color);
+        x += length;
+    }
+    Ok(pixels)
+}
+
+#[derive(Clone)]
+pub struct Bitmap {
+    pub object_id: u16,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub forced: bool,
+    /// 4 bytes per pixel, row by row
+    pub rgba: Vec<u8>,
+}
+
+impl fmt::Debug for Bitmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Bitmap(object={}, {}x{} at ({}, {}), forced={})",
+            self.object_id, self.width, self.height, self.x, self.y, self.forced
+        )
+    }
+}
+
+impl Bitmap {
+    /// Writes the bitmap as a Netpbm PAM image (RGB_ALPHA)
+    pub fn write_pam<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(
+            writer,
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            self.width, self.height
+        )?;
+        writer.write_all(&self.rgba)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Object {
+    width: u16,
+    height: u16,
+    data: Vec<u8>,
+    complete: bool,
+}
+
+/// Keeps the palettes and objects of the current epoch
+#[derive(Debug, Default)]
+pub struct Decoder {
+    palettes: HashMap<u8, [[u8; 4]; 256]>,
+    objects: HashMap<u16, Object>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update_palette(&mut self, definition: &PaletteDefinition) {
+        let palette = self
+            .palettes
+            .entry(definition.palette_id)
+            .or_insert([[0; 4]; 256]);
+        for entry in definition.entries.iter() {
+            palette[entry.id as usize] = to_rgba(entry.y, entry.cr, entry.cb, entry.alpha);
+        }
+    }
+
+    /// Takes in the definitions of the display set, and renders what it shows. An empty
+    /// result means the screen is cleared.
+    pub fn decode(&mut self, display_set: &DisplaySet) -> Result<Vec<Bitmap>, DecodeError> {
+        let Some(composition) = display_set.composition() else {
+            return Ok(Vec::new());
+        };
+        if composition.composition_state == CompositionState::EpochStart {
+            self.palettes.clear();
+            self.objects.clear();
+        }
+        for segment in display_set.segments.iter() {
+            match &segment.segment {
+                Segment::PDS(definition) => self.update_palette(definition),
+                Segment::ODS(definition) => {
+                    if let Some((_, width, height)) = definition.size {
+                        self.objects.insert(
+                            definition.object_id,
+                            Object {
+                                width,
+                                height,
+                                data: Vec::new(),
+                                complete: false,
+                            },
+                        );
+                    }
+                    if let Some(object) = self.objects.get_mut(&definition.object_id) {
+                        object.data.extend_from_slice(&definition.data);
+                        object.complete = definition.last_in_sequence;
+                    }
+                }
+                _ => (),
+            }
+        }
+        if composition.objects.is_empty() {
+            return Ok(Vec::new());
+        }
+        let palette = self
+            .palettes
+            .get(&composition.palette_id)
+            .ok_or(DecodeError::MissingPalette(composition.palette_id))?;
+        let mut bitmaps = Vec::new();
+        for placement in composition.objects.iter() {
+            let object = match self.objects.get(&placement.object_id) {
+                Some(object) if object.complete => object,
+                _ => return Err(DecodeError::MissingObject(placement.object_id)),
+            };
+            let pixels = decode_rle(&object.data, object.width, object.height)?;
+            let (x, y, width, height) = match placement.crop {
+                Some(crop) => (
+                    crop.x.min(object.width),
+                    crop.y.min(object.height),
+                    crop.width.min(object.width - crop.x.min(object.width)),
+                    crop.height.min(object.height - crop.y.min(object.height)),
+                ),
+                None => (0, 0, object.width, object.height),
+            };
+            let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+            for row in y..y + height {
+                let start = row as usize * object.width as usize + x as usize;
+                for index in &pixels[start..start + width as usize] {
+                    rgba.extend_from_slice(&palette[*index as usize]);
+                }
+            }
+            bitmaps.push(Bitmap {
+                object_id: placement.object_id,
+                x: placement.x,
+                y: placement.y,
+                width,
+                height,
+                forced: placement.forced,
+                rgba,
+            });
+        }
+        Ok(bitmaps)
+    }
+}
@@ -0,0 +1,226 @@
+// HDMV Presentation Graphics (Blu-ray / AVCHD subtitles), stream_type 0x90
+//
+// A PGS stream is a sequence of segments. A display set is all the segments from a
+// presentation composition segment (PCS) up to and including the next END segment.
+pub mod decoder;
+pub mod segments;
+pub mod stream;
+
+use mts_parser::stream_packet::PESPacket;
+use segments::{
+    ObjectDefinition, PaletteDefinition, PresentationComposition, Segment, WindowDefinition,
+};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use winnow::error;
+
+pub struct TimedSegment {
+    /// Raw 90 kHz PTS / DTS of the PES packet the segment starts in
+    pub pts: Option<u64>,
+    pub dts: Option<u64>,
+    pub segment: Segment,
+    /// The segment as it was in the stream, including type and size
+    pub raw: Vec<u8>,
+}
+
+impl fmt::Debug for TimedSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pts={:?} {:?}", self.pts, self.segment)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DisplaySet {
+    pub segments: Vec<TimedSegment>,
+    /// False if the stream ended before the END segment
+    pub complete: bool,
+}
+
+impl DisplaySet {
+    /// The PTS of the composition; the moment the display set is shown
+    pub fn pts(&self) -> Option<u64> {
+        self.segments.first().and_then(|s| s.pts)
+    }
+
+    pub fn composition(&self) -> Option<&PresentationComposition> {
+        self.segments.iter().find_map(|s| match &s.segment {
+            Segment::PCS(composition) => Some(composition),
+            _ => None,
+        })
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &WindowDefinition> {
+        self.segments
+            .iter()
+            .filter_map(|s| match &s.segment {
+                Segment::WDS(windows) => Some(windows),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    pub fn palettes(&self) -> impl Iterator<Item = &PaletteDefinition> {
+        self.segments.iter().filter_map(|s| match &s.segment {
+            Segment::PDS(palette) => Some(palette),
+            _ => None,
+        })
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &ObjectDefinition> {
+        self.segments.iter().filter_map(|s| match &s.segment {
+            Segment::ODS(object) => Some(object),
+            _ => None,
+        })
+    }
+}
+
+/// Groups the segments in the PES packets of one PGS stream into display sets
+pub struct DisplaySetIterator<I: Iterator<Item = PESPacket>> {
+    packets: I,
+    buffer: Vec<u8>,
+    /// (offset in buffer, pts, dts) of every PES packet in the buffer
+    marks: VecDeque<(usize, Option<u64>, Option<u64>)>,
+    /// PTS / DTS of the packet at the front of the buffer
+    timestamps: (Option<u64>, Option<u64>),
+    current: Option<DisplaySet>,
+    has_reached_eof: bool,
+    skipped_bytes: u64,
+}
+
+impl<I: Iterator<Item = PESPacket>> DisplaySetIterator<I> {
+    pub fn new(packets: I) -> Self {
+        Self {
+            packets,
+            buffer: Vec::new(),
+            marks: VecDeque::new(),
+            timestamps: (None, None),
+            current: None,
+            has_reached_eof: false,
+            skipped_bytes: 0,
+        }
+    }
+
+    /// Bytes that could not be parsed as segments
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    fn read_packet(&mut self) {
+        let Some(packet) = self.packets.next() else {
+            self.has_reached_eof = true;
+            return;
+        };
+        let (pts, dts) = packet
+            .header
+            .as_ref()
+            .map_or((None, None), |h| (h.pts, h.dts));
+        self.marks.push_back((self.buffer.len(), pts, dts));
+        self.buffer.extend_from_slice(&packet.data);
+    }
+
+    /// Drops `count` bytes from the front of the buffer, and returns the PTS / DTS of the
+    /// PES packet the first of them was in
+    fn consume(&mut self, count: usize) -> (Option<u64>, Option<u64>) {
+        while let Some((0, pts, dts)) = self.marks.front() {
+            self.timestamps = (*pts, *dts);
+            self.marks.pop_front();
+        }
+        self.buffer.drain(..count);
+        for mark in self.marks.iter_mut() {
+            mark.0 = mark.0.saturating_sub(count);
+        }
+        self.timestamps
+    }
+}
+
+impl<I: Iterator<Item = PESPacket>> Iterator for DisplaySetIterator<I> {
+    type Item = DisplaySet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.buffer.is_empty() && !self.has_reached_eof {
+                self.read_packet();
+                continue;
+            }
+            if self.buffer.is_empty() {
+                return self.current.take();
+            }
+            let input = stream::partialstream(&self.buffer, self.has_reached_eof);
+            match Segment::parse(input) {
+                Ok((_, (segment, raw))) => {
+                    let raw = raw.to_vec();
+                    let (pts, dts) = self.consume(raw.len());
+                    let timed_segment = TimedSegment {
+                        pts,
+                        dts,
+                        segment,
+                        raw,
+                    };
+                    let starts_set = matches!(timed_segment.segment, Segment::PCS(_));
+                    let ends_set = matches!(timed_segment.segment, Segment::END);
+                    let finished = match starts_set {
+                        true => self.current.take(),
+                        false => None,
+                    };
+                    let current = self.current.get_or_insert_with(DisplaySet::default);
+                    current.segments.push(timed_segment);
+                    if ends_set {
+                        current.complete = true;
+                        return self.current.take();
+                    }
+                    if finished.is_some() {
+                        return finished;
+                    }
+                }
+                Err(error::ErrMode::Incomplete(_)) if !self.has_reached_eof => {
+                    self.read_packet();
+                }
+                Err(_) => {
+                    // a malformed segment is skipped by its length, so the segments after
+                    // it are kept; without a whole type and length, a byte is skipped
+                    let count = match self.buffer.get(..3) {
+                        Some(header) => {
+                            let length = u16::from_be_bytes([header[1], header[2]]) as usize;
+                            (3 + length).min(self.buffer.len())
+                        }
+                        None => 1,
+                    };
+                    self.skipped_bytes += count as u64;
+                    self.consume(count);
+                }
+            }
+        }
+    }
+}
+
+/// Writes display sets in the `.sup` format: every segment prefixed with "PG" and its
+/// 32 bit PTS and DTS
+pub struct SupWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> SupWriter<W> {
+    const MAGIC: &[u8] = b"PG";
+
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write_display_set(&mut self, display_set: &DisplaySet) -> io::Result<()> {
+        let pts = display_set.pts().unwrap_or(0);
+        for segment in display_set.segments.iter() {
+            let dts = segment.dts.unwrap_or(0);
+            self.writer.write_all(Self::MAGIC)?;
+            self.writer
+                .write_all(&(segment.pts.unwrap_or(pts) as u32).to_be_bytes())?;
+            self.writer.write_all(&(dts as u32).to_be_bytes())?;
+            self.writer.write_all(&segment.raw)?;
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
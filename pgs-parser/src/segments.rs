@@ -0,0 +1,330 @@
+use super::stream::{stream, PartialStream, Stream};
+use std::fmt;
+use winnow::{binary, combinator, error, token, IResult, Parser};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositionState {
+    Normal,
+    AcquisitionPoint,
+    EpochStart,
+    Reserved(u8),
+}
+
+impl CompositionState {
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0x00 => Self::Normal,
+            0x40 => Self::AcquisitionPoint,
+            0x80 => Self::EpochStart,
+            _ => Self::Reserved(byte),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompositionObject {
+    pub object_id: u16,
+    pub window_id: u8,
+    pub forced: bool,
+    pub x: u16,
+    pub y: u16,
+    pub crop: Option<Crop>,
+}
+
+impl CompositionObject {
+    const CROPPED: u8 = 0x80;
+    const FORCED: u8 = 0x40;
+
+    fn parse(input: Stream) -> IResult<Stream, Self> {
+        let (input, (object_id, window_id, flags, x, y)) = (
+            binary::be_u16,
+            binary::be_u8,
+            binary::be_u8,
+            binary::be_u16,
+            binary::be_u16,
+        )
+            .parse_next(input)?;
+        let (input, crop) = combinator::cond(
+            flags & Self::CROPPED != 0,
+            (
+                binary::be_u16,
+                binary::be_u16,
+                binary::be_u16,
+                binary::be_u16,
+            )
+                .map(|(x, y, width, height)| Crop {
+                    x,
+                    y,
+                    width,
+                    height,
+                }),
+        )
+        .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                object_id,
+                window_id,
+                forced: flags & Self::FORCED != 0,
+                x,
+                y,
+                crop,
+            },
+        ))
+    }
+}
+
+/// Presentation Composition Segment; starts a display set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresentationComposition {
+    pub width: u16,
+    pub height: u16,
+    pub frame_rate: u8,
+    pub composition_number: u16,
+    pub composition_state: CompositionState,
+    pub palette_update: bool,
+    pub palette_id: u8,
+    pub objects: Vec<CompositionObject>,
+}
+
+impl PresentationComposition {
+    fn parse(input: Stream) -> IResult<Stream, Self> {
+        let (
+            input,
+            (
+                width,
+                height,
+                frame_rate,
+                composition_number,
+                composition_state,
+                palette_update,
+                palette_id,
+                count,
+            ),
+        ) = (
+            binary::be_u16,
+            binary::be_u16,
+            binary::be_u8,
+            binary::be_u16,
+            binary::be_u8,
+            binary::be_u8,
+            binary::be_u8,
+            binary::be_u8,
+        )
+            .parse_next(input)?;
+        let (input, objects) =
+            combinator::repeat(count as usize, CompositionObject::parse).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                width,
+                height,
+                frame_rate,
+                composition_number,
+                composition_state: CompositionState::from_byte(composition_state),
+                palette_update: palette_update & 0x80 != 0,
+                palette_id,
+                objects,
+            },
+        ))
+    }
+}
+
+/// One window of a Window Definition Segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowDefinition {
+    pub window_id: u8,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl WindowDefinition {
+    fn parse(input: Stream) -> IResult<Stream, Self> {
+        (
+            binary::be_u8,
+            binary::be_u16,
+            binary::be_u16,
+            binary::be_u16,
+            binary::be_u16,
+        )
+            .map(|(window_id, x, y, width, height)| Self {
+                window_id,
+                x,
+                y,
+                width,
+                height,
+            })
+            .parse_next(input)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteEntry {
+    pub id: u8,
+    pub y: u8,
+    pub cr: u8,
+    pub cb: u8,
+    pub alpha: u8,
+}
+
+impl PaletteEntry {
+    fn parse(input: Stream) -> IResult<Stream, Self> {
+        (
+            binary::be_u8,
+            binary::be_u8,
+            binary::be_u8,
+            binary::be_u8,
+            binary::be_u8,
+        )
+            .map(|(id, y, cr, cb, alpha)| Self {
+                id,
+                y,
+                cr,
+                cb,
+                alpha,
+            })
+            .parse_next(input)
+    }
+}
+
+/// Palette Definition Segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteDefinition {
+    pub palette_id: u8,
+    pub version: u8,
+    pub entries: Vec<PaletteEntry>,
+}
+
+impl PaletteDefinition {
+    fn parse(input: Stream) -> IResult<Stream, Self> {
+        let (input, (palette_id, version, entries)) = (
+            binary::be_u8,
+            binary::be_u8,
+            combinator::repeat(0.., PaletteEntry::parse),
+        )
+            .parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                palette_id,
+                version,
+                entries,
+            },
+        ))
+    }
+}
+
+/// Object Definition Segment; large objects are split over several of these
+#[derive(Clone, PartialEq, Eq)]
+pub struct ObjectDefinition {
+    pub object_id: u16,
+    pub version: u8,
+    pub first_in_sequence: bool,
+    pub last_in_sequence: bool,
+    /// Only in the first segment of a sequence: (data length, width, height). The length
+    /// counts the RLE data of all segments plus the 4 bytes of width and height.
+    pub size: Option<(u32, u16, u16)>,
+    /// (Part of the) run length encoded bitmap
+    pub data: Vec<u8>,
+}
+
+impl ObjectDefinition {
+    const FIRST_IN_SEQUENCE: u8 = 0x80;
+    const LAST_IN_SEQUENCE: u8 = 0x40;
+
+    fn parse(input: Stream) -> IResult<Stream, Self> {
+        let (input, (object_id, version, flags)) =
+            (binary::be_u16, binary::be_u8, binary::be_u8).parse_next(input)?;
+        let first_in_sequence = flags & Self::FIRST_IN_SEQUENCE != 0;
+        let (input, size) = combinator::cond(
+            first_in_sequence,
+            (binary::be_u24, binary::be_u16, binary::be_u16),
+        )
+        .parse_next(input)?;
+        let (input, data) = token::take_while(0.., |_| true).parse_next(input)?;
+        Ok((
+            input,
+            Self {
+                object_id,
+                version,
+                first_in_sequence,
+                last_in_sequence: flags & Self::LAST_IN_SEQUENCE != 0,
+                size,
+                data: data.to_vec(),
+            },
+        ))
+    }
+}
+
+impl fmt::Debug for ObjectDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ObjectDefinition")
+            .field("object_id", &self.object_id)
+            .field("version", &self.version)
+            .field("first_in_sequence", &self.first_in_sequence)
+            .field("last_in_sequence", &self.last_in_sequence)
+            .field("size", &self.size)
+            .field("data.len()", &self.data.len())
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    PDS(PaletteDefinition),
+    ODS(ObjectDefinition),
+    PCS(PresentationComposition),
+    WDS(Vec<WindowDefinition>),
+    END,
+    Unknown(u8, Vec<u8>),
+}
+
+impl Segment {
+    pub const PDS_TYPE: u8 = 0x14;
+    pub const ODS_TYPE: u8 = 0x15;
+    pub const PCS_TYPE: u8 = 0x16;
+    pub const WDS_TYPE: u8 = 0x17;
+    pub const END_TYPE: u8 = 0x80;
+
+    /// Parses one segment; returns the segment and its raw bytes (including type and size)
+    pub fn parse(input: PartialStream<'_>) -> IResult<PartialStream<'_>, (Self, &[u8])> {
+        let (input, ((segment_type, body), raw)) =
+            (binary::be_u8, binary::length_data(binary::be_u16))
+                .with_recognized()
+                .parse_next(input)?;
+        let body_input = stream(body);
+        let result = match segment_type {
+            Self::PDS_TYPE => PaletteDefinition::parse
+                .map(Self::PDS)
+                .parse_next(body_input),
+            Self::ODS_TYPE => ObjectDefinition::parse
+                .map(Self::ODS)
+                .parse_next(body_input),
+            Self::PCS_TYPE => PresentationComposition::parse
+                .map(Self::PCS)
+                .parse_next(body_input),
+            Self::WDS_TYPE => binary::length_count(binary::be_u8, WindowDefinition::parse)
+                .map(Self::WDS)
+                .parse_next(body_input),
+            Self::END_TYPE => Ok((body_input, Self::END)),
+            _ => Ok((body_input, Self::Unknown(segment_type, body.to_vec()))),
+        };
+        match result {
+            Ok((_, segment)) => Ok((input, (segment, raw))),
+            Err(e) => Err(e.map(|e: error::Error<_>| error::Error {
+                input,
+                kind: e.kind,
+            })),
+        }
+    }
+}
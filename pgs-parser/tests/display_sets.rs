@@ -0,0 +1,33 @@
+use mts_parser::stream_packet::PESPacket;
+use pgs_parser::{segments::Segment, DisplaySetIterator};
+
+fn packet(data: &[u8]) -> PESPacket {
+    PESPacket {
+        stream_id: 0xbd,
+        header: None,
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn malformed_segment_is_skipped_by_its_length() {
+    let data = [
+        // WDS that announces two windows but has none
+        0x17, 0x00, 0x01, 0x02, //
+        // unknown segment type
+        0x42, 0x00, 0x01, 0xff, //
+        0x80, 0x00, 0x00, //
+        // a type and half a length
+        0x16, 0x00,
+    ];
+    let mut display_sets = DisplaySetIterator::new(vec![packet(&data)].into_iter());
+    let display_set = display_sets.next().unwrap();
+    let segments: Vec<&Segment> = display_set.segments.iter().map(|s| &s.segment).collect();
+    assert_eq!(
+        segments,
+        [&Segment::Unknown(0x42, vec![0xff]), &Segment::END]
+    );
+    assert!(display_set.complete);
+    assert!(display_sets.next().is_none());
+    assert_eq!(display_sets.skipped_bytes(), 4 + 2);
+}
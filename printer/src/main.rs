@@ -1,65 +1,586 @@
-use clap::Parser;
-use h264_parser::{nalunits::NALUnit, NALUnitIterator};
-use mts_parser::{MTSPacketIterator, ElementIterator};
+use audio_parser::{aac, ac3, lpcm, mpa, AudioFrame, AudioFrameIterator, FrameParser};
+use clap::{Parser, ValueEnum};
+use h264_parser::{
+    access_unit::AccessUnitIterator,
+    annexb::ByteStreamIterator,
+    avcc::AVCDecoderConfigurationRecord,
+    gop::{GOPIterator, GOPSummary},
+    mdpm::{CSVWriter, CameraMetadataIterator, ClipMetadata, GPXWriter},
+    nalunits::{pps::ParameterSets, NALUnit},
+    stream::stream,
+    writer::{Framing, NALUnitWriter},
+    NALUnitIterator,
+};
+use mts_parser::{
+    av_sync::AVSyncAnalyzer,
+    descriptors,
+    id3::ID3TagIterator,
+    pes_reader::PESReader,
+    stream_packet::{ElementaryStreamInfo, PESPacket, PMTTable, StreamKind, StreamPacket},
+    ElementIterator, MTSPacketIterator,
+};
+use h265_parser::nalunits::Payload;
+use mpeg2_parser::PictureIterator;
+use pgs_parser::{decoder::Decoder, DisplaySetIterator, SupWriter};
 use std::error::Error;
+use std::fmt::Debug;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{BufWriter, Cursor};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Filename to process
     input: PathBuf,
+    /// What to print
+    #[arg(long, value_enum, default_value_t = Mode::Elements)]
+    mode: Mode,
+    /// Directory to write extracted streams to (default: next to the input)
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Every element (PAT, PMT, PES) in the transport stream
+    Elements,
+    /// Audio/video synchronisation and interleaving report
+    AvSync,
+    /// Every frame of every audio stream
+    Audio,
+    /// Write every LPCM stream to a WAV file
+    ExtractWav,
+    /// Every picture of every video stream
+    Video,
+    /// Every display set of every PGS subtitle stream
+    Subtitles,
+    /// Write every PGS subtitle stream to a .sup file, and its bitmaps to PAM images
+    ExtractSubtitles,
+    /// Metadata descriptors and every ID3 tag of every timed metadata stream
+    Metadata,
+    /// Write the AVCHD camera metadata of every H.264 stream to CSV and GPX files
+    ExtractCameraMetadata,
+    /// Re-serialise every NAL unit of every H.264 stream and compare it to the original
+    VerifyH264,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
     println!("Hello {}!", args.input.to_str().expect("Not unicode path"));
-    let file = File::open(args.input)?;
-    parse_mts(file)
+    let file = File::open(&args.input)?;
+    match args.mode {
+        Mode::Elements => parse_mts(file),
+        Mode::AvSync => report_av_sync(file),
+        Mode::Audio => print_audio(&args.input),
+        Mode::ExtractWav => extract_wav(&args.input, args.output.as_deref()),
+        Mode::Video => print_video(&args.input),
+        Mode::Subtitles => print_subtitles(&args.input),
+        Mode::ExtractSubtitles => extract_subtitles(&args.input, args.output.as_deref()),
+        Mode::Metadata => print_metadata(&args.input),
+        Mode::ExtractCameraMetadata => extract_camera_metadata(&args.input, args.output.as_deref()),
+        Mode::VerifyH264 => verify_h264(&args.input),
+    }
 }
 
-fn parse_mts(file: File) -> Result<(), Box<dyn Error>> {
-    let packet_iterator = MTSPacketIterator::new(Box::new(file));
-    let element_iterator = ElementIterator::new(packet_iterator);
-    for (pid, element) in element_iterator {
-        println!("pid(0x{:x}), {:?}", pid, element);
+fn find_pmt(path: &Path) -> Result<Option<PMTTable>, Box<dyn Error>> {
+    let packet_iterator = MTSPacketIterator::new(Box::new(File::open(path)?));
+    for (_, element) in ElementIterator::new(packet_iterator) {
+        if let StreamPacket::PMT(pmt) = element {
+            return Ok(Some(pmt));
+        }
+    }
+    Ok(None)
+}
+
+/// The elementary streams in the first PMT of the file
+fn find_elementary_streams(path: &Path) -> Result<Vec<ElementaryStreamInfo>, Box<dyn Error>> {
+    Ok(find_pmt(path)?.map_or(Vec::new(), |pmt| pmt.elementary_stream_info_data))
+}
+
+fn pes_packets(path: &Path, pid: u16) -> Result<impl Iterator<Item = PESPacket>, Box<dyn Error>> {
+    let packet_iterator = MTSPacketIterator::new(Box::new(File::open(path)?));
+    Ok(ElementIterator::new(packet_iterator).pes_packets(pid))
+}
+
+fn print_audio_frames<P>(path: &Path, pid: u16, parser: P) -> Result<(), Box<dyn Error>>
+where
+    P: FrameParser,
+    P::Frame: Debug,
+{
+    let mut frames = AudioFrameIterator::new(pes_packets(path, pid)?, parser);
+    let mut count = 0;
+    let mut samples = 0_u64;
+    for timed_frame in frames.by_ref() {
+        let pts = timed_frame.pts.map_or("-".to_string(), |pts| pts.to_string());
+        println!("pid(0x{:x}) {} {:?}", pid, pts, timed_frame.frame);
+        count += 1;
+        samples += timed_frame.frame.samples() as u64;
+    }
+    println!(
+        "pid(0x{:x}): {} frames, {} samples, {} bytes skipped",
+        pid,
+        count,
+        samples,
+        frames.skipped_bytes()
+    );
+    Ok(())
+}
+
+fn print_ac3_frames(path: &Path, pid: u16) -> Result<(), Box<dyn Error>> {
+    let mut frames = AudioFrameIterator::new(pes_packets(path, pid)?, ac3::AC3Parser);
+    let mut summary = ac3::AC3StreamSummary::new();
+    for timed_frame in frames.by_ref() {
+        let pts = timed_frame.pts.map_or("-".to_string(), |pts| pts.to_string());
+        println!("pid(0x{:x}) {} {:?}", pid, pts, timed_frame.frame);
+        summary.add(&timed_frame.frame);
+    }
+    println!(
+        "pid(0x{:x}): {}, {} bytes skipped",
+        pid,
+        summary,
+        frames.skipped_bytes()
+    );
+    Ok(())
+}
+
+fn print_mpa_frames(path: &Path, pid: u16) -> Result<(), Box<dyn Error>> {
+    let mut frames = AudioFrameIterator::new(pes_packets(path, pid)?, mpa::MPAParser);
+    let mut summary = mpa::MPAStreamSummary::new();
+    for timed_frame in frames.by_ref() {
+        let pts = timed_frame.pts.map_or("-".to_string(), |pts| pts.to_string());
+        println!("pid(0x{:x}) {} {:?}", pid, pts, timed_frame.frame);
+        summary.add(&timed_frame.frame);
+    }
+    println!(
+        "pid(0x{:x}): {}, {} bytes skipped",
+        pid,
+        summary,
+        frames.skipped_bytes()
+    );
+    Ok(())
+}
+
+fn print_lpcm_headers(path: &Path, pid: u16) -> Result<(), Box<dyn Error>> {
+    for pes in pes_packets(path, pid)? {
+        let pts = pes.header.as_ref().and_then(|h| h.pts);
+        match lpcm::LPCMHeader::parse(audio_parser::stream::stream(&pes.data)) {
+            Ok((_, header)) => println!("pid(0x{:x}) pts={:?} {:?}", pid, pts, header),
+            Err(e) => println!("pid(0x{:x}) pts={:?} invalid LPCM header: {:?}", pid, pts, e),
+        }
     }
     Ok(())
 }
 
-pub fn parse_h264(file: File) -> Result<(), Box<dyn Error>> {
-    let nal_unit_iterator = NALUnitIterator::new(Box::new(file));
-    let mut framecnt = 0;
-    for nal_unit in nal_unit_iterator {
-        match nal_unit {
-            NALUnit::IDRPicture(_) | NALUnit::NonIDRPicture(_) => {
-                framecnt += 1;
-                if framecnt % 24 == 0 {
-                    println!("")
+fn extract_wav(path: &Path, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let stem = path.file_stem().expect("Input is a file").to_string_lossy();
+    let directory = output.or(path.parent()).unwrap_or(Path::new("."));
+    for esi in find_elementary_streams(path)? {
+        if esi.stream_type != 0x80 {
+            continue;
+        }
+        let wav_path = directory.join(format!("{}.{:04x}.wav", stem, esi.pid));
+        let writer = BufWriter::new(File::create(&wav_path)?);
+        let mut extractor = lpcm::LPCMExtractor::new(writer);
+        for pes in pes_packets(path, esi.pid)? {
+            extractor.add_pes(&pes)?;
+        }
+        println!(
+            "pid(0x{:x}): {:?}, {} sample frames ({} of inserted silence) to {}",
+            esi.pid,
+            extractor.header(),
+            extractor.frames_written(),
+            extractor.inserted_silence(),
+            wav_path.display()
+        );
+        extractor.finish()?;
+    }
+    Ok(())
+}
+
+fn print_mpeg2_pictures(path: &Path, pid: u16) -> Result<(), Box<dyn Error>> {
+    let mut count = 0;
+    for picture in PictureIterator::new(pes_packets(path, pid)?) {
+        if let Some(sequence) = &picture.sequence {
+            println!(
+                "pid(0x{:x}) sequence {}x{} {:?} fps, {} bit/s, progressive={}, {:?}",
+                pid,
+                sequence.width(),
+                sequence.height(),
+                sequence.frame_rate(),
+                sequence.bit_rate(),
+                sequence.progressive(),
+                sequence.display_extension
+            );
+        }
+        println!("pid(0x{:x}) {:?}", pid, picture);
+        count += 1;
+    }
+    println!("pid(0x{:x}): {} pictures", pid, count);
+    Ok(())
+}
+
+fn print_h265_nal_units(path: &Path, pid: u16) -> Result<(), Box<dyn Error>> {
+    let reader = PESReader::new(pes_packets(path, pid)?);
+    let mut pictures = std::collections::BTreeMap::new();
+    for nal_unit in h265_parser::NALUnitIterator::new(Box::new(reader)) {
+        match &nal_unit.payload {
+            Payload::SPS(sps) => {
+                let ptl = &sps.profile_tier_level;
+                println!(
+                    "pid(0x{:x}) SPS {}x{} {} {}-bit, {} profile, {} tier, level {}, {:?} fps",
+                    pid,
+                    sps.width(),
+                    sps.height(),
+                    sps.chroma_format_name(),
+                    sps.bit_depth_luma,
+                    ptl.profile_name(),
+                    ptl.tier_name(),
+                    ptl.level(),
+                    sps.frame_rate(),
+                );
+                if let Some(colour) = sps.colour_description() {
+                    println!(
+                        "pid(0x{:x}) colour primaries {}, transfer {}, matrix {}, hdr={}",
+                        pid,
+                        colour.primaries_name(),
+                        colour.transfer_name(),
+                        colour.matrix_name(),
+                        colour.is_hdr()
+                    );
                 }
-                if framecnt % 2 == 0 {
+            }
+            Payload::Slice(Some(slice)) if !slice.first_slice_segment_in_pic => (),
+            Payload::Slice(slice) => {
+                let class = nal_unit.header.picture_class();
+                *pictures.entry(format!("{:?}", class)).or_insert(0) += 1;
+                println!(
+                    "pid(0x{:x}) picture {:?} {:?} poc_lsb={:?}",
+                    pid,
+                    class,
+                    slice.as_ref().and_then(|s| s.slice_type),
+                    slice.as_ref().and_then(|s| s.pic_order_cnt_lsb),
+                );
+            }
+            _ => println!("pid(0x{:x}) {:?}", pid, nal_unit),
+        }
+    }
+    println!("pid(0x{:x}): pictures {:?}", pid, pictures);
+    Ok(())
+}
+
+fn print_video(path: &Path) -> Result<(), Box<dyn Error>> {
+    for esi in find_elementary_streams(path)? {
+        if esi.kind() != StreamKind::Video {
+            continue;
+        }
+        match esi.stream_type {
+            0x01 | 0x02 => print_mpeg2_pictures(path, esi.pid)?,
+            // 0x20 is the MVC sub-bitstream with the non-base views
+            0x1b | 0x20 => print_h264_gops(path, esi.pid)?,
+            0x24 => print_h265_nal_units(path, esi.pid)?,
+            stream_type => println!(
+                "pid(0x{:x}): unsupported video stream_type 0x{:02x}",
+                esi.pid, stream_type
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn print_subtitles(path: &Path) -> Result<(), Box<dyn Error>> {
+    for esi in find_elementary_streams(path)? {
+        if esi.stream_type != 0x90 {
+            continue;
+        }
+        let mut display_sets = DisplaySetIterator::new(pes_packets(path, esi.pid)?);
+        let mut decoder = Decoder::new();
+        for display_set in display_sets.by_ref() {
+            println!("pid(0x{:x}) pts={:?}", esi.pid, display_set.pts());
+            for segment in display_set.segments.iter() {
+                println!("    {:?}", segment.segment);
+            }
+            match decoder.decode(&display_set) {
+                Ok(bitmaps) => println!("    shows {:?}", bitmaps),
+                Err(e) => println!("    can't decode: {}", e),
+            }
+        }
+        println!(
+            "pid(0x{:x}): {} bytes skipped",
+            esi.pid,
+            display_sets.skipped_bytes()
+        );
+    }
+    Ok(())
+}
+
+fn extract_subtitles(path: &Path, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let stem = path.file_stem().expect("Input is a file").to_string_lossy();
+    let directory = output.or(path.parent()).unwrap_or(Path::new("."));
+    for esi in find_elementary_streams(path)? {
+        if esi.stream_type != 0x90 {
+            continue;
+        }
+        let sup_path = directory.join(format!("{}.{:04x}.sup", stem, esi.pid));
+        let mut writer = SupWriter::new(BufWriter::new(File::create(&sup_path)?));
+        let mut decoder = Decoder::new();
+        let mut bitmap_count = 0;
+        let display_sets = DisplaySetIterator::new(pes_packets(path, esi.pid)?);
+        for (index, display_set) in display_sets.enumerate() {
+            writer.write_display_set(&display_set)?;
+            let bitmaps = match decoder.decode(&display_set) {
+                Ok(bitmaps) => bitmaps,
+                Err(e) => {
+                    println!("pid(0x{:x}) pts={:?}: {}", esi.pid, display_set.pts(), e);
                     continue;
                 }
+            };
+            for bitmap in bitmaps {
+                let pam_path = directory.join(format!(
+                    "{}.{:04x}.{:05}.{}.pam",
+                    stem, esi.pid, index, bitmap.object_id
+                ));
+                bitmap.write_pam(BufWriter::new(File::create(pam_path)?))?;
+                bitmap_count += 1;
             }
-            _ => ()
         }
-        match nal_unit {
-            NALUnit::Unknown(_nu) => {
-                // println!("{:?}", _nu);
+        println!(
+            "pid(0x{:x}): wrote {} and {} bitmaps",
+            esi.pid,
+            sup_path.display(),
+            bitmap_count
+        );
+    }
+    Ok(())
+}
+
+fn extract_camera_metadata(path: &Path, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let stem = path.file_stem().expect("Input is a file").to_string_lossy();
+    let directory = output.or(path.parent()).unwrap_or(Path::new("."));
+    for esi in find_elementary_streams(path)? {
+        if esi.stream_type != 0x1b {
+            continue;
+        }
+        let csv_path = directory.join(format!("{}.{:04x}.csv", stem, esi.pid));
+        let gpx_path = directory.join(format!("{}.{:04x}.gpx", stem, esi.pid));
+        let mut csv = CSVWriter::new(BufWriter::new(File::create(&csv_path)?))?;
+        let mut gpx = GPXWriter::new(BufWriter::new(File::create(&gpx_path)?), &stem)?;
+        let mut clip = ClipMetadata::new();
+        let reader = PESReader::new(pes_packets(path, esi.pid)?);
+        for gop in CameraMetadataIterator::new(NALUnitIterator::new(Box::new(reader))) {
+            csv.write_gop(&gop)?;
+            gpx.write_gop(&gop)?;
+            clip.add(&gop);
+        }
+        gpx.finish()?;
+        println!(
+            "pid(0x{:x}): {}, to {} and {}",
+            esi.pid,
+            clip,
+            csv_path.display(),
+            gpx_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Writes every NAL unit with start codes, reads it back and compares it to the original;
+/// SPS and PPS are also written from their fields
+fn verify_h264(path: &Path) -> Result<(), Box<dyn Error>> {
+    for esi in find_elementary_streams(path)? {
+        if !matches!(esi.stream_type, 0x1b | 0x20) {
+            continue;
+        }
+        let reader = PESReader::new(pes_packets(path, esi.pid)?);
+        let mut parameter_sets = ParameterSets::new();
+        let (mut nal_units, mut bytes, mut mismatches) = (0, 0, 0);
+        let mut parameter_set_units = Vec::new();
+        let mut units = ByteStreamIterator::new(reader);
+        while let Some(unit) = units.next() {
+            let nal_unit = units.parse(&unit, &mut parameter_sets);
+            let original = &unit.data[..];
+            let mut writer = NALUnitWriter::new(Vec::new(), Framing::AnnexB);
+            writer.write(&nal_unit)?;
+            let written = writer.into_inner();
+            let read_back = ByteStreamIterator::new(Cursor::new(&written)).next();
+            nal_units += 1;
+            bytes += original.len();
+            // parameter sets are also serialised from their fields
+            let rbsp = match &nal_unit {
+                NALUnit::SPS(nu) => Some((Some(nu.sps.to_rbsp()), &nu.rest)),
+                NALUnit::PPS(nu) => Some((nu.pps.to_rbsp(), &nu.rest)),
+                _ => None,
+            };
+            if rbsp.is_some_and(|(rbsp, rest)| rbsp.as_ref() != Some(rest)) {
+                mismatches += 1;
+                println!(
+                    "pid(0x{:x}) NAL unit at {}: type {}, fields serialised differently",
+                    esi.pid,
+                    unit.offset,
+                    nal_unit.nal_unit_type(),
+                );
+            } else if read_back.map(|unit| unit.data).as_deref() != Some(original) {
+                mismatches += 1;
+                println!(
+                    "pid(0x{:x}) NAL unit at {}: type {}, {} bytes, re-serialised as {:02x?}",
+                    esi.pid,
+                    unit.offset,
+                    nal_unit.nal_unit_type(),
+                    original.len(),
+                    &written[..written.len().min(16)],
+                );
+            }
+            if matches!(nal_unit, NALUnit::SPS(_) | NALUnit::PPS(_)) {
+                parameter_set_units.push(nal_unit);
             }
-            NALUnit::IDRPicture(_) => {
-                print!("R")
+        }
+        for problem in units.problems() {
+            println!("pid(0x{:x}) {}", esi.pid, problem);
+        }
+        println!(
+            "pid(0x{:x}): {} NAL units ({} bytes), {} re-serialised differently",
+            esi.pid, nal_units, bytes, mismatches
+        );
+        // an avcC from the first parameter sets, read back and checked against the last
+        let Some(record) = AVCDecoderConfigurationRecord::from_nal_units(&parameter_set_units, 4)
+        else {
+            continue;
+        };
+        let Some(data) = record.to_bytes() else {
+            continue;
+        };
+        match AVCDecoderConfigurationRecord::parse(stream(&data)) {
+            Ok((_, parsed)) if parsed == record => {
+                let problems: Vec<String> =
+                    parsed.check(&parameter_sets).iter().map(|m| m.to_string()).collect();
+                println!(
+                    "pid(0x{:x}): avcC of {} bytes, {}",
+                    esi.pid,
+                    data.len(),
+                    match problems.is_empty() {
+                        true => "agrees with the stream".to_string(),
+                        false => problems.join(", "),
+                    }
+                );
             }
-            NALUnit::NonIDRPicture(ref non_idr) => match non_idr.ref_idc {
-                3 => print!("I"),
-                2 => print!("P"),
-                0 => print!("B"),
-                x => print!("??{}??", x),
-            },
-            _ => (),
+            _ => println!("pid(0x{:x}): avcC of {} bytes doesn't read back", esi.pid, data.len()),
+        }
+    }
+    Ok(())
+}
+
+fn print_audio(path: &Path) -> Result<(), Box<dyn Error>> {
+    for esi in find_elementary_streams(path)? {
+        if esi.kind() != StreamKind::Audio {
+            continue;
         }
+        match esi.stream_type {
+            0x03 | 0x04 => print_mpa_frames(path, esi.pid)?,
+            0x0f => print_audio_frames(path, esi.pid, aac::ADTSParser)?,
+            0x11 => print_audio_frames(path, esi.pid, aac::LATMParser::new())?,
+            0x80 => print_lpcm_headers(path, esi.pid)?,
+            0x81 | 0x84 | 0x87 | 0xa1 => print_ac3_frames(path, esi.pid)?,
+            stream_type => println!(
+                "pid(0x{:x}): unsupported audio stream_type 0x{:02x}",
+                esi.pid, stream_type
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn print_metadata(path: &Path) -> Result<(), Box<dyn Error>> {
+    let Some(pmt) = find_pmt(path)? else {
+        return Ok(());
+    };
+    for pointer in descriptors::metadata_pointer_descriptors(&pmt.program_descriptiors) {
+        println!("program {:?}", pointer);
+    }
+    for esi in pmt.elementary_stream_info_data.iter() {
+        if esi.kind() != StreamKind::Metadata {
+            continue;
+        }
+        for descriptor in descriptors::metadata_descriptors(&esi.descriptors) {
+            println!("pid(0x{:x}) {:?}", esi.pid, descriptor);
+        }
+        let mut tags = ID3TagIterator::new(pes_packets(path, esi.pid)?);
+        for timed_tag in tags.by_ref() {
+            let pts = timed_tag.pts.map_or("-".to_string(), |pts| pts.to_string());
+            println!("pid(0x{:x}) {} ID3v2.{}", esi.pid, pts, timed_tag.tag.version.0);
+            for frame in timed_tag.tag.frames.iter() {
+                println!("pid(0x{:x})     {:?}", esi.pid, frame);
+            }
+        }
+        println!("pid(0x{:x}): {} bytes skipped", esi.pid, tags.skipped_bytes());
+    }
+    Ok(())
+}
+
+fn parse_mts(file: File) -> Result<(), Box<dyn Error>> {
+    let packet_iterator = MTSPacketIterator::new(Box::new(file));
+    let element_iterator = ElementIterator::new(packet_iterator);
+    for (pid, element) in element_iterator {
+        println!("pid(0x{:x}), {:?}", pid, element);
+    }
+    Ok(())
+}
+
+fn report_av_sync(file: File) -> Result<(), Box<dyn Error>> {
+    let packet_iterator = MTSPacketIterator::new(Box::new(file));
+    let element_iterator = ElementIterator::new(packet_iterator);
+    let mut analyzer = AVSyncAnalyzer::default();
+    for (pid, element) in element_iterator {
+        analyzer.add_stream_packet(pid, &element);
+    }
+    print!("{}", analyzer.finish());
+    Ok(())
+}
+
+fn print_h264_gops(path: &Path, pid: u16) -> Result<(), Box<dyn Error>> {
+    let reader = PESReader::new(pes_packets(path, pid)?);
+    let mut gops = GOPIterator::new(AccessUnitIterator::new(Box::new(reader)));
+    let mut summary = GOPSummary::new();
+    for gop in gops.by_ref() {
+        println!("pid(0x{:x}) GOP at {}: {}", pid, gop.offset, gop);
+        summary.add(&gop);
+    }
+    for problem in gops.get_ref().problems() {
+        println!("pid(0x{:x}) {}", pid, problem);
+    }
+    if let Some(sps) = gops.get_ref().parameter_sets().active_sps() {
+        println!(
+            "pid(0x{:x}) SPS {}x{} {} {}-bit, {} profile, level {}, {:?} fps",
+            pid,
+            sps.width(),
+            sps.height(),
+            sps.chroma_format_name(),
+            sps.bit_depth_luma,
+            sps.profile_name(),
+            sps.level(),
+            sps.frame_rate(),
+        );
+    }
+    let mut subset_sps: Vec<_> = gops.get_ref().parameter_sets().subset_sps.values().collect();
+    subset_sps.sort_by_key(|subset_sps| subset_sps.sps.seq_parameter_set_id);
+    for subset_sps in subset_sps {
+        let Some(mvc) = subset_sps.mvc.as_ref() else {
+            continue;
+        };
+        let dependencies: Vec<String> = mvc.view_ids[1..]
+            .iter()
+            .map(|view_id| format!("{} on {:?}", view_id, mvc.dependencies(*view_id)))
+            .collect();
+        println!(
+            "pid(0x{:x}) subset SPS {}: {} profile, level {}, views {:?}, dependencies {}",
+            pid,
+            subset_sps.sps.seq_parameter_set_id,
+            subset_sps.sps.profile_name(),
+            subset_sps.sps.level(),
+            mvc.view_ids,
+            dependencies.join(", "),
+        );
     }
+    println!("pid(0x{:x}): {}", pid, summary);
     Ok(())
 }